@@ -0,0 +1,191 @@
+// Full-stream audio decoding using symphonia
+use std::fs::File;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, VerificationCheck, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Only the first few decode error messages are kept, the rest are just counted
+const MAX_ERROR_MESSAGES: usize = 20;
+
+/// Stream properties and problems found while decoding a file
+#[derive(Debug, Clone, Default)]
+pub struct DecodeSummary {
+    pub codec: String,
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub bits_per_sample: Option<u32>,
+    /// Frame count declared by the stream header, if any
+    pub expected_frames: Option<u64>,
+    pub decoded_frames: u64,
+    pub decode_error_count: usize,
+    pub decode_errors: Vec<String>,
+    /// Result of the embedded checksum (FLAC MD5), `None` if the stream has none
+    pub verify_ok: Option<bool>,
+    /// Set when decoding stopped early because of a cancellation request
+    pub cancelled: bool,
+}
+
+impl DecodeSummary {
+    /// True when the stream ended noticeably (more than half a second) before its declared length
+    pub fn is_truncated(&self) -> bool {
+        if self.cancelled {
+            return false;
+        }
+        match self.expected_frames {
+            Some(expected) if expected > 0 => {
+                let slack = (self.sample_rate as u64 / 2).max(4096);
+                self.decoded_frames + slack < expected
+            }
+            _ => false,
+        }
+    }
+
+    fn record_error(&mut self, message: String) {
+        self.decode_error_count += 1;
+        if self.decode_errors.len() < MAX_ERROR_MESSAGES {
+            self.decode_errors.push(message);
+        }
+    }
+}
+
+/// Decode every packet of the default audio track in `path`.
+///
+/// `on_samples` receives each decoded chunk as interleaved f32 samples along with the
+/// channel count. Recoverable decode errors are recorded in the summary and decoding
+/// continues; only failures to open or probe the file are returned as `Err`.
+pub fn decode_file<F>(
+    path: &Path,
+    cancel: Option<&AtomicBool>,
+    mut on_samples: F,
+) -> Result<DecodeSummary, String>
+where
+    F: FnMut(&[f32], usize),
+{
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| format!("Unsupported or unreadable audio file {:?}: {}", path, e))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| format!("No audio track found in {:?}", path))?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&params, &DecoderOptions { verify: true })
+        .map_err(|e| format!("No decoder for {:?}: {}", path, e))?;
+
+    // Encoders that don't compute the MD5 leave it zeroed, which would always "fail"
+    let has_checksum = matches!(
+        params.verification_check,
+        Some(VerificationCheck::Md5(md5)) if md5 != [0u8; 16]
+    );
+
+    let mut summary = DecodeSummary {
+        codec: symphonia::default::get_codecs()
+            .get_codec(params.codec)
+            .map(|d| d.short_name.to_string())
+            .unwrap_or_else(|| "unknown".to_string()),
+        sample_rate: params.sample_rate.unwrap_or(0),
+        channels: params.channels.map(|c| c.count()).unwrap_or(0),
+        bits_per_sample: params.bits_per_sample,
+        expected_frames: params.n_frames,
+        ..Default::default()
+    };
+
+    let mut sample_buf: Option<SampleBuffer<f32>> = None;
+
+    loop {
+        if cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+            summary.cancelled = true;
+            break;
+        }
+
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            // End of stream (or a stream that was cut short, checked via is_truncated)
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(SymphoniaError::ResetRequired) => {
+                summary.record_error("Stream parameters changed mid-stream".to_string());
+                break;
+            }
+            Err(e) => {
+                summary.record_error(format!("Demux error: {}", e));
+                break;
+            }
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                let frames = decoded.frames();
+                if frames == 0 {
+                    continue;
+                }
+
+                if summary.sample_rate == 0 {
+                    summary.sample_rate = spec.rate;
+                }
+                if summary.channels == 0 {
+                    summary.channels = spec.channels.count();
+                }
+
+                let buf = match sample_buf.as_mut() {
+                    Some(buf) if buf.capacity() >= decoded.capacity() * spec.channels.count() => {
+                        buf
+                    }
+                    _ => sample_buf.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+                };
+                buf.copy_interleaved_ref(decoded);
+
+                summary.decoded_frames += frames as u64;
+                on_samples(buf.samples(), spec.channels.count());
+            }
+            Err(SymphoniaError::DecodeError(msg)) => {
+                summary.record_error(format!("Decode error at packet ts {}: {}", packet.ts(), msg));
+            }
+            Err(SymphoniaError::IoError(e)) => {
+                summary.record_error(format!("I/O error while decoding: {}", e));
+                break;
+            }
+            Err(e) => {
+                summary.record_error(format!("Decoder failure: {}", e));
+                break;
+            }
+        }
+    }
+
+    if !summary.cancelled && has_checksum {
+        summary.verify_ok = decoder.finalize().verify_ok;
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,7 @@
+// Audio analysis: full-stream decoding, integrity checks, transcode detection and spectrograms
+pub mod decode;
+pub mod spectrogram;
+pub mod transcode;
+pub mod verify;
+
+pub use transcode::analyze_file;
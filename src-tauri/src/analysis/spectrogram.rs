@@ -0,0 +1,98 @@
+// Spectrogram rendering for manual inspection of analysis verdicts
+use crate::error::{AudionError, ResultExt};
+use image::{Rgb, RgbImage};
+use std::path::Path;
+
+/// Number of frequency rows kept per column (linear scale, 0Hz at the bottom)
+pub const SPECTROGRAM_ROWS: usize = 512;
+
+/// Longer tracks are averaged down to at most this many columns
+const MAX_COLUMNS: usize = 1600;
+
+/// Levels below this are drawn black, levels at or above `TOP_DB` white
+const FLOOR_DB: f32 = -120.0;
+const TOP_DB: f32 = -20.0;
+
+/// Time/frequency power data in dB
+pub struct Spectrogram {
+    columns: Vec<Vec<f32>>,
+    sample_rate: u32,
+}
+
+impl Spectrogram {
+    pub fn new(columns: Vec<Vec<f32>>, sample_rate: u32) -> Self {
+        Self {
+            columns,
+            sample_rate,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Render as a PNG, optionally marking the detected cutoff with a dashed line
+    pub fn save_png(&self, path: &Path, cutoff_hz: Option<f64>) -> Result<(), AudionError> {
+        if self.columns.is_empty() {
+            return Err(AudionError::InvalidInput(
+                "No audio data to render".to_string(),
+            ));
+        }
+
+        let width = self.columns.len().min(MAX_COLUMNS);
+        let per_pixel = self.columns.len() as f32 / width as f32;
+        let mut img = RgbImage::new(width as u32, SPECTROGRAM_ROWS as u32);
+
+        for x in 0..width {
+            let start = (x as f32 * per_pixel) as usize;
+            let end = (((x + 1) as f32 * per_pixel) as usize).clamp(start + 1, self.columns.len());
+            let group = &self.columns[start..end];
+
+            for row in 0..SPECTROGRAM_ROWS {
+                let level = group.iter().map(|c| c[row]).sum::<f32>() / group.len() as f32;
+                let y = (SPECTROGRAM_ROWS - 1 - row) as u32;
+                img.put_pixel(x as u32, y, colormap(level));
+            }
+        }
+
+        if let Some(hz) = cutoff_hz {
+            let nyquist = self.sample_rate as f64 / 2.0;
+            if nyquist > 0.0 && hz < nyquist {
+                let row = ((hz / nyquist) * SPECTROGRAM_ROWS as f64) as usize;
+                let y = (SPECTROGRAM_ROWS - 1 - row.min(SPECTROGRAM_ROWS - 1)) as u32;
+                for x in (0..width as u32).filter(|x| (x / 6) % 2 == 0) {
+                    img.put_pixel(x, y, Rgb([0, 255, 255]));
+                }
+            }
+        }
+
+        img.save_with_format(path, image::ImageFormat::Png)
+            .context("Failed to write spectrogram")
+    }
+}
+
+/// Black -> purple -> red -> yellow -> white, similar to common spectrum viewers
+fn colormap(level_db: f32) -> Rgb<u8> {
+    let t = ((level_db - FLOOR_DB) / (TOP_DB - FLOOR_DB)).clamp(0.0, 1.0);
+    const STOPS: [(f32, [f32; 3]); 5] = [
+        (0.0, [0.0, 0.0, 0.0]),
+        (0.3, [80.0, 0.0, 120.0]),
+        (0.6, [220.0, 30.0, 30.0]),
+        (0.85, [255.0, 220.0, 0.0]),
+        (1.0, [255.0, 255.0, 255.0]),
+    ];
+
+    for pair in STOPS.windows(2) {
+        let (t0, c0) = pair[0];
+        let (t1, c1) = pair[1];
+        if t <= t1 {
+            let f = (t - t0) / (t1 - t0);
+            return Rgb([
+                (c0[0] + (c1[0] - c0[0]) * f) as u8,
+                (c0[1] + (c1[1] - c0[1]) * f) as u8,
+                (c0[2] + (c1[2] - c0[2]) * f) as u8,
+            ]);
+        }
+    }
+    Rgb([255, 255, 255])
+}
@@ -0,0 +1,458 @@
+// Spectral analysis for detecting lossy transcodes and upsampled audio
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::decode::{decode_file, DecodeSummary};
+use super::spectrogram::{Spectrogram, SPECTROGRAM_ROWS};
+
+/// FFT window length (~93ms at 44.1kHz, ~10.8Hz per bin)
+const FFT_SIZE: usize = 4096;
+
+/// Width of the moving average used to smooth the long-term spectrum
+const SMOOTHING_HZ: f32 = 150.0;
+
+/// Content counts as present when it sits this far above the noise floor
+const PRESENCE_THRESHOLD_DB: f32 = 15.0;
+
+/// Minimum drop across the cutoff for it to look like an encoder lowpass
+const CLIFF_THRESHOLD_DB: f32 = 25.0;
+
+/// Highest sensible content for 44.1/48kHz sources; anything at a higher
+/// sample rate that stops here was most likely upsampled from CD or lossy
+const CD_BAND_LIMIT_HZ: f32 = 24_500.0;
+
+/// Tracks quieter than this (mean level in the 200Hz-4kHz range) can't be judged
+const MIN_REFERENCE_DB: f32 = -90.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    /// Full-band content, consistent with a genuine lossless source
+    Lossless,
+    /// Sharp lowpass well below Nyquist, typical of MP3/AAC/Vorbis encoders
+    LossyTranscode,
+    /// High sample rate or bit depth without matching content
+    Upsampled,
+    /// Too quiet, too short or too ambiguous to decide
+    Inconclusive,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Lossless => "lossless",
+            Verdict::LossyTranscode => "lossy_transcode",
+            Verdict::Upsampled => "upsampled",
+            Verdict::Inconclusive => "inconclusive",
+        }
+    }
+}
+
+/// Result of analysing a single file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscodeReport {
+    pub verdict: Verdict,
+    /// 0.0 - 1.0, how sure we are about the verdict
+    pub confidence: f64,
+    /// Highest frequency with real content, if one could be found
+    pub cutoff_hz: Option<f64>,
+    /// Level drop across the cutoff (dB); large values mean a brickwall lowpass
+    pub cliff_db: Option<f64>,
+    pub sample_rate: u32,
+    pub bits_per_sample: Option<u32>,
+    /// Bits actually used by the samples when fewer than claimed (padded hi-res)
+    pub effective_bits: Option<u32>,
+    pub codec: String,
+    /// Human readable explanation of the evidence
+    pub reasons: Vec<String>,
+}
+
+/// Location and steepness of the spectral cutoff
+#[derive(Debug, Clone, Copy)]
+pub struct Cutoff {
+    pub hz: f32,
+    pub cliff_db: f32,
+}
+
+/// Accumulates a long-term average spectrum (and optionally spectrogram columns)
+/// from interleaved sample chunks
+pub struct SpectrumAnalyzer {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    pending: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+    power_sum: Vec<f64>,
+    frames: usize,
+    columns: Option<Vec<Vec<f32>>>,
+    /// Tracks whether every sample fits on a 16-bit grid
+    on_16bit_grid: bool,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(keep_columns: bool) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+        // Hann window
+        let window = (0..FFT_SIZE)
+            .map(|i| {
+                let x = std::f32::consts::PI * 2.0 * i as f32 / (FFT_SIZE - 1) as f32;
+                0.5 - 0.5 * x.cos()
+            })
+            .collect();
+
+        Self {
+            fft,
+            window,
+            pending: Vec::with_capacity(FFT_SIZE * 2),
+            scratch: vec![Complex::new(0.0, 0.0); FFT_SIZE],
+            power_sum: vec![0.0; FFT_SIZE / 2 + 1],
+            frames: 0,
+            columns: keep_columns.then(Vec::new),
+            on_16bit_grid: true,
+        }
+    }
+
+    /// Feed interleaved samples; channels are mixed down to mono
+    pub fn push(&mut self, samples: &[f32], channels: usize) {
+        let channels = channels.max(1);
+
+        if self.on_16bit_grid {
+            self.on_16bit_grid = samples.iter().all(|s| (s * 32768.0).fract() == 0.0);
+        }
+
+        for frame in samples.chunks_exact(channels) {
+            self.pending
+                .push(frame.iter().sum::<f32>() / channels as f32);
+        }
+
+        while self.pending.len() >= FFT_SIZE {
+            self.process_window();
+            self.pending.drain(..FFT_SIZE);
+        }
+    }
+
+    fn process_window(&mut self) {
+        for (i, slot) in self.scratch.iter_mut().enumerate() {
+            *slot = Complex::new(self.pending[i] * self.window[i], 0.0);
+        }
+        self.fft.process(&mut self.scratch);
+
+        let norm = (FFT_SIZE * FFT_SIZE) as f32;
+        let powers: Vec<f32> = self.scratch[..=FFT_SIZE / 2]
+            .iter()
+            .map(|c| c.norm_sqr() / norm)
+            .collect();
+
+        for (sum, p) in self.power_sum.iter_mut().zip(&powers) {
+            *sum += *p as f64;
+        }
+        self.frames += 1;
+
+        if let Some(columns) = self.columns.as_mut() {
+            // Reduce to a fixed number of rows by averaging neighbouring bins
+            let per_row = powers.len() as f32 / SPECTROGRAM_ROWS as f32;
+            let column = (0..SPECTROGRAM_ROWS)
+                .map(|row| {
+                    let start = (row as f32 * per_row) as usize;
+                    let end = (((row + 1) as f32 * per_row) as usize).clamp(start + 1, powers.len());
+                    let avg = powers[start..end].iter().sum::<f32>() / (end - start) as f32;
+                    to_db(avg)
+                })
+                .collect();
+            columns.push(column);
+        }
+    }
+
+    /// Average spectrum in dB, one value per FFT bin (0..=Nyquist)
+    pub fn average_db(&self) -> Vec<f32> {
+        let frames = self.frames.max(1) as f64;
+        self.power_sum
+            .iter()
+            .map(|sum| to_db((sum / frames) as f32))
+            .collect()
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.frames
+    }
+
+    pub fn fits_16bit_grid(&self) -> bool {
+        self.on_16bit_grid
+    }
+
+    pub fn take_spectrogram(&mut self, sample_rate: u32) -> Option<Spectrogram> {
+        self.columns
+            .take()
+            .map(|columns| Spectrogram::new(columns, sample_rate))
+    }
+}
+
+fn to_db(power: f32) -> f32 {
+    10.0 * power.max(1e-20).log10()
+}
+
+/// Moving average in the power domain, returned in dB
+fn smooth_db(values_db: &[f32], radius: usize) -> Vec<f32> {
+    let powers: Vec<f32> = values_db.iter().map(|db| 10f32.powf(db / 10.0)).collect();
+    (0..powers.len())
+        .map(|i| {
+            let start = i.saturating_sub(radius);
+            let end = (i + radius + 1).min(powers.len());
+            to_db(powers[start..end].iter().sum::<f32>() / (end - start) as f32)
+        })
+        .collect()
+}
+
+/// Find the highest frequency that still carries real content in an average spectrum.
+///
+/// `spectrum_db` holds one value per FFT bin from 0Hz to Nyquist. Returns `None`
+/// when the material is too quiet to tell content from noise.
+pub fn detect_cutoff(spectrum_db: &[f32], bin_hz: f32) -> Option<Cutoff> {
+    if spectrum_db.len() < 16 || bin_hz <= 0.0 {
+        return None;
+    }
+
+    let radius = ((SMOOTHING_HZ / bin_hz) as usize / 2).max(1);
+    let smoothed = smooth_db(spectrum_db, radius);
+    let last = smoothed.len() - 1;
+    let bin_of = |hz: f32| ((hz / bin_hz) as usize).min(last);
+
+    let (ref_start, ref_end) = (bin_of(200.0), bin_of(4_000.0).max(bin_of(200.0) + 1));
+    let reference = smooth_db(&smoothed[ref_start..ref_end], ref_end - ref_start)[0];
+    if reference < MIN_REFERENCE_DB {
+        return None;
+    }
+
+    // The noise floor is the quietest region above 1kHz
+    let floor = smoothed[bin_of(1_000.0)..]
+        .iter()
+        .cloned()
+        .fold(f32::INFINITY, f32::min);
+
+    // No quiet region at all: content runs right up to Nyquist
+    if reference - floor < PRESENCE_THRESHOLD_DB {
+        return Some(Cutoff {
+            hz: last as f32 * bin_hz,
+            cliff_db: 0.0,
+        });
+    }
+
+    let threshold = floor + PRESENCE_THRESHOLD_DB;
+    let cutoff_bin = (0..smoothed.len()).rev().find(|&i| smoothed[i] > threshold)?;
+
+    // Compare the level just below the cutoff with the level just above it
+    let span = bin_of(500.0).max(1);
+    let below = smoothed[cutoff_bin.saturating_sub(span)];
+    let above = smoothed[(cutoff_bin + span).min(last)];
+
+    Some(Cutoff {
+        hz: cutoff_bin as f32 * bin_hz,
+        cliff_db: (below - above).max(0.0),
+    })
+}
+
+/// Turn the measured cutoff into a verdict
+pub fn classify(
+    cutoff: Option<Cutoff>,
+    sample_rate: u32,
+    bits_per_sample: Option<u32>,
+    fits_16bit_grid: bool,
+    window_count: usize,
+) -> (Verdict, f64, Vec<String>) {
+    let mut reasons = Vec::new();
+
+    // Less than ~2 seconds of audio isn't enough for a stable average
+    if window_count < 20 || sample_rate == 0 {
+        reasons.push("Not enough audio to analyse".to_string());
+        return (Verdict::Inconclusive, 0.0, reasons);
+    }
+
+    let cutoff = match cutoff {
+        Some(c) => c,
+        None => {
+            reasons.push("Track is too quiet to separate content from noise".to_string());
+            return (Verdict::Inconclusive, 0.0, reasons);
+        }
+    };
+
+    let nyquist = sample_rate as f32 / 2.0;
+    let ratio = cutoff.hz / nyquist;
+    let padded_bits = bits_per_sample.is_some_and(|b| b > 16) && fits_16bit_grid;
+
+    reasons.push(format!(
+        "Content extends to {:.1} kHz of {:.1} kHz available ({:.0} dB drop at the cutoff)",
+        cutoff.hz / 1000.0,
+        nyquist / 1000.0,
+        cutoff.cliff_db
+    ));
+
+    if padded_bits {
+        reasons.push(format!(
+            "Stream claims {} bits but every sample fits in 16 bits",
+            bits_per_sample.unwrap_or(0)
+        ));
+    }
+
+    // Hi-res container whose content stops where a CD/lossy source would
+    if sample_rate > 48_000 && cutoff.hz <= CD_BAND_LIMIT_HZ {
+        reasons.push("High sample rate but no content above the CD band".to_string());
+        let depth = ((CD_BAND_LIMIT_HZ - cutoff.hz) / CD_BAND_LIMIT_HZ).clamp(0.0, 1.0);
+        let mut confidence = 0.7 + 0.25 * depth as f64;
+        if padded_bits {
+            confidence += 0.05;
+        }
+        return (Verdict::Upsampled, confidence.min(0.99), reasons);
+    }
+
+    // Brickwall lowpass clearly below Nyquist: the encoder fingerprint of lossy codecs
+    if ratio < 0.93 && cutoff.cliff_db >= CLIFF_THRESHOLD_DB {
+        reasons.push("Sharp lowpass below Nyquist, typical of lossy encoders".to_string());
+        let steepness = ((cutoff.cliff_db - CLIFF_THRESHOLD_DB) / 40.0).clamp(0.0, 1.0);
+        let lowness = ((0.93 - ratio) / 0.2).clamp(0.0, 1.0);
+        let confidence = 0.55 + 0.25 * steepness as f64 + 0.19 * lowness as f64;
+        return (Verdict::LossyTranscode, confidence.min(0.99), reasons);
+    }
+
+    if padded_bits {
+        return (Verdict::Upsampled, 0.75, reasons);
+    }
+
+    if ratio < 0.93 {
+        // Gentle rolloff: could be a dark recording or a soft encoder lowpass
+        reasons.push("High frequencies roll off gradually; source quality is unclear".to_string());
+        return (Verdict::Inconclusive, 0.3, reasons);
+    }
+
+    let margin = ((ratio - 0.93) / 0.05).clamp(0.0, 1.0);
+    (Verdict::Lossless, 0.6 + 0.35 * margin as f64, reasons)
+}
+
+/// Decode and analyse a file. When `keep_spectrogram` is set the spectrogram data is
+/// returned as well so it can be rendered for manual inspection.
+pub fn analyze_file(
+    path: &Path,
+    keep_spectrogram: bool,
+) -> Result<(TranscodeReport, Option<Spectrogram>), String> {
+    let mut analyzer = SpectrumAnalyzer::new(keep_spectrogram);
+    let summary: DecodeSummary =
+        decode_file(path, None, |samples, channels| analyzer.push(samples, channels))?;
+
+    let bin_hz = summary.sample_rate as f32 / FFT_SIZE as f32;
+    let cutoff = detect_cutoff(&analyzer.average_db(), bin_hz);
+    let (verdict, confidence, mut reasons) = classify(
+        cutoff,
+        summary.sample_rate,
+        summary.bits_per_sample,
+        analyzer.fits_16bit_grid(),
+        analyzer.window_count(),
+    );
+
+    if summary.decode_error_count > 0 {
+        reasons.push(format!(
+            "{} decode error(s) while reading the file",
+            summary.decode_error_count
+        ));
+    }
+
+    let effective_bits = match summary.bits_per_sample {
+        Some(bits) if bits > 16 && analyzer.fits_16bit_grid() => Some(16),
+        _ => None,
+    };
+
+    let report = TranscodeReport {
+        verdict,
+        confidence,
+        cutoff_hz: cutoff.map(|c| c.hz as f64),
+        cliff_db: cutoff.map(|c| c.cliff_db as f64),
+        sample_rate: summary.sample_rate,
+        bits_per_sample: summary.bits_per_sample,
+        effective_bits,
+        codec: summary.codec,
+        reasons,
+    };
+
+    let spectrogram = analyzer.take_spectrogram(summary.sample_rate);
+    Ok((report, spectrogram))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44_100;
+
+    /// Deterministic pseudo-random noise in -1..1
+    fn noise(seed: &mut u32) -> f32 {
+        *seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (*seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+    }
+
+    /// Five seconds of white noise through a windowed-sinc lowpass at `cutoff_hz`
+    fn lowpassed_noise(cutoff_hz: f32) -> Vec<f32> {
+        const TAPS: usize = 255;
+        let fc = cutoff_hz / RATE as f32;
+        let taps: Vec<f32> = (0..TAPS)
+            .map(|n| {
+                let m = n as f32 - (TAPS / 2) as f32;
+                let sinc = if m == 0.0 {
+                    2.0 * fc
+                } else {
+                    (2.0 * std::f32::consts::PI * fc * m).sin() / (std::f32::consts::PI * m)
+                };
+                let hamming = 0.54
+                    - 0.46 * (2.0 * std::f32::consts::PI * n as f32 / (TAPS - 1) as f32).cos();
+                sinc * hamming
+            })
+            .collect();
+
+        let mut seed = 7;
+        let input: Vec<f32> = (0..RATE as usize * 5 + TAPS)
+            .map(|_| noise(&mut seed) * 0.2)
+            .collect();
+        input
+            .windows(TAPS)
+            .map(|w| w.iter().zip(&taps).map(|(x, h)| x * h).sum())
+            .collect()
+    }
+
+    fn run(samples: &[f32]) -> (Option<Cutoff>, Verdict) {
+        let mut analyzer = SpectrumAnalyzer::new(false);
+        analyzer.push(samples, 1);
+        let cutoff = detect_cutoff(&analyzer.average_db(), RATE as f32 / FFT_SIZE as f32);
+        let (verdict, _, _) = classify(cutoff, RATE, Some(16), false, analyzer.window_count());
+        (cutoff, verdict)
+    }
+
+    #[test]
+    fn test_lowpassed_content_is_flagged_as_transcode() {
+        let (cutoff, verdict) = run(&lowpassed_noise(16_000.0));
+        let cutoff = cutoff.expect("cutoff should be detected");
+        assert!((cutoff.hz - 16_000.0).abs() < 600.0, "cutoff at {}", cutoff.hz);
+        assert_eq!(verdict, Verdict::LossyTranscode);
+    }
+
+    #[test]
+    fn test_full_band_content_is_lossless() {
+        let mut seed = 42;
+        let samples: Vec<f32> = (0..RATE * 5).map(|_| noise(&mut seed) * 0.2).collect();
+        let (_, verdict) = run(&samples);
+        assert_eq!(verdict, Verdict::Lossless);
+    }
+
+    #[test]
+    fn test_silence_is_inconclusive() {
+        let (cutoff, verdict) = run(&vec![0.0; RATE as usize * 5]);
+        assert!(cutoff.is_none());
+        assert_eq!(verdict, Verdict::Inconclusive);
+    }
+
+    #[test]
+    fn test_hires_without_hires_content_is_upsampled() {
+        let cutoff = Some(Cutoff { hz: 21_000.0, cliff_db: 60.0 });
+        let (verdict, confidence, _) = classify(cutoff, 96_000, Some(24), false, 100);
+        assert_eq!(verdict, Verdict::Upsampled);
+        assert!(confidence > 0.7);
+    }
+}
@@ -0,0 +1,193 @@
+// Integrity verification: fully decode a file and classify what went wrong
+use super::decode::{decode_file, DecodeSummary};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyStatus {
+    Ok,
+    /// The embedded checksum (FLAC MD5) doesn't match the decoded audio
+    ChecksumMismatch,
+    /// The stream ends well before the length declared in its header
+    Truncated,
+    DecodeErrors,
+    /// The file couldn't be opened or probed at all
+    Unreadable,
+}
+
+impl VerifyStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerifyStatus::Ok => "ok",
+            VerifyStatus::ChecksumMismatch => "checksum_mismatch",
+            VerifyStatus::Truncated => "truncated",
+            VerifyStatus::DecodeErrors => "decode_errors",
+            VerifyStatus::Unreadable => "unreadable",
+        }
+    }
+}
+
+/// Outcome of verifying a single file
+#[derive(Debug, Clone)]
+pub struct VerifyOutcome {
+    pub status: VerifyStatus,
+    pub decode_error_count: usize,
+    pub errors: Vec<String>,
+    pub expected_frames: Option<u64>,
+    pub decoded_frames: u64,
+    pub checksum_ok: Option<bool>,
+}
+
+impl VerifyOutcome {
+    /// Pick the most severe problem found while decoding
+    pub fn from_summary(summary: DecodeSummary) -> Self {
+        let truncated = summary.is_truncated();
+        let status = if summary.verify_ok == Some(false) {
+            VerifyStatus::ChecksumMismatch
+        } else if truncated {
+            VerifyStatus::Truncated
+        } else if summary.decode_error_count > 0 {
+            VerifyStatus::DecodeErrors
+        } else {
+            VerifyStatus::Ok
+        };
+
+        let mut errors = summary.decode_errors;
+        if status == VerifyStatus::ChecksumMismatch {
+            errors.insert(0, "Embedded MD5 does not match decoded audio".to_string());
+        }
+        if truncated {
+            errors.push(format!(
+                "Stream ended after {} of {} frames",
+                summary.decoded_frames,
+                summary.expected_frames.unwrap_or(0)
+            ));
+        }
+
+        Self {
+            status,
+            decode_error_count: summary.decode_error_count,
+            errors,
+            expected_frames: summary.expected_frames,
+            decoded_frames: summary.decoded_frames,
+            checksum_ok: summary.verify_ok,
+        }
+    }
+
+    fn unreadable(error: String) -> Self {
+        Self {
+            status: VerifyStatus::Unreadable,
+            decode_error_count: 0,
+            errors: vec![error],
+            expected_frames: None,
+            decoded_frames: 0,
+            checksum_ok: None,
+        }
+    }
+}
+
+/// Decode `path` from start to end. Returns `None` if cancelled before finishing.
+pub fn verify_file(path: &Path, cancel: Option<&AtomicBool>) -> Option<VerifyOutcome> {
+    match decode_file(path, cancel, |_, _| {}) {
+        Ok(summary) if summary.cancelled => None,
+        Ok(summary) => Some(VerifyOutcome::from_summary(summary)),
+        Err(e) => Some(VerifyOutcome::unreadable(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// 16-bit mono PCM WAV whose header declares `declared_frames` but only holds `frames`
+    fn write_wav(path: &Path, frames: u32, declared_frames: u32) {
+        let sample_rate = 44_100u32;
+        let data_len = declared_frames * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for i in 0..frames {
+            let sample = ((i as f32 * 0.05).sin() * 8000.0) as i16;
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        std::fs::File::create(path).unwrap().write_all(&bytes).unwrap();
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("audion_verify_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn complete_file_is_ok() {
+        let path = temp_path("ok.wav");
+        write_wav(&path, 44_100, 44_100);
+        let outcome = verify_file(&path, None).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(outcome.status, VerifyStatus::Ok);
+        assert_eq!(outcome.decoded_frames, 44_100);
+    }
+
+    #[test]
+    fn short_file_is_truncated() {
+        let path = temp_path("short.wav");
+        write_wav(&path, 44_100, 44_100 * 3);
+        let outcome = verify_file(&path, None).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(outcome.status, VerifyStatus::Truncated);
+        assert_eq!(outcome.expected_frames, Some(44_100 * 3));
+    }
+
+    #[test]
+    fn garbage_is_unreadable() {
+        let path = temp_path("garbage.flac");
+        std::fs::write(&path, b"definitely not audio").unwrap();
+        let outcome = verify_file(&path, None).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(outcome.status, VerifyStatus::Unreadable);
+        assert_eq!(outcome.errors.len(), 1);
+    }
+
+    #[test]
+    fn checksum_mismatch_takes_priority() {
+        let summary = DecodeSummary {
+            sample_rate: 44_100,
+            expected_frames: Some(44_100),
+            decoded_frames: 44_100,
+            decode_error_count: 1,
+            decode_errors: vec!["bad frame".to_string()],
+            verify_ok: Some(false),
+            ..Default::default()
+        };
+        let outcome = VerifyOutcome::from_summary(summary);
+
+        assert_eq!(outcome.status, VerifyStatus::ChecksumMismatch);
+        assert_eq!(outcome.errors.len(), 2);
+    }
+
+    #[test]
+    fn cancelled_verification_returns_nothing() {
+        let path = temp_path("cancel.wav");
+        write_wav(&path, 44_100, 44_100);
+        let cancel = AtomicBool::new(true);
+        let outcome = verify_file(&path, Some(&cancel));
+        std::fs::remove_file(&path).ok();
+
+        assert!(outcome.is_none());
+    }
+}
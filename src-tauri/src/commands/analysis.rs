@@ -0,0 +1,112 @@
+// Audio analysis Tauri commands (transcode / upsampling detection)
+use crate::analysis;
+use crate::db::{queries, Database};
+use crate::error::{AudionError, ResultExt};
+use crate::scanner::cover_storage;
+use tauri::State;
+
+/// Decode a local track, check it for signs of lossy transcoding or upsampling
+/// and store the verdict. Optionally renders a spectrogram into the covers cache.
+#[tauri::command]
+pub async fn analyze_track(
+    track_id: i64,
+    render_spectrogram: Option<bool>,
+    db: State<'_, Database>,
+) -> Result<queries::TrackAnalysis, AudionError> {
+    let render_spectrogram = render_spectrogram.unwrap_or(false);
+
+    let (path, source_type): (String, Option<String>) = {
+        let conn = db.read()?;
+        conn.query_row(
+            "SELECT path, source_type FROM tracks WHERE id = ?1",
+            [track_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .context(&format!("Track {} not found", track_id))?
+    }; // Connection returned to the pool while decoding
+
+    let is_local = source_type.is_none() || source_type.as_deref() == Some("local");
+    if !is_local || !std::path::Path::new(&path).is_file() {
+        return Err(AudionError::Unsupported(
+            "Only local files can be analysed".to_string(),
+        ));
+    }
+
+    let analysis_path = path.clone();
+    let (report, spectrogram) = tauri::async_runtime::spawn_blocking(move || {
+        analysis::analyze_file(std::path::Path::new(&analysis_path), render_spectrogram)
+            .map_err(AudionError::Decode)
+    })
+    .await??;
+
+    let spectrogram_path = match spectrogram {
+        Some(spec) if !spec.is_empty() => {
+            let file_path = cover_storage::get_cache_directory()?
+                .join(format!("spectrogram_{}.png", track_id));
+            spec.save_png(&file_path, report.cutoff_hz)?;
+            Some(file_path.to_string_lossy().to_string())
+        }
+        _ => None,
+    };
+
+    println!(
+        "[ANALYSIS] Track {} ({}): {} ({:.0}% confidence)",
+        track_id,
+        report.codec,
+        report.verdict.as_str(),
+        report.confidence * 100.0
+    );
+
+    let conn = db.write()?;
+
+    // Keep a previously rendered spectrogram if this run didn't produce one
+    let spectrogram_path = match spectrogram_path {
+        Some(p) => Some(p),
+        None => queries::get_track_analysis(&conn, track_id)?
+            .and_then(|a| a.spectrogram_path)
+            .filter(|p| std::path::Path::new(p).exists()),
+    };
+
+    let analysis = queries::TrackAnalysis {
+        track_id,
+        verdict: report.verdict.as_str().to_string(),
+        confidence: report.confidence,
+        cutoff_hz: report.cutoff_hz,
+        sample_rate: Some(report.sample_rate as i64),
+        bits_per_sample: report
+            .effective_bits
+            .or(report.bits_per_sample)
+            .map(|b| b as i64),
+        reasons: report.reasons,
+        spectrogram_path,
+        analyzed_at: None,
+    };
+
+    queries::save_track_analysis(&conn, &analysis)
+        .context("Failed to save analysis")?;
+
+    queries::get_track_analysis(&conn, track_id)?
+        .ok_or_else(|| AudionError::Internal("Analysis was not saved".to_string()))
+}
+
+/// Get the stored analysis for a track, if it has been analysed
+#[tauri::command]
+pub async fn get_track_analysis(
+    track_id: i64,
+    db: State<'_, Database>,
+) -> Result<Option<queries::TrackAnalysis>, AudionError> {
+    let conn = db.read()?;
+    queries::get_track_analysis(&conn, track_id).map_err(AudionError::from)
+}
+
+/// List analysed tracks that look transcoded or upsampled
+#[tauri::command]
+pub async fn get_transcode_suspects(
+    min_confidence: Option<f64>,
+    db: State<'_, Database>,
+) -> Result<Vec<queries::TrackAnalysis>, AudionError> {
+    let conn = db.read()?;
+    queries::get_suspect_analyses(&conn, min_confidence.unwrap_or(0.5))
+        .map_err(AudionError::from)
+}
+
@@ -1,5 +1,6 @@
 // Cover management Tauri commands
 use crate::db::{queries, Database};
+use crate::error::{AudionError, ResultExt};
 use crate::scanner::cover_storage::{
     cleanup_orphaned_covers, get_album_art_file_path, get_track_cover_file_path,
     save_album_art_from_base64, save_track_cover_from_base64,
@@ -73,17 +74,6 @@ pub struct MergeCoverResult {
     pub errors: Vec<String>,
 }
 
-// Helper trait for cleaner error conversion
-trait ToStringErr<T> {
-    fn to_str_err(self) -> Result<T, String>;
-}
-
-impl<T, E: std::fmt::Display> ToStringErr<T> for Result<T, E> {
-    fn to_str_err(self) -> Result<T, String> {
-        self.map_err(|e| e.to_string())
-    }
-}
-
 // Calculate optimal batch size based on progress and queue depth
 fn calculate_batch_size(
     items_processed: usize,
@@ -130,18 +120,18 @@ struct MigrationResult {
 pub async fn migrate_covers_to_files(
     window: tauri::Window,
     db: State<'_, Database>,
-) -> Result<MigrationProgress, String> {
+) -> Result<MigrationProgress, AudionError> {
     println!("[MIGRATION] Starting cover migration...");
     let total_start = Instant::now();
 
     // 1: Fetch all items to migrate (with lock)
     let (tracks, albums) = {
-        let conn = db.conn.lock().to_str_err()?;
+        let conn = db.read()?;
         
         println!("[MIGRATION] Fetching tracks from database...");
         let mut stmt = conn.prepare(
             "SELECT id, track_cover FROM tracks WHERE track_cover IS NOT NULL AND track_cover_path IS NULL"
-        ).to_str_err()?;
+        )?;
         
         let tracks: Vec<(i64, String)> = stmt.query_map([], |row| {
             Ok((
@@ -149,7 +139,7 @@ pub async fn migrate_covers_to_files(
                 row.get::<_, String>(1)?,
             ))
         })
-        .to_str_err()?
+        ?
         .filter_map(|r| r.ok())
         .collect();
         
@@ -158,7 +148,7 @@ pub async fn migrate_covers_to_files(
         println!("[MIGRATION] Fetching albums from database...");
         let mut stmt = conn.prepare(
             "SELECT id, art_data FROM albums WHERE art_data IS NOT NULL AND art_path IS NULL"
-        ).to_str_err()?;
+        )?;
         
         let albums: Vec<(i64, String)> = stmt.query_map([], |row| {
             Ok((
@@ -166,7 +156,7 @@ pub async fn migrate_covers_to_files(
                 row.get::<_, String>(1)?,
             ))
         })
-        .to_str_err()?
+        ?
         .filter_map(|r| r.ok())
         .collect();
         
@@ -234,7 +224,7 @@ pub async fn migrate_covers_to_files(
 
     // 3: Batch assembly  db writes ,frontend updates
     let window_clone = window.clone();
-    let db_conn = Arc::clone(&db.conn);
+    let db_writer = db.inner().clone();
     let total_start_clone = total_start;
 
     let batch_result = tauri::async_runtime::spawn_blocking(move || {
@@ -245,7 +235,7 @@ pub async fn migrate_covers_to_files(
         let mut errors = Vec::new();
         let mut pending = Vec::new();
 
-        let mut conn = db_conn.lock().unwrap();
+        let mut conn = db_writer.write().unwrap();
 
         loop {
             // Adaptive batch sizing based on queue depth
@@ -341,8 +331,7 @@ pub async fn migrate_covers_to_files(
 
         (tracks_migrated, albums_migrated, errors)
     })
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     let (tracks_migrated, albums_migrated, errors) = batch_result;
 
@@ -391,7 +380,7 @@ struct AlbumCoverGroup {
 pub async fn merge_duplicate_covers(
     window: tauri::Window,
     db: State<'_, Database>,
-) -> Result<MergeCoverResult, String> {
+) -> Result<MergeCoverResult, AudionError> {
     println!("[MERGE] Starting cover merge...");
     let total_start = Instant::now();
 
@@ -399,16 +388,16 @@ pub async fn merge_duplicate_covers(
 
     // 1: Get all albums
     let albums = {
-        let conn = db.conn.lock().to_str_err()?;
+        let conn = db.read()?;
         let mut stmt = conn
             .prepare("SELECT DISTINCT album, album_id FROM tracks WHERE album IS NOT NULL")
-            .to_str_err()?;
+            ?;
 
         let albums: Vec<(String, Option<i64>)> = stmt
             .query_map([], |row| {
                 Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?))
             })
-            .to_str_err()?
+            ?
             .filter_map(|r| r.ok())
             .collect();
 
@@ -435,7 +424,7 @@ pub async fn merge_duplicate_covers(
 
             // Get all tracks for this album with cover paths
             let tracks = {
-                let conn = db_clone.conn.lock().unwrap();
+                let conn = db_clone.read().unwrap();
                 let mut track_stmt = conn
                     .prepare(
                         "SELECT id, track_cover_path FROM tracks 
@@ -538,7 +527,7 @@ pub async fn merge_duplicate_covers(
     // 3: Process merge results(runs concurrently with analysis)
     let albums_processed_for_emit = albums_processed.clone();
     let window_clone = window.clone();
-    let db_conn = Arc::clone(&db.conn);
+    let db_writer = db.inner().clone();
     let total_start_clone = total_start;
 
     let merge_result = tauri::async_runtime::spawn_blocking(move || {
@@ -579,7 +568,7 @@ pub async fn merge_duplicate_covers(
 
                 // Batch update in transaction
                 if !updates.is_empty() {
-                    let mut conn = db_conn.lock().unwrap();
+                    let mut conn = db_writer.write().unwrap();
                     let tx_db = match conn.transaction() {
                         Ok(tx) => tx,
                         Err(e) => {
@@ -646,8 +635,7 @@ pub async fn merge_duplicate_covers(
 
         (covers_merged, space_saved_bytes, errors)
     })
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     let (covers_merged, space_saved_bytes, mut merge_errors) = merge_result;
     errors.append(&mut merge_errors);
@@ -697,7 +685,7 @@ pub async fn sync_cover_paths_from_files(
     window: tauri::Window,
     db: State<'_, Database>,
     app_handle: tauri::AppHandle,
-) -> Result<MigrationProgress, String> {
+) -> Result<MigrationProgress, AudionError> {
     println!("[SYNC] Syncing cover paths from existing files...");
     let start = std::time::Instant::now();
 
@@ -706,7 +694,7 @@ pub async fn sync_cover_paths_from_files(
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        .context("Failed to get app data directory")?;
 
     let covers_dir = app_data_dir.join("covers");
     let tracks_dir = covers_dir.join("tracks");
@@ -791,7 +779,7 @@ fn batch_update_paths_with_progress(
     total_items: usize,
     tracks_synced: &mut usize,
     albums_synced: &mut usize,
-) -> Result<usize, String> {
+) -> Result<usize, AudionError> {
     if updates.is_empty() {
         return Ok(0);
     }
@@ -801,8 +789,8 @@ fn batch_update_paths_with_progress(
     let start_time = std::time::Instant::now();
 
     for (batch_idx, chunk) in updates.chunks(BATCH_SIZE).enumerate() {
-        let mut conn = db.conn.lock().to_str_err()?;
-        let tx = conn.transaction().to_str_err()?;
+        let mut conn = db.write()?;
+        let tx = conn.transaction()?;
 
         for (path_str, id) in chunk {
             let sql = format!("UPDATE {} SET {} = ?1 WHERE id = ?2", table, column);
@@ -826,7 +814,7 @@ fn batch_update_paths_with_progress(
             }
         }
 
-        tx.commit().to_str_err()?;
+        tx.commit()?;
 
         // Emit progress event after each batch
         let elapsed_ms = start_time.elapsed().as_millis() as u64;
@@ -894,17 +882,17 @@ fn scan_covers_directory(dir: &std::path::Path) -> Vec<(String, i64)> {
 }
 
 // Helper function for hashing
-fn get_file_hash(path: &str) -> Result<String, String> {
+fn get_file_hash(path: &str) -> Result<String, AudionError> {
     let path = std::path::Path::new(path);
 
     // Calculate hash
-    let mut file = fs::File::open(path).to_str_err()?;
+    let mut file = fs::File::open(path)?;
     let mut hasher = Sha256::new();
 
     // Read in 64KB chunks for efficiency
     let mut buffer = [0u8; 65536];
     loop {
-        let bytes_read = file.read(&mut buffer).to_str_err()?;
+        let bytes_read = file.read(&mut buffer)?;
         if bytes_read == 0 {
             break;
         }
@@ -919,62 +907,62 @@ fn get_file_hash(path: &str) -> Result<String, String> {
 pub async fn get_track_cover_path(
     track_id: i64,
     db: State<'_, Database>,
-) -> Result<Option<String>, String> {
-    let conn = db.conn.lock().to_str_err()?;
-    get_track_cover_file_path(&conn, track_id).to_str_err()
+) -> Result<Option<String>, AudionError> {
+    let conn = db.read()?;
+    get_track_cover_file_path(&conn, track_id).map_err(AudionError::from)
 }
 
 #[tauri::command]
 pub async fn get_batch_cover_paths(
     track_ids: Vec<i64>,
     db: State<'_, Database>,
-) -> Result<HashMap<i64, String>, String> {
-    let conn = db.conn.lock().to_str_err()?;
-    queries::get_batch_cover_paths(&conn, &track_ids).to_str_err()
+) -> Result<HashMap<i64, String>, AudionError> {
+    let conn = db.read()?;
+    queries::get_batch_cover_paths(&conn, &track_ids).map_err(AudionError::from)
 }
 
 #[tauri::command]
 pub async fn get_album_art_path(
     album_id: i64,
     db: State<'_, Database>,
-) -> Result<Option<String>, String> {
-    let conn = db.conn.lock().to_str_err()?;
-    get_album_art_file_path(&conn, album_id).to_str_err()
+) -> Result<Option<String>, AudionError> {
+    let conn = db.read()?;
+    get_album_art_file_path(&conn, album_id).map_err(AudionError::from)
 }
 
 #[tauri::command]
-pub async fn get_cover_as_asset_url(file_path: String) -> Result<String, String> {
+pub async fn get_cover_as_asset_url(file_path: String) -> Result<String, AudionError> {
     Ok(file_path)
 }
 
 #[tauri::command]
-pub async fn preload_covers(_track_ids: Vec<i64>, _db: State<'_, Database>) -> Result<(), String> {
+pub async fn preload_covers(_track_ids: Vec<i64>, _db: State<'_, Database>) -> Result<(), AudionError> {
     Ok(())
 }
 
 #[tauri::command]
-pub async fn cleanup_orphaned_cover_files(db: State<'_, Database>) -> Result<usize, String> {
-    let conn = db.conn.lock().to_str_err()?;
-    cleanup_orphaned_covers(&conn).to_str_err()
+pub async fn cleanup_orphaned_cover_files(db: State<'_, Database>) -> Result<usize, AudionError> {
+    let conn = db.read()?;
+    cleanup_orphaned_covers(&conn)
 }
 
 #[tauri::command]
-pub async fn clear_base64_covers(db: State<'_, Database>) -> Result<usize, String> {
-    let conn = db.conn.lock().to_str_err()?;
+pub async fn clear_base64_covers(db: State<'_, Database>) -> Result<usize, AudionError> {
+    let conn = db.write()?;
 
     let tracks_cleared = conn
         .execute(
             "UPDATE tracks SET track_cover = NULL WHERE track_cover_path IS NOT NULL",
             [],
         )
-        .map_err(|e| format!("Failed to clear track covers: {}", e))?;
+        .context("Failed to clear track covers")?;
 
     let albums_cleared = conn
         .execute(
             "UPDATE albums SET art_data = NULL WHERE art_path IS NOT NULL",
             [],
         )
-        .map_err(|e| format!("Failed to clear album art: {}", e))?;
+        .context("Failed to clear album art")?;
 
     let total_cleared = tracks_cleared + albums_cleared;
     println!(
@@ -0,0 +1,55 @@
+// Play history commands
+use crate::db::history::{self, HistoryQuery, NewPlayEvent, PlayEvent, PlayStats};
+use crate::db::smart_playlists::{self, RuleField};
+use crate::db::Database;
+use crate::error::AudionError;
+use tauri::State;
+
+/// What a playback (or forgetting one) changes on the track
+const PLAY_FIELDS: &[RuleField] = &[
+    RuleField::PlayCount,
+    RuleField::SkipCount,
+    RuleField::LastPlayed,
+];
+
+/// Record a playback when it ends (finished, skipped or stopped). Returns the event id.
+#[tauri::command]
+pub async fn record_play_event(
+    event: NewPlayEvent,
+    db: State<'_, Database>,
+) -> Result<i64, AudionError> {
+    let conn = db.write()?;
+    let id = history::record_play_event(&conn, &event)?;
+    smart_playlists::refresh_using(&conn, PLAY_FIELDS)?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn get_play_history(
+    query: Option<HistoryQuery>,
+    db: State<'_, Database>,
+) -> Result<Vec<PlayEvent>, AudionError> {
+    let conn = db.read()?;
+    history::get_play_history(&conn, &query.unwrap_or_default()).map_err(AudionError::from)
+}
+
+#[tauri::command]
+pub async fn get_play_stats(
+    track_ids: Vec<i64>,
+    db: State<'_, Database>,
+) -> Result<Vec<PlayStats>, AudionError> {
+    let conn = db.read()?;
+    history::get_play_stats(&conn, &track_ids).map_err(AudionError::from)
+}
+
+/// Forget all history, or one track's. Play counts are reset to match.
+#[tauri::command]
+pub async fn clear_play_history(
+    track_id: Option<i64>,
+    db: State<'_, Database>,
+) -> Result<usize, AudionError> {
+    let conn = db.write()?;
+    let cleared = history::clear_play_history(&conn, track_id)?;
+    smart_playlists::refresh_using(&conn, PLAY_FIELDS)?;
+    Ok(cleared)
+}
@@ -0,0 +1,109 @@
+// Label commands. Tracks are listed by label through `browse_tracks` with a
+// `label_id` filter, or searched with `label:`.
+use crate::db::labels::{self, Label, LabelTarget};
+use crate::db::smart_playlists::{self, RuleField};
+use crate::db::Database;
+use crate::error::AudionError;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_labels(db: State<'_, Database>) -> Result<Vec<Label>, AudionError> {
+    let conn = db.read()?;
+    labels::list(&conn).map_err(AudionError::from)
+}
+
+/// Labels linked directly to a track or album (a track's album labels aren't included)
+#[tauri::command]
+pub async fn get_item_labels(
+    target: LabelTarget,
+    id: i64,
+    db: State<'_, Database>,
+) -> Result<Vec<Label>, AudionError> {
+    let conn = db.read()?;
+    labels::labels_of(&conn, target, id).map_err(AudionError::from)
+}
+
+#[tauri::command]
+pub async fn create_label(
+    name: String,
+    color: Option<String>,
+    db: State<'_, Database>,
+) -> Result<i64, AudionError> {
+    let conn = db.write()?;
+    labels::create(&conn, &name, color.as_deref())
+}
+
+#[tauri::command]
+pub async fn rename_label(
+    label_id: i64,
+    name: String,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    labels::rename(&conn, label_id, &name)?;
+    smart_playlists::refresh_using(&conn, &[RuleField::Label])
+}
+
+#[tauri::command]
+pub async fn set_label_color(
+    label_id: i64,
+    color: Option<String>,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    labels::set_color(&conn, label_id, color.as_deref())
+}
+
+/// Relabel everything labelled `source_id` with `target_id`, then delete the source
+#[tauri::command]
+pub async fn merge_labels(
+    source_id: i64,
+    target_id: i64,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    labels::merge(&tx, source_id, target_id)?;
+    smart_playlists::refresh_using(&tx, &[RuleField::Label])?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_label(label_id: i64, db: State<'_, Database>) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    labels::delete(&conn, label_id)?;
+    smart_playlists::refresh_using(&conn, &[RuleField::Label])
+}
+
+/// Link each label to each of the tracks or albums; returns how many links were added
+#[tauri::command]
+pub async fn assign_labels(
+    label_ids: Vec<i64>,
+    target: LabelTarget,
+    ids: Vec<i64>,
+    db: State<'_, Database>,
+) -> Result<usize, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    let added = labels::assign(&tx, &label_ids, target, &ids)?;
+    smart_playlists::refresh_using(&tx, &[RuleField::Label])?;
+    tx.commit()?;
+    Ok(added)
+}
+
+/// Returns how many links were removed
+#[tauri::command]
+pub async fn unassign_labels(
+    label_ids: Vec<i64>,
+    target: LabelTarget,
+    ids: Vec<i64>,
+    db: State<'_, Database>,
+) -> Result<usize, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    let removed = labels::unassign(&tx, &label_ids, target, &ids)?;
+    smart_playlists::refresh_using(&tx, &[RuleField::Label])?;
+    tx.commit()?;
+    Ok(removed)
+}
@@ -1,5 +1,10 @@
 // Library-related Tauri commands
-use crate::db::{queries, Database};
+use crate::db::browse::{self, AlbumPageRequest, ArtistPageRequest, Page, TrackPageRequest};
+use crate::db::removed::{self, LibraryItem, RemovedItems, RemovedOptions};
+use crate::db::smart_playlists;
+use crate::db::sort_names::{self, SortNameOptions};
+use crate::db::{fuzzy, queries, search, Database};
+use crate::error::{AudionError, ResultExt};
 use crate::scanner::{cover_storage, extract_metadata, scan_directory};
 use crate::security;
 use serde::{Deserialize, Serialize};
@@ -75,7 +80,7 @@ fn calculate_batch_size(
 }
 
 #[tauri::command]
-pub async fn scan_music(paths: Vec<String>, db: State<'_, Database>) -> Result<ScanResult, String> {
+pub async fn scan_music(paths: Vec<String>, db: State<'_, Database>) -> Result<ScanResult, AudionError> {
     let mut tracks_added = 0;
     let mut tracks_updated = 0;
     let mut errors = Vec::new();
@@ -91,7 +96,7 @@ pub async fn scan_music(paths: Vec<String>, db: State<'_, Database>) -> Result<S
 
         tokio::task::spawn_blocking(move || {
             let scan_result = scan_directory(&path_clone);
-            let conn = db_clone.conn.lock().unwrap();
+            let conn = db_clone.write().unwrap();
 
             // Add folder to database
             let _ = queries::add_music_folder(&conn, &path_clone);
@@ -174,7 +179,7 @@ pub async fn scan_music(paths: Vec<String>, db: State<'_, Database>) -> Result<S
     }
 
     // Cleanup after scan
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let conn = db.write()?;
     let tracks_deleted = queries::cleanup_deleted_tracks(&conn, &paths)
         .unwrap_or_else(|e| {
             errors.push(format!("Failed to cleanup deleted tracks: {}", e));
@@ -182,6 +187,10 @@ pub async fn scan_music(paths: Vec<String>, db: State<'_, Database>) -> Result<S
         });
     let _ = queries::cleanup_empty_albums(&conn);
 
+    if let Err(e) = smart_playlists::refresh_all(&conn) {
+        errors.push(format!("Failed to refresh smart playlists: {}", e));
+    }
+
     Ok(ScanResult {
         tracks_added,
         tracks_updated,
@@ -192,28 +201,28 @@ pub async fn scan_music(paths: Vec<String>, db: State<'_, Database>) -> Result<S
 
 /// Add a music folder with path validation
 #[tauri::command]
-pub async fn add_folder(path: String, db: State<'_, Database>) -> Result<(), String> {
+pub async fn add_folder(path: String, db: State<'_, Database>) -> Result<(), AudionError> {
     let path_buf = std::path::PathBuf::from(&path);
     
     // Validate path exists and is a directory
     if !path_buf.exists() {
-        return Err("Invalid path: Does not exist".to_string());
+        return Err(AudionError::NotFound("Invalid path: Does not exist".to_string()));
     }
     
     if !path_buf.is_dir() {
-        return Err("Invalid path: Not a directory".to_string());
+        return Err(AudionError::InvalidInput("Invalid path: Not a directory".to_string()));
     }
 
     // Canonicalize path to prevent traversal/obfuscation
     let canonical_path = path_buf
         .canonicalize()
-        .map_err(|e| format!("Failed to resolve path: {}", e))?;
+        .context("Failed to resolve path")?;
     
     let path_str = canonical_path.to_string_lossy().to_string();
 
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let conn = db.write()?;
     queries::add_music_folder(&conn, &path_str)
-        .map_err(|e| format!("Failed to add folder: {}", e))?;
+        .context("Failed to add folder")?;
     
     Ok(())
 }
@@ -222,18 +231,18 @@ pub async fn add_folder(path: String, db: State<'_, Database>) -> Result<(), Str
 pub async fn rescan_music(
     window: tauri::Window,
     db: State<'_, Database>,
-) -> Result<ScanResult, String> {
+) -> Result<ScanResult, AudionError> {
     let total_start = Instant::now();
 
     // 1: Cleanup
     let (folders, tracks_deleted) = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let conn = db.write()?;
 
     // Get all scanned folders
-        let folders = queries::get_music_folders(&conn).map_err(|e| e.to_string())?;
+        let folders = queries::get_music_folders(&conn)?;
 
         let tracks_deleted = queries::cleanup_deleted_tracks(&conn, &folders)
-            .map_err(|e| format!("Failed to cleanup deleted tracks: {}", e))?;
+            .context("Failed to cleanup deleted tracks")?;
 
     // Clean up empty albums after track cleanup
         let _ = queries::cleanup_empty_albums(&conn);
@@ -278,7 +287,7 @@ pub async fn rescan_music(
 
     // 4: Batch assembly + DB writes + frontend updates
     let window_clone = window.clone();
-    let db_conn = Arc::clone(&db.conn);
+    let db_writer = db.inner().clone();
     let folders_clone = folders.clone();
     let total_start_clone = total_start;
 
@@ -290,8 +299,6 @@ pub async fn rescan_music(
         let mut errors = Vec::new();
         let mut pending = Vec::new();
 
-        let mut conn = db_conn.lock().unwrap();
-
         loop {
             // Collect one batch from the channel
             let queue_depth = rx.len();
@@ -313,7 +320,8 @@ pub async fn rescan_music(
                 break; // nothing left anywhere
             }
 
-            // Single transaction for the whole batch
+            // Single transaction for the whole batch; the writer is only held per batch
+            let mut conn = db_writer.write().unwrap();
             let tx_db = conn.transaction().unwrap();
             let mut batch_tracks = Vec::new();
 
@@ -398,6 +406,7 @@ pub async fn rescan_music(
             }
 
             tx_db.commit().unwrap();
+            drop(conn);
 
             // Emit batch to frontend
             tracks_sent += batch_tracks.len();
@@ -428,6 +437,7 @@ pub async fn rescan_music(
         }
 
         // Update folder timestamps
+        let conn = db_writer.write().unwrap();
         for folder in &folders_clone {
             if let Err(e) = queries::update_folder_last_scanned(&conn, folder) {
                 errors.push(format!("Scan time update failed for {}: {}", folder, e));
@@ -435,11 +445,15 @@ pub async fn rescan_music(
         }
 
         (tracks_added, tracks_updated, batches_sent, errors)
-    }).await.map_err(|e| e.to_string())?;
+    }).await?;
 
     let (tracks_added, tracks_updated, _batches_sent, mut errors) = batch_result;
     errors.extend(scan_errors);
 
+    if let Err(e) = smart_playlists::refresh_all(&*db.write()?) {
+        errors.push(format!("Failed to refresh smart playlists: {}", e));
+    }
+
     // Emit completion event
     let _ = window.emit("scan-complete", ScanResult {
         tracks_added,
@@ -449,9 +463,9 @@ pub async fn rescan_music(
     });
 
     // Background orphan cleanup (non-blocking)
-    let db_conn_cleanup = Arc::clone(&db.conn);
+    let db_cleanup = db.inner().clone();
     tauri::async_runtime::spawn(async move {
-        if let Ok(conn) = db_conn_cleanup.lock() {
+        if let Ok(conn) = db_cleanup.read() {
             let _ = cover_storage::cleanup_orphaned_covers(&conn);
         }
     });
@@ -465,25 +479,22 @@ pub async fn rescan_music(
 }
 
 #[tauri::command]
-pub async fn get_library(db: State<'_, Database>) -> Result<Library, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    // Ensure FTS is initialized on first load
-    let _ = queries::init_fts(&conn);
+pub async fn get_library(db: State<'_, Database>) -> Result<Library, AudionError> {
+    let conn = db.read()?;
 
     // Fetch tracks WITHOUT cover data (ultra-fast)
-    let tracks = queries::get_all_tracks_with_paths(&conn).map_err(|e| e.to_string())?;
+    let tracks = queries::get_all_tracks_with_paths(&conn)?;
 
     // Fetch albums WITHOUT art data (fast)
-    let albums = queries::get_all_albums_with_paths(&conn).map_err(|e| e.to_string())?;
+    let albums = queries::get_all_albums_with_paths(&conn)?;
 
     // Fetch artists
-    let artists = queries::get_all_artists(&conn).map_err(|e| e.to_string())?;
+    let artists = queries::get_all_artists(&conn)?;
 
     // Background orphan cleanup
-    let db_conn_cleanup = db.conn.clone();
+    let db_cleanup = db.inner().clone();
     tauri::async_runtime::spawn(async move {
-        if let Ok(conn) = db_conn_cleanup.lock() {
+        if let Ok(conn) = db_cleanup.read() {
             let _ = cover_storage::cleanup_orphaned_covers(&conn);
         }
     });
@@ -496,9 +507,9 @@ pub async fn get_tracks_paginated(
     limit: i32,
     offset: i32,
     db: State<'_, Database>,
-) -> Result<Vec<queries::Track>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::get_tracks_paginated(&conn, limit, offset).map_err(|e| e.to_string())
+) -> Result<Vec<queries::Track>, AudionError> {
+    let conn = db.read()?;
+    queries::get_tracks_paginated(&conn, limit, offset).map_err(AudionError::from)
 }
 
 #[tauri::command]
@@ -506,65 +517,132 @@ pub async fn get_albums_paginated(
     limit: i32,
     offset: i32,
     db: State<'_, Database>,
-) -> Result<Vec<queries::Album>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::get_albums_paginated(&conn, limit, offset).map_err(|e| e.to_string())
+) -> Result<Vec<queries::Album>, AudionError> {
+    let conn = db.read()?;
+    queries::get_albums_paginated(&conn, limit, offset).map_err(AudionError::from)
+}
+
+#[tauri::command]
+pub async fn get_sort_name_options(db: State<'_, Database>) -> Result<SortNameOptions, AudionError> {
+    let conn = db.read()?;
+    sort_names::load_options(&conn).map_err(AudionError::from)
 }
 
+/// Change which leading articles sorting ignores. Sort names that didn't come
+/// from tags are regenerated right away.
+#[tauri::command]
+pub async fn set_sort_name_options(
+    options: SortNameOptions,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    sort_names::save_options(&tx, &options).context("Failed to update sort names")?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// A sorted, filtered page of tracks. Pass `next_cursor` back in the request
+/// for the following page.
+#[tauri::command]
+pub async fn browse_tracks(
+    request: TrackPageRequest,
+    db: State<'_, Database>,
+) -> Result<Page<queries::Track>, AudionError> {
+    let conn = db.read()?;
+    browse::browse_tracks(&conn, &request)
+}
+
+#[tauri::command]
+pub async fn browse_albums(
+    request: AlbumPageRequest,
+    db: State<'_, Database>,
+) -> Result<Page<queries::Album>, AudionError> {
+    let conn = db.read()?;
+    browse::browse_albums(&conn, &request)
+}
+
+#[tauri::command]
+pub async fn browse_artists(
+    request: ArtistPageRequest,
+    db: State<'_, Database>,
+) -> Result<Page<queries::Artist>, AudionError> {
+    let conn = db.read()?;
+    browse::browse_artists(&conn, &request)
+}
+
+/// Search tracks. Besides plain words the query accepts field filters and
+/// comparisons (`artist:radiohead year:>=2000 duration:<4:00`), `-` to exclude,
+/// `OR`, parentheses and "quoted phrases". Syntax errors come back as
+/// `invalid_query` with the character range of the problem. Plain-text searches
+/// with few results are topped up with near matches for typos.
 #[tauri::command]
 pub async fn search_library(
     query: String,
     limit: i32,
     offset: i32,
     db: State<'_, Database>,
-) -> Result<Vec<queries::Track>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::search_tracks(&conn, &query, limit, offset).map_err(|e| e.to_string())
+) -> Result<Vec<queries::Track>, AudionError> {
+    let Some(search) = search::compile_query(&query)? else {
+        return Ok(Vec::new());
+    };
+    let conn = db.read()?;
+    let mut tracks = queries::search_tracks(&conn, &search, limit, offset)?;
+    if offset == 0 {
+        fuzzy::add_near_matches(&conn, &search, &mut tracks, limit.max(0) as usize)?;
+    }
+    Ok(tracks)
+}
+
+/// Rebuild the full-text search index from the tracks table
+#[tauri::command]
+pub async fn rebuild_search_index(db: State<'_, Database>) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    queries::rebuild_search_index(&conn).context("Failed to rebuild search index")
 }
 
 #[tauri::command]
 pub async fn get_tracks_by_album(
     album_id: i64,
     db: State<'_, Database>,
-) -> Result<Vec<queries::Track>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::get_tracks_by_album(&conn, album_id).map_err(|e| e.to_string())
+) -> Result<Vec<queries::Track>, AudionError> {
+    let conn = db.read()?;
+    queries::get_tracks_by_album(&conn, album_id).map_err(AudionError::from)
 }
 
 #[tauri::command]
 pub async fn get_tracks_by_artist(
     artist: String,
     db: State<'_, Database>,
-) -> Result<Vec<queries::Track>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::get_tracks_by_artist(&conn, &artist).map_err(|e| e.to_string())
+) -> Result<Vec<queries::Track>, AudionError> {
+    let conn = db.read()?;
+    queries::get_tracks_by_artist(&conn, &artist).map_err(AudionError::from)
 }
 
 #[tauri::command]
 pub async fn get_album(
     album_id: i64,
     db: State<'_, Database>,
-) -> Result<Option<queries::Album>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::get_album_by_id(&conn, album_id).map_err(|e| e.to_string())
+) -> Result<Option<queries::Album>, AudionError> {
+    let conn = db.read()?;
+    queries::get_album_by_id(&conn, album_id).map_err(AudionError::from)
 }
 
 #[tauri::command]
 pub async fn get_albums_by_artist(
     artist: String,
     db: State<'_, Database>,
-) -> Result<Vec<queries::Album>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+) -> Result<Vec<queries::Album>, AudionError> {
+    let conn = db.read()?;
 
     let mut stmt = conn
         .prepare(
             "SELECT DISTINCT a.id, a.name, a.artist, a.art_data, a.art_path 
              FROM albums a
              INNER JOIN tracks t ON t.album_id = a.id
-             WHERE t.artist = ?1
-             ORDER BY a.name",
-        )
-        .map_err(|e| e.to_string())?;
+             WHERE t.artist = ?1 AND t.removed_at IS NULL
+             ORDER BY a.sort_name COLLATE SORTNAME",
+        )?;
 
     let albums = stmt
         .query_map([&artist], |row| {
@@ -575,18 +653,41 @@ pub async fn get_albums_by_artist(
                 art_data: row.get(3)?,
                 art_path: row.get(4)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(albums)
 }
 
-/// Delete a track from the library (moves file to trash for safety)
+/// Remove a track from the library. It stays in recently removed, with its
+/// playlist entries and stats, until it's restored or expires; the file is
+/// left alone (see `trash_track_file`).
 #[tauri::command]
-pub async fn delete_track(track_id: i64, db: State<'_, Database>) -> Result<bool, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub async fn delete_track(track_id: i64, db: State<'_, Database>) -> Result<bool, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    removed::remove_track(&tx, track_id)?;
+    tx.commit()?;
+    log::info!("[AUDIT] Track {} removed from library", track_id);
+    Ok(true)
+}
+
+/// Remove an album and its tracks from the library, restorably (see `delete_track`)
+#[tauri::command]
+pub async fn delete_album(album_id: i64, db: State<'_, Database>) -> Result<bool, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    removed::remove_album(&tx, album_id)?;
+    tx.commit()?;
+    log::info!("[AUDIT] Album {} removed from library", album_id);
+    Ok(true)
+}
+
+/// Move a local track's file to the trash and delete the track for good,
+/// playlist entries and stats included. Works on removed tracks too.
+#[tauri::command]
+pub async fn trash_track_file(track_id: i64, db: State<'_, Database>) -> Result<bool, AudionError> {
+    let conn = db.write()?;
 
     // Get track info before deletion
     let track_info: Option<(String, Option<String>, Option<String>)> = conn
@@ -615,7 +716,7 @@ pub async fn delete_track(track_id: i64, db: State<'_, Database>) -> Result<bool
     }
 
     let result = queries::delete_track(&conn, track_id)
-        .map_err(|e| format!("Failed to delete track: {}", e))?;
+        .context("Failed to delete track")?;
 
     // Clean up empty albums after track deletion
     let _ = queries::cleanup_empty_albums(&conn);
@@ -624,10 +725,11 @@ pub async fn delete_track(track_id: i64, db: State<'_, Database>) -> Result<bool
     Ok(result)
 }
 
-/// Delete an album and all its tracks (moves files to trash for safety)
+/// Move the files of an album's local tracks to the trash and delete the
+/// album and its tracks for good, including any already removed
 #[tauri::command]
-pub async fn delete_album(album_id: i64, db: State<'_, Database>) -> Result<bool, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub async fn trash_album_files(album_id: i64, db: State<'_, Database>) -> Result<bool, AudionError> {
+    let conn = db.write()?;
 
     // Get album art path before deletion
     let art_path: Option<String> = conn
@@ -639,37 +741,103 @@ pub async fn delete_album(album_id: i64, db: State<'_, Database>) -> Result<bool
         .ok()
         .flatten();
 
-    // Get all tracks for this album to delete files
-    let tracks = queries::get_tracks_by_album(&conn, album_id).map_err(|e| e.to_string())?;
+    // Get all tracks for this album to delete files, removed ones included
+    let tracks = conn
+        .prepare("SELECT path, source_type, track_cover_path FROM tracks WHERE album_id = ?1")?
+        .query_map([album_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     log::info!("[AUDIT] Deleting album {} with {} tracks", album_id, tracks.len());
 
-    for track in tracks {
+    for (path, source_type, cover_path) in tracks {
         // Only delete file if it's a local track
-        let is_local = track.source_type.is_none() || track.source_type.as_deref() == Some("local");
+        let is_local = source_type.is_none() || source_type.as_deref() == Some("local");
 
         if is_local {
-            let path_obj = std::path::Path::new(&track.path);
+            let path_obj = std::path::Path::new(&path);
             // Use secure deletion (moves to trash with path validation)
             if let Err(e) = security::safe_delete_file(path_obj) {
-                log::error!("[AUDIT] Failed to delete track file {}: {}", track.path, e);
+                log::error!("[AUDIT] Failed to delete track file {}: {}", path, e);
                 // Continue with other tracks
             }
         }
 
         // Delete track cover file
-        let _ = cover_storage::delete_track_cover_file(track.track_cover_path.as_deref());
+        let _ = cover_storage::delete_track_cover_file(cover_path.as_deref());
     }
 
     // Delete album art file
     let _ = cover_storage::delete_album_art_file(art_path.as_deref());
 
-    let result = queries::delete_album(&conn, album_id).map_err(|e| format!("Failed to delete album: {}", e))?;
+    let result = queries::delete_album(&conn, album_id).context("Failed to delete album")?;
     
     log::info!("[AUDIT] Album {} deleted from library", album_id);
     Ok(result)
 }
 
+/// Tracks and albums that can still be restored
+#[tauri::command]
+pub async fn get_removed_items(db: State<'_, Database>) -> Result<RemovedItems, AudionError> {
+    let conn = db.read()?;
+    removed::removed(&conn).map_err(AudionError::from)
+}
+
+/// Put a removed track (and its album, if that was removed) back in the library
+#[tauri::command]
+pub async fn restore_track(track_id: i64, db: State<'_, Database>) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    removed::restore_track(&conn, track_id)
+}
+
+/// Put a removed album back in the library with the tracks removed along with it
+#[tauri::command]
+pub async fn restore_album(album_id: i64, db: State<'_, Database>) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    removed::restore_album(&tx, album_id)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Delete a removed track or album without waiting for it to expire. Files
+/// are left alone.
+#[tauri::command]
+pub async fn delete_removed_permanently(
+    item: LibraryItem,
+    id: i64,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    removed::delete_permanently(&tx, item, id)?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_removed_options(db: State<'_, Database>) -> Result<RemovedOptions, AudionError> {
+    let conn = db.read()?;
+    removed::load_options(&conn).map_err(AudionError::from)
+}
+
+/// Save the options; a shorter retention period takes effect right away
+#[tauri::command]
+pub async fn set_removed_options(
+    options: RemovedOptions,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    removed::save_options(&conn, &options)?;
+    removed::purge_expired(&conn)?;
+    Ok(())
+}
+
 /// Input for adding an external (streaming) track to the library
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExternalTrackInput {
@@ -692,11 +860,11 @@ pub struct ExternalTrackInput {
 pub async fn add_external_track(
     track: ExternalTrackInput,
     db: State<'_, Database>,
-) -> Result<i64, String> {
+) -> Result<i64, AudionError> {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
 
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let conn = db.write()?;
 
     // Use stream_url as path if provided, otherwise construct from source_type://external_id
     let path = track
@@ -723,6 +891,13 @@ pub async fn add_external_track(
         album: track.album,
         track_number: None,
         duration: track.duration,
+        year: None,
+        genre: None,
+        sort_title: None,
+        sort_artist: None,
+        sort_album: None,
+        rating: None,
+        isrc: None,
         album_art: None,   // External tracks use cover_url instead
         track_cover: None, // External tracks use cover_url instead
         format: track.format,
@@ -736,24 +911,29 @@ pub async fn add_external_track(
 
     queries::insert_or_update_track(&conn, &track_insert)
         .map(|(track_id, _was_new)| track_id)
-        .map_err(|e| format!("Failed to add external track: {}", e))
+        .context("Failed to add external track")
 }
 
 /// Reset the database by clearing all data
 #[tauri::command]
-pub async fn reset_database(db: State<'_, Database>) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub async fn reset_database(db: State<'_, Database>) -> Result<(), AudionError> {
+    let conn = db.write()?;
 
     conn.execute_batch(
         "
         DELETE FROM playlist_tracks;
         DELETE FROM playlists;
+        DELETE FROM playlist_folders;
+        DELETE FROM play_events;
+        DELETE FROM play_queue;
+        DELETE FROM play_queue_state;
         DELETE FROM tracks;
         DELETE FROM albums;
         DELETE FROM music_folders;
+        DELETE FROM labels;
         ",
     )
-    .map_err(|e| format!("Failed to reset database: {}", e))?;
+    .context("Failed to reset database")?;
 
     Ok(())
 }
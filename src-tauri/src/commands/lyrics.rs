@@ -4,6 +4,8 @@ use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 use tauri::{AppHandle, Manager};
 
+use crate::error::{AudionError, ResultExt};
+
 /// Get LRC file path for a music file or URL
 fn resolve_lrc_path(app: &AppHandle, music_path: &str) -> PathBuf {
     // Check if it's a real local file that exists
@@ -37,17 +39,17 @@ pub fn save_lrc_file(
     app: AppHandle,
     music_path: String,
     lrc_content: String,
-) -> Result<(), String> {
+) -> Result<(), AudionError> {
     let lrc_path = resolve_lrc_path(&app, &music_path);
 
-    fs::write(&lrc_path, lrc_content).map_err(|e| format!("Failed to save LRC file: {}", e))?;
+    fs::write(&lrc_path, lrc_content).context("Failed to save LRC file")?;
 
     Ok(())
 }
 
 /// Load LRC file if it exists
 #[tauri::command]
-pub fn load_lrc_file(app: AppHandle, music_path: String) -> Result<Option<String>, String> {
+pub fn load_lrc_file(app: AppHandle, music_path: String) -> Result<Option<String>, AudionError> {
     let lrc_path = resolve_lrc_path(&app, &music_path);
 
     if !lrc_path.exists() {
@@ -55,21 +57,21 @@ pub fn load_lrc_file(app: AppHandle, music_path: String) -> Result<Option<String
     }
 
     let content =
-        fs::read_to_string(&lrc_path).map_err(|e| format!("Failed to read LRC file: {}", e))?;
+        fs::read_to_string(&lrc_path).context("Failed to read LRC file")?;
 
     Ok(Some(content))
 }
 
 /// Delete LRC file for a music file
 #[tauri::command]
-pub fn delete_lrc_file(app: AppHandle, music_path: String) -> Result<bool, String> {
+pub fn delete_lrc_file(app: AppHandle, music_path: String) -> Result<bool, AudionError> {
     let lrc_path = resolve_lrc_path(&app, &music_path);
 
     if !lrc_path.exists() {
         return Ok(false);
     }
 
-    fs::remove_file(&lrc_path).map_err(|e| format!("Failed to delete LRC file: {}", e))?;
+    fs::remove_file(&lrc_path).context("Failed to delete LRC file")?;
     Ok(true)
 }
 
@@ -78,13 +80,13 @@ pub fn delete_lrc_file(app: AppHandle, music_path: String) -> Result<bool, Strin
 pub async fn musixmatch_request(
     action: String,
     params: Vec<(String, String)>,
-) -> Result<String, String> {
+) -> Result<String, AudionError> {
     // Build a client with cookie store and proper redirect policy
     let client = reqwest::Client::builder()
         .cookie_store(true)
         .redirect(reqwest::redirect::Policy::limited(10))
         .build()
-        .map_err(|e| format!("Failed to create client: {}", e))?;
+        .context("Failed to create client")?;
 
     let url = format!("https://apic-desktop.musixmatch.com/ws/1.1/{}", action);
 
@@ -110,12 +112,12 @@ pub async fn musixmatch_request(
         .header("Referer", "https://www.musixmatch.com/")
         .send()
         .await
-        .map_err(|e| format!("Request failed: {}", e))?;
+        .context("Request failed")?;
 
     let text = response
         .text()
         .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+        .context("Failed to read response")?;
 
     Ok(text)
 }
@@ -282,7 +284,7 @@ fn parse_lrc_content(lrc_content: &str) -> Vec<LyricLineJson> {
 pub fn get_lyrics(
     app: AppHandle,
     music_path: String,
-) -> Result<Option<Vec<LyricLineJson>>, String> {
+) -> Result<Option<Vec<LyricLineJson>>, AudionError> {
     let lrc_path = resolve_lrc_path(&app, &music_path);
 
     if !lrc_path.exists() {
@@ -290,7 +292,7 @@ pub fn get_lyrics(
     }
 
     let content =
-        fs::read_to_string(&lrc_path).map_err(|e| format!("Failed to read LRC file: {}", e))?;
+        fs::read_to_string(&lrc_path).context("Failed to read LRC file")?;
 
     let lyrics = parse_lrc_content(&content);
 
@@ -303,7 +305,7 @@ pub fn get_current_lyric(
     app: AppHandle,
     music_path: String,
     current_time: f64,
-) -> Result<Option<CurrentLyricJson>, String> {
+) -> Result<Option<CurrentLyricJson>, AudionError> {
     let lrc_path = resolve_lrc_path(&app, &music_path);
 
     if !lrc_path.exists() {
@@ -311,7 +313,7 @@ pub fn get_current_lyric(
     }
 
     let content =
-        fs::read_to_string(&lrc_path).map_err(|e| format!("Failed to read LRC file: {}", e))?;
+        fs::read_to_string(&lrc_path).context("Failed to read LRC file")?;
 
     let lyrics = parse_lrc_content(&content);
 
@@ -1,5 +1,6 @@
 // Audio save and metadata commands
 use futures::StreamExt;
+use lofty::file::TaggedFile;
 use lofty::prelude::*;
 use lofty::probe::Probe;
 use lofty::picture::{MimeType, Picture, PictureType};
@@ -12,6 +13,8 @@ use std::path::{Path, PathBuf};
 use tauri::{command, AppHandle, Emitter, State};
 
 use crate::db::{self, Database};
+use crate::error::{AudionError, ResultExt};
+use crate::scanner::metadata;
 
 #[derive(serde::Deserialize)]
 pub struct DownloadAudioInput {
@@ -35,7 +38,7 @@ struct DownloadProgress {
 pub async fn download_and_save_audio(
     app: AppHandle,
     input: DownloadAudioInput,
-) -> Result<String, String> {
+) -> Result<String, AudionError> {
     let path = std::path::Path::new(&input.path);
 
     // Security: Validate path to prevent directory traversal
@@ -47,9 +50,11 @@ pub async fn download_and_save_audio(
             parent,
             path.file_name().unwrap_or_default().to_str().unwrap_or(""),
         )
-        .map_err(|e| format!("Security Error: {}", e))?;
+        .context("Security Error")?;
     } else {
-        return Err("Invalid path: No parent directory".to_string());
+        return Err(AudionError::InvalidInput(
+            "Invalid path: No parent directory".to_string(),
+        ));
     }
 
     // Debug: Log input values
@@ -57,7 +62,7 @@ pub async fn download_and_save_audio(
 
     // Ensure parent directory exists
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+        fs::create_dir_all(parent).context("Failed to create directory")?;
     }
 
     // Download the audio file from URL with progress
@@ -83,7 +88,7 @@ pub async fn download_and_save_audio(
                     corrected.extension().and_then(|e| e.to_str()).unwrap_or("")
                 );
                 fs::rename(path, &corrected)
-                    .map_err(|e| format!("Failed to rename file to correct extension: {}", e))?;
+                    .context("Failed to rename file to correct extension")?;
                 
                 // Emit progress event after rename so frontend can match on the correct filename
                 let _ = app.emit(
@@ -174,28 +179,28 @@ async fn download_file_with_progress(
     app: &AppHandle,
     url: &str,
     file_path: &str,
-) -> Result<(), String> {
+) -> Result<(), AudionError> {
     let response = reqwest::get(url)
         .await
-        .map_err(|e| format!("Failed to download audio: {}", e))?;
+        .context("Failed to download audio")?;
 
     if !response.status().is_success() {
-        return Err(format!(
+        return Err(AudionError::Network(format!(
             "Download failed with status: {}",
             response.status()
-        ));
+        )));
     }
 
     let total_size = response.content_length().unwrap_or(0);
     let mut file =
-        fs::File::create(file_path).map_err(|e| format!("Failed to create file: {}", e))?;
+        fs::File::create(file_path).context("Failed to create file")?;
     let mut stream = response.bytes_stream();
     let mut downloaded: u64 = 0;
 
     while let Some(item) = stream.next().await {
-        let chunk = item.map_err(|e| format!("Error while downloading: {}", e))?;
+        let chunk = item.context("Error while downloading")?;
         file.write_all(&chunk)
-            .map_err(|e| format!("Error while writing to file: {}", e))?;
+            .context("Error while writing to file")?;
 
         downloaded += chunk.len() as u64;
 
@@ -218,10 +223,10 @@ pub async fn update_track_after_download(
     state: State<'_, Database>,
     track_id: i64,
     local_path: String,
-) -> Result<(), String> {
-    let conn = state.conn.lock().unwrap();
+) -> Result<(), AudionError> {
+    let conn = state.write()?;
     db::queries::update_track_after_download(&conn, track_id, &local_path)
-        .map_err(|e| format!("Failed to update track after download: {}", e))
+        .context("Failed to update track after download")
 }
 
 #[command]
@@ -229,31 +234,35 @@ pub async fn update_track_cover_url(
     state: State<'_, Database>,
     track_id: i64,
     cover_url: Option<String>,
-) -> Result<(), String> {
-    let conn = state.conn.lock().unwrap();
+) -> Result<(), AudionError> {
+    let conn = state.write()?;
     db::queries::update_track_cover_url(&conn, track_id, cover_url.as_deref())
-        .map_err(|e| format!("Failed to update cover URL: {}", e))
+        .context("Failed to update cover URL")
 }
 
-async fn write_metadata_to_file(path: &Path, input: &DownloadAudioInput) -> Result<(), String> {
-    // Read the file for metadata
-    let mut tagged_file = Probe::open(path)
+/// Read a file's tags for rewriting
+fn read_tagged_file(path: &Path) -> Result<TaggedFile, AudionError> {
+    Probe::open(path)
         .ok()
         .and_then(|p| p.guess_file_type().ok())
         .and_then(|p| p.read().ok())
-        .ok_or("Failed to read file for metadata")?;
+        .ok_or_else(|| AudionError::Decode("Failed to read file for metadata".to_string()))
+}
 
-    // Get or create primary tag
-    let tag = match tagged_file.primary_tag_mut() {
-        Some(tag) => tag,
-        None => {
-            let tag_type = tagged_file.primary_tag_type();
-            tagged_file.insert_tag(Tag::new(tag_type));
-            tagged_file
-                .primary_tag_mut()
-                .ok_or("Failed to create tag")?
-        }
-    };
+/// Get or create the primary tag
+fn primary_tag_mut(tagged_file: &mut TaggedFile) -> Result<&mut Tag, AudionError> {
+    if tagged_file.primary_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    tagged_file
+        .primary_tag_mut()
+        .ok_or_else(|| AudionError::Internal("Failed to create tag".to_string()))
+}
+
+async fn write_metadata_to_file(path: &Path, input: &DownloadAudioInput) -> Result<(), AudionError> {
+    let mut tagged_file = read_tagged_file(path)?;
+    let tag = primary_tag_mut(&mut tagged_file)?;
 
     // Set metadata
     if let Some(title) = &input.title {
@@ -289,34 +298,43 @@ async fn write_metadata_to_file(path: &Path, input: &DownloadAudioInput) -> Resu
 
     // Save the metadata
     tag.save_to_path(path, WriteOptions::default())
-        .map_err(|e| format!("Failed to save metadata: {}", e))?;
+        .context("Failed to save metadata")?;
 
     Ok(())
 }
 
-async fn download_cover(url: &str) -> Result<Vec<u8>, String> {
+/// Write a star rating into a file's tags (`None` removes it)
+pub fn write_rating_to_file(path: &Path, rating: Option<f64>) -> Result<(), AudionError> {
+    let mut tagged_file = read_tagged_file(path)?;
+    let tag = primary_tag_mut(&mut tagged_file)?;
+    metadata::set_rating(tag, rating);
+    tag.save_to_path(path, WriteOptions::default())
+        .context("Failed to save rating")
+}
+
+async fn download_cover(url: &str) -> Result<Vec<u8>, AudionError> {
     let response = reqwest::get(url)
         .await
-        .map_err(|e| format!("Failed to fetch cover: {}", e))?;
+        .context("Failed to fetch cover")?;
 
     let bytes = response
         .bytes()
         .await
-        .map_err(|e| format!("Failed to read cover: {}", e))?;
+        .context("Failed to read cover")?;
 
     Ok(bytes.to_vec())
 }
 
-async fn write_m4a_metadata(path: &Path, input: &DownloadAudioInput) -> Result<(), String> {
+async fn write_m4a_metadata(path: &Path, input: &DownloadAudioInput) -> Result<(), AudionError> {
     // imp: do NOT fall back to Mp4Tag::default() on failure.
     // Writing a bare default tag onto an existing MP4 file will corrupt it
     // because the default tag has no knowledge of the file's existing atom structure.
     // If read fails, the file is not a valid mp4 container
     let mut tag = Mp4Tag::read_from_path(path).map_err(|e| {
-        format!(
+        AudionError::Decode(format!(
             "Failed to read M4A container (file may not be a valid M4A/MP4): {}",
             e
-        )
+        ))
     })?;
 
     if let Some(title) = &input.title {
@@ -354,7 +372,7 @@ async fn write_m4a_metadata(path: &Path, input: &DownloadAudioInput) -> Result<(
     }
 
     tag.write_to_path(path)
-        .map_err(|e| format!("Failed to save M4A metadata: {}", e))?;
+        .map_err(|e| AudionError::Io(format!("Failed to save M4A metadata: {}", e)))?;
 
     Ok(())
 }
\ No newline at end of file
@@ -1,11 +1,20 @@
 // Tauri IPC commands
+pub mod analysis;
+pub mod history;
+pub mod labels;
 pub mod library;
 pub mod lyrics;
 pub mod metadata;
 pub mod network;
+pub mod play_queue;
 pub mod playlist;
+pub mod playlist_files;
 pub mod plugin;
+pub mod ratings;
 pub mod covers;
+pub mod search;
+pub mod stats;
+pub mod verify;
 
 pub use library::*;
 pub use lyrics::*;
@@ -15,3 +24,12 @@ pub use playlist::*;
 pub use plugin::*;
 pub mod window;
 pub use covers::*;
+pub use analysis::*;
+pub use verify::*;
+pub use search::*;
+pub use history::*;
+pub use stats::*;
+pub use ratings::*;
+pub use playlist_files::*;
+pub use play_queue::*;
+pub use labels::*;
@@ -2,6 +2,7 @@
 // These commands allow the frontend/plugins to make HTTP requests through the Rust backend,
 // bypassing browser CORS restrictions.
 
+use crate::error::{AudionError, ResultExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -22,13 +23,13 @@ pub struct ProxyFetchResponse {
 
 /// Proxy fetch command - makes HTTP requests from the Rust backend to bypass CORS
 #[tauri::command]
-pub async fn proxy_fetch(request: ProxyFetchRequest) -> Result<ProxyFetchResponse, String> {
+pub async fn proxy_fetch(request: ProxyFetchRequest) -> Result<ProxyFetchResponse, AudionError> {
     let client = reqwest::Client::new();
 
     let method = request.method.unwrap_or_else(|| "GET".to_string());
     let method = method
         .parse::<reqwest::Method>()
-        .map_err(|e| format!("Invalid HTTP method: {}", e))?;
+        .map_err(|e| AudionError::InvalidInput(format!("Invalid HTTP method: {}", e)))?;
 
     let mut req_builder = client.request(method, &request.url);
 
@@ -54,7 +55,7 @@ pub async fn proxy_fetch(request: ProxyFetchRequest) -> Result<ProxyFetchRespons
     let response = req_builder
         .send()
         .await
-        .map_err(|e| format!("Request failed: {}", e))?;
+        .context("Request failed")?;
 
     let status = response.status().as_u16();
 
@@ -69,7 +70,7 @@ pub async fn proxy_fetch(request: ProxyFetchRequest) -> Result<ProxyFetchRespons
     let body = response
         .text()
         .await
-        .map_err(|e| format!("Failed to read response body: {}", e))?;
+        .context("Failed to read response body")?;
 
     Ok(ProxyFetchResponse {
         status,
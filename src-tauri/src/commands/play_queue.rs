@@ -0,0 +1,43 @@
+// Saved play queue commands
+use crate::db::play_queue::{self, QueueState, SavedQueue};
+use crate::db::Database;
+use crate::error::AudionError;
+use tauri::State;
+
+/// Replace the saved queue in one transaction
+#[tauri::command]
+pub async fn save_play_queue(
+    state: QueueState,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    play_queue::save(&tx, &state)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Update where playback is in the saved queue, without saving the queue again
+#[tauri::command]
+pub async fn save_play_queue_position(
+    current_index: usize,
+    position: f64,
+    shuffle_index: Option<usize>,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    play_queue::save_position(&conn, current_index, position, shuffle_index)
+}
+
+/// The last saved session, with its tracks; `None` if there's nothing to restore
+#[tauri::command]
+pub async fn load_play_queue(db: State<'_, Database>) -> Result<Option<SavedQueue>, AudionError> {
+    let conn = db.read()?;
+    play_queue::load(&conn)
+}
+
+#[tauri::command]
+pub async fn clear_play_queue(db: State<'_, Database>) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    play_queue::clear(&conn).map_err(AudionError::from)
+}
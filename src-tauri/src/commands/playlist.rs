@@ -1,53 +1,85 @@
 // Playlist-related Tauri commands
+use crate::db::browse::{SortDirection, TrackSort};
+use crate::db::playlist_folders::{self, PlaylistTree};
+use crate::db::playlist_history::{
+    self, DeletedPlaylist, HistoryOptions, JournalEntry, PlaylistChange,
+};
+use crate::db::playlists::{self, PlaylistEntry};
+use crate::db::smart_playlists::{self, SmartRules};
 use crate::db::{queries, Database};
+use crate::error::AudionError;
+use crate::playlist_collage;
+use rusqlite::Transaction;
 use tauri::State;
-use rusqlite::params;
 
 #[tauri::command]
-pub async fn create_playlist(name: String, db: State<'_, Database>) -> Result<i64, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::create_playlist(&conn, &name).map_err(|e| e.to_string())
+pub async fn create_playlist(name: String, db: State<'_, Database>) -> Result<i64, AudionError> {
+    let conn = db.write()?;
+    queries::create_playlist(&conn, &name).map_err(AudionError::from)
 }
 
 #[tauri::command]
-pub async fn get_playlists(db: State<'_, Database>) -> Result<Vec<queries::Playlist>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::get_all_playlists(&conn).map_err(|e| e.to_string())
+pub async fn get_playlists(db: State<'_, Database>) -> Result<Vec<queries::Playlist>, AudionError> {
+    let conn = db.read()?;
+    queries::get_all_playlists(&conn).map_err(AudionError::from)
 }
 
 #[tauri::command]
 pub async fn get_playlist_tracks(
     playlist_id: i64,
     db: State<'_, Database>,
-) -> Result<Vec<queries::Track>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::get_playlist_tracks(&conn, playlist_id).map_err(|e| e.to_string())
+) -> Result<Vec<queries::Track>, AudionError> {
+    let conn = db.read()?;
+    queries::get_playlist_tracks(&conn, playlist_id).map_err(AudionError::from)
 }
 
+/// Tracks with their entry ids, in playlist order
+#[tauri::command]
+pub async fn get_playlist_entries(
+    playlist_id: i64,
+    db: State<'_, Database>,
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    let conn = db.read()?;
+    playlists::get_entries(&conn, playlist_id).map_err(AudionError::from)
+}
+
+/// Append a track (again, if it's already there). Returns the new entry's id.
 #[tauri::command]
 pub async fn add_track_to_playlist(
     playlist_id: i64,
     track_id: i64,
     db: State<'_, Database>,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::add_track_to_playlist(&conn, playlist_id, track_id).map_err(|e| e.to_string())
+) -> Result<i64, AudionError> {
+    let mut conn = db.write()?;
+    smart_playlists::ensure_static(&conn, playlist_id)?;
+    let tx = conn.transaction()?;
+    playlist_history::record(&tx, playlist_id, PlaylistChange::Add)?;
+    let entry_id = playlists::add(&tx, playlist_id, track_id)?;
+    tx.commit()?;
+    Ok(entry_id)
 }
 
 #[tauri::command]
-pub async fn remove_track_from_playlist(
+pub async fn remove_playlist_entry(
     playlist_id: i64,
-    track_id: i64,
+    entry_id: i64,
     db: State<'_, Database>,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::remove_track_from_playlist(&conn, playlist_id, track_id).map_err(|e| e.to_string())
+) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    smart_playlists::ensure_static(&conn, playlist_id)?;
+    let tx = conn.transaction()?;
+    playlist_history::record(&tx, playlist_id, PlaylistChange::Remove)?;
+    playlists::remove(&tx, playlist_id, entry_id)?;
+    tx.commit()?;
+    Ok(())
 }
 
+/// Move a playlist to recently deleted; it can be restored until the retention
+/// period runs out
 #[tauri::command]
-pub async fn delete_playlist(playlist_id: i64, db: State<'_, Database>) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::delete_playlist(&conn, playlist_id).map_err(|e| e.to_string())
+pub async fn delete_playlist(playlist_id: i64, db: State<'_, Database>) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    playlist_history::trash(&conn, playlist_id)
 }
 
 #[tauri::command]
@@ -55,97 +87,359 @@ pub async fn rename_playlist(
     playlist_id: i64,
     new_name: String,
     db: State<'_, Database>,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    queries::rename_playlist(&conn, playlist_id, &new_name).map_err(|e| e.to_string())
+) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    playlist_history::record(&tx, playlist_id, PlaylistChange::Rename)?;
+    queries::rename_playlist(&tx, playlist_id, &new_name)?;
+    tx.commit()?;
+    Ok(())
 }
 
+/// A cover generated from the art of the playlist's first albums, redrawn
+/// when they change. `None` when it has a custom cover or there's no art.
+#[tauri::command]
+pub async fn get_playlist_collage(
+    playlist_id: i64,
+    db: State<'_, Database>,
+) -> Result<Option<String>, AudionError> {
+    let Some(art) = playlist_collage::sources(&*db.read()?, playlist_id)? else {
+        return Ok(None);
+    };
+    let path =
+        tauri::async_runtime::spawn_blocking(move || playlist_collage::cached(playlist_id, &art))
+            .await??;
+    Ok(path.map(|path| path.to_string_lossy().into_owned()))
+}
+
+/// Set a custom cover (it takes the place of the generated one), or clear it
 #[tauri::command]
 pub async fn update_playlist_cover(
     playlist_id: i64,
     cover_url: Option<String>,
     db: State<'_, Database>,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
     queries::update_playlist_cover(&conn, playlist_id, cover_url.as_deref())
-        .map_err(|e| e.to_string())
+        .map_err(AudionError::from)
 }
 
-/// Reorder tracks in a playlist by moving a track from one position to another
+/// Move an entry to `to_index` (0-based)
+#[tauri::command]
+pub async fn move_playlist_entry(
+    playlist_id: i64,
+    entry_id: i64,
+    to_index: i64,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    smart_playlists::ensure_static(&conn, playlist_id)?;
+    let tx = conn.transaction()?;
+    playlist_history::record(&tx, playlist_id, PlaylistChange::Move)?;
+    playlists::move_entry(&tx, playlist_id, entry_id, to_index)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Run a bulk edit on a regular playlist in one transaction, journaled so it
+/// can be undone, and return the new order
+fn edit_playlist(
+    db: &Database,
+    playlist_id: i64,
+    change: PlaylistChange,
+    edit: impl FnOnce(&Transaction) -> Result<(), AudionError>,
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    let mut conn = db.write()?;
+    smart_playlists::ensure_static(&conn, playlist_id)?;
+    let tx = conn.transaction()?;
+    playlist_history::record(&tx, playlist_id, change)?;
+    edit(&tx)?;
+    let entries = playlists::get_entries(&tx, playlist_id)?;
+    tx.commit()?;
+    Ok(entries)
+}
+
+/// Insert tracks at `position` (0-based), or append them when it's omitted
+#[tauri::command]
+pub async fn add_tracks_to_playlist(
+    playlist_id: i64,
+    track_ids: Vec<i64>,
+    position: Option<i64>,
+    db: State<'_, Database>,
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    edit_playlist(&db, playlist_id, PlaylistChange::Add, |tx| {
+        playlists::add_many(tx, playlist_id, &track_ids, position).map(|_| ())
+    })
+}
+
+#[tauri::command]
+pub async fn remove_playlist_entries(
+    playlist_id: i64,
+    entry_ids: Vec<i64>,
+    db: State<'_, Database>,
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    edit_playlist(&db, playlist_id, PlaylistChange::Remove, |tx| {
+        playlists::remove_many(tx, playlist_id, &entry_ids)?;
+        Ok(())
+    })
+}
 
-/// from_index - The current index of the track to move (0-based)
-/// to_index - The target index where the track should be moved (0-based)
+/// Move `count` entries starting at `from_index` so the first ends up at `to_index`
 #[tauri::command]
-pub async fn reorder_playlist_tracks(
+pub async fn move_playlist_entries(
     playlist_id: i64,
     from_index: i64,
+    count: i64,
     to_index: i64,
     db: State<'_, Database>,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    // Get all tracks in the playlist ordered by position
-    let mut stmt = conn.prepare(
-        "SELECT track_id, position FROM playlist_tracks 
-         WHERE playlist_id = ?1 
-         ORDER BY position"
-    ).map_err(|e| e.to_string())?;
-    
-    let tracks: Vec<(i64, i64)> = stmt
-        .query_map(params![playlist_id], |row| {
-            Ok((row.get(0)?, row.get(1)?))
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
-
-    if tracks.is_empty() {
-        return Err("Playlist is empty".to_string());
-    }
-
-    // Validate indices
-    let max_index = tracks.len() as i64 - 1;
-    if from_index < 0 || from_index > max_index {
-        return Err(format!("Invalid from_index: {}", from_index));
-    }
-    if to_index < 0 || to_index > max_index {
-        return Err(format!("Invalid to_index: {}", to_index));
-    }
-
-    // If indices are the same, no reordering needed
-    if from_index == to_index {
-        return Ok(());
-    }
-
-    // Create a new ordered list of track IDs
-    let mut track_ids: Vec<i64> = tracks.iter().map(|(id, _)| *id).collect();
-    
-    // Remove the track from its current position
-    let moved_track_id = track_ids.remove(from_index as usize);
-    
-    // Insert it at the new position
-    track_ids.insert(to_index as usize, moved_track_id);
-
-    // Update all positions in the database
-    // Use a transaction to ensure atomicity
-    conn.execute("BEGIN TRANSACTION", [])
-        .map_err(|e| e.to_string())?;
-
-    for (new_position, track_id) in track_ids.iter().enumerate() {
-        conn.execute(
-            "UPDATE playlist_tracks 
-             SET position = ?1 
-             WHERE playlist_id = ?2 AND track_id = ?3",
-            params![new_position as i64, playlist_id, track_id],
-        ).map_err(|e| {
-            // Rollback on error
-            let _ = conn.execute("ROLLBACK", []);
-            e.to_string()
-        })?;
-    }
-
-    conn.execute("COMMIT", [])
-        .map_err(|e| e.to_string())?;
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    edit_playlist(&db, playlist_id, PlaylistChange::Move, |tx| {
+        playlists::move_range(tx, playlist_id, from_index, count, to_index)
+    })
+}
 
+/// Remove repeated tracks, keeping the first of each
+#[tauri::command]
+pub async fn dedupe_playlist(
+    playlist_id: i64,
+    db: State<'_, Database>,
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    edit_playlist(&db, playlist_id, PlaylistChange::Dedupe, |tx| {
+        playlists::dedupe(tx, playlist_id)?;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub async fn sort_playlist(
+    playlist_id: i64,
+    sort: TrackSort,
+    direction: Option<SortDirection>,
+    db: State<'_, Database>,
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    edit_playlist(&db, playlist_id, PlaylistChange::Sort, |tx| {
+        playlists::sort(tx, playlist_id, sort, direction.unwrap_or_default())
+            .map_err(AudionError::from)
+    })
+}
+
+/// Shuffle the saved order of a playlist
+#[tauri::command]
+pub async fn shuffle_playlist(
+    playlist_id: i64,
+    db: State<'_, Database>,
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    edit_playlist(&db, playlist_id, PlaylistChange::Shuffle, |tx| {
+        playlists::shuffle(tx, playlist_id).map_err(AudionError::from)
+    })
+}
+
+#[tauri::command]
+pub async fn replace_playlist_tracks(
+    playlist_id: i64,
+    track_ids: Vec<i64>,
+    db: State<'_, Database>,
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    edit_playlist(&db, playlist_id, PlaylistChange::Replace, |tx| {
+        playlists::replace(tx, playlist_id, &track_ids)
+    })
+}
+
+/// Create a playlist filled by rules instead of by hand. Returns its id.
+#[tauri::command]
+pub async fn create_smart_playlist(
+    name: String,
+    rules: SmartRules,
+    db: State<'_, Database>,
+) -> Result<i64, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    let playlist_id = smart_playlists::create(&tx, &name, &rules)?;
+    tx.commit()?;
+    Ok(playlist_id)
+}
+
+/// Change a smart playlist's rules (or make a playlist smart). Returns the new track count.
+#[tauri::command]
+pub async fn update_smart_playlist(
+    playlist_id: i64,
+    rules: SmartRules,
+    db: State<'_, Database>,
+) -> Result<usize, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    let count = smart_playlists::update(&tx, playlist_id, &rules)?;
+    tx.commit()?;
+    Ok(count)
+}
+
+/// The rules of a smart playlist; `None` for a regular one
+#[tauri::command]
+pub async fn get_smart_playlist_rules(
+    playlist_id: i64,
+    db: State<'_, Database>,
+) -> Result<Option<SmartRules>, AudionError> {
+    let conn = db.read()?;
+    smart_playlists::get_rules(&conn, playlist_id)
+}
+
+/// Re-evaluate a smart playlist's rules against the library. Returns the new track count.
+#[tauri::command]
+pub async fn refresh_smart_playlist(
+    playlist_id: i64,
+    db: State<'_, Database>,
+) -> Result<usize, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    let count = smart_playlists::refresh(&tx, playlist_id)?;
+    tx.commit()?;
+    Ok(count)
+}
+
+/// Folders and the playlists filed in them, in display order
+#[tauri::command]
+pub async fn get_playlist_tree(db: State<'_, Database>) -> Result<PlaylistTree, AudionError> {
+    let conn = db.read()?;
+    playlist_folders::tree(&conn).map_err(AudionError::from)
+}
+
+/// A new folder at the end of `parent_id`, or of the top level
+#[tauri::command]
+pub async fn create_playlist_folder(
+    name: String,
+    parent_id: Option<i64>,
+    db: State<'_, Database>,
+) -> Result<i64, AudionError> {
+    let conn = db.write()?;
+    playlist_folders::create_folder(&conn, &name, parent_id)
+}
+
+#[tauri::command]
+pub async fn rename_playlist_folder(
+    folder_id: i64,
+    name: String,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    playlist_folders::rename_folder(&conn, folder_id, &name)
+}
+
+/// Move a folder into `parent_id` (the top level when omitted) at `position`
+/// among its sibling folders, or at the end
+#[tauri::command]
+pub async fn move_playlist_folder(
+    folder_id: i64,
+    parent_id: Option<i64>,
+    position: Option<i64>,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    playlist_folders::move_folder(&tx, folder_id, parent_id, position)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// File a playlist in `folder_id` (the top level when omitted) at `position`
+/// among the playlists there, or at the end
+#[tauri::command]
+pub async fn move_playlist_to_folder(
+    playlist_id: i64,
+    folder_id: Option<i64>,
+    position: Option<i64>,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    playlist_folders::move_playlist(&tx, playlist_id, folder_id, position)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Delete a folder. `delete_playlists` also deletes its subfolders and every
+/// playlist in them; otherwise they move up a level. Returns how many
+/// playlists were deleted.
+#[tauri::command]
+pub async fn delete_playlist_folder(
+    folder_id: i64,
+    delete_playlists: bool,
+    db: State<'_, Database>,
+) -> Result<usize, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    let deleted = playlist_folders::delete_folder(&tx, folder_id, delete_playlists)?;
+    tx.commit()?;
+    Ok(deleted)
+}
+
+/// Changes that can be undone, newest first
+#[tauri::command]
+pub async fn get_playlist_history(
+    playlist_id: i64,
+    db: State<'_, Database>,
+) -> Result<Vec<JournalEntry>, AudionError> {
+    let conn = db.read()?;
+    playlist_history::history(&conn, playlist_id)
+}
+
+/// Undo the last `steps` changes (1 when omitted) and return the restored order
+#[tauri::command]
+pub async fn undo_playlist_changes(
+    playlist_id: i64,
+    steps: Option<usize>,
+    db: State<'_, Database>,
+) -> Result<Vec<PlaylistEntry>, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    playlist_history::undo(&tx, playlist_id, steps.unwrap_or(1))?;
+    let entries = playlists::get_entries(&tx, playlist_id)?;
+    tx.commit()?;
+    Ok(entries)
+}
+
+#[tauri::command]
+pub async fn get_deleted_playlists(
+    db: State<'_, Database>,
+) -> Result<Vec<DeletedPlaylist>, AudionError> {
+    let conn = db.read()?;
+    playlist_history::deleted(&conn).map_err(AudionError::from)
+}
+
+#[tauri::command]
+pub async fn restore_playlist(
+    playlist_id: i64,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    playlist_history::restore(&conn, playlist_id)
+}
+
+/// Delete a playlist from recently deleted without waiting for it to expire
+#[tauri::command]
+pub async fn delete_playlist_permanently(
+    playlist_id: i64,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    playlist_history::delete_permanently(&conn, playlist_id)
+}
+
+#[tauri::command]
+pub async fn get_playlist_history_options(
+    db: State<'_, Database>,
+) -> Result<HistoryOptions, AudionError> {
+    let conn = db.read()?;
+    playlist_history::load_options(&conn).map_err(AudionError::from)
+}
+
+/// Save the options; a shorter retention period takes effect right away
+#[tauri::command]
+pub async fn set_playlist_history_options(
+    options: HistoryOptions,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    playlist_history::save_options(&conn, &options)?;
+    playlist_history::purge_expired(&conn)?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,221 @@
+// Playlist file export and import commands
+use crate::db::matching::{self, LibraryIndex, MatchKind, MatchStatus, TrackMatch};
+use crate::db::{playlists, queries, Database};
+use crate::error::{AudionError, ResultExt};
+use crate::playlist_files::{
+    self, ExportOptions, PlaylistFile, PlaylistFormat, PlaylistItem, TrackListFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::State;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub playlist_id: i64,
+    pub name: String,
+    pub matched_by_path: usize,
+    pub matched_by_metadata: usize,
+    /// Entries with no library track; they're left out of the playlist
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnresolvedEntry {
+    /// 0-based position in the file
+    pub index: usize,
+    #[serde(flatten)]
+    pub item: PlaylistItem,
+}
+
+/// How many candidates are offered for a row that needs confirming
+const CANDIDATES_PER_ROW: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackListRow {
+    /// 0-based position in the list
+    pub index: usize,
+    pub item: PlaylistItem,
+    pub status: MatchStatus,
+    /// The track to use when `status` is `matched`
+    pub track_id: Option<i64>,
+    /// Best first; the choices to confirm when `status` is `ambiguous`
+    pub candidates: Vec<TrackMatch>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfirmedRow {
+    pub item: PlaylistItem,
+    /// `None` for rows left unmatched
+    pub track_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackListImport {
+    pub playlist_id: i64,
+    pub matched: usize,
+    /// Unmatched rows kept as placeholder tracks
+    pub placeholders: usize,
+    /// Unmatched rows left out
+    pub skipped: usize,
+}
+
+/// Write a playlist to a user-chosen file. Returns the number of tracks written.
+#[tauri::command]
+pub async fn export_playlist(
+    playlist_id: i64,
+    format: PlaylistFormat,
+    path: String,
+    options: Option<ExportOptions>,
+    db: State<'_, Database>,
+) -> Result<usize, AudionError> {
+    let playlist = {
+        let conn = db.read()?;
+        let name = playlists::playlist_name(&conn, playlist_id)?
+            .ok_or_else(|| AudionError::NotFound(format!("Playlist {} not found", playlist_id)))?;
+        PlaylistFile {
+            name: Some(name),
+            items: playlists::export_items(&conn, playlist_id)?,
+        }
+    };
+
+    let path = PathBuf::from(path);
+    let contents = playlist_files::write(format, &playlist, &path, &options.unwrap_or_default())?;
+    std::fs::write(&path, contents).context("Failed to write playlist")?;
+    Ok(playlist.items.len())
+}
+
+/// Create a playlist from a file, matching its entries to library tracks by
+/// path first and by title, artist and length second
+#[tauri::command]
+pub async fn import_playlist(
+    path: String,
+    format: Option<PlaylistFormat>,
+    name: Option<String>,
+    db: State<'_, Database>,
+) -> Result<ImportReport, AudionError> {
+    let path = PathBuf::from(path);
+    let format = format
+        .or_else(|| PlaylistFormat::from_path(&path))
+        .ok_or_else(|| {
+            AudionError::Unsupported(format!("Unknown playlist format: {}", path.display()))
+        })?;
+    let bytes = std::fs::read(&path).context("Failed to read playlist")?;
+    let base = path.parent().map(PathBuf::from).unwrap_or_default();
+    let playlist = playlist_files::parse(format, &playlist_files::decode(&bytes), &base)
+        .map_err(AudionError::InvalidInput)?;
+
+    let name = name
+        .or(playlist.name)
+        .filter(|name| !name.trim().is_empty())
+        .or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "Imported playlist".to_string());
+
+    let mut conn = db.write()?;
+    let index = LibraryIndex::load(&conn)?;
+    let mut report = ImportReport {
+        playlist_id: 0,
+        name,
+        matched_by_path: 0,
+        matched_by_metadata: 0,
+        unresolved: Vec::new(),
+    };
+    let mut track_ids = Vec::new();
+    for (i, item) in playlist.items.into_iter().enumerate() {
+        match index.resolve(&item) {
+            Some((track_id, kind)) => {
+                match kind {
+                    MatchKind::Path => report.matched_by_path += 1,
+                    _ => report.matched_by_metadata += 1,
+                }
+                track_ids.push(track_id);
+            }
+            None => report.unresolved.push(UnresolvedEntry { index: i, item }),
+        }
+    }
+
+    let tx = conn.transaction()?;
+    report.playlist_id = queries::create_playlist(&tx, &report.name)?;
+    playlists::add_many(&tx, report.playlist_id, &track_ids, None)?;
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Match a CSV or JSON track list from a streaming service against the
+/// library. Nothing is saved; confirm the rows and pass them to
+/// `create_playlist_from_track_list`.
+#[tauri::command]
+pub async fn match_track_list(
+    path: String,
+    format: Option<TrackListFormat>,
+    db: State<'_, Database>,
+) -> Result<Vec<TrackListRow>, AudionError> {
+    let path = PathBuf::from(path);
+    let format = format
+        .or_else(|| TrackListFormat::from_path(&path))
+        .ok_or_else(|| {
+            AudionError::Unsupported(format!("Unknown track list format: {}", path.display()))
+        })?;
+    let bytes = std::fs::read(&path).context("Failed to read track list")?;
+    let items = playlist_files::parse_track_list(format, &playlist_files::decode(&bytes))
+        .map_err(AudionError::InvalidInput)?;
+
+    let index = {
+        let conn = db.read()?;
+        LibraryIndex::load(&conn)?
+    };
+    Ok(items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let candidates = index.fuzzy_matches(&item, CANDIDATES_PER_ROW);
+            let status = matching::match_status(&candidates);
+            TrackListRow {
+                index: i,
+                track_id: (status == MatchStatus::Matched).then(|| candidates[0].track_id),
+                item,
+                status,
+                candidates,
+            }
+        })
+        .collect())
+}
+
+/// Create a playlist from confirmed track list rows, in order. Unmatched
+/// rows become placeholder tracks when `keep_unmatched` is set.
+#[tauri::command]
+pub async fn create_playlist_from_track_list(
+    name: String,
+    rows: Vec<ConfirmedRow>,
+    keep_unmatched: bool,
+    db: State<'_, Database>,
+) -> Result<TrackListImport, AudionError> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+    let mut report = TrackListImport {
+        playlist_id: queries::create_playlist(&tx, &name)?,
+        matched: 0,
+        placeholders: 0,
+        skipped: 0,
+    };
+
+    let mut track_ids = Vec::with_capacity(rows.len());
+    for row in &rows {
+        match row.track_id {
+            Some(track_id) => {
+                report.matched += 1;
+                track_ids.push(track_id);
+            }
+            None if keep_unmatched => {
+                report.placeholders += 1;
+                track_ids.push(playlists::placeholder_track(&tx, &row.item)?);
+            }
+            None => report.skipped += 1,
+        }
+    }
+    playlists::add_many(&tx, report.playlist_id, &track_ids, None)?;
+    tx.commit()?;
+    Ok(report)
+}
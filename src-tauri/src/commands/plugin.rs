@@ -6,6 +6,8 @@ use std::fs;
 use std::path::PathBuf;
 use tauri::Manager;
 
+use crate::error::{AudionError, ResultExt};
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PluginManifest {
     pub name: String,
@@ -81,15 +83,15 @@ fn get_safe_name_from_manifest(manifest: &PluginManifest) -> String {
 }
 
 // Validate that safe_name matches actual folder structure
-fn validate_safe_name(manifest: &PluginManifest, actual_folder: &str) -> Result<(), String> {
+fn validate_safe_name(manifest: &PluginManifest, actual_folder: &str) -> Result<(), AudionError> {
     let expected_safe_name = get_safe_name_from_manifest(manifest);
 
     if expected_safe_name != actual_folder {
-        return Err(format!(
+        return Err(AudionError::InvalidInput(format!(
             "Plugin manifest safe_name mismatch: expected folder '{}' but found '{}'. \
             Update plugin.json to set \"safe_name\": \"{}\"",
             expected_safe_name, actual_folder, actual_folder
-        ));
+        )));
     }
 
     Ok(())
@@ -108,10 +110,10 @@ fn load_plugin_states(plugin_dir: &str) -> PluginStateStore {
     }
 }
 
-fn save_plugin_states(plugin_dir: &str, store: &PluginStateStore) -> Result<(), String> {
+fn save_plugin_states(plugin_dir: &str, store: &PluginStateStore) -> Result<(), AudionError> {
     let state_path = get_state_file_path(plugin_dir);
-    let content = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
-    fs::write(&state_path, content).map_err(|e| e.to_string())?;
+    let content = serde_json::to_string_pretty(store)?;
+    fs::write(&state_path, content)?;
     Ok(())
 }
 
@@ -162,7 +164,7 @@ pub fn list_plugins(plugin_dir: String) -> Vec<PluginInfo> {
 }
 
 #[tauri::command]
-pub fn enable_plugin(name: String, plugin_dir: String) -> Result<bool, String> {
+pub fn enable_plugin(name: String, plugin_dir: String) -> Result<bool, AudionError> {
     let mut states = load_plugin_states(&plugin_dir);
 
     // First try to read manifest to get proper safe_name
@@ -230,13 +232,13 @@ pub fn enable_plugin(name: String, plugin_dir: String) -> Result<bool, String> {
             save_plugin_states(&plugin_dir, &states)?;
             Ok(true)
         } else {
-            Err(format!("Plugin not found: {}", name))
+            Err(AudionError::NotFound(format!("Plugin not found: {}", name)))
         }
     }
 }
 
 #[tauri::command]
-pub fn disable_plugin(name: String, plugin_dir: String) -> Result<bool, String> {
+pub fn disable_plugin(name: String, plugin_dir: String) -> Result<bool, AudionError> {
     let mut states = load_plugin_states(&plugin_dir);
 
     if let Some(state) = states.plugins.get_mut(&name) {
@@ -244,17 +246,17 @@ pub fn disable_plugin(name: String, plugin_dir: String) -> Result<bool, String>
         save_plugin_states(&plugin_dir, &states)?;
         Ok(true)
     } else {
-        Err(format!("Plugin not tracked: {}", name))
+        Err(AudionError::NotFound(format!("Plugin not tracked: {}", name)))
     }
 }
 
 #[tauri::command]
-pub async fn install_plugin(repo_url: String, plugin_dir: String) -> Result<PluginInfo, String> {
+pub async fn install_plugin(repo_url: String, plugin_dir: String) -> Result<PluginInfo, AudionError> {
     // Parse GitHub URL to get owner/repo
     let parts: Vec<&str> = repo_url.trim_end_matches('/').split('/').collect();
 
     if parts.len() < 2 {
-        return Err("Invalid repository URL".to_string());
+        return Err(AudionError::InvalidInput("Invalid repository URL".to_string()));
     }
 
     let owner = parts[parts.len() - 2];
@@ -270,13 +272,13 @@ pub async fn install_plugin(repo_url: String, plugin_dir: String) -> Result<Plug
         .header("User-Agent", "Audion-Plugin-Manager")
         .send()
         .await
-        .map_err(|e| format!("Failed to fetch repo info: {}", e))?;
+        .context("Failed to fetch repo info")?;
 
     let default_branch = if repo_response.status().is_success() {
         let repo_info: serde_json::Value = repo_response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse repo info: {}", e))?;
+            .context("Failed to parse repo info")?;
         repo_info["default_branch"]
             .as_str()
             .unwrap_or("main")
@@ -296,19 +298,19 @@ pub async fn install_plugin(repo_url: String, plugin_dir: String) -> Result<Plug
         .header("User-Agent", "Audion-Plugin-Manager")
         .send()
         .await
-        .map_err(|e| format!("Failed to fetch plugin.json: {}", e))?;
+        .context("Failed to fetch plugin.json")?;
 
     if !manifest_response.status().is_success() {
-        return Err(format!(
+        return Err(AudionError::Network(format!(
             "Failed to fetch plugin.json: HTTP {}",
             manifest_response.status()
-        ));
+        )));
     }
 
     let mut manifest: PluginManifest = manifest_response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse plugin.json: {}", e))?;
+        .context("Failed to parse plugin.json")?;
 
     // Inject repo URL into manifest for future update checks
     manifest.repo = Some(repo_url.clone());
@@ -320,13 +322,13 @@ pub async fn install_plugin(repo_url: String, plugin_dir: String) -> Result<Plug
     // Validate that the manifest is consistent (defensive check for new installs)
     validate_safe_name(&manifest, &safe_name)?;
 
-    fs::create_dir_all(&plugin_path).map_err(|e| format!("Failed to create plugin dir: {}", e))?;
+    fs::create_dir_all(&plugin_path).context("Failed to create plugin dir")?;
 
     // Save plugin.json (with repo URL included)
     let manifest_json = serde_json::to_string_pretty(&manifest)
-        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+        .context("Failed to serialize manifest")?;
     fs::write(plugin_path.join("plugin.json"), &manifest_json)
-        .map_err(|e| format!("Failed to save plugin.json: {}", e))?;
+        .context("Failed to save plugin.json")?;
 
     // Fetch the entry file (index.js or plugin.wasm)
     let entry_url = format!(
@@ -339,23 +341,23 @@ pub async fn install_plugin(repo_url: String, plugin_dir: String) -> Result<Plug
         .header("User-Agent", "Audion-Plugin-Manager")
         .send()
         .await
-        .map_err(|e| format!("Failed to fetch entry file: {}", e))?;
+        .context("Failed to fetch entry file")?;
 
     if !entry_response.status().is_success() {
-        return Err(format!(
+        return Err(AudionError::Network(format!(
             "Failed to fetch {}: HTTP {}",
             manifest.entry,
             entry_response.status()
-        ));
+        )));
     }
 
     let entry_bytes = entry_response
         .bytes()
         .await
-        .map_err(|e| format!("Failed to read entry file: {}", e))?;
+        .context("Failed to read entry file")?;
 
     fs::write(plugin_path.join(&manifest.entry), &entry_bytes)
-        .map_err(|e| format!("Failed to save entry file: {}", e))?;
+        .context("Failed to save entry file")?;
 
     // Add to state
     let mut states = load_plugin_states(&plugin_dir);
@@ -386,7 +388,7 @@ pub async fn install_plugin(repo_url: String, plugin_dir: String) -> Result<Plug
 }
 
 #[tauri::command]
-pub fn uninstall_plugin(name: String, plugin_dir: String) -> Result<bool, String> {
+pub fn uninstall_plugin(name: String, plugin_dir: String) -> Result<bool, AudionError> {
     // Try to get safe name, but don't validate
     let safe_name = {
         let fallback_safe_name = to_safe_name(&name);
@@ -402,11 +404,11 @@ pub fn uninstall_plugin(name: String, plugin_dir: String) -> Result<bool, String
     let plugin_path = PathBuf::from(&plugin_dir).join(&safe_name);
 
     if !plugin_path.exists() {
-        return Err(format!("Plugin not found: {}", name));
+        return Err(AudionError::NotFound(format!("Plugin not found: {}", name)));
     }
 
     // Remove plugin directory
-    fs::remove_dir_all(&plugin_path).map_err(|e| format!("Failed to remove plugin: {}", e))?;
+    fs::remove_dir_all(&plugin_path).context("Failed to remove plugin")?;
 
     // Remove from state (using original name as key)
     let mut states = load_plugin_states(&plugin_dir);
@@ -427,7 +429,7 @@ pub fn grant_permissions(
     name: String,
     plugin_dir: String,
     permissions: Vec<String>,
-) -> Result<bool, String> {
+) -> Result<bool, AudionError> {
     let mut states = load_plugin_states(&plugin_dir);
 
     if let Some(state) = states.plugins.get_mut(&name) {
@@ -440,7 +442,7 @@ pub fn grant_permissions(
         save_plugin_states(&plugin_dir, &states)?;
         Ok(true)
     } else {
-        Err(format!("Plugin not tracked: {}", name))
+        Err(AudionError::NotFound(format!("Plugin not tracked: {}", name)))
     }
 }
 
@@ -451,13 +453,13 @@ pub fn check_cross_plugin_permission(
     target_plugin: String,
     method: String,
     plugin_dir: String,
-) -> Result<bool, String> {
+) -> Result<bool, AudionError> {
     // Get caller plugin's manifest using safe name
     let safe_caller_name = to_safe_name(&caller_plugin);
     let caller_path = PathBuf::from(&plugin_dir).join(&safe_caller_name);
 
     let manifest = read_plugin_manifest(&caller_path)
-        .ok_or_else(|| format!("Caller plugin not found: {}", caller_plugin))?;
+        .ok_or_else(|| AudionError::NotFound(format!("Caller plugin not found: {}", caller_plugin)))?;
 
     // Check if caller has permission for this target plugin + method
     // The manifest contains display names in cross_plugin_access, so compare directly
@@ -475,13 +477,13 @@ pub fn check_cross_plugin_permission(
 pub fn get_cross_plugin_permissions(
     plugin_name: String,
     plugin_dir: String,
-) -> Result<Vec<CrossPluginAccess>, String> {
+) -> Result<Vec<CrossPluginAccess>, AudionError> {
     // First try with fallback safe name to locate the plugin
     let fallback_safe_name = to_safe_name(&plugin_name);
     let plugin_path = PathBuf::from(&plugin_dir).join(&fallback_safe_name);
 
     let manifest = read_plugin_manifest(&plugin_path)
-        .ok_or_else(|| format!("Plugin not found: {}", plugin_name))?;
+        .ok_or_else(|| AudionError::NotFound(format!("Plugin not found: {}", plugin_name)))?;
 
     // Return cross_plugin_access as-is (contains display names like "Tidal Search")
     Ok(manifest.cross_plugin_access)
@@ -492,7 +494,7 @@ pub fn revoke_permissions(
     name: String,
     plugin_dir: String,
     permissions: Vec<String>,
-) -> Result<bool, String> {
+) -> Result<bool, AudionError> {
     let mut states = load_plugin_states(&plugin_dir);
 
     if let Some(state) = states.plugins.get_mut(&name) {
@@ -502,18 +504,17 @@ pub fn revoke_permissions(
         save_plugin_states(&plugin_dir, &states)?;
         Ok(true)
     } else {
-        Err(format!("Plugin not tracked: {}", name))
+        Err(AudionError::NotFound(format!("Plugin not tracked: {}", name)))
     }
 }
 
 #[tauri::command]
-pub fn get_plugin_dir(app_handle: tauri::AppHandle) -> Result<String, String> {
+pub fn get_plugin_dir(app_handle: tauri::AppHandle) -> Result<String, AudionError> {
     let app_dir = app_handle
         .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
+        .app_data_dir()?;
     let plugin_dir = app_dir.join("plugins");
-    fs::create_dir_all(&plugin_dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&plugin_dir)?;
     Ok(plugin_dir.to_string_lossy().to_string())
 }
 
@@ -551,7 +552,7 @@ pub struct PluginUpdateInfo {
 }
 
 #[tauri::command]
-pub async fn check_plugin_updates(plugin_dir: String) -> Result<Vec<PluginUpdateInfo>, String> {
+pub async fn check_plugin_updates(plugin_dir: String) -> Result<Vec<PluginUpdateInfo>, AudionError> {
     let mut updates = Vec::new();
     let dir = PathBuf::from(&plugin_dir);
     let client = reqwest::Client::new();
@@ -629,13 +630,13 @@ pub async fn check_plugin_updates(plugin_dir: String) -> Result<Vec<PluginUpdate
 }
 
 #[tauri::command]
-pub async fn update_plugin(name: String, plugin_dir: String) -> Result<PluginInfo, String> {
+pub async fn update_plugin(name: String, plugin_dir: String) -> Result<PluginInfo, AudionError> {
     // Get the current plugin's manifest to retrieve repo URL and preserve state
     let fallback_safe_name = to_safe_name(&name);
     let plugin_path = PathBuf::from(&plugin_dir).join(&fallback_safe_name);
 
     let manifest =
-        read_plugin_manifest(&plugin_path).ok_or_else(|| format!("Plugin not found: {}", name))?;
+        read_plugin_manifest(&plugin_path).ok_or_else(|| AudionError::NotFound(format!("Plugin not found: {}", name)))?;
 
     let safe_name = get_safe_name_from_manifest(&manifest);
 
@@ -646,7 +647,7 @@ pub async fn update_plugin(name: String, plugin_dir: String) -> Result<PluginInf
 
     let repo_url = manifest
         .repo
-        .ok_or_else(|| format!("Plugin {} has no repository URL", name))?;
+        .ok_or_else(|| AudionError::Unsupported(format!("Plugin {} has no repository URL", name)))?;
 
     // Load current state to preserve enabled status and permissions
     let states = load_plugin_states(&plugin_dir);
@@ -654,12 +655,12 @@ pub async fn update_plugin(name: String, plugin_dir: String) -> Result<PluginInf
 
     // Remove the old plugin files (but keep state)
     fs::remove_dir_all(&plugin_path)
-        .map_err(|e| format!("Failed to remove old plugin files: {}", e))?;
+        .context("Failed to remove old plugin files")?;
 
     // Reinstall from repo (reuse install_plugin logic)
     let parts: Vec<&str> = repo_url.trim_end_matches('/').split('/').collect();
     if parts.len() < 2 {
-        return Err("Invalid repository URL".to_string());
+        return Err(AudionError::InvalidInput("Invalid repository URL".to_string()));
     }
 
     let owner = parts[parts.len() - 2];
@@ -695,19 +696,19 @@ pub async fn update_plugin(name: String, plugin_dir: String) -> Result<PluginInf
         .header("User-Agent", "Audion-Plugin-Manager")
         .send()
         .await
-        .map_err(|e| format!("Failed to fetch plugin.json: {}", e))?;
+        .context("Failed to fetch plugin.json")?;
 
     if !manifest_response.status().is_success() {
-        return Err(format!(
+        return Err(AudionError::Network(format!(
             "Failed to fetch plugin.json: HTTP {}",
             manifest_response.status()
-        ));
+        )));
     }
 
     let mut new_manifest: PluginManifest = manifest_response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse plugin.json: {}", e))?;
+        .context("Failed to parse plugin.json")?;
 
     // Inject repo URL into manifest for future update checks
     new_manifest.repo = Some(repo_url.clone());
@@ -719,13 +720,13 @@ pub async fn update_plugin(name: String, plugin_dir: String) -> Result<PluginInf
     // Create plugin directory
     let new_plugin_path = PathBuf::from(&plugin_dir).join(&new_safe_name);
     fs::create_dir_all(&new_plugin_path)
-        .map_err(|e| format!("Failed to create plugin dir: {}", e))?;
+        .context("Failed to create plugin dir")?;
 
     // Save new plugin.json
     let manifest_json = serde_json::to_string_pretty(&new_manifest)
-        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+        .context("Failed to serialize manifest")?;
     fs::write(new_plugin_path.join("plugin.json"), &manifest_json)
-        .map_err(|e| format!("Failed to save plugin.json: {}", e))?;
+        .context("Failed to save plugin.json")?;
 
     // Fetch the entry file
     let entry_url = format!(
@@ -738,23 +739,23 @@ pub async fn update_plugin(name: String, plugin_dir: String) -> Result<PluginInf
         .header("User-Agent", "Audion-Plugin-Manager")
         .send()
         .await
-        .map_err(|e| format!("Failed to fetch entry file: {}", e))?;
+        .context("Failed to fetch entry file")?;
 
     if !entry_response.status().is_success() {
-        return Err(format!(
+        return Err(AudionError::Network(format!(
             "Failed to fetch {}: HTTP {}",
             new_manifest.entry,
             entry_response.status()
-        ));
+        )));
     }
 
     let entry_bytes = entry_response
         .bytes()
         .await
-        .map_err(|e| format!("Failed to read entry file: {}", e))?;
+        .context("Failed to read entry file")?;
 
     fs::write(new_plugin_path.join(&new_manifest.entry), &entry_bytes)
-        .map_err(|e| format!("Failed to save entry file: {}", e))?;
+        .context("Failed to save entry file")?;
 
     // Update state, preserving enabled status and permissions from before
     let mut states = load_plugin_states(&plugin_dir);
@@ -792,12 +793,12 @@ pub async fn update_plugin(name: String, plugin_dir: String) -> Result<PluginInf
 
 // windows currently ignore images
 #[tauri::command]
-pub fn save_notification_image(data_uri: String) -> Result<String, String> {
+pub fn save_notification_image(data_uri: String) -> Result<String, AudionError> {
     // Parse the data URI
     // Format: data:image/jpeg;base64,<base64_data>
     let parts: Vec<&str> = data_uri.split(',').collect();
     if parts.len() != 2 {
-        return Err("Invalid data URI format".to_string());
+        return Err(AudionError::InvalidInput("Invalid data URI format".to_string()));
     }
 
     let header = parts[0];
@@ -817,7 +818,7 @@ pub fn save_notification_image(data_uri: String) -> Result<String, String> {
     // Decode base64
     let image_data = general_purpose::STANDARD
         .decode(base64_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+        .map_err(|e| AudionError::InvalidInput(format!("Failed to decode base64: {}", e)))?;
 
     // Get temp directory
     let temp_dir = std::env::temp_dir();
@@ -835,12 +836,12 @@ pub fn save_notification_image(data_uri: String) -> Result<String, String> {
     let temp_path = temp_dir.join(filename);
 
     // Write to file
-    fs::write(&temp_path, image_data).map_err(|e| format!("Failed to write file: {}", e))?;
+    fs::write(&temp_path, image_data).context("Failed to write file")?;
 
     // Return the absolute path as string
     temp_path
         .to_str()
-        .ok_or_else(|| "Failed to convert path to string".to_string())
+        .ok_or_else(|| AudionError::Internal("Failed to convert path to string".to_string()))
         .map(|s| s.to_string())
 }
 
@@ -850,15 +851,15 @@ pub async fn plugin_save_data(
     key: String,
     value: String,
     plugin_dir: String,
-) -> Result<(), String> {
+) -> Result<(), AudionError> {
     let safe_name = to_safe_name(&plugin_name);
     let storage_dir = std::path::PathBuf::from(&plugin_dir)
         .join(&safe_name)
         .join("storage");
-    fs::create_dir_all(&storage_dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&storage_dir)?;
 
     let file_path = storage_dir.join(format!("{}.json", key));
-    fs::write(file_path, value).map_err(|e| e.to_string())?;
+    fs::write(file_path, value)?;
     Ok(())
 }
 
@@ -867,7 +868,7 @@ pub async fn plugin_get_data(
     plugin_name: String,
     key: String,
     plugin_dir: String,
-) -> Result<Option<String>, String> {
+) -> Result<Option<String>, AudionError> {
     let safe_name = to_safe_name(&plugin_name);
     let file_path = std::path::PathBuf::from(&plugin_dir)
         .join(&safe_name)
@@ -875,7 +876,7 @@ pub async fn plugin_get_data(
         .join(format!("{}.json", key));
 
     if file_path.exists() {
-        let content = fs::read_to_string(file_path).map_err(|e| e.to_string())?;
+        let content = fs::read_to_string(file_path)?;
         Ok(Some(content))
     } else {
         Ok(None)
@@ -886,7 +887,7 @@ pub async fn plugin_get_data(
 pub async fn plugin_list_keys(
     plugin_name: String,
     plugin_dir: String,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<String>, AudionError> {
     let safe_name = to_safe_name(&plugin_name);
     let storage_dir = std::path::PathBuf::from(&plugin_dir)
         .join(&safe_name)
@@ -908,7 +909,7 @@ pub async fn plugin_list_keys(
 }
 
 #[tauri::command]
-pub async fn plugin_clear_data(plugin_name: String, plugin_dir: String) -> Result<usize, String> {
+pub async fn plugin_clear_data(plugin_name: String, plugin_dir: String) -> Result<usize, AudionError> {
     let safe_name = to_safe_name(&plugin_name);
     let storage_dir = std::path::PathBuf::from(&plugin_dir)
         .join(&safe_name)
@@ -922,8 +923,8 @@ pub async fn plugin_clear_data(plugin_name: String, plugin_dir: String) -> Resul
         .map(|entries| entries.flatten().count())
         .unwrap_or(0);
 
-    fs::remove_dir_all(&storage_dir).map_err(|e| e.to_string())?;
-    fs::create_dir_all(&storage_dir).map_err(|e| e.to_string())?;
+    fs::remove_dir_all(&storage_dir)?;
+    fs::create_dir_all(&storage_dir)?;
 
     Ok(count)
 }
@@ -0,0 +1,102 @@
+// Rating and favourite commands
+use crate::commands::metadata::write_rating_to_file;
+use crate::db::ratings::{self, AlbumRating, RatingOptions, TrackRating};
+use crate::db::smart_playlists::{self, RuleField};
+use crate::db::Database;
+use crate::error::{AudionError, ResultExt};
+use std::path::Path;
+use tauri::State;
+
+/// Rate a track from 0 to 5 stars in half steps, or clear it with `None`. When
+/// writing to files is turned on, the rating is also saved in the file's tags.
+#[tauri::command]
+pub async fn set_track_rating(
+    track_id: i64,
+    rating: Option<f64>,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let path = {
+        let conn = db.write()?;
+        ratings::set_track_rating(&conn, track_id, rating)?;
+        smart_playlists::refresh_using(&conn, &[RuleField::Rating])?;
+        if !ratings::load_options(&conn)?.write_to_files {
+            return Ok(());
+        }
+        ratings::local_file_path(&conn, track_id)?
+    };
+
+    match path {
+        Some(path) if Path::new(&path).is_file() => {
+            tauri::async_runtime::spawn_blocking(move || {
+                write_rating_to_file(Path::new(&path), rating)
+            })
+            .await?
+            .context("Rating saved, but not written to the file")
+        }
+        _ => Ok(()),
+    }
+}
+
+#[tauri::command]
+pub async fn set_track_loved(
+    track_id: i64,
+    loved: bool,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    ratings::set_track_loved(&conn, track_id, loved)?;
+    smart_playlists::refresh_using(&conn, &[RuleField::Loved])
+}
+
+#[tauri::command]
+pub async fn set_album_rating(
+    album_id: i64,
+    rating: Option<f64>,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    ratings::set_album_rating(&conn, album_id, rating)
+}
+
+#[tauri::command]
+pub async fn set_album_loved(
+    album_id: i64,
+    loved: bool,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    ratings::set_album_loved(&conn, album_id, loved)
+}
+
+#[tauri::command]
+pub async fn get_track_ratings(
+    track_ids: Vec<i64>,
+    db: State<'_, Database>,
+) -> Result<Vec<TrackRating>, AudionError> {
+    let conn = db.read()?;
+    ratings::get_track_ratings(&conn, &track_ids).map_err(AudionError::from)
+}
+
+#[tauri::command]
+pub async fn get_album_ratings(
+    album_ids: Vec<i64>,
+    db: State<'_, Database>,
+) -> Result<Vec<AlbumRating>, AudionError> {
+    let conn = db.read()?;
+    ratings::get_album_ratings(&conn, &album_ids).map_err(AudionError::from)
+}
+
+#[tauri::command]
+pub async fn get_rating_options(db: State<'_, Database>) -> Result<RatingOptions, AudionError> {
+    let conn = db.read()?;
+    ratings::load_options(&conn).map_err(AudionError::from)
+}
+
+#[tauri::command]
+pub async fn set_rating_options(
+    options: RatingOptions,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let conn = db.write()?;
+    ratings::save_options(&conn, &options).map_err(AudionError::from)
+}
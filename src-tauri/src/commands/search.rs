@@ -0,0 +1,36 @@
+// Search-related Tauri commands
+use crate::db::grouped::{self, GroupLimits, GroupedResults};
+use crate::db::search_query;
+use crate::db::suggest::{self, Suggestion};
+use crate::db::Database;
+use crate::error::AudionError;
+use tauri::State;
+
+const DEFAULT_SUGGESTION_LIMIT: usize = 8;
+
+/// Suggestions for the search box, meant to be called on every keystroke
+#[tauri::command]
+pub async fn search_suggestions(
+    prefix: String,
+    limit: Option<usize>,
+    db: State<'_, Database>,
+) -> Result<Vec<Suggestion>, AudionError> {
+    let conn = db.read()?;
+    suggest::search_suggestions(&conn, &prefix, limit.unwrap_or(DEFAULT_SUGGESTION_LIMIT))
+        .map_err(AudionError::from)
+}
+
+/// Search artists, albums, playlists and tracks at once; each group is capped
+/// by its own limit and reports how many matched in total
+#[tauri::command]
+pub async fn search_grouped(
+    query: String,
+    limits: Option<GroupLimits>,
+    db: State<'_, Database>,
+) -> Result<GroupedResults, AudionError> {
+    let Some(expr) = search_query::parse(&query)? else {
+        return Ok(GroupedResults::default());
+    };
+    let conn = db.read()?;
+    grouped::search_grouped(&conn, &expr, limits.unwrap_or_default()).map_err(AudionError::from)
+}
@@ -0,0 +1,76 @@
+// Listening statistics commands
+use crate::db::history::{self, OnThisDay};
+use crate::db::stats::{self, ListeningStats, StatsQuery, YearSummary};
+use crate::db::Database;
+use crate::error::{AudionError, ResultExt};
+use crate::year_card;
+use serde::Deserialize;
+use std::path::PathBuf;
+use tauri::State;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryFormat {
+    Json,
+    Png,
+}
+
+#[tauri::command]
+pub async fn get_listening_stats(
+    query: Option<StatsQuery>,
+    db: State<'_, Database>,
+) -> Result<ListeningStats, AudionError> {
+    let conn = db.read()?;
+    stats::listening_stats(&conn, &query.unwrap_or_default()).map_err(AudionError::from)
+}
+
+/// What was played on this date (local `YYYY-MM-DD`) in earlier years
+#[tauri::command]
+pub async fn get_on_this_day(
+    date: String,
+    utc_offset_minutes: i32,
+    limit: Option<u32>,
+    db: State<'_, Database>,
+) -> Result<Vec<OnThisDay>, AudionError> {
+    let conn = db.read()?;
+    history::on_this_day(&conn, &date, utc_offset_minutes, limit.unwrap_or(20))
+        .map_err(AudionError::from)
+}
+
+#[tauri::command]
+pub async fn get_year_summary(
+    year: i32,
+    utc_offset_minutes: i32,
+    db: State<'_, Database>,
+) -> Result<YearSummary, AudionError> {
+    let conn = db.read()?;
+    stats::year_summary(&conn, year, utc_offset_minutes).map_err(AudionError::from)
+}
+
+/// Write a year summary to a user-chosen file, as JSON or as a shareable PNG card
+#[tauri::command]
+pub async fn export_year_summary(
+    year: i32,
+    utc_offset_minutes: i32,
+    path: String,
+    format: SummaryFormat,
+    db: State<'_, Database>,
+) -> Result<(), AudionError> {
+    let summary = {
+        let conn = db.read()?;
+        stats::year_summary(&conn, year, utc_offset_minutes)?
+    };
+
+    let path = PathBuf::from(path);
+    match format {
+        SummaryFormat::Json => {
+            let json = serde_json::to_string_pretty(&summary)?;
+            std::fs::write(&path, json).context("Failed to write year summary")?;
+        }
+        SummaryFormat::Png => {
+            tauri::async_runtime::spawn_blocking(move || year_card::save_png(&summary, &path))
+                .await??;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,214 @@
+// Library integrity verification Tauri commands
+use crate::analysis::verify::{verify_file, VerifyStatus};
+use crate::db::{queries, Database};
+use crate::error::AudionError;
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, State};
+
+/// Results are written (and progress emitted) at most this often
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+const FLUSH_BATCH: usize = 50;
+
+/// Shared flags for the (single) running verification job
+#[derive(Default)]
+pub struct VerificationState {
+    running: Arc<AtomicBool>,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Clears the running flag however the job ends
+struct RunningGuard(Arc<AtomicBool>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct VerifyProgress {
+    pub current: usize,
+    pub total: usize,
+    pub problems: usize,
+    pub current_path: Option<String>,
+    pub estimated_time_remaining_ms: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct VerifySummary {
+    pub total: usize,
+    pub verified: usize,
+    pub ok: usize,
+    pub problems: usize,
+    pub cancelled: bool,
+    pub errors: Vec<String>,
+}
+
+/// Fully decode every local track (or only those under `folders`) and record
+/// decode errors, truncation and checksum mismatches. Emits `verify-progress`
+/// while running and `verify-complete` at the end.
+#[tauri::command]
+pub async fn verify_library(
+    folders: Option<Vec<String>>,
+    window: tauri::Window,
+    db: State<'_, Database>,
+    state: State<'_, VerificationState>,
+) -> Result<VerifySummary, AudionError> {
+    if state.running.swap(true, Ordering::SeqCst) {
+        return Err(AudionError::AlreadyRunning(
+            "A verification is already running".to_string(),
+        ));
+    }
+    let _guard = RunningGuard(state.running.clone());
+    state.cancel.store(false, Ordering::SeqCst);
+
+    let folders = folders.unwrap_or_default();
+    let tracks = {
+        let conn = db.read()?;
+        queries::get_local_tracks_for_verification(&conn, &folders)?
+    };
+    let total = tracks.len();
+
+    println!("[VERIFY] Verifying {} tracks", total);
+
+    // Decode in parallel, results are funnelled to a single writer
+    let (tx, rx): (
+        Sender<queries::VerificationResult>,
+        Receiver<queries::VerificationResult>,
+    ) = unbounded();
+    let cancel = state.cancel.clone();
+    std::thread::spawn(move || {
+        tracks.par_iter().for_each(|(track_id, path)| {
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            if let Some(outcome) = verify_file(Path::new(path), Some(&cancel)) {
+                let _ = tx.send(queries::VerificationResult {
+                    track_id: *track_id,
+                    path: path.clone(),
+                    status: outcome.status.as_str().to_string(),
+                    decode_error_count: outcome.decode_error_count as i64,
+                    errors: outcome.errors,
+                    expected_frames: outcome.expected_frames.map(|f| f as i64),
+                    decoded_frames: Some(outcome.decoded_frames as i64),
+                    checksum_ok: outcome.checksum_ok,
+                    verified_at: None,
+                });
+            }
+        });
+    });
+
+    let db_writer = db.inner().clone();
+    let cancel = state.cancel.clone();
+    let window_clone = window.clone();
+
+    let summary = tauri::async_runtime::spawn_blocking(move || {
+        let start = Instant::now();
+        let mut verified = 0usize;
+        let mut problems = 0usize;
+        let mut errors = Vec::new();
+        let mut pending = Vec::new();
+        let mut last_flush = Instant::now();
+        let mut disconnected = false;
+
+        while !disconnected {
+            match rx.recv_timeout(FLUSH_INTERVAL) {
+                Ok(result) => pending.push(result),
+                Err(crossbeam::channel::RecvTimeoutError::Timeout) => {}
+                Err(crossbeam::channel::RecvTimeoutError::Disconnected) => disconnected = true,
+            }
+
+            let due = pending.len() >= FLUSH_BATCH || last_flush.elapsed() >= FLUSH_INTERVAL;
+            if pending.is_empty() || !(due || disconnected) {
+                continue;
+            }
+
+            // Lock only for the write so the library stays usable during long runs
+            match db_writer.write() {
+                Ok(mut conn) => match conn.transaction() {
+                    Ok(tx_db) => {
+                        for result in &pending {
+                            if let Err(e) = queries::save_verification_result(&tx_db, result) {
+                                errors.push(format!("Failed to save result for {}: {}", result.path, e));
+                            }
+                        }
+                        if let Err(e) = tx_db.commit() {
+                            errors.push(format!("Failed to commit verification results: {}", e));
+                        }
+                    }
+                    Err(e) => errors.push(format!("Failed to start transaction: {}", e)),
+                },
+                Err(e) => errors.push(e.to_string()),
+            }
+
+            verified += pending.len();
+            problems += pending
+                .iter()
+                .filter(|r| r.status != VerifyStatus::Ok.as_str())
+                .count();
+
+            let elapsed_ms = start.elapsed().as_millis() as u64;
+            let avg_ms_per_track = elapsed_ms / verified.max(1) as u64;
+
+            let _ = window_clone.emit("verify-progress", VerifyProgress {
+                current: verified,
+                total,
+                problems,
+                current_path: pending.last().map(|r| r.path.clone()),
+                estimated_time_remaining_ms: total.saturating_sub(verified) as u64 * avg_ms_per_track,
+            });
+
+            pending.clear();
+            last_flush = Instant::now();
+        }
+
+        VerifySummary {
+            total,
+            verified,
+            ok: verified - problems,
+            problems,
+            cancelled: cancel.load(Ordering::SeqCst),
+            errors,
+        }
+    })
+    .await?;
+
+    println!(
+        "[VERIFY] Done: {}/{} verified, {} with problems{}",
+        summary.verified,
+        summary.total,
+        summary.problems,
+        if summary.cancelled { " (cancelled)" } else { "" }
+    );
+
+    let _ = window.emit("verify-complete", summary.clone());
+
+    Ok(summary)
+}
+
+/// Ask a running verification to stop; results recorded so far are kept
+#[tauri::command]
+pub async fn cancel_verification(state: State<'_, VerificationState>) -> Result<bool, AudionError> {
+    if !state.running.load(Ordering::SeqCst) {
+        return Ok(false);
+    }
+    state.cancel.store(true, Ordering::SeqCst);
+    Ok(true)
+}
+
+/// Get stored verification results, optionally only the tracks with problems
+#[tauri::command]
+pub async fn get_verification_results(
+    problems_only: Option<bool>,
+    db: State<'_, Database>,
+) -> Result<Vec<queries::VerificationResult>, AudionError> {
+    let conn = db.read()?;
+    queries::get_verification_results(&conn, problems_only.unwrap_or(false))
+        .map_err(AudionError::from)
+}
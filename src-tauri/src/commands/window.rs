@@ -1,3 +1,4 @@
+use crate::error::AudionError;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
@@ -45,16 +46,18 @@ pub fn load_window_config(app_handle: &AppHandle) -> WindowConfig {
     WindowConfig::default()
 }
 
-pub fn save_window_config(app_handle: &AppHandle, config: &WindowConfig) -> Result<(), String> {
+pub fn save_window_config(app_handle: &AppHandle, config: &WindowConfig) -> Result<(), AudionError> {
     if let Some(config_path) = get_config_path(app_handle) {
         if let Some(parent) = config_path.parent() {
             let _ = fs::create_dir_all(parent);
         }
-        let content = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
-        fs::write(config_path, content).map_err(|e| e.to_string())?;
+        let content = serde_json::to_string_pretty(config)?;
+        fs::write(config_path, content)?;
         Ok(())
     } else {
-        Err("Failed to resolve app data directory".to_string())
+        Err(AudionError::Internal(
+            "Failed to resolve app data directory".to_string(),
+        ))
     }
 }
 
@@ -64,7 +67,7 @@ pub fn get_window_start_mode(app_handle: AppHandle) -> WindowStartMode {
 }
 
 #[tauri::command]
-pub fn set_window_start_mode(app_handle: AppHandle, mode: WindowStartMode) -> Result<(), String> {
+pub fn set_window_start_mode(app_handle: AppHandle, mode: WindowStartMode) -> Result<(), AudionError> {
     let mut config = load_window_config(&app_handle);
     config.start_mode = mode;
     save_window_config(&app_handle, &config)
@@ -0,0 +1,672 @@
+// Sorted, filtered library pages with keyset (cursor) pagination.
+//
+// Every sort is a list of key expressions ending in a unique one (the row id),
+// so "everything after the last row" is a single row-value comparison that the
+// matching index can seek to, however deep the page.
+use super::labels;
+use super::queries::{Album, Artist, Track};
+use super::search::escape_like;
+use crate::error::AudionError;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rusqlite::types::Value;
+use rusqlite::{Connection, Row};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+pub const MAX_PAGE_SIZE: u32 = 500;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackSort {
+    /// Artist, then album, track number and title
+    #[default]
+    Artist,
+    /// Album, then track number and title
+    Album,
+    Title,
+    DateAdded,
+    Duration,
+    Bitrate,
+    Year,
+    PlayCount,
+    LastPlayed,
+    /// Unrated tracks sort below 0 stars
+    Rating,
+}
+
+impl TrackSort {
+    // Each list must match an index created by migrations 7, 8, 9 and 10
+    pub(super) fn keys(self) -> &'static [&'static str] {
+        match self {
+            TrackSort::Artist => &[
+                "t.sort_artist COLLATE SORTNAME",
+                "t.sort_album COLLATE SORTNAME",
+                "COALESCE(t.track_number, 0)",
+                "t.sort_title COLLATE SORTNAME",
+                "t.id",
+            ],
+            TrackSort::Album => &[
+                "t.sort_album COLLATE SORTNAME",
+                "COALESCE(t.album_id, 0)",
+                "COALESCE(t.track_number, 0)",
+                "t.sort_title COLLATE SORTNAME",
+                "t.id",
+            ],
+            TrackSort::Title => &["t.sort_title COLLATE SORTNAME", "t.id"],
+            TrackSort::DateAdded => &["COALESCE(t.date_added, '')", "t.id"],
+            TrackSort::Duration => &["COALESCE(t.duration, 0)", "t.id"],
+            TrackSort::Bitrate => &["COALESCE(t.bitrate, 0)", "t.id"],
+            TrackSort::Year => &["COALESCE(t.year, 0)", "t.id"],
+            TrackSort::PlayCount => &["t.play_count", "t.id"],
+            TrackSort::LastPlayed => &["COALESCE(t.last_played, '')", "t.id"],
+            TrackSort::Rating => &["COALESCE(t.rating, -1)", "t.id"],
+        }
+    }
+
+    /// `ORDER BY` terms for a whole-list sort, every key in the same direction
+    pub(super) fn order_by(self, direction: SortDirection) -> String {
+        let direction = match direction {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        };
+        self.keys()
+            .iter()
+            .map(|key| format!("{} {}", key, direction))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Track filters; unset fields don't restrict anything
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TrackFilter {
+    pub format: Option<String>,
+    /// "local" also matches tracks without a source type
+    pub source_type: Option<String>,
+    pub album_id: Option<i64>,
+    /// Only tracks somewhere under this folder
+    pub folder: Option<String>,
+    pub min_bitrate: Option<i32>,
+    pub min_rating: Option<f64>,
+    pub loved: Option<bool>,
+    /// Tracks with this label, on the track or its album
+    pub label_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TrackPageRequest {
+    pub sort: TrackSort,
+    pub direction: SortDirection,
+    pub filter: TrackFilter,
+    /// `next_cursor` of the previous page; `None` for the first page
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlbumSort {
+    /// Artist, then album name
+    #[default]
+    Artist,
+    Name,
+    /// The order albums first appeared in the library
+    Added,
+}
+
+impl AlbumSort {
+    fn keys(self) -> &'static [&'static str] {
+        match self {
+            AlbumSort::Artist => &["a.sort_artist COLLATE SORTNAME", "a.sort_name COLLATE SORTNAME", "a.id"],
+            AlbumSort::Name => &["a.sort_name COLLATE SORTNAME", "a.id"],
+            AlbumSort::Added => &["a.id"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AlbumFilter {
+    pub artist: Option<String>,
+    pub min_rating: Option<f64>,
+    pub loved: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AlbumPageRequest {
+    pub sort: AlbumSort,
+    pub direction: SortDirection,
+    pub filter: AlbumFilter,
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtistSort {
+    #[default]
+    Name,
+    TrackCount,
+}
+
+impl ArtistSort {
+    fn keys(self) -> &'static [&'static str] {
+        match self {
+            ArtistSort::Name => &["MIN(t.sort_artist) COLLATE SORTNAME", "t.artist"],
+            ArtistSort::TrackCount => &["COUNT(*)", "MIN(t.sort_artist) COLLATE SORTNAME", "t.artist"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ArtistPageRequest {
+    pub sort: ArtistSort,
+    pub direction: SortDirection,
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass back to get the next page; `None` on the last one
+    pub next_cursor: Option<String>,
+}
+
+pub fn browse_tracks(conn: &Connection, request: &TrackPageRequest) -> Result<Page<Track>, AudionError> {
+    let filter = &request.filter;
+    let mut conditions = vec![
+        "t.removed_at IS NULL".to_string(),
+        "t.source_type IS NOT 'placeholder'".to_string(),
+    ];
+    let mut params = Vec::new();
+    if let Some(format) = &filter.format {
+        conditions.push("t.format = ? COLLATE NOCASE".to_string());
+        params.push(Value::Text(format.clone()));
+    }
+    if let Some(source_type) = &filter.source_type {
+        conditions.push("COALESCE(t.source_type, 'local') = ? COLLATE NOCASE".to_string());
+        params.push(Value::Text(source_type.clone()));
+    }
+    if let Some(album_id) = filter.album_id {
+        conditions.push("t.album_id = ?".to_string());
+        params.push(Value::Integer(album_id));
+    }
+    if let Some(folder) = &filter.folder {
+        conditions.push("t.path LIKE ? ESCAPE '\\'".to_string());
+        params.push(Value::Text(folder_pattern(folder)));
+    }
+    if let Some(min_bitrate) = filter.min_bitrate {
+        conditions.push("t.bitrate >= ?".to_string());
+        params.push(Value::Integer(min_bitrate.into()));
+    }
+    if let Some(min_rating) = filter.min_rating {
+        conditions.push("t.rating >= ?".to_string());
+        params.push(Value::Real(min_rating));
+    }
+    if let Some(loved) = filter.loved {
+        conditions.push("t.loved = ?".to_string());
+        params.push(Value::Integer(loved.into()));
+    }
+    if let Some(label_id) = filter.label_id {
+        conditions.push(format!("? IN ({})", labels::TRACK_LABEL_IDS));
+        params.push(Value::Integer(label_id));
+    }
+
+    let query = PageQuery {
+        columns: &[
+            "t.id", "t.path", "t.title", "t.artist", "t.album", "t.track_number", "t.duration", "t.album_id",
+            "t.format", "t.bitrate", "t.source_type", "t.cover_url", "t.external_id", "t.local_src", "t.track_cover_path",
+        ],
+        from: "tracks t",
+        conditions,
+        params,
+        group_by: None,
+        keys: request.sort.keys(),
+        direction: request.direction,
+        tag: format!("tracks/{:?}/{:?}", request.sort, request.direction),
+    };
+    query.run(conn, request.cursor.as_deref(), request.limit, |row| {
+        Ok(Track {
+            id: row.get(0)?,
+            path: row.get(1)?,
+            title: row.get(2)?,
+            artist: row.get(3)?,
+            album: row.get(4)?,
+            track_number: row.get(5)?,
+            duration: row.get(6)?,
+            album_id: row.get(7)?,
+            format: row.get(8)?,
+            bitrate: row.get(9)?,
+            source_type: row.get(10)?,
+            cover_url: row.get(11)?,
+            external_id: row.get(12)?,
+            local_src: row.get(13)?,
+            track_cover: None,
+            track_cover_path: row.get(14)?,
+        })
+    })
+}
+
+pub fn browse_albums(conn: &Connection, request: &AlbumPageRequest) -> Result<Page<Album>, AudionError> {
+    let mut conditions = vec!["a.removed_at IS NULL".to_string()];
+    let mut params = Vec::new();
+    if let Some(artist) = &request.filter.artist {
+        conditions.push("a.artist = ?".to_string());
+        params.push(Value::Text(artist.clone()));
+    }
+    if let Some(min_rating) = request.filter.min_rating {
+        conditions.push("a.rating >= ?".to_string());
+        params.push(Value::Real(min_rating));
+    }
+    if let Some(loved) = request.filter.loved {
+        conditions.push("a.loved = ?".to_string());
+        params.push(Value::Integer(loved.into()));
+    }
+
+    let query = PageQuery {
+        columns: &["a.id", "a.name", "a.artist", "a.art_path"],
+        from: "albums a",
+        conditions,
+        params,
+        group_by: None,
+        keys: request.sort.keys(),
+        direction: request.direction,
+        tag: format!("albums/{:?}/{:?}", request.sort, request.direction),
+    };
+    query.run(conn, request.cursor.as_deref(), request.limit, |row| {
+        Ok(Album {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            artist: row.get(2)?,
+            art_data: None,
+            art_path: row.get(3)?,
+        })
+    })
+}
+
+pub fn browse_artists(conn: &Connection, request: &ArtistPageRequest) -> Result<Page<Artist>, AudionError> {
+    let query = PageQuery {
+        columns: &["t.artist", "COUNT(*)", "COUNT(DISTINCT t.album)"],
+        from: "tracks t",
+        conditions: vec![
+            "t.artist IS NOT NULL".to_string(),
+            "t.removed_at IS NULL".to_string(),
+            "t.source_type IS NOT 'placeholder'".to_string(),
+        ],
+        params: Vec::new(),
+        group_by: Some("t.artist"),
+        keys: request.sort.keys(),
+        direction: request.direction,
+        tag: format!("artists/{:?}/{:?}", request.sort, request.direction),
+    };
+    query.run(conn, request.cursor.as_deref(), request.limit, |row| {
+        Ok(Artist {
+            name: row.get(0)?,
+            track_count: row.get(1)?,
+            album_count: row.get(2)?,
+        })
+    })
+}
+
+/// `LIKE` pattern for paths inside `folder`, whichever separator it uses
+pub(crate) fn folder_pattern(folder: &str) -> String {
+    let trimmed = folder.trim_end_matches(['/', '\\']);
+    let separator = if trimmed.contains('\\') && !trimmed.contains('/') {
+        "\\"
+    } else {
+        "/"
+    };
+    format!("{}{}%", escape_like(trimmed), escape_like(separator))
+}
+
+struct PageQuery {
+    columns: &'static [&'static str],
+    from: &'static str,
+    conditions: Vec<String>,
+    params: Vec<Value>,
+    group_by: Option<&'static str>,
+    /// Sort key expressions, most significant first; the last one is unique
+    keys: &'static [&'static str],
+    direction: SortDirection,
+    /// Identifies the listing and order a cursor belongs to
+    tag: String,
+}
+
+impl PageQuery {
+    fn run<T>(
+        mut self,
+        conn: &Connection,
+        cursor: Option<&str>,
+        limit: Option<u32>,
+        map: impl Fn(&Row) -> rusqlite::Result<T>,
+    ) -> Result<Page<T>, AudionError> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as usize;
+        let keys = self.keys.join(", ");
+        let (comparison, bound, order) = match self.direction {
+            SortDirection::Asc => (">", ">=", "ASC"),
+            SortDirection::Desc => ("<", "<=", "DESC"),
+        };
+
+        // Grouped listings sort on aggregates, so their keyset goes in HAVING
+        let mut having = None;
+        if let Some(cursor) = cursor {
+            let after = decode_cursor(cursor, &self.tag, self.keys.len())?;
+            let placeholders = vec!["?"; after.len()].join(", ");
+            // SQLite won't seek an index on a row value that includes the
+            // rowid, so the first key also gets a plain bound to seek on
+            let condition = format!(
+                "{} {} ? AND ({}) {} ({})",
+                self.keys[0], bound, keys, comparison, placeholders
+            );
+            self.params.push(after[0].clone());
+            self.params.extend(after);
+            if self.group_by.is_some() {
+                having = Some(condition);
+            } else {
+                self.conditions.push(condition);
+            }
+        }
+
+        // The sort keys come after the mapped columns, for the next cursor
+        let mut sql = format!("SELECT {}, {} FROM {}", self.columns.join(", "), keys, self.from);
+        if !self.conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", self.conditions.join(" AND ")));
+        }
+        if let Some(group_by) = self.group_by {
+            sql.push_str(&format!(" GROUP BY {}", group_by));
+        }
+        if let Some(having) = having {
+            sql.push_str(&format!(" HAVING {}", having));
+        }
+        let order_by: Vec<String> = self.keys.iter().map(|k| format!("{} {}", k, order)).collect();
+        sql.push_str(&format!(" ORDER BY {} LIMIT ?", order_by.join(", ")));
+        // One extra row tells whether there's another page
+        self.params.push(Value::Integer(limit as i64 + 1));
+
+        let first_key = self.columns.len();
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt.query(rusqlite::params_from_iter(self.params))?;
+        let mut items = Vec::new();
+        let mut last_keys = Vec::new();
+        let mut has_more = false;
+        while let Some(row) = rows.next()? {
+            if items.len() == limit {
+                has_more = true;
+                break;
+            }
+            last_keys = (first_key..first_key + self.keys.len())
+                .map(|i| row.get::<_, Value>(i))
+                .collect::<rusqlite::Result<_>>()?;
+            items.push(map(row)?);
+        }
+
+        Ok(Page {
+            items,
+            next_cursor: has_more.then(|| encode_cursor(&self.tag, &last_keys)),
+        })
+    }
+}
+
+/// Opaque cursor: URL-safe base64 of `[tag, key...]` in JSON
+fn encode_cursor(tag: &str, keys: &[Value]) -> String {
+    let mut parts = vec![serde_json::Value::from(tag)];
+    parts.extend(keys.iter().map(|key| match key {
+        Value::Null | Value::Blob(_) => serde_json::Value::Null,
+        Value::Integer(n) => (*n).into(),
+        Value::Real(f) => (*f).into(),
+        Value::Text(s) => s.as_str().into(),
+    }));
+    URL_SAFE_NO_PAD.encode(serde_json::Value::Array(parts).to_string())
+}
+
+fn decode_cursor(cursor: &str, tag: &str, key_count: usize) -> Result<Vec<Value>, AudionError> {
+    let invalid = || AudionError::InvalidInput("Invalid or outdated page cursor".to_string());
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let parts: Vec<serde_json::Value> = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+    let Some((cursor_tag, keys)) = parts.split_first() else {
+        return Err(invalid());
+    };
+    // A cursor only makes sense for the listing and order it came from
+    if cursor_tag.as_str() != Some(tag) || keys.len() != key_count {
+        return Err(invalid());
+    }
+    keys.iter()
+        .map(|key| match key {
+            serde_json::Value::Null => Ok(Value::Null),
+            serde_json::Value::String(s) => Ok(Value::Text(s.clone())),
+            serde_json::Value::Number(n) => n
+                .as_i64()
+                .map(Value::Integer)
+                .or_else(|| n.as_f64().map(Value::Real))
+                .ok_or_else(invalid),
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{schema, sort_names};
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO albums (id, name, artist) VALUES (1, 'OK Computer', 'Radiohead'), (2, 'The Works', 'Queen');
+            INSERT INTO tracks (path, title, artist, album, album_id, track_number, duration, bitrate, format, source_type, date_added) VALUES
+                ('/music/rock/1.flac', 'Airbag', 'Radiohead', 'OK Computer', 1, 1, 284, 900, 'Flac', NULL, '2024-01-03'),
+                ('/music/rock/2.flac', 'Paranoid Android', 'Radiohead', 'OK Computer', 1, 2, 383, 950, 'Flac', NULL, '2024-01-01'),
+                ('/music/rock/3.mp3', 'Radio Ga Ga', 'Queen', 'The Works', 2, 1, 348, 320, 'Mpeg', 'local', '2024-01-02'),
+                ('/music/pop_hits/4.mp3', 'Halo', 'Beyoncé', NULL, NULL, NULL, 261, 128, 'Mpeg', NULL, '2024-01-04'),
+                ('/music/pop%/5.mp3', 'Untitled', NULL, NULL, NULL, NULL, NULL, NULL, 'Mpeg', 'tidal', NULL);
+            ",
+        )
+        .unwrap();
+        sort_names::regenerate(&conn, &Default::default()).unwrap();
+        conn
+    }
+
+    /// Walk every page, two rows at a time
+    fn all_titles(conn: &Connection, mut request: TrackPageRequest) -> Vec<String> {
+        request.limit = Some(2);
+        let mut titles = Vec::new();
+        loop {
+            let page = browse_tracks(conn, &request).unwrap();
+            titles.extend(page.items.into_iter().map(|t| t.title.unwrap_or_default()));
+            match page.next_cursor {
+                Some(cursor) => request.cursor = Some(cursor),
+                None => return titles,
+            }
+        }
+    }
+
+    fn sorted(sort: TrackSort, direction: SortDirection) -> TrackPageRequest {
+        TrackPageRequest {
+            sort,
+            direction,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pages_follow_each_sort_order() {
+        let conn = library();
+        assert_eq!(
+            all_titles(&conn, TrackPageRequest::default()),
+            vec!["Untitled", "Halo", "Radio Ga Ga", "Airbag", "Paranoid Android"]
+        );
+        assert_eq!(
+            all_titles(&conn, sorted(TrackSort::DateAdded, SortDirection::Desc)),
+            vec!["Halo", "Airbag", "Radio Ga Ga", "Paranoid Android", "Untitled"]
+        );
+        assert_eq!(
+            all_titles(&conn, sorted(TrackSort::Bitrate, SortDirection::Asc)),
+            vec!["Untitled", "Halo", "Radio Ga Ga", "Airbag", "Paranoid Android"]
+        );
+    }
+
+    #[test]
+    fn filters_narrow_the_pages() {
+        let conn = library();
+        let request = |filter: TrackFilter| TrackPageRequest {
+            filter,
+            ..Default::default()
+        };
+
+        let local_mp3 = TrackFilter {
+            format: Some("mpeg".into()),
+            source_type: Some("local".into()),
+            ..Default::default()
+        };
+        assert_eq!(all_titles(&conn, request(local_mp3)), vec!["Halo", "Radio Ga Ga"]);
+
+        let folder = TrackFilter {
+            folder: Some("/music/rock/".into()),
+            min_bitrate: Some(900),
+            ..Default::default()
+        };
+        assert_eq!(all_titles(&conn, request(folder)), vec!["Airbag", "Paranoid Android"]);
+
+        // LIKE wildcards in folder names are literal
+        let folder = TrackFilter {
+            folder: Some("/music/pop%".into()),
+            ..Default::default()
+        };
+        assert_eq!(all_titles(&conn, request(folder)), vec!["Untitled"]);
+
+        conn.execute_batch(
+            "UPDATE tracks SET rating = 4.5, loved = 1 WHERE title = 'Airbag';
+             UPDATE tracks SET rating = 2 WHERE title = 'Halo';",
+        )
+        .unwrap();
+        let rated = TrackFilter {
+            min_rating: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(all_titles(&conn, request(rated)), vec!["Halo", "Airbag"]);
+        let loved = TrackFilter {
+            loved: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(all_titles(&conn, request(loved)), vec!["Airbag"]);
+    }
+
+    #[test]
+    fn albums_and_artists_page_too() {
+        let conn = library();
+        let albums = browse_albums(
+            &conn,
+            &AlbumPageRequest {
+                sort: AlbumSort::Name,
+                limit: Some(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(albums.items[0].name, "OK Computer");
+        let next = browse_albums(
+            &conn,
+            &AlbumPageRequest {
+                sort: AlbumSort::Name,
+                limit: Some(1),
+                cursor: albums.next_cursor,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(next.items[0].name, "The Works");
+        assert!(next.next_cursor.is_none());
+
+        let mut request = ArtistPageRequest {
+            sort: ArtistSort::TrackCount,
+            direction: SortDirection::Desc,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = browse_artists(&conn, &request).unwrap();
+        let names: Vec<_> = page.items.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["Radiohead", "Queen"]);
+        request.cursor = page.next_cursor;
+        let page = browse_artists(&conn, &request).unwrap();
+        assert_eq!(page.items[0].name, "Beyoncé");
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn cursors_are_tied_to_their_listing() {
+        let conn = library();
+        let page = browse_tracks(
+            &conn,
+            &TrackPageRequest {
+                limit: Some(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut other = sorted(TrackSort::Title, SortDirection::Asc);
+        other.cursor = page.next_cursor;
+        assert!(matches!(
+            browse_tracks(&conn, &other),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        other.cursor = Some("not a cursor".into());
+        assert!(matches!(
+            browse_tracks(&conn, &other),
+            Err(AudionError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn sorts_are_served_by_indexes() {
+        let conn = library();
+        for sort in [
+            TrackSort::Artist,
+            TrackSort::Album,
+            TrackSort::Title,
+            TrackSort::DateAdded,
+            TrackSort::Duration,
+            TrackSort::Bitrate,
+            TrackSort::Year,
+            TrackSort::PlayCount,
+            TrackSort::LastPlayed,
+            TrackSort::Rating,
+        ] {
+            let keys = sort.keys().join(", ");
+            let plan: Vec<String> = conn
+                .prepare(&format!(
+                    "EXPLAIN QUERY PLAN SELECT t.id FROM tracks t WHERE {0} >= ? AND ({1}) > ({2}) ORDER BY {1} LIMIT 10",
+                    sort.keys()[0],
+                    keys,
+                    vec!["?"; sort.keys().len()].join(", ")
+                ))
+                .unwrap()
+                .query_map(rusqlite::params_from_iter(vec![Value::Null; sort.keys().len() + 1]), |row| {
+                    row.get(3)
+                })
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+            let plan = plan.join("\n");
+            assert!(plan.contains("SEARCH t USING"), "{:?}: {}", sort, plan);
+            assert!(plan.contains("INDEX idx_tracks_by_"), "{:?}: {}", sort, plan);
+            assert!(!plan.contains("TEMP B-TREE"), "{:?}: {}", sort, plan);
+        }
+    }
+}
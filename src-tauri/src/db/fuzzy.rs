@@ -0,0 +1,207 @@
+// Typo-tolerant matching: the trigram index finds candidates, which are
+// scored here by edit distance
+use super::queries::{self, Track};
+use super::search::CompiledSearch;
+use crate::utils::fold_text;
+use rusqlite::{Connection, Result};
+use std::cmp::Ordering;
+
+/// How many trigram candidates are scored per query
+const CANDIDATES: i32 = 200;
+
+/// A word needs at least this similarity (0..1) to count as a near match
+pub const MIN_SIMILARITY: f64 = 0.7;
+
+/// Exact search results below this count get topped up with near matches
+pub const FALLBACK_BELOW: usize = 5;
+
+/// Folded words, split the same way as the search index
+pub fn words(text: &str) -> Vec<String> {
+    fold_text(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// FTS5 expression matching rows that share any trigram with the words
+fn trigram_match(words: &[String]) -> Option<String> {
+    let mut trigrams: Vec<String> = Vec::new();
+    for word in words {
+        let chars: Vec<char> = word.chars().collect();
+        for window in chars.windows(3) {
+            let trigram: String = window.iter().collect();
+            if !trigrams.contains(&trigram) {
+                trigrams.push(trigram);
+            }
+        }
+    }
+
+    if trigrams.is_empty() {
+        return None;
+    }
+    Some(
+        trigrams
+            .iter()
+            .map(|t| format!("\"{}\"", t))
+            .collect::<Vec<_>>()
+            .join(" OR "),
+    )
+}
+
+/// Normalized Damerau-Levenshtein similarity. A half-typed word is also
+/// compared against the start of `word`, slightly discounted.
+pub fn word_similarity(query_word: &str, word: &str) -> f64 {
+    let full = strsim::normalized_damerau_levenshtein(query_word, word);
+    let typed = query_word.chars().count();
+    if word.chars().count() <= typed {
+        return full;
+    }
+    let start: String = word.chars().take(typed).collect();
+    full.max(strsim::normalized_damerau_levenshtein(query_word, &start) * 0.95)
+}
+
+/// Mean over the query words of each one's best match among the words of `text`
+pub fn text_similarity(query_words: &[String], text: &str) -> f64 {
+    let text_words = words(text);
+    if query_words.is_empty() || text_words.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = query_words
+        .iter()
+        .map(|q| {
+            text_words
+                .iter()
+                .map(|w| word_similarity(q, w))
+                .fold(0.0, f64::max)
+        })
+        .sum();
+    total / query_words.len() as f64
+}
+
+/// Tracks whose title, artist and album together come close to `query`,
+/// best first, with their similarity
+pub fn fuzzy_search_tracks(conn: &Connection, query: &str, limit: usize) -> Result<Vec<(Track, f64)>> {
+    let query_words = words(query);
+    let Some(trigram_match) = trigram_match(&query_words) else {
+        return Ok(Vec::new());
+    };
+
+    let mut scored: Vec<(Track, f64, f64)> = queries::fuzzy_track_candidates(conn, &trigram_match, CANDIDATES)?
+        .into_iter()
+        .filter_map(|track| {
+            let title = track.title.as_deref().unwrap_or_default();
+            let all = format!(
+                "{} {} {}",
+                title,
+                track.artist.as_deref().unwrap_or_default(),
+                track.album.as_deref().unwrap_or_default()
+            );
+            let score = text_similarity(&query_words, &all);
+            // Title similarity breaks ties, so the song beats others by the same artist
+            let title_score = text_similarity(&query_words, title);
+            (score >= MIN_SIMILARITY).then_some((track, score, title_score))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(Ordering::Equal)
+            .then(b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal))
+    });
+    Ok(scored
+        .into_iter()
+        .take(limit)
+        .map(|(track, score, _)| (track, score))
+        .collect())
+}
+
+/// When a plain-text search found fewer than `FALLBACK_BELOW` tracks, append
+/// near matches (up to `limit` results in total)
+pub fn add_near_matches(
+    conn: &Connection,
+    search: &CompiledSearch,
+    tracks: &mut Vec<Track>,
+    limit: usize,
+) -> Result<()> {
+    let Some(text) = &search.plain_text else {
+        return Ok(());
+    };
+    if tracks.len() >= FALLBACK_BELOW || tracks.len() >= limit {
+        return Ok(());
+    }
+
+    for (track, _) in fuzzy_search_tracks(conn, text, limit)? {
+        if tracks.len() >= limit {
+            break;
+        }
+        if !tracks.iter().any(|t| t.id == track.id) {
+            tracks.push(track);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{schema, search};
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (path, title, artist, album) VALUES
+                ('/m/1.flac', 'Karma Police', 'Radiohead', 'OK Computer'),
+                ('/m/2.flac', 'Help!', 'The Beatles', 'Help!'),
+                ('/m/3.flac', 'Yesterday', 'The Beatles', 'Help!'),
+                ('/m/4.flac', 'Déjà Vu', 'Beyoncé', 'B''Day');
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn fuzzy_titles(conn: &Connection, query: &str) -> Vec<String> {
+        fuzzy_search_tracks(conn, query, 10)
+            .unwrap()
+            .into_iter()
+            .map(|(t, _)| t.title.unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn similarity_tolerates_typos_and_partial_words() {
+        assert!(word_similarity("radiohaed", "radiohead") > 0.85);
+        assert!(word_similarity("beatels", "beatles") > 0.85);
+        assert!(word_similarity("radiohe", "radiohead") > 0.9);
+        assert!(word_similarity("queen", "radiohead") < MIN_SIMILARITY);
+    }
+
+    #[test]
+    fn finds_misspelled_artists_and_titles() {
+        let conn = library();
+        assert_eq!(fuzzy_titles(&conn, "radiohaed"), vec!["Karma Police"]);
+        assert_eq!(fuzzy_titles(&conn, "beatels help"), vec!["Help!", "Yesterday"]);
+        assert_eq!(fuzzy_titles(&conn, "deja vu beyonse"), vec!["Déjà Vu"]);
+        assert!(fuzzy_titles(&conn, "metallica").is_empty());
+        assert!(fuzzy_titles(&conn, "ab").is_empty());
+    }
+
+    #[test]
+    fn near_matches_only_top_up_plain_text_searches() {
+        let conn = library();
+
+        let search = search::compile_query("radiohaed").unwrap().unwrap();
+        let mut tracks = queries::search_tracks(&conn, &search, 20, 0).unwrap();
+        assert!(tracks.is_empty());
+        add_near_matches(&conn, &search, &mut tracks, 20).unwrap();
+        assert_eq!(tracks.len(), 1);
+
+        let filtered = search::compile_query("radiohaed year:>2000").unwrap().unwrap();
+        let mut tracks = Vec::new();
+        add_near_matches(&conn, &filtered, &mut tracks, 20).unwrap();
+        assert!(tracks.is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+// One query, grouped results: matching artists, albums, playlists and tracks
+use super::fuzzy;
+use super::queries::{self, AlbumMatch, ArtistMatch, Playlist, Track};
+use super::search;
+use super::search_query::{Expr, TextField};
+use crate::utils::fold_text;
+use rusqlite::{Connection, Result};
+use serde::{Deserialize, Serialize};
+
+/// How many items each group returns; totals are counted regardless
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct GroupLimits {
+    pub artists: usize,
+    pub albums: usize,
+    pub playlists: usize,
+    pub tracks: usize,
+}
+
+impl Default for GroupLimits {
+    fn default() -> Self {
+        Self {
+            artists: 5,
+            albums: 5,
+            playlists: 5,
+            tracks: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchGroup<T> {
+    pub items: Vec<T>,
+    /// Everything that matched, not just the returned items
+    pub total: i64,
+}
+
+impl<T> Default for SearchGroup<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            total: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GroupedResults {
+    pub artists: SearchGroup<ArtistMatch>,
+    pub albums: SearchGroup<AlbumMatch>,
+    pub playlists: SearchGroup<Playlist>,
+    pub tracks: SearchGroup<Track>,
+}
+
+/// Run a parsed query against every group. Free text is matched against
+/// artist names for the artist group and album names for the album group;
+/// field filters narrow down the tracks behind both.
+pub fn search_grouped(conn: &Connection, expr: &Expr, limits: GroupLimits) -> Result<GroupedResults> {
+    let mut results = GroupedResults::default();
+
+    if let Some(search) = search::compile(expr) {
+        let mut tracks = queries::search_tracks(conn, &search, limits.tracks as i32, 0)?;
+        let total = queries::count_search_tracks(conn, &search)?;
+        fuzzy::add_near_matches(conn, &search, &mut tracks, limits.tracks)?;
+        results.tracks = SearchGroup {
+            total: total.max(tracks.len() as i64),
+            items: tracks,
+        };
+    }
+
+    if let Some(search) = search::compile(&scoped(expr, TextField::Artist)) {
+        let (items, total) = queries::search_artists(conn, &search, limits.artists as i32)?;
+        results.artists = SearchGroup { items, total };
+    }
+
+    if let Some(search) = search::compile(&scoped(expr, TextField::Album)) {
+        let (items, total) = queries::search_albums(conn, &search, limits.albums as i32)?;
+        results.albums = SearchGroup { items, total };
+    }
+
+    let words = free_words(expr);
+    if !words.is_empty() {
+        let mut playlists: Vec<Playlist> = queries::get_all_playlists(conn)?
+            .into_iter()
+            .filter(|p| {
+                let name = fold_text(&p.name);
+                words.iter().all(|w| name.contains(w.as_str()))
+            })
+            .collect();
+        let total = playlists.len() as i64;
+        playlists.truncate(limits.playlists);
+        results.playlists = SearchGroup {
+            items: playlists,
+            total,
+        };
+    }
+
+    Ok(results)
+}
+
+/// The same query with free text restricted to one column
+fn scoped(expr: &Expr, field: TextField) -> Expr {
+    match expr {
+        Expr::And(terms) => Expr::And(terms.iter().map(|t| scoped(t, field)).collect()),
+        Expr::Or(terms) => Expr::Or(terms.iter().map(|t| scoped(t, field)).collect()),
+        Expr::Not(inner) => Expr::Not(Box::new(scoped(inner, field))),
+        Expr::Text {
+            field: None,
+            value,
+            phrase,
+        } => Expr::Text {
+            field: Some(field),
+            value: value.clone(),
+            phrase: *phrase,
+        },
+        other => other.clone(),
+    }
+}
+
+/// Folded words of the top-level free text terms, which playlist names must contain
+fn free_words(expr: &Expr) -> Vec<String> {
+    let terms: Vec<&Expr> = match expr {
+        Expr::And(terms) => terms.iter().collect(),
+        other => vec![other],
+    };
+    terms
+        .into_iter()
+        .filter_map(|term| match term {
+            Expr::Text {
+                field: None, value, ..
+            } => Some(fuzzy::words(value)),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{schema, search_query};
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO albums (id, name, artist, art_path) VALUES
+                (1, 'OK Computer', 'Radiohead', '/art/1.jpg'),
+                (2, 'Kid A', 'Radiohead', NULL),
+                (3, 'Radio Songs', 'Various', NULL);
+            INSERT INTO tracks (path, title, artist, album, album_id, year) VALUES
+                ('/m/1.flac', 'Airbag', 'Radiohead', 'OK Computer', 1, 1997),
+                ('/m/2.flac', 'Karma Police', 'Radiohead', 'OK Computer', 1, 1997),
+                ('/m/3.flac', 'Idioteque', 'Radiohead', 'Kid A', 2, 2000),
+                ('/m/4.flac', 'Radio Ga Ga', 'Queen', 'Radio Songs', 3, 1984),
+                ('/m/5.flac', 'Video Killed the Radio Star', 'The Buggles', 'Radio Songs', 3, 1979);
+            INSERT INTO playlists (name) VALUES ('Radio Edits'), ('Gym'), ('Late Night Radio');
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn grouped(conn: &Connection, query: &str, limits: GroupLimits) -> GroupedResults {
+        let expr = search_query::parse(query).unwrap().unwrap();
+        search_grouped(conn, &expr, limits).unwrap()
+    }
+
+    #[test]
+    fn groups_have_their_own_limits_and_totals() {
+        let conn = library();
+        let limits = GroupLimits {
+            artists: 5,
+            albums: 1,
+            playlists: 1,
+            tracks: 2,
+        };
+        let results = grouped(&conn, "radio", limits);
+
+        let artists: Vec<_> = results.artists.items.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(artists, vec!["Radiohead"]);
+        assert_eq!(results.artists.items[0].track_count, 3);
+        assert_eq!(results.artists.total, 1);
+
+        assert_eq!(results.albums.items.len(), 1);
+        assert_eq!(results.albums.items[0].name, "Radio Songs");
+        assert_eq!(results.albums.total, 1);
+
+        assert_eq!(results.playlists.items.len(), 1);
+        assert_eq!(results.playlists.total, 2);
+
+        assert_eq!(results.tracks.items.len(), 2);
+        assert_eq!(results.tracks.total, 5);
+    }
+
+    #[test]
+    fn filters_apply_to_every_track_group() {
+        let conn = library();
+        let results = grouped(&conn, "year:<2000", GroupLimits::default());
+
+        assert_eq!(results.tracks.total, 4);
+        let albums: Vec<_> = results.albums.items.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(albums, vec!["OK Computer", "Radio Songs"]);
+        assert_eq!(results.albums.items[0].art_path.as_deref(), Some("/art/1.jpg"));
+        assert_eq!(results.artists.total, 3);
+        // No free text, so no playlist can match
+        assert_eq!(results.playlists.total, 0);
+    }
+}
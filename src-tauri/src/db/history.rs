@@ -0,0 +1,473 @@
+// Play history: one event per playback, and the per-track counters built from it
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+
+/// A playback counts as a play once it finished, or ran for half the track
+/// or this long, whichever comes first
+const PLAY_THRESHOLD_MS: i64 = 4 * 60 * 1000;
+
+/// Where playback was started from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaySource {
+    Library,
+    Album,
+    Artist,
+    Playlist,
+    Search,
+    Queue,
+    Other,
+}
+
+impl PlaySource {
+    fn as_str(self) -> &'static str {
+        match self {
+            PlaySource::Library => "library",
+            PlaySource::Album => "album",
+            PlaySource::Artist => "artist",
+            PlaySource::Playlist => "playlist",
+            PlaySource::Search => "search",
+            PlaySource::Queue => "queue",
+            PlaySource::Other => "other",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "library" => PlaySource::Library,
+            "album" => PlaySource::Album,
+            "artist" => PlaySource::Artist,
+            "playlist" => PlaySource::Playlist,
+            "search" => PlaySource::Search,
+            "queue" => PlaySource::Queue,
+            _ => PlaySource::Other,
+        }
+    }
+}
+
+/// A finished (or abandoned) playback, as reported by the player
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct NewPlayEvent {
+    /// Library track; external tracks may instead be identified by `external_id`
+    pub track_id: Option<i64>,
+    pub external_id: Option<String>,
+    /// Where the track itself comes from ("local", or a plugin's source type)
+    pub track_source: Option<String>,
+    /// Shown in history when the track isn't in the library
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Track length, when the library doesn't know it
+    pub duration_ms: Option<i64>,
+    /// UTC, `YYYY-MM-DD HH:MM:SS`; defaults to now
+    pub started_at: Option<String>,
+    pub listened_ms: i64,
+    pub completed: bool,
+    pub skipped: bool,
+    pub source: Option<PlaySource>,
+    /// Playlist or album id, search query...
+    pub source_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayEvent {
+    pub id: i64,
+    /// `None` when the track isn't (or is no longer) in the library
+    pub track_id: Option<i64>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track_source: Option<String>,
+    pub external_id: Option<String>,
+    pub started_at: String,
+    pub listened_ms: i64,
+    pub completed: bool,
+    pub skipped: bool,
+    /// Whether it counted towards the track's play count
+    pub counted: bool,
+    pub source: Option<PlaySource>,
+    pub source_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub track_id: Option<i64>,
+    /// Inclusive lower and exclusive upper bound on `started_at`
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Only events before this one, for paging back through history
+    pub before_id: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayStats {
+    pub track_id: i64,
+    pub play_count: i64,
+    pub skip_count: i64,
+    pub last_played: Option<String>,
+}
+
+/// Store an event; the triggers on `play_events` update the track's counters
+pub fn record_play_event(conn: &Connection, event: &NewPlayEvent) -> Result<i64> {
+    // Streamed tracks that were added to the library count for that track
+    let track_id = match (event.track_id, &event.external_id) {
+        (Some(id), _) => Some(id),
+        (None, Some(external_id)) => conn
+            .query_row(
+                "SELECT id FROM tracks WHERE external_id = ?1 AND (?2 IS NULL OR source_type = ?2)",
+                params![external_id, event.track_source],
+                |row| row.get(0),
+            )
+            .optional()?,
+        (None, None) => None,
+    };
+
+    // What the library knows about the track wins over what the player sent
+    let known = match track_id {
+        Some(id) => conn
+            .query_row(
+                "SELECT title, artist, album, COALESCE(source_type, 'local'), external_id, duration
+                 FROM tracks WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                        row.get::<_, Option<i64>>(5)?,
+                    ))
+                },
+            )
+            .optional()?,
+        None => None,
+    };
+    // An id the library doesn't know is kept out of the foreign key
+    let track_id = track_id.filter(|_| known.is_some());
+    let (title, artist, album, track_source, external_id, duration_secs) =
+        known.unwrap_or_default();
+    let duration_ms = duration_secs.map(|s| s * 1000).or(event.duration_ms);
+
+    let threshold = duration_ms
+        .filter(|d| *d > 0)
+        .map_or(PLAY_THRESHOLD_MS, |d| (d / 2).min(PLAY_THRESHOLD_MS));
+    let counted = event.completed || event.listened_ms >= threshold;
+
+    conn.execute(
+        "INSERT INTO play_events
+            (track_id, title, artist, album, track_source, external_id, started_at,
+             listened_ms, completed, skipped, counted, source, source_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, COALESCE(?7, CURRENT_TIMESTAMP), ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            track_id,
+            title.or_else(|| event.title.clone()),
+            artist.or_else(|| event.artist.clone()),
+            album.or_else(|| event.album.clone()),
+            track_source.or_else(|| event.track_source.clone()),
+            external_id.or_else(|| event.external_id.clone()),
+            event.started_at,
+            event.listened_ms.max(0),
+            event.completed,
+            event.skipped,
+            counted,
+            event.source.map(PlaySource::as_str),
+            event.source_id,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Events newest first
+pub fn get_play_history(conn: &Connection, query: &HistoryQuery) -> Result<Vec<PlayEvent>> {
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+    if let Some(track_id) = query.track_id {
+        conditions.push("track_id = ?");
+        values.push(Value::Integer(track_id));
+    }
+    if let Some(since) = &query.since {
+        conditions.push("started_at >= ?");
+        values.push(Value::Text(since.clone()));
+    }
+    if let Some(until) = &query.until {
+        conditions.push("started_at < ?");
+        values.push(Value::Text(until.clone()));
+    }
+    if let Some(before_id) = query.before_id {
+        conditions.push("(started_at, id) < (SELECT started_at, id FROM play_events WHERE id = ?)");
+        values.push(Value::Integer(before_id));
+    }
+    values.push(Value::Integer(query.limit.unwrap_or(100).min(1000).into()));
+
+    let filter = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, track_id, title, artist, album, track_source, external_id, started_at,
+                listened_ms, completed, skipped, counted, source, source_id
+         FROM play_events {}
+         ORDER BY started_at DESC, id DESC
+         LIMIT ?",
+        filter
+    ))?;
+    let events = stmt
+        .query_map(rusqlite::params_from_iter(values), row_to_play_event)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(events)
+}
+
+fn row_to_play_event(row: &Row) -> Result<PlayEvent> {
+    Ok(PlayEvent {
+        id: row.get(0)?,
+        track_id: row.get(1)?,
+        title: row.get(2)?,
+        artist: row.get(3)?,
+        album: row.get(4)?,
+        track_source: row.get(5)?,
+        external_id: row.get(6)?,
+        started_at: row.get(7)?,
+        listened_ms: row.get(8)?,
+        completed: row.get(9)?,
+        skipped: row.get(10)?,
+        counted: row.get(11)?,
+        source: row
+            .get::<_, Option<String>>(12)?
+            .map(|s| PlaySource::parse(&s)),
+        source_id: row.get(13)?,
+    })
+}
+
+pub fn get_play_stats(conn: &Connection, track_ids: &[i64]) -> Result<Vec<PlayStats>> {
+    if track_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; track_ids.len()].join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, play_count, skip_count, last_played FROM tracks WHERE id IN ({})",
+        placeholders
+    ))?;
+    let stats = stmt
+        .query_map(rusqlite::params_from_iter(track_ids), |row| {
+            Ok(PlayStats {
+                track_id: row.get(0)?,
+                play_count: row.get(1)?,
+                skip_count: row.get(2)?,
+                last_played: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(stats)
+}
+
+/// Delete events (all of them, or one track's); the counters follow
+pub fn clear_play_history(conn: &Connection, track_id: Option<i64>) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM play_events WHERE ?1 IS NULL OR track_id = ?1",
+        params![track_id],
+    )
+}
+
+/// Plays from the same day in earlier years
+#[derive(Debug, Clone, Serialize)]
+pub struct OnThisDay {
+    pub year: i32,
+    pub events: Vec<PlayEvent>,
+}
+
+/// Events on `date`'s month and day (local time) in the years before it,
+/// most recent year first. Each year keeps at most `limit` events.
+pub fn on_this_day(
+    conn: &Connection,
+    date: &str,
+    utc_offset_minutes: i32,
+    limit: u32,
+) -> Result<Vec<OnThisDay>> {
+    let offset = format!("{:+} minutes", utc_offset_minutes);
+    let mut stmt = conn.prepare(
+        "SELECT id, track_id, title, artist, album, track_source, external_id, started_at,
+                listened_ms, completed, skipped, counted, source, source_id,
+                CAST(strftime('%Y', started_at, ?1) AS INTEGER)
+         FROM play_events
+         WHERE strftime('%m-%d', started_at, ?1) = strftime('%m-%d', ?2)
+           AND date(started_at, ?1) < date(?2)
+         ORDER BY started_at DESC, id DESC",
+    )?;
+    let rows = stmt.query_map(params![offset, date], |row| {
+        Ok((row.get::<_, i32>(14)?, row_to_play_event(row)?))
+    })?;
+
+    let mut days: Vec<OnThisDay> = Vec::new();
+    for row in rows {
+        let (year, event) = row?;
+        match days.last_mut() {
+            Some(day) if day.year == year => {
+                if day.events.len() < limit as usize {
+                    day.events.push(event);
+                }
+            }
+            _ => days.push(OnThisDay {
+                year,
+                events: vec![event],
+            }),
+        }
+    }
+    Ok(days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (id, path, title, artist, duration) VALUES (1, '/m/1.flac', 'Airbag', 'Radiohead', 284);
+            INSERT INTO tracks (id, path, title, artist, duration, source_type, external_id)
+                VALUES (2, 'tidal://123', 'Halo', 'Beyoncé', 261, 'tidal', '123');
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn stats(conn: &Connection, track_id: i64) -> (i64, i64, Option<String>) {
+        let s = get_play_stats(conn, &[track_id]).unwrap().remove(0);
+        (s.play_count, s.skip_count, s.last_played)
+    }
+
+    fn event(track_id: i64, started_at: &str, listened_ms: i64) -> NewPlayEvent {
+        NewPlayEvent {
+            track_id: Some(track_id),
+            started_at: Some(started_at.into()),
+            listened_ms,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn counters_follow_events() {
+        let conn = library();
+        // Half of a 284 s track counts as a play
+        record_play_event(&conn, &event(1, "2024-05-01 10:00:00", 142_000)).unwrap();
+        record_play_event(
+            &conn,
+            &NewPlayEvent {
+                skipped: true,
+                ..event(1, "2024-05-02 10:00:00", 5_000)
+            },
+        )
+        .unwrap();
+        record_play_event(
+            &conn,
+            &NewPlayEvent {
+                completed: true,
+                ..event(1, "2024-04-01 10:00:00", 1_000)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(stats(&conn, 1), (2, 1, Some("2024-05-02 10:00:00".into())));
+
+        clear_play_history(&conn, Some(1)).unwrap();
+        assert_eq!(stats(&conn, 1), (0, 0, None));
+    }
+
+    #[test]
+    fn external_tracks_are_recorded() {
+        let conn = library();
+        // Resolved to the library copy by its external id
+        record_play_event(
+            &conn,
+            &NewPlayEvent {
+                external_id: Some("123".into()),
+                track_source: Some("tidal".into()),
+                completed: true,
+                source: Some(PlaySource::Search),
+                source_id: Some("halo".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(stats(&conn, 2).0, 1);
+
+        // Never added to the library: kept with what the player sent
+        record_play_event(
+            &conn,
+            &NewPlayEvent {
+                external_id: Some("999".into()),
+                track_source: Some("tidal".into()),
+                title: Some("Formation".into()),
+                artist: Some("Beyoncé".into()),
+                started_at: Some("2030-01-01 00:00:00".into()),
+                listened_ms: 30_000,
+                duration_ms: Some(200_000),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let history = get_play_history(&conn, &HistoryQuery::default()).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].track_id, None);
+        assert_eq!(history[0].title.as_deref(), Some("Formation"));
+        assert!(!history[0].counted);
+        assert_eq!(history[1].track_id, Some(2));
+        assert_eq!(history[1].source, Some(PlaySource::Search));
+
+        let older = get_play_history(
+            &conn,
+            &HistoryQuery {
+                before_id: Some(history[0].id),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(older.len(), 1);
+    }
+
+    #[test]
+    fn history_outlives_deleted_tracks() {
+        let conn = library();
+        conn.execute("PRAGMA foreign_keys = ON", []).unwrap();
+        record_play_event(&conn, &event(1, "2024-05-01 10:00:00", 300_000)).unwrap();
+        conn.execute("DELETE FROM tracks WHERE id = 1", []).unwrap();
+
+        let history = get_play_history(&conn, &HistoryQuery::default()).unwrap();
+        assert_eq!(history[0].track_id, None);
+        assert_eq!(history[0].title.as_deref(), Some("Airbag"));
+    }
+
+    #[test]
+    fn on_this_day_groups_earlier_years() {
+        let conn = library();
+        for started_at in [
+            "2022-05-01 08:00:00",
+            "2023-05-01 09:00:00",
+            "2023-05-01 23:30:00",
+            "2023-05-02 10:00:00",
+            "2024-05-01 10:00:00",
+        ] {
+            record_play_event(&conn, &event(1, started_at, 300_000)).unwrap();
+        }
+
+        let days = on_this_day(&conn, "2024-05-01", 0, 10).unwrap();
+        let years: Vec<_> = days.iter().map(|d| (d.year, d.events.len())).collect();
+        assert_eq!(years, vec![(2023, 2), (2022, 1)]);
+
+        // An hour east of UTC the late play moves to May 2nd, and the one
+        // before midnight on April 30th doesn't exist
+        let days = on_this_day(&conn, "2024-05-01", 60, 10).unwrap();
+        let years: Vec<_> = days.iter().map(|d| (d.year, d.events.len())).collect();
+        assert_eq!(years, vec![(2023, 1), (2022, 1)]);
+    }
+}
@@ -0,0 +1,424 @@
+// User labels for tracks and albums. A track has the labels linked to it plus
+// those of its album; search, smart playlists and browsing all go by that.
+use crate::error::AudionError;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+
+/// Ids of the labels of track `t`, its own and its album's
+pub const TRACK_LABEL_IDS: &str = "SELECT label_id FROM track_labels WHERE track_id = t.id
+     UNION SELECT label_id FROM album_labels WHERE album_id = t.album_id";
+
+/// Condition that track `t` has a label `l` matching `condition`
+pub fn has_label(condition: &str) -> String {
+    format!(
+        "EXISTS (SELECT 1 FROM labels l WHERE l.id IN ({}) AND {})",
+        TRACK_LABEL_IDS, condition
+    )
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Label {
+    pub id: i64,
+    pub name: String,
+    /// `#rgb` or `#rrggbb`
+    pub color: Option<String>,
+    pub created_at: Option<String>,
+    /// Tracks and albums the label is linked to directly
+    pub track_count: i64,
+    pub album_count: i64,
+}
+
+/// What a label is linked to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelTarget {
+    Track,
+    Album,
+}
+
+impl LabelTarget {
+    fn table(self) -> &'static str {
+        match self {
+            LabelTarget::Track => "tracks",
+            LabelTarget::Album => "albums",
+        }
+    }
+
+    fn link_table(self) -> &'static str {
+        match self {
+            LabelTarget::Track => "track_labels",
+            LabelTarget::Album => "album_labels",
+        }
+    }
+
+    fn link_column(self) -> &'static str {
+        match self {
+            LabelTarget::Track => "track_id",
+            LabelTarget::Album => "album_id",
+        }
+    }
+}
+
+const LABEL_COLUMNS: &str = "l.id, l.name, l.color, l.created_at,
+     (SELECT COUNT(*) FROM track_labels WHERE label_id = l.id),
+     (SELECT COUNT(*) FROM album_labels WHERE label_id = l.id)";
+
+fn label_from_row(row: &rusqlite::Row) -> Result<Label> {
+    Ok(Label {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        color: row.get(2)?,
+        created_at: row.get(3)?,
+        track_count: row.get(4)?,
+        album_count: row.get(5)?,
+    })
+}
+
+/// Every label, by name
+pub fn list(conn: &Connection) -> Result<Vec<Label>> {
+    conn.prepare(&format!(
+        "SELECT {} FROM labels l ORDER BY l.name COLLATE NOCASE",
+        LABEL_COLUMNS
+    ))?
+    .query_map([], label_from_row)?
+    .collect()
+}
+
+/// Labels linked directly to a track or album, by name
+pub fn labels_of(conn: &Connection, target: LabelTarget, id: i64) -> Result<Vec<Label>> {
+    conn.prepare(&format!(
+        "SELECT {} FROM labels l JOIN {} link ON link.label_id = l.id
+         WHERE link.{} = ?1 ORDER BY l.name COLLATE NOCASE",
+        LABEL_COLUMNS,
+        target.link_table(),
+        target.link_column()
+    ))?
+    .query_map([id], label_from_row)?
+    .collect()
+}
+
+fn ensure_exists(conn: &Connection, label_id: i64) -> Result<(), AudionError> {
+    conn.query_row("SELECT 1 FROM labels WHERE id = ?1", [label_id], |_| Ok(()))
+        .optional()?
+        .ok_or_else(|| AudionError::NotFound(format!("Label {} not found", label_id)))
+}
+
+/// A trimmed name that no other label has (ignoring case)
+fn label_name<'a>(
+    conn: &Connection,
+    name: &'a str,
+    except: Option<i64>,
+) -> Result<&'a str, AudionError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AudionError::InvalidInput(
+            "Label name can't be empty".to_string(),
+        ));
+    }
+    let taken = conn
+        .query_row(
+            "SELECT 1 FROM labels WHERE name = ?1 AND id IS NOT ?2",
+            params![name, except],
+            |_| Ok(()),
+        )
+        .optional()?;
+    if taken.is_some() {
+        return Err(AudionError::InvalidInput(format!(
+            "A label named '{}' already exists",
+            name
+        )));
+    }
+    Ok(name)
+}
+
+fn label_color(color: Option<&str>) -> Result<Option<&str>, AudionError> {
+    let Some(color) = color.map(str::trim).filter(|c| !c.is_empty()) else {
+        return Ok(None);
+    };
+    let valid = color.strip_prefix('#').is_some_and(|hex| {
+        matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    });
+    if !valid {
+        return Err(AudionError::InvalidInput(format!(
+            "Invalid label colour '{}' (expected #rgb or #rrggbb)",
+            color
+        )));
+    }
+    Ok(Some(color))
+}
+
+pub fn create(conn: &Connection, name: &str, color: Option<&str>) -> Result<i64, AudionError> {
+    let name = label_name(conn, name, None)?;
+    let color = label_color(color)?;
+    conn.execute(
+        "INSERT INTO labels (name, color) VALUES (?1, ?2)",
+        params![name, color],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Renaming to the name of another label is refused; merge them instead
+pub fn rename(conn: &Connection, label_id: i64, name: &str) -> Result<(), AudionError> {
+    ensure_exists(conn, label_id)?;
+    let name = label_name(conn, name, Some(label_id))?;
+    conn.execute(
+        "UPDATE labels SET name = ?1 WHERE id = ?2",
+        params![name, label_id],
+    )?;
+    Ok(())
+}
+
+/// `None` (or an empty string) removes the colour
+pub fn set_color(conn: &Connection, label_id: i64, color: Option<&str>) -> Result<(), AudionError> {
+    ensure_exists(conn, label_id)?;
+    let color = label_color(color)?;
+    conn.execute(
+        "UPDATE labels SET color = ?1 WHERE id = ?2",
+        params![color, label_id],
+    )?;
+    Ok(())
+}
+
+/// Move everything labelled `source_id` to `target_id` and delete the source
+pub fn merge(conn: &Connection, source_id: i64, target_id: i64) -> Result<(), AudionError> {
+    ensure_exists(conn, source_id)?;
+    ensure_exists(conn, target_id)?;
+    if source_id == target_id {
+        return Err(AudionError::InvalidInput(
+            "A label can't be merged into itself".to_string(),
+        ));
+    }
+    for target in [LabelTarget::Track, LabelTarget::Album] {
+        conn.execute(
+            &format!(
+                "INSERT OR IGNORE INTO {0} ({1}, label_id) SELECT {1}, ?2 FROM {0} WHERE label_id = ?1",
+                target.link_table(),
+                target.link_column()
+            ),
+            params![source_id, target_id],
+        )?;
+    }
+    conn.execute("DELETE FROM labels WHERE id = ?1", [source_id])?;
+    Ok(())
+}
+
+/// Delete a label and unlink it from everything
+pub fn delete(conn: &Connection, label_id: i64) -> Result<(), AudionError> {
+    ensure_exists(conn, label_id)?;
+    conn.execute("DELETE FROM labels WHERE id = ?1", [label_id])?;
+    Ok(())
+}
+
+/// Link every label to every track (or album) in `ids`. Ids that don't
+/// exist are skipped, as are links already made. Returns how many were added.
+pub fn assign(
+    conn: &Connection,
+    label_ids: &[i64],
+    target: LabelTarget,
+    ids: &[i64],
+) -> Result<usize, AudionError> {
+    for label_id in label_ids {
+        ensure_exists(conn, *label_id)?;
+    }
+    let mut insert = conn.prepare(&format!(
+        "INSERT OR IGNORE INTO {} ({}, label_id) SELECT id, ?2 FROM {} WHERE id = ?1",
+        target.link_table(),
+        target.link_column(),
+        target.table()
+    ))?;
+    let mut added = 0;
+    for label_id in label_ids {
+        for id in ids {
+            added += insert.execute(params![id, label_id])?;
+        }
+    }
+    Ok(added)
+}
+
+/// Remove the links between the labels and `ids`. Returns how many there were.
+pub fn unassign(
+    conn: &Connection,
+    label_ids: &[i64],
+    target: LabelTarget,
+    ids: &[i64],
+) -> Result<usize, AudionError> {
+    let mut delete = conn.prepare(&format!(
+        "DELETE FROM {} WHERE {} = ?1 AND label_id = ?2",
+        target.link_table(),
+        target.link_column()
+    ))?;
+    let mut removed = 0;
+    for label_id in label_ids {
+        for id in ids {
+            removed += delete.execute(params![id, label_id])?;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::browse::{self, TrackFilter, TrackPageRequest};
+    use crate::db::smart_playlists::{self, RuleField, RuleNode, RuleOperator, SmartRules};
+    use crate::db::{queries, schema, search};
+    use rusqlite::params_from_iter;
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO albums (id, name) VALUES (1, 'Live');
+            INSERT INTO tracks (id, path, title, album_id) VALUES
+                (1, '/m/1.flac', 'One', NULL), (2, '/m/2.flac', 'Two', 1), (3, '/m/3.flac', 'Three', 1),
+                (4, '/m/4.flac', 'Four', NULL);
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn names(conn: &Connection) -> Vec<(String, i64, i64)> {
+        list(conn)
+            .unwrap()
+            .into_iter()
+            .map(|l| (l.name, l.track_count, l.album_count))
+            .collect()
+    }
+
+    fn titles(
+        conn: &Connection,
+        condition: &str,
+        values: Vec<rusqlite::types::Value>,
+    ) -> Vec<String> {
+        conn.prepare(&format!(
+            "SELECT t.title FROM tracks t WHERE {} ORDER BY t.id",
+            condition
+        ))
+        .unwrap()
+        .query_map(params_from_iter(values), |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_>>()
+        .unwrap()
+    }
+
+    #[test]
+    fn labels_are_renamed_merged_and_deleted() {
+        let conn = library();
+        let workout = create(&conn, " Workout ", Some("#f80")).unwrap();
+        let gym = create(&conn, "gym", None).unwrap();
+        let retag = create(&conn, "Needs retag", None).unwrap();
+        assert!(matches!(
+            create(&conn, "WORKOUT", None),
+            Err(AudionError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            rename(&conn, gym, "workout"),
+            Err(AudionError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            set_color(&conn, gym, Some("orange")),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        assert_eq!(
+            assign(&conn, &[workout, gym], LabelTarget::Track, &[1, 2, 99]).unwrap(),
+            4
+        );
+        assert_eq!(assign(&conn, &[gym], LabelTarget::Track, &[1]).unwrap(), 0);
+        assign(&conn, &[gym], LabelTarget::Album, &[1]).unwrap();
+        rename(&conn, retag, "Needs Retag").unwrap();
+
+        merge(&conn, gym, workout).unwrap();
+        assert_eq!(
+            names(&conn),
+            vec![
+                ("Needs Retag".to_string(), 0, 0),
+                ("Workout".to_string(), 2, 1)
+            ]
+        );
+        assert!(matches!(
+            merge(&conn, workout, workout),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        set_color(&conn, workout, Some("")).unwrap();
+        let labels = labels_of(&conn, LabelTarget::Album, 1).unwrap();
+        assert_eq!(labels[0].color, None);
+
+        assert_eq!(
+            unassign(&conn, &[workout], LabelTarget::Track, &[1, 3]).unwrap(),
+            1
+        );
+        delete(&conn, workout).unwrap();
+        assert!(labels_of(&conn, LabelTarget::Track, 2).unwrap().is_empty());
+        assert!(matches!(
+            delete(&conn, workout),
+            Err(AudionError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn tracks_carry_their_albums_labels() {
+        let conn = library();
+        let wedding = create(&conn, "Wedding set", None).unwrap();
+        let retag = create(&conn, "Needs retag", None).unwrap();
+        assign(&conn, &[wedding], LabelTarget::Album, &[1]).unwrap();
+        assign(&conn, &[wedding], LabelTarget::Track, &[4]).unwrap();
+        assign(&conn, &[retag], LabelTarget::Track, &[3]).unwrap();
+
+        let page = browse::browse_tracks(
+            &conn,
+            &TrackPageRequest {
+                filter: TrackFilter {
+                    label_id: Some(wedding),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let ids: Vec<i64> = page.items.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![2, 3, 4]);
+
+        let search = search::compile_query("label:wedding -label:retag")
+            .unwrap()
+            .unwrap();
+        let found: Vec<String> = queries::search_tracks(&conn, &search, 50, 0)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title.unwrap_or_default())
+            .collect();
+        assert_eq!(found, vec!["Two", "Four"]);
+
+        let rule = |operator, value| -> SmartRules {
+            serde_json::from_value(serde_json::json!({
+                "rules": [{ "field": "label", "operator": operator, "value": value }]
+            }))
+            .unwrap()
+        };
+        let matching = |rules: SmartRules| {
+            assert!(matches!(&rules.rules[0], RuleNode::Rule(r) if r.field == RuleField::Label));
+            let mut values = Vec::new();
+            let condition = smart_playlists::compile(&rules, &mut values).unwrap();
+            titles(&conn, &condition, values)
+        };
+        assert_eq!(
+            matching(rule(RuleOperator::Is, "needs RETAG".into())),
+            vec!["Three"]
+        );
+        assert_eq!(
+            matching(rule(RuleOperator::IsNot, "wedding set".into())),
+            vec!["One"]
+        );
+        assert_eq!(
+            matching(rule(RuleOperator::StartsWith, "wed".into())),
+            vec!["Two", "Three", "Four"]
+        );
+        assert_eq!(
+            matching(rule(RuleOperator::IsNotSet, serde_json::Value::Null)),
+            vec!["One"]
+        );
+    }
+}
@@ -0,0 +1,469 @@
+// Finding library tracks for entries of imported playlists and track lists
+use super::fuzzy::{text_similarity, words};
+use crate::playlist_files::PlaylistItem;
+use crate::utils::fold_text;
+use rusqlite::{Connection, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// How far apart two durations can be and still be the same recording
+const DURATION_TOLERANCE_SECS: i32 = 3;
+
+/// Fuzzy matches further apart than this in length are different recordings
+const MAX_DURATION_GAP_SECS: i32 = 30;
+
+/// Fuzzy candidates below this confidence aren't offered at all
+pub const MIN_CONFIDENCE: f64 = 0.6;
+
+/// A best match at or above this confidence, clearly ahead of the runner-up,
+/// is taken without asking
+pub const AUTO_ACCEPT: f64 = 0.9;
+const AUTO_ACCEPT_MARGIN: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Path,
+    Metadata,
+    Isrc,
+    Fuzzy,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackMatch {
+    pub track_id: i64,
+    pub kind: MatchKind,
+    /// 0-1
+    pub confidence: f64,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchStatus {
+    Matched,
+    /// Several plausible tracks, or one that isn't a confident match
+    Ambiguous,
+    Unmatched,
+}
+
+struct Candidate {
+    id: i64,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    duration: Option<i32>,
+    folded_artist: String,
+    folded_album: String,
+    core_title: String,
+}
+
+/// The library's paths and names, loaded once per import
+pub struct LibraryIndex {
+    tracks: Vec<Candidate>,
+    by_path: HashMap<String, usize>,
+    by_title: HashMap<String, Vec<usize>>,
+    by_isrc: HashMap<String, Vec<usize>>,
+    by_title_word: HashMap<String, Vec<usize>>,
+}
+
+impl LibraryIndex {
+    pub fn load(conn: &Connection) -> Result<Self> {
+        let mut index = LibraryIndex {
+            tracks: Vec::new(),
+            by_path: HashMap::new(),
+            by_title: HashMap::new(),
+            by_isrc: HashMap::new(),
+            by_title_word: HashMap::new(),
+        };
+        let mut stmt = conn.prepare(
+            "SELECT id, path, title, artist, album, duration, isrc FROM tracks WHERE removed_at IS NULL AND source_type IS NOT 'placeholder' ORDER BY id",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let i = index.tracks.len();
+            let title: Option<String> = row.get(2)?;
+            let artist: Option<String> = row.get(3)?;
+            let album: Option<String> = row.get(4)?;
+            let isrc: Option<String> = row.get(6)?;
+
+            index.by_path.entry(row.get(1)?).or_insert(i);
+            if let Some(title) = &title {
+                index
+                    .by_title
+                    .entry(fold_text(title.trim()))
+                    .or_default()
+                    .push(i);
+            }
+            if let Some(isrc) = &isrc {
+                index.by_isrc.entry(isrc.clone()).or_default().push(i);
+            }
+            let core_title = core_title(title.as_deref().unwrap_or_default());
+            for word in core_title.split(' ').collect::<HashSet<_>>() {
+                if !word.is_empty() {
+                    index
+                        .by_title_word
+                        .entry(word.to_string())
+                        .or_default()
+                        .push(i);
+                }
+            }
+
+            index.tracks.push(Candidate {
+                id: row.get(0)?,
+                folded_artist: fold(artist.as_deref()),
+                folded_album: fold(album.as_deref()),
+                core_title,
+                title,
+                artist,
+                album,
+                duration: row.get(5)?,
+            });
+        }
+        Ok(index)
+    }
+
+    /// The library track an entry refers to: the same path, or else the same
+    /// title and artist (ignoring case and accents) at about the same length
+    pub fn resolve(&self, item: &PlaylistItem) -> Option<(i64, MatchKind)> {
+        if let Some(&i) = item.path.as_ref().and_then(|path| self.by_path.get(path)) {
+            return Some((self.tracks[i].id, MatchKind::Path));
+        }
+
+        let title = fold_text(item.title.as_deref()?.trim());
+        let artist = fold(item.artist.as_deref());
+        let album = fold(item.album.as_deref());
+        self.by_title
+            .get(&title)?
+            .iter()
+            .map(|&i| &self.tracks[i])
+            .filter(|candidate| artist.is_empty() || candidate.folded_artist == artist)
+            .filter_map(|candidate| {
+                let gap = duration_gap(item.duration, candidate.duration).unwrap_or(0);
+                (gap <= DURATION_TOLERANCE_SECS).then_some((candidate, gap))
+            })
+            // Prefer the same album, then the closest length; ids break ties
+            .min_by_key(|(candidate, gap)| {
+                (album.is_empty() || candidate.folded_album != album, *gap)
+            })
+            .map(|(candidate, _)| (candidate.id, MatchKind::Metadata))
+    }
+
+    /// Plausible library tracks for a row from another service, best first.
+    /// A shared ISRC is certain; otherwise title, artist, album and length
+    /// are compared loosely, since services tag versions and featured
+    /// artists differently.
+    pub fn fuzzy_matches(&self, item: &PlaylistItem, limit: usize) -> Vec<TrackMatch> {
+        let mut scored: HashMap<usize, (MatchKind, f64)> = HashMap::new();
+
+        let isrc = item.isrc.as_deref().and_then(normalize_isrc);
+        for &i in isrc
+            .as_ref()
+            .and_then(|isrc| self.by_isrc.get(isrc))
+            .into_iter()
+            .flatten()
+        {
+            scored.insert(i, (MatchKind::Isrc, 1.0));
+        }
+
+        let title = core_title(item.title.as_deref().unwrap_or_default());
+        let title_words: Vec<String> = title.split(' ').map(str::to_string).collect();
+        let artist_words = words(item.artist.as_deref().unwrap_or_default());
+        let album_words = words(item.album.as_deref().unwrap_or_default());
+        let candidates: HashSet<usize> = title_words
+            .iter()
+            .filter_map(|word| self.by_title_word.get(word))
+            .flatten()
+            .copied()
+            .collect();
+
+        for i in candidates {
+            if scored.contains_key(&i) {
+                continue;
+            }
+            let track = &self.tracks[i];
+            let gap = duration_gap(item.duration, track.duration).unwrap_or(0);
+            if gap > MAX_DURATION_GAP_SECS {
+                continue;
+            }
+
+            let title_score = (text_similarity(&title_words, &track.core_title)
+                + text_similarity(&words(&track.core_title), &title))
+                / 2.0;
+            // Either side may list extra (featured) artists
+            let artist_score = match (artist_words.is_empty(), track.folded_artist.is_empty()) {
+                (true, _) | (_, true) => 0.5,
+                _ => text_similarity(&artist_words, &track.folded_artist).max(text_similarity(
+                    &words(&track.folded_artist),
+                    item.artist.as_deref().unwrap_or_default(),
+                )),
+            };
+            let mut confidence = if album_words.is_empty() || track.folded_album.is_empty() {
+                0.6 * title_score + 0.4 * artist_score
+            } else {
+                let album_score = text_similarity(&album_words, &track.folded_album);
+                0.5 * title_score + 0.35 * artist_score + 0.15 * album_score
+            };
+            if gap > DURATION_TOLERANCE_SECS {
+                confidence *= 1.0
+                    - 0.5 * (gap - DURATION_TOLERANCE_SECS) as f64
+                        / (MAX_DURATION_GAP_SECS - DURATION_TOLERANCE_SECS) as f64;
+            }
+            if confidence >= MIN_CONFIDENCE {
+                scored.insert(i, (MatchKind::Fuzzy, confidence));
+            }
+        }
+
+        let mut matches: Vec<TrackMatch> = scored
+            .into_iter()
+            .map(|(i, (kind, confidence))| {
+                let track = &self.tracks[i];
+                TrackMatch {
+                    track_id: track.id,
+                    kind,
+                    confidence: (confidence * 1000.0).round() / 1000.0,
+                    title: track.title.clone(),
+                    artist: track.artist.clone(),
+                    album: track.album.clone(),
+                    duration: track.duration,
+                }
+            })
+            .collect();
+        matches.sort_by(|a, b| {
+            b.confidence
+                .total_cmp(&a.confidence)
+                .then(a.track_id.cmp(&b.track_id))
+        });
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// Whether the best of `matches` (sorted best first) can be taken as is
+pub fn match_status(matches: &[TrackMatch]) -> MatchStatus {
+    match matches {
+        [] => MatchStatus::Unmatched,
+        [best, rest @ ..]
+            if best.confidence >= AUTO_ACCEPT
+                && rest
+                    .first()
+                    .is_none_or(|next| best.confidence - next.confidence >= AUTO_ACCEPT_MARGIN) =>
+        {
+            MatchStatus::Matched
+        }
+        _ => MatchStatus::Ambiguous,
+    }
+}
+
+/// Uppercase ISRC without separators ("gb-aye-97-00123" -> "GBAYE9700123"),
+/// or `None` if it isn't one
+pub fn normalize_isrc(raw: &str) -> Option<String> {
+    let isrc: String = raw
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    (isrc.len() == 12).then_some(isrc)
+}
+
+/// Folded title words without the version and featuring notes services add:
+/// "Song (feat. X) - 2011 Remaster" -> "song"
+fn core_title(title: &str) -> String {
+    let mut core = String::new();
+    let mut depth = 0;
+    for c in title.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = (depth - 1).max(0),
+            c if depth == 0 => core.push(c),
+            _ => {}
+        }
+    }
+    let core = core.split(" - ").next().unwrap_or_default();
+    let core = [" feat. ", " feat ", " ft. ", " featuring "]
+        .iter()
+        .fold(core.to_lowercase(), |core, marker| {
+            core.split(marker).next().unwrap_or_default().to_string()
+        });
+    let core = words(&core).join(" ");
+    // A title that's all notes keeps them
+    if core.is_empty() {
+        words(title).join(" ")
+    } else {
+        core
+    }
+}
+
+fn duration_gap(a: Option<i32>, b: Option<i32>) -> Option<i32> {
+    Some((a? - b?).abs())
+}
+
+fn fold(text: Option<&str>) -> String {
+    text.map(|text| fold_text(text.trim())).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{playlists, queries, schema};
+
+    #[test]
+    fn resolves_by_path_then_by_metadata() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (id, path, title, artist, album, duration) VALUES
+                (1, '/m/joga.flac', 'Jóga', 'Björk', 'Homogenic', 305),
+                (2, '/m/joga-live.flac', 'Jóga', 'Björk', 'Live', 330),
+                (3, '/m/joga-single.flac', 'Jóga', 'Björk', 'Jóga (single)', 306),
+                (4, '/m/other.flac', 'Jóga', 'Someone Else', 'Homogenic', 305);
+            ",
+        )
+        .unwrap();
+        let index = LibraryIndex::load(&conn).unwrap();
+        let item = |path: &str, artist: Option<&str>, album: Option<&str>, duration| PlaylistItem {
+            path: Some(path.into()),
+            title: Some("JOGA ".into()),
+            artist: artist.map(String::from),
+            album: album.map(String::from),
+            duration,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            index.resolve(&item("/m/joga-live.flac", None, None, None)),
+            Some((2, MatchKind::Path))
+        );
+        // Moved file: same song by name, the album decides between versions
+        assert_eq!(
+            index.resolve(&item(
+                "/old/joga.mp3",
+                Some("bjork"),
+                Some("jóga (SINGLE)"),
+                Some(305)
+            )),
+            Some((3, MatchKind::Metadata))
+        );
+        // Without an album the closest length wins
+        assert_eq!(
+            index.resolve(&item("/old/joga.mp3", Some("Björk"), None, Some(329))),
+            Some((2, MatchKind::Metadata))
+        );
+        assert_eq!(
+            index.resolve(&item("/old/joga.mp3", Some("Björk"), None, Some(200))),
+            None
+        );
+        assert_eq!(
+            index.resolve(&item("/old/joga.mp3", Some("Nobody"), None, None)),
+            None
+        );
+    }
+
+    #[test]
+    fn fuzzy_matches_score_streaming_rows() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (id, path, title, artist, album, duration, isrc) VALUES
+                (1, '/m/1.flac', 'Paranoid Android', 'Radiohead', 'OK Computer', 386, NULL),
+                (2, '/m/2.flac', 'Get Lucky', 'Daft Punk', 'Random Access Memories', 369, NULL),
+                (3, '/m/3.flac', 'Intro', 'The xx', 'xx', 127, NULL),
+                (4, '/m/4.flac', 'Intro', 'M83', 'Hurry Up, We''re Dreaming', 332, NULL),
+                (5, '/m/5.flac', 'Some Other Name', 'Someone', NULL, 200, 'GBAYE9700123');
+            ",
+        )
+        .unwrap();
+        let index = LibraryIndex::load(&conn).unwrap();
+        let row = |title: &str, artist: &str, duration: Option<i32>| PlaylistItem {
+            title: Some(title.into()),
+            artist: Some(artist.into()),
+            duration,
+            ..Default::default()
+        };
+
+        let isrc = PlaylistItem {
+            isrc: Some("gb-aye-97-00123".into()),
+            ..row("Anything", "Anyone", None)
+        };
+        let matches = index.fuzzy_matches(&isrc, 5);
+        assert_eq!((matches[0].track_id, matches[0].kind), (5, MatchKind::Isrc));
+        assert_eq!(match_status(&matches), MatchStatus::Matched);
+
+        // Version notes and featured artists don't get in the way
+        let matches = index.fuzzy_matches(
+            &row(
+                "Get Lucky (feat. Pharrell Williams) - Radio Edit",
+                "Daft Punk, Pharrell Williams",
+                Some(370),
+            ),
+            5,
+        );
+        assert_eq!(matches[0].track_id, 2);
+        assert_eq!(match_status(&matches), MatchStatus::Matched);
+
+        let matches = index.fuzzy_matches(&row("Paranoid Androd", "Radiohed", Some(387)), 5);
+        assert_eq!(matches[0].track_id, 1);
+        assert!(matches[0].confidence < 1.0);
+
+        // Two songs called Intro and no artist to tell them apart
+        let matches = index.fuzzy_matches(
+            &PlaylistItem {
+                title: Some("Intro".into()),
+                ..Default::default()
+            },
+            5,
+        );
+        assert_eq!(matches.len(), 2);
+        assert_eq!(match_status(&matches), MatchStatus::Ambiguous);
+
+        // Far too long to be the same recording
+        let matches = index.fuzzy_matches(&row("Intro", "The xx", Some(400)), 5);
+        assert!(matches.iter().all(|m| m.track_id != 3));
+        assert_eq!(
+            match_status(&index.fuzzy_matches(&row("Nothing Like It", "Nobody", None), 5)),
+            MatchStatus::Unmatched
+        );
+        assert_eq!(normalize_isrc("bad"), None);
+    }
+
+    #[test]
+    fn placeholders_are_never_matched() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        let item = PlaylistItem {
+            title: Some("Karma Police".into()),
+            artist: Some("Radiohead".into()),
+            duration: Some(264),
+            isrc: Some("GBAYE9700456".into()),
+            ..Default::default()
+        };
+        // The first import didn't find it, so it got a stand-in
+        let placeholder = playlists::placeholder_track(&conn, &item).unwrap();
+        assert!(LibraryIndex::load(&conn).unwrap().fuzzy_matches(&item, 5).is_empty());
+
+        conn.execute(
+            "INSERT INTO tracks (id, path, title, artist, duration, isrc)
+             VALUES (50, '/m/karma.flac', 'Karma Police', 'Radiohead', 264, 'GBAYE9700456')",
+            [],
+        )
+        .unwrap();
+        let index = LibraryIndex::load(&conn).unwrap();
+        let matches = index.fuzzy_matches(&item, 5);
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].track_id, matches[0].kind), (50, MatchKind::Isrc));
+        assert_eq!(index.resolve(&item), Some((50, MatchKind::Metadata)));
+
+        let library: Vec<i64> = queries::get_all_tracks(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(library, vec![50]);
+        assert_ne!(placeholder, 50);
+    }
+}
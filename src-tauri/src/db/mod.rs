@@ -1,24 +1,66 @@
 // Database module for SQLite operations
+pub mod browse;
+pub mod fuzzy;
+pub mod grouped;
+pub mod history;
+pub mod labels;
+pub mod matching;
+pub mod play_queue;
+pub mod playlist_folders;
+pub mod playlist_history;
+pub mod playlists;
 pub mod queries;
+pub mod ratings;
+pub mod removed;
 pub mod schema;
+pub mod search;
+pub mod search_query;
+pub mod smart_playlists;
+pub mod sort_names;
+pub mod stats;
+pub mod suggest;
 
-use rusqlite::Connection;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use crate::error::{AudionError, ResultExt};
+use rusqlite::{Connection, OpenFlags};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
 
+/// Number of read-only connections kept open alongside the writer
+const READ_POOL_SIZE: usize = 4;
+
+/// How long a statement waits on a locked database before giving up
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One serialized writer plus a pool of read-only connections.
+///
+/// In WAL mode readers see the last committed state and are never blocked by an
+/// open write transaction, so library browsing keeps working during scans.
+/// Cloning is cheap and shares the same connections.
 #[derive(Clone)]
 pub struct Database {
-    pub conn: Arc<Mutex<Connection>>,
+    writer: Arc<Mutex<Connection>>,
+    readers: Arc<ReadPool>,
 }
 
 impl Database {
-    pub fn new(app_dir: &PathBuf) -> Result<Self, rusqlite::Error> {
-        let db_path = app_dir.join("rlist.db");
-        let conn = Connection::open(&db_path)?;
+    pub fn new(app_dir: &PathBuf) -> Result<Self, AudionError> {
+        Self::open(&app_dir.join("rlist.db"), READ_POOL_SIZE)
+    }
+
+    pub fn open(db_path: &Path, read_connections: usize) -> Result<Self, AudionError> {
+        let mut conn = Connection::open(db_path)
+            .context(&format!("Failed to open database {:?}", db_path))?;
 
         // Enable WAL mode for better concurrency and resilience to corruption
         // Use execute_batch because these PRAGMAs return results which execute() doesn't like
-        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+            .context("Failed to configure database")?;
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .context("Failed to configure database")?;
+        // Before anything reads the indexes that use it
+        sort_names::register_collation(&conn).context("Failed to configure database")?;
 
         // Run an integrity check on startup
         match conn.query_row("PRAGMA integrity_check;", [], |row| row.get::<_, String>(0)) {
@@ -31,11 +73,200 @@ impl Database {
             _ => {} // Everything is fine ("ok")
         }
 
-        // Initialize schema
-        schema::init_schema(&conn)?;
+        // Create or migrate the schema (backs up the file first if it needs migrating)
+        schema::init_schema(&mut conn, Some(db_path)).map_err(AudionError::Database)?;
+
+        // Readers are opened after migrating so they all see the final schema
+        let mut readers = Vec::with_capacity(read_connections.max(1));
+        for _ in 0..read_connections.max(1) {
+            let reader = Connection::open_with_flags(
+                db_path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )
+            .context("Failed to open read connection")?;
+            reader
+                .busy_timeout(BUSY_TIMEOUT)
+                .context("Failed to configure read connection")?;
+            sort_names::register_collation(&reader)
+                .context("Failed to configure read connection")?;
+            readers.push(reader);
+        }
 
         Ok(Self {
-            conn: Arc::new(Mutex::new(conn)),
+            writer: Arc::new(Mutex::new(conn)),
+            readers: Arc::new(ReadPool {
+                idle: Mutex::new(readers),
+                available: Condvar::new(),
+            }),
         })
     }
+
+    /// Borrow a read-only connection, waiting only if every reader is in use
+    pub fn read(&self) -> Result<PooledConnection<'_>, AudionError> {
+        self.readers.get()
+    }
+
+    /// Lock the single writer connection. Keep the guard for as short as possible:
+    /// other writes queue behind it (reads don't).
+    pub fn write(&self) -> Result<MutexGuard<'_, Connection>, AudionError> {
+        self.writer
+            .lock()
+            .map_err(|e| AudionError::Internal(format!("Database writer lock poisoned: {}", e)))
+    }
+}
+
+struct ReadPool {
+    idle: Mutex<Vec<Connection>>,
+    available: Condvar,
+}
+
+impl ReadPool {
+    fn get(&self) -> Result<PooledConnection<'_>, AudionError> {
+        let mut idle = self
+            .idle
+            .lock()
+            .map_err(|e| AudionError::Internal(format!("Database read pool poisoned: {}", e)))?;
+        loop {
+            if let Some(conn) = idle.pop() {
+                return Ok(PooledConnection {
+                    pool: self,
+                    conn: Some(conn),
+                });
+            }
+            idle = self
+                .available
+                .wait(idle)
+                .map_err(|e| AudionError::Internal(format!("Database read pool poisoned: {}", e)))?;
+        }
+    }
+}
+
+/// A read-only connection on loan from the pool; returned when dropped
+pub struct PooledConnection<'a> {
+    pool: &'a ReadPool,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("pooled connection used after release")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            if let Ok(mut idle) = self.pool.idle.lock() {
+                idle.push(conn);
+                self.pool.available.notify_one();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    fn temp_db(name: &str) -> (PathBuf, Database) {
+        let dir = std::env::temp_dir().join(format!("audion_db_{}_{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::open(&dir.join("rlist.db"), 2).unwrap();
+        (dir, db)
+    }
+
+    fn insert_tracks(conn: &Connection, from: usize, count: usize) {
+        for i in from..from + count {
+            conn.execute(
+                "INSERT INTO tracks (path, title, artist) VALUES (?1, ?2, 'Artist')",
+                rusqlite::params![format!("/music/{}.flac", i), format!("Track {}", i)],
+            )
+            .unwrap();
+        }
+    }
+
+    fn count_tracks(db: &Database) -> i64 {
+        db.read()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM tracks", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_continue_during_long_write_transaction() {
+        let (dir, db) = temp_db("long_write");
+        insert_tracks(&db.write().unwrap(), 0, 100);
+
+        let (started_tx, started_rx) = mpsc::channel();
+        let (finish_tx, finish_rx) = mpsc::channel::<()>();
+        let writer_db = db.clone();
+        let writer = std::thread::spawn(move || {
+            let mut conn = writer_db.write().unwrap();
+            let tx = conn.transaction().unwrap();
+            insert_tracks(&tx, 100, 5_000);
+            started_tx.send(()).unwrap();
+            // Hold the write transaction open until the reads are done
+            finish_rx.recv().unwrap();
+            tx.commit().unwrap();
+        });
+        started_rx.recv().unwrap();
+
+        // Reads run while the writer still holds its lock and open transaction
+        let start = Instant::now();
+        for _ in 0..50 {
+            assert_eq!(count_tracks(&db), 100);
+        }
+        let elapsed = start.elapsed();
+        println!("[DB] 50 reads during open write transaction took {:?}", elapsed);
+        assert!(elapsed < BUSY_TIMEOUT, "reads were blocked by the writer");
+
+        finish_tx.send(()).unwrap();
+        writer.join().unwrap();
+        assert_eq!(count_tracks(&db), 5_100);
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn read_connections_are_read_only() {
+        let (dir, db) = temp_db("read_only");
+
+        let result = db
+            .read()
+            .unwrap()
+            .execute("INSERT INTO tracks (path) VALUES ('/music/x.flac')", []);
+        assert!(result.is_err());
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn pool_waits_for_a_free_reader() {
+        let (dir, db) = temp_db("pool_wait");
+        let first = db.read().unwrap();
+        let second = db.read().unwrap();
+
+        let (done_tx, done_rx) = mpsc::channel();
+        let waiting_db = db.clone();
+        let waiter = std::thread::spawn(move || {
+            let _third = waiting_db.read().unwrap();
+            done_tx.send(()).unwrap();
+        });
+
+        // Both readers are taken, so the third borrow has to wait
+        assert!(done_rx.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(first);
+        assert!(done_rx.recv_timeout(BUSY_TIMEOUT).is_ok());
+
+        waiter.join().unwrap();
+        drop(second);
+        drop(db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
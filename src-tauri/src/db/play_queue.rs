@@ -0,0 +1,382 @@
+// The play queue from the last session, saved by the player so it can pick up
+// where it left off after a restart or crash. Queue indexes are positions in
+// play_queue; tracks deleted or removed from the library since the save are
+// dropped when it's loaded.
+use super::queries::Track;
+use crate::error::AudionError;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatMode {
+    #[default]
+    None,
+    One,
+    All,
+}
+
+impl RepeatMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            RepeatMode::None => "none",
+            RepeatMode::One => "one",
+            RepeatMode::All => "all",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "one" => RepeatMode::One,
+            "all" => RepeatMode::All,
+            _ => RepeatMode::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueState {
+    pub track_ids: Vec<i64>,
+    /// Index into `track_ids` of the track playing
+    pub current_index: usize,
+    /// Seconds into the current track
+    pub position: f64,
+    pub shuffle: bool,
+    /// Queue indexes in shuffled play order
+    #[serde(default)]
+    pub shuffle_order: Vec<usize>,
+    /// Where in `shuffle_order` playback is
+    #[serde(default)]
+    pub shuffle_index: usize,
+    #[serde(default)]
+    pub repeat: RepeatMode,
+    /// Tracks queued by hand after the current one
+    #[serde(default)]
+    pub user_queue_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedQueue {
+    #[serde(flatten)]
+    pub state: QueueState,
+    /// The queue's tracks, matching `track_ids`
+    pub tracks: Vec<Track>,
+    pub saved_at: Option<String>,
+}
+
+fn check(state: &QueueState) -> Result<(), AudionError> {
+    let len = state.track_ids.len();
+    let problem = if state.current_index >= len.max(1) {
+        Some(format!(
+            "Current index {} is out of range for a queue of {}",
+            state.current_index, len
+        ))
+    } else if !state.position.is_finite() || state.position < 0.0 {
+        Some(format!("Invalid playback position {}", state.position))
+    } else if state.shuffle_order.iter().any(|&i| i >= len) {
+        Some("Shuffle order refers to tracks outside the queue".to_string())
+    } else if state.shuffle_index > state.shuffle_order.len() {
+        Some(format!(
+            "Shuffle index {} is out of range",
+            state.shuffle_index
+        ))
+    } else {
+        None
+    };
+    problem.map_or(Ok(()), |problem| Err(AudionError::InvalidInput(problem)))
+}
+
+/// Replace the saved queue. Call it in a transaction so a crash mid-save
+/// leaves the previous queue.
+pub fn save(conn: &Connection, state: &QueueState) -> Result<(), AudionError> {
+    check(state)?;
+    conn.execute("DELETE FROM play_queue", [])?;
+    let mut insert = conn.prepare(
+        "INSERT INTO play_queue (position, track_id)
+         SELECT ?1, ?2 WHERE EXISTS (SELECT 1 FROM tracks WHERE id = ?2)",
+    )?;
+    for (position, track_id) in state.track_ids.iter().enumerate() {
+        insert.execute(params![position as i64, track_id])?;
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO play_queue_state
+            (id, current_index, position_secs, shuffle, shuffle_order, shuffle_index, repeat_mode, user_queue_count, saved_at)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, CURRENT_TIMESTAMP)",
+        params![
+            state.current_index as i64,
+            state.position,
+            state.shuffle,
+            serde_json::to_string(&state.shuffle_order)?,
+            state.shuffle_index as i64,
+            state.repeat.as_str(),
+            state.user_queue_count as i64,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Save just the playback position, which changes far more often than the queue
+pub fn save_position(
+    conn: &Connection,
+    current_index: usize,
+    position: f64,
+    shuffle_index: Option<usize>,
+) -> Result<(), AudionError> {
+    let saved: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT (SELECT COALESCE(MAX(position) + 1, 0) FROM play_queue), shuffle_order
+             FROM play_queue_state WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((len, shuffle_order)) = saved else {
+        return Err(AudionError::NotFound("No play queue saved".to_string()));
+    };
+    let shuffle_len = serde_json::from_str::<Vec<usize>>(&shuffle_order)?.len();
+    if current_index as i64 >= len.max(1)
+        || !position.is_finite()
+        || position < 0.0
+        || shuffle_index.is_some_and(|index| index > shuffle_len)
+    {
+        return Err(AudionError::InvalidInput(
+            "Playback position is outside the saved queue".to_string(),
+        ));
+    }
+    conn.execute(
+        "UPDATE play_queue_state
+         SET current_index = ?1, position_secs = ?2, shuffle_index = COALESCE(?3, shuffle_index),
+             saved_at = CURRENT_TIMESTAMP
+         WHERE id = 1",
+        params![
+            current_index as i64,
+            position,
+            shuffle_index.map(|index| index as i64)
+        ],
+    )?;
+    Ok(())
+}
+
+/// The saved queue, or `None` if nothing was saved or none of its tracks are
+/// left. If the current track is gone, the next one that's left is current,
+/// from its start.
+pub fn load(conn: &Connection) -> Result<Option<SavedQueue>, AudionError> {
+    let saved = conn
+        .query_row(
+            "SELECT current_index, position_secs, shuffle, shuffle_order, shuffle_index, repeat_mode, user_queue_count, saved_at
+             FROM play_queue_state WHERE id = 1",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)? as usize,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, bool>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)? as usize,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, i64>(6)? as usize,
+                    row.get::<_, Option<String>>(7)?,
+                ))
+            },
+        )
+        .optional()?;
+    let Some((current, position, shuffle, shuffle_order, shuffle_index, repeat, queued, saved_at)) =
+        saved
+    else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT q.position, t.id, t.path, t.title, t.artist, t.album, t.track_number, t.duration, t.album_id, t.format, t.bitrate, t.source_type, t.cover_url, t.external_id, t.local_src, t.track_cover, t.track_cover_path
+         FROM play_queue q
+         JOIN tracks t ON t.id = q.track_id
+         WHERE t.removed_at IS NULL
+         ORDER BY q.position",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)? as usize,
+                Track {
+                    id: row.get(1)?,
+                    path: row.get(2)?,
+                    title: row.get(3)?,
+                    artist: row.get(4)?,
+                    album: row.get(5)?,
+                    track_number: row.get(6)?,
+                    duration: row.get(7)?,
+                    album_id: row.get(8)?,
+                    format: row.get(9)?,
+                    bitrate: row.get(10)?,
+                    source_type: row.get(11)?,
+                    cover_url: row.get(12)?,
+                    external_id: row.get(13)?,
+                    local_src: row.get(14)?,
+                    track_cover: row.get(15)?,
+                    track_cover_path: row.get(16)?,
+                },
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    // Saved index -> index in what's left
+    let index: HashMap<usize, usize> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, (saved, _))| (*saved, i))
+        .collect();
+    let current_index = rows
+        .iter()
+        .position(|(saved, _)| *saved >= current)
+        .unwrap_or(rows.len() - 1);
+
+    let saved_order: Vec<usize> = serde_json::from_str(&shuffle_order)?;
+    let shuffle_order: Vec<usize> = saved_order
+        .iter()
+        .filter_map(|saved| index.get(saved).copied())
+        .collect();
+    let shuffle_index = shuffle_order
+        .iter()
+        .position(|&i| i == current_index)
+        .unwrap_or_else(|| {
+            let before = saved_order[..shuffle_index.min(saved_order.len())]
+                .iter()
+                .filter(|saved| index.contains_key(saved))
+                .count();
+            before.min(shuffle_order.len().saturating_sub(1))
+        });
+    // Queued tracks still waiting after the (new) current one
+    let now_playing = rows[current_index].0;
+    let user_queue_count = rows
+        .iter()
+        .filter(|(saved, _)| *saved > now_playing && *saved <= current + queued)
+        .count();
+
+    let (track_ids, tracks) = rows.into_iter().map(|(_, track)| (track.id, track)).unzip();
+    Ok(Some(SavedQueue {
+        state: QueueState {
+            track_ids,
+            current_index,
+            position: if now_playing == current {
+                position
+            } else {
+                0.0
+            },
+            shuffle,
+            shuffle_order,
+            shuffle_index,
+            repeat: RepeatMode::parse(&repeat),
+            user_queue_count,
+        },
+        tracks,
+        saved_at,
+    }))
+}
+
+pub fn clear(conn: &Connection) -> Result<()> {
+    conn.execute_batch("DELETE FROM play_queue; DELETE FROM play_queue_state;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (id, path, title) VALUES
+                (1, '/music/a.flac', 'A'), (2, '/music/b.flac', 'B'), (3, '/music/c.flac', 'C'),
+                (4, '/music/d.flac', 'D');
+            INSERT INTO tracks (id, path, title, source_type, external_id) VALUES
+                (5, 'tidal://123', 'Stream', 'tidal', '123');
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn session() -> QueueState {
+        QueueState {
+            track_ids: vec![3, 5, 1, 2, 4],
+            current_index: 2,
+            position: 42.5,
+            shuffle: true,
+            shuffle_order: vec![4, 2, 0, 1, 3],
+            shuffle_index: 1,
+            repeat: RepeatMode::All,
+            user_queue_count: 1,
+        }
+    }
+
+    #[test]
+    fn restores_the_saved_session() {
+        let mut conn = library();
+        assert!(load(&conn).unwrap().is_none());
+
+        let tx = conn.transaction().unwrap();
+        save(&tx, &session()).unwrap();
+        tx.commit().unwrap();
+        let saved = load(&conn).unwrap().unwrap();
+        assert_eq!(saved.state, session());
+        assert_eq!(saved.tracks[1].source_type.as_deref(), Some("tidal"));
+
+        save_position(&conn, 3, 7.0, Some(4)).unwrap();
+        let saved = load(&conn).unwrap().unwrap();
+        assert_eq!(
+            (
+                saved.state.current_index,
+                saved.state.position,
+                saved.state.shuffle_index
+            ),
+            (3, 7.0, 4)
+        );
+        assert!(matches!(
+            save_position(&conn, 5, 0.0, None),
+            Err(AudionError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            save(
+                &conn,
+                &QueueState {
+                    shuffle_order: vec![9],
+                    ..session()
+                }
+            ),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        clear(&conn).unwrap();
+        assert!(load(&conn).unwrap().is_none());
+        assert!(matches!(
+            save_position(&conn, 0, 0.0, None),
+            Err(AudionError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn tracks_deleted_since_are_dropped() {
+        let conn = library();
+        save(&conn, &session()).unwrap();
+        // The current track and one before it
+        conn.execute("DELETE FROM tracks WHERE id IN (1, 5)", [])
+            .unwrap();
+
+        let saved = load(&conn).unwrap().unwrap();
+        assert_eq!(saved.state.track_ids, vec![3, 2, 4]);
+        // Track 2 was next, so it's current now, from the start
+        assert_eq!(saved.state.current_index, 1);
+        assert_eq!(saved.state.position, 0.0);
+        assert_eq!(saved.state.shuffle_order, vec![2, 0, 1]);
+        assert_eq!(saved.state.shuffle_index, 2);
+        // Track 2 was the one queued by hand
+        assert_eq!(saved.state.user_queue_count, 0);
+        assert_eq!(saved.tracks.len(), 3);
+    }
+}
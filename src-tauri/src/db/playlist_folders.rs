@@ -0,0 +1,405 @@
+// Playlist folders: a tree of folders that playlists can be filed in. Folders
+// and playlists each have a position among their siblings of the same kind;
+// the order is what counts, so positions may have gaps after deletes.
+use super::queries::{Playlist, PlaylistKind};
+use crate::error::AudionError;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistFolder {
+    pub id: i64,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub created_at: Option<String>,
+    pub folders: Vec<PlaylistFolder>,
+    pub playlists: Vec<Playlist>,
+}
+
+/// Every folder and playlist. At each level folders come before playlists.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistTree {
+    pub folders: Vec<PlaylistFolder>,
+    pub playlists: Vec<Playlist>,
+}
+
+// Which table a sibling list is in
+#[derive(Clone, Copy)]
+enum Item {
+    Folder,
+    Playlist,
+}
+
+impl Item {
+    fn table(self) -> &'static str {
+        match self {
+            Item::Folder => "playlist_folders",
+            Item::Playlist => "playlists",
+        }
+    }
+
+    fn parent_column(self) -> &'static str {
+        match self {
+            Item::Folder => "parent_id",
+            Item::Playlist => "folder_id",
+        }
+    }
+}
+
+pub fn tree(conn: &Connection) -> Result<PlaylistTree> {
+    let mut folders: HashMap<Option<i64>, Vec<PlaylistFolder>> = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT id, name, parent_id, created_at FROM playlist_folders ORDER BY position, id",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let parent_id: Option<i64> = row.get(2)?;
+        folders.entry(parent_id).or_default().push(PlaylistFolder {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            parent_id,
+            created_at: row.get(3)?,
+            folders: Vec::new(),
+            playlists: Vec::new(),
+        });
+    }
+
+    let mut playlists: HashMap<Option<i64>, Vec<Playlist>> = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT id, name, cover_url, created_at, smart_rules IS NOT NULL, folder_id
+         FROM playlists WHERE deleted_at IS NULL ORDER BY position, id",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let folder_id: Option<i64> = row.get(5)?;
+        playlists.entry(folder_id).or_default().push(Playlist {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            cover_url: row.get(2)?,
+            created_at: row.get(3)?,
+            kind: if row.get(4)? {
+                PlaylistKind::Smart
+            } else {
+                PlaylistKind::Static
+            },
+            folder_id,
+        });
+    }
+
+    let (folders, playlists) = children(None, &mut folders, &mut playlists);
+    Ok(PlaylistTree { folders, playlists })
+}
+
+fn children(
+    parent_id: Option<i64>,
+    folders: &mut HashMap<Option<i64>, Vec<PlaylistFolder>>,
+    playlists: &mut HashMap<Option<i64>, Vec<Playlist>>,
+) -> (Vec<PlaylistFolder>, Vec<Playlist>) {
+    let mut level = folders.remove(&parent_id).unwrap_or_default();
+    for folder in &mut level {
+        (folder.folders, folder.playlists) = children(Some(folder.id), folders, playlists);
+    }
+    (level, playlists.remove(&parent_id).unwrap_or_default())
+}
+
+fn ensure_exists(conn: &Connection, item: Item, id: i64) -> Result<(), AudionError> {
+    let found = conn
+        .query_row(
+            &format!("SELECT 1 FROM {} WHERE id = ?1", item.table()),
+            [id],
+            |_| Ok(()),
+        )
+        .optional()?;
+    found.ok_or_else(|| match item {
+        Item::Folder => AudionError::NotFound(format!("Playlist folder {} not found", id)),
+        Item::Playlist => AudionError::NotFound(format!("Playlist {} not found", id)),
+    })
+}
+
+fn folder_name(name: &str) -> Result<&str, AudionError> {
+    match name.trim() {
+        "" => Err(AudionError::InvalidInput(
+            "Folder name can't be empty".to_string(),
+        )),
+        name => Ok(name),
+    }
+}
+
+/// Put a folder or playlist in `parent_id` (the top level when `None`) at
+/// `position` among its siblings, or after them when it's omitted
+fn place(
+    conn: &Connection,
+    item: Item,
+    id: i64,
+    parent_id: Option<i64>,
+    position: Option<i64>,
+) -> Result<(), AudionError> {
+    let mut siblings = conn
+        .prepare(&format!(
+            "SELECT id FROM {} WHERE {} IS ?1 AND id != ?2 ORDER BY position, id",
+            item.table(),
+            item.parent_column()
+        ))?
+        .query_map(params![parent_id, id], |row| row.get(0))?
+        .collect::<Result<Vec<i64>>>()?;
+
+    let index = match position {
+        None => siblings.len(),
+        Some(position) if (0..=siblings.len() as i64).contains(&position) => position as usize,
+        Some(position) => {
+            return Err(AudionError::InvalidInput(format!(
+                "Position {} is out of range (0-{})",
+                position,
+                siblings.len()
+            )))
+        }
+    };
+    siblings.insert(index, id);
+
+    let mut update = conn.prepare(&format!(
+        "UPDATE {} SET {} = ?1, position = ?2 WHERE id = ?3",
+        item.table(),
+        item.parent_column()
+    ))?;
+    for (position, sibling) in siblings.iter().enumerate() {
+        update.execute(params![parent_id, position as i64, sibling])?;
+    }
+    Ok(())
+}
+
+/// A new folder at the end of `parent_id`
+pub fn create_folder(
+    conn: &Connection,
+    name: &str,
+    parent_id: Option<i64>,
+) -> Result<i64, AudionError> {
+    let name = folder_name(name)?;
+    if let Some(parent_id) = parent_id {
+        ensure_exists(conn, Item::Folder, parent_id)?;
+    }
+    conn.execute(
+        "INSERT INTO playlist_folders (name, parent_id, position)
+         SELECT ?1, ?2, COALESCE(MAX(position) + 1, 0) FROM playlist_folders WHERE parent_id IS ?2",
+        params![name, parent_id],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn rename_folder(conn: &Connection, folder_id: i64, name: &str) -> Result<(), AudionError> {
+    let name = folder_name(name)?;
+    let updated = conn.execute(
+        "UPDATE playlist_folders SET name = ?1 WHERE id = ?2",
+        params![name, folder_id],
+    )?;
+    if updated == 0 {
+        return Err(AudionError::NotFound(format!(
+            "Playlist folder {} not found",
+            folder_id
+        )));
+    }
+    Ok(())
+}
+
+/// Move a folder, with everything in it, into `parent_id` (the top level when
+/// `None`). A folder can't go inside itself.
+pub fn move_folder(
+    conn: &Connection,
+    folder_id: i64,
+    parent_id: Option<i64>,
+    position: Option<i64>,
+) -> Result<(), AudionError> {
+    ensure_exists(conn, Item::Folder, folder_id)?;
+    if let Some(parent_id) = parent_id {
+        ensure_exists(conn, Item::Folder, parent_id)?;
+        if subtree(conn, folder_id)?.contains(&parent_id) {
+            return Err(AudionError::InvalidInput(
+                "A folder can't be moved into itself".to_string(),
+            ));
+        }
+    }
+    place(conn, Item::Folder, folder_id, parent_id, position)
+}
+
+/// File a playlist in `folder_id` (the top level when `None`)
+pub fn move_playlist(
+    conn: &Connection,
+    playlist_id: i64,
+    folder_id: Option<i64>,
+    position: Option<i64>,
+) -> Result<(), AudionError> {
+    ensure_exists(conn, Item::Playlist, playlist_id)?;
+    if let Some(folder_id) = folder_id {
+        ensure_exists(conn, Item::Folder, folder_id)?;
+    }
+    place(conn, Item::Playlist, playlist_id, folder_id, position)
+}
+
+/// The folder and every folder below it
+fn subtree(conn: &Connection, folder_id: i64) -> Result<Vec<i64>> {
+    conn.prepare(
+        "WITH RECURSIVE subtree(id) AS (
+             SELECT ?1
+             UNION ALL
+             SELECT f.id FROM playlist_folders f JOIN subtree s ON f.parent_id = s.id
+         )
+         SELECT id FROM subtree",
+    )?
+    .query_map([folder_id], |row| row.get(0))?
+    .collect()
+}
+
+/// Delete a folder. With `delete_playlists` its subfolders go too and every
+/// playlist in them moves to recently deleted (see `playlist_history`);
+/// otherwise what it holds moves up to its parent, after what's already there.
+/// Returns how many playlists were deleted.
+pub fn delete_folder(
+    conn: &Connection,
+    folder_id: i64,
+    delete_playlists: bool,
+) -> Result<usize, AudionError> {
+    let parent_id: Option<i64> = conn
+        .query_row(
+            "SELECT parent_id FROM playlist_folders WHERE id = ?1",
+            [folder_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| AudionError::NotFound(format!("Playlist folder {} not found", folder_id)))?;
+
+    if !delete_playlists {
+        for item in [Item::Folder, Item::Playlist] {
+            let contents = conn
+                .prepare(&format!(
+                    "SELECT id FROM {} WHERE {} = ?1 ORDER BY position, id",
+                    item.table(),
+                    item.parent_column()
+                ))?
+                .query_map([folder_id], |row| row.get(0))?
+                .collect::<Result<Vec<i64>>>()?;
+            for id in contents {
+                place(conn, item, id, parent_id, None)?;
+            }
+        }
+        conn.execute("DELETE FROM playlist_folders WHERE id = ?1", [folder_id])?;
+        return Ok(0);
+    }
+
+    let folders = subtree(conn, folder_id)?;
+    let mut deleted = 0;
+    let mut trash = conn.prepare(
+        "UPDATE playlists SET deleted_at = CURRENT_TIMESTAMP
+         WHERE folder_id = ?1 AND deleted_at IS NULL",
+    )?;
+    for id in &folders {
+        deleted += trash.execute([id])?;
+    }
+    // Innermost first, so no folder is removed before its children
+    let mut delete = conn.prepare("DELETE FROM playlist_folders WHERE id = ?1")?;
+    for id in folders.iter().rev() {
+        delete.execute([id])?;
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{queries, schema};
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        for name in ["Chill", "Gym", "Road Trip", "Party"] {
+            queries::create_playlist(&conn, name).unwrap();
+        }
+        conn
+    }
+
+    fn names(playlists: &[Playlist]) -> Vec<&str> {
+        playlists.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn builds_and_rearranges_the_tree() {
+        let conn = library();
+        let moods = create_folder(&conn, "Moods", None).unwrap();
+        let calm = create_folder(&conn, "Calm", Some(moods)).unwrap();
+        move_playlist(&conn, 1, Some(calm), None).unwrap();
+        move_playlist(&conn, 4, Some(moods), None).unwrap();
+        move_playlist(&conn, 2, Some(moods), Some(0)).unwrap();
+
+        let tree = tree(&conn).unwrap();
+        assert_eq!(names(&tree.playlists), ["Road Trip"]);
+        let moods_node = &tree.folders[0];
+        assert_eq!(names(&moods_node.playlists), ["Gym", "Party"]);
+        assert_eq!(moods_node.folders[0].name, "Calm");
+        assert_eq!(names(&moods_node.folders[0].playlists), ["Chill"]);
+
+        // New playlists go at the end of the top level
+        queries::create_playlist(&conn, "Focus").unwrap();
+        assert_eq!(
+            names(&super::tree(&conn).unwrap().playlists),
+            ["Road Trip", "Focus"]
+        );
+
+        assert!(matches!(
+            move_folder(&conn, moods, Some(calm), None),
+            Err(AudionError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            move_folder(&conn, moods, Some(moods), None),
+            Err(AudionError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            move_playlist(&conn, 3, Some(moods), Some(5)),
+            Err(AudionError::InvalidInput(_))
+        ));
+        move_folder(&conn, calm, None, Some(0)).unwrap();
+        let top: Vec<String> = super::tree(&conn)
+            .unwrap()
+            .folders
+            .into_iter()
+            .map(|folder| folder.name)
+            .collect();
+        assert_eq!(top, ["Calm", "Moods"]);
+    }
+
+    #[test]
+    fn deleting_a_folder_keeps_or_deletes_its_playlists() {
+        let conn = library();
+        let outer = create_folder(&conn, "Outer", None).unwrap();
+        let inner = create_folder(&conn, "Inner", Some(outer)).unwrap();
+        move_playlist(&conn, 1, Some(outer), None).unwrap();
+        move_playlist(&conn, 2, Some(inner), None).unwrap();
+
+        // Keeping: Inner and Chill move up to the top level, at the end
+        assert_eq!(delete_folder(&conn, outer, false).unwrap(), 0);
+        let tree = tree(&conn).unwrap();
+        assert_eq!(names(&tree.playlists), ["Road Trip", "Party", "Chill"]);
+        assert_eq!(tree.folders.len(), 1);
+        assert_eq!(tree.folders[0].parent_id, None);
+        assert_eq!(names(&tree.folders[0].playlists), ["Gym"]);
+
+        let outer = create_folder(&conn, "Outer", None).unwrap();
+        move_folder(&conn, inner, Some(outer), None).unwrap();
+        move_playlist(&conn, 1, Some(outer), None).unwrap();
+        assert_eq!(delete_folder(&conn, outer, true).unwrap(), 2);
+        let tree = super::tree(&conn).unwrap();
+        assert!(tree.folders.is_empty());
+        assert_eq!(names(&tree.playlists), ["Road Trip", "Party"]);
+        // They can still be restored, at the top level
+        let trashed: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM playlists WHERE deleted_at IS NOT NULL AND folder_id IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(trashed, 2);
+        assert!(matches!(
+            delete_folder(&conn, outer, true),
+            Err(AudionError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,399 @@
+// Playlist history: a journal of edits that can be undone, and recently
+// deleted playlists that can be restored until the retention period runs out.
+// Each journal row is the playlist's name and entries from just before a
+// change, so undoing n changes restores the nth newest row.
+use super::{queries, smart_playlists};
+use crate::error::AudionError;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+
+const OPTIONS_KEY: &str = "playlist_history_options";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryOptions {
+    /// How many days a deleted playlist can be restored for
+    pub retention_days: u32,
+    /// Changes kept per playlist for undo; 0 turns the journal off
+    pub undo_limit: u32,
+}
+
+impl Default for HistoryOptions {
+    fn default() -> Self {
+        HistoryOptions {
+            retention_days: 30,
+            undo_limit: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistChange {
+    Add,
+    Remove,
+    Move,
+    Dedupe,
+    Sort,
+    Shuffle,
+    Replace,
+    Rename,
+}
+
+impl PlaylistChange {
+    fn as_str(self) -> &'static str {
+        match self {
+            PlaylistChange::Add => "add",
+            PlaylistChange::Remove => "remove",
+            PlaylistChange::Move => "move",
+            PlaylistChange::Dedupe => "dedupe",
+            PlaylistChange::Sort => "sort",
+            PlaylistChange::Shuffle => "shuffle",
+            PlaylistChange::Replace => "replace",
+            PlaylistChange::Rename => "rename",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalEntry {
+    pub id: i64,
+    /// One of the `PlaylistChange` names
+    pub change: String,
+    /// Name and track count from before the change
+    pub name: String,
+    pub track_count: usize,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeletedPlaylist {
+    pub id: i64,
+    pub name: String,
+    pub track_count: i64,
+    pub deleted_at: String,
+    /// When it's deleted for good
+    pub expires_at: String,
+}
+
+pub fn load_options(conn: &Connection) -> Result<HistoryOptions> {
+    Ok(queries::get_setting(conn, OPTIONS_KEY)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+pub fn save_options(conn: &Connection, options: &HistoryOptions) -> Result<()> {
+    let json = serde_json::to_string(options)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    queries::set_setting(conn, OPTIONS_KEY, &json)
+}
+
+/// Journal the playlist as it is before `change`. Call it in the same
+/// transaction as the change, so a failed change leaves no entry.
+pub fn record(
+    conn: &Connection,
+    playlist_id: i64,
+    change: PlaylistChange,
+) -> Result<(), AudionError> {
+    let name: String = conn
+        .query_row(
+            "SELECT name FROM playlists WHERE id = ?1",
+            [playlist_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| AudionError::NotFound(format!("Playlist {} not found", playlist_id)))?;
+    // (entry id, track id) pairs, in order
+    let entries = conn
+        .prepare(
+            "SELECT id, track_id FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY position, id",
+        )?
+        .query_map([playlist_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i64, i64)>>>()?;
+
+    conn.execute(
+        "INSERT INTO playlist_journal (playlist_id, change, name, entries) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            playlist_id,
+            change.as_str(),
+            name,
+            serde_json::to_string(&entries)?
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM playlist_journal WHERE playlist_id = ?1 AND id NOT IN (
+             SELECT id FROM playlist_journal WHERE playlist_id = ?1 ORDER BY id DESC LIMIT ?2
+         )",
+        params![playlist_id, load_options(conn)?.undo_limit],
+    )?;
+    Ok(())
+}
+
+/// Changes that can be undone, newest first
+pub fn history(conn: &Connection, playlist_id: i64) -> Result<Vec<JournalEntry>, AudionError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, change, name, entries, created_at FROM playlist_journal
+         WHERE playlist_id = ?1 ORDER BY id DESC",
+    )?;
+    let mut rows = stmt.query([playlist_id])?;
+    let mut journal = Vec::new();
+    while let Some(row) = rows.next()? {
+        let entries: Vec<(i64, i64)> = serde_json::from_str(&row.get::<_, String>(3)?)?;
+        journal.push(JournalEntry {
+            id: row.get(0)?,
+            change: row.get(1)?,
+            name: row.get(2)?,
+            track_count: entries.len(),
+            created_at: row.get(4)?,
+        });
+    }
+    Ok(journal)
+}
+
+/// Undo the last `steps` changes (or as many as there are). Entries get their
+/// old ids back; tracks removed from the library since are left out. A smart
+/// playlist only gets its name back, since its tracks come from its rules.
+/// Returns how many changes were undone.
+pub fn undo(conn: &Connection, playlist_id: i64, steps: usize) -> Result<usize, AudionError> {
+    let undone = conn
+        .prepare(
+            "SELECT id, name, entries FROM playlist_journal
+             WHERE playlist_id = ?1 ORDER BY id DESC LIMIT ?2",
+        )?
+        .query_map(params![playlist_id, steps as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<Vec<(i64, String, String)>>>()?;
+    let Some((oldest, name, entries)) = undone.last() else {
+        return Err(AudionError::InvalidInput(
+            "Nothing to undo for this playlist".to_string(),
+        ));
+    };
+
+    conn.execute(
+        "UPDATE playlists SET name = ?2 WHERE id = ?1",
+        params![playlist_id, name],
+    )?;
+    if smart_playlists::get_rules(conn, playlist_id)?.is_none() {
+        let entries: Vec<(i64, i64)> = serde_json::from_str(entries)?;
+        conn.execute(
+            "DELETE FROM playlist_tracks WHERE playlist_id = ?1",
+            [playlist_id],
+        )?;
+        let mut insert = conn.prepare(
+            "INSERT INTO playlist_tracks (id, playlist_id, track_id, position)
+             SELECT ?1, ?2, ?3, ?4 WHERE EXISTS (SELECT 1 FROM tracks WHERE id = ?3)",
+        )?;
+        let mut position = 0;
+        for (entry_id, track_id) in entries {
+            position += insert.execute(params![entry_id, playlist_id, track_id, position])? as i64;
+        }
+    }
+
+    conn.execute(
+        "DELETE FROM playlist_journal WHERE playlist_id = ?1 AND id >= ?2",
+        params![playlist_id, oldest],
+    )?;
+    Ok(undone.len())
+}
+
+// The retention period as a datetime() modifier
+fn retention(conn: &Connection) -> Result<String> {
+    Ok(format!("{} days", load_options(conn)?.retention_days))
+}
+
+/// Permanently delete playlists that have been in recently deleted for longer
+/// than the retention period. Returns how many went.
+pub fn purge_expired(conn: &Connection) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM playlists
+         WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', '-' || ?1)",
+        [retention(conn)?],
+    )
+}
+
+/// Move a playlist to recently deleted
+pub fn trash(conn: &Connection, playlist_id: i64) -> Result<(), AudionError> {
+    let updated = conn.execute(
+        "UPDATE playlists SET deleted_at = CURRENT_TIMESTAMP WHERE id = ?1 AND deleted_at IS NULL",
+        [playlist_id],
+    )?;
+    if updated == 0 {
+        return Err(AudionError::NotFound(format!(
+            "Playlist {} not found",
+            playlist_id
+        )));
+    }
+    purge_expired(conn)?;
+    Ok(())
+}
+
+/// Playlists that can still be restored, most recently deleted first
+pub fn deleted(conn: &Connection) -> Result<Vec<DeletedPlaylist>> {
+    let retention = retention(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.name,
+                (SELECT COUNT(*) FROM playlist_tracks pt WHERE pt.playlist_id = p.id),
+                p.deleted_at, datetime(p.deleted_at, '+' || ?1)
+         FROM playlists p
+         WHERE p.deleted_at IS NOT NULL AND p.deleted_at > datetime('now', '-' || ?1)
+         ORDER BY p.deleted_at DESC, p.id DESC",
+    )?;
+    let playlists = stmt
+        .query_map([retention], |row| {
+            Ok(DeletedPlaylist {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                track_count: row.get(2)?,
+                deleted_at: row.get(3)?,
+                expires_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(playlists)
+}
+
+/// Bring a playlist back from recently deleted, at the end of its folder (or
+/// the top level, if the folder has been deleted since)
+pub fn restore(conn: &Connection, playlist_id: i64) -> Result<(), AudionError> {
+    purge_expired(conn)?;
+    let updated = conn.execute(
+        "UPDATE playlists SET deleted_at = NULL, position = (
+             SELECT COALESCE(MAX(p.position) + 1, 0) FROM playlists p
+             WHERE p.folder_id IS playlists.folder_id AND p.deleted_at IS NULL
+         )
+         WHERE id = ?1 AND deleted_at IS NOT NULL",
+        [playlist_id],
+    )?;
+    if updated == 0 {
+        return Err(AudionError::NotFound(format!(
+            "Playlist {} isn't in recently deleted",
+            playlist_id
+        )));
+    }
+    Ok(())
+}
+
+/// Delete a playlist from recently deleted right away
+pub fn delete_permanently(conn: &Connection, playlist_id: i64) -> Result<(), AudionError> {
+    let trashed = conn
+        .query_row(
+            "SELECT 1 FROM playlists WHERE id = ?1 AND deleted_at IS NOT NULL",
+            [playlist_id],
+            |_| Ok(()),
+        )
+        .optional()?;
+    if trashed.is_none() {
+        return Err(AudionError::NotFound(format!(
+            "Playlist {} isn't in recently deleted",
+            playlist_id
+        )));
+    }
+    queries::delete_playlist(conn, playlist_id)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{playlists, schema};
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (id, path, title) VALUES
+                (1, '/music/a.flac', 'A'), (2, '/music/b.flac', 'B'), (3, '/music/c.flac', 'C');
+            INSERT INTO playlists (id, name) VALUES (1, 'Mix');
+            ",
+        )
+        .unwrap();
+        playlists::add_many(&conn, 1, &[1, 2, 3], None).unwrap();
+        conn
+    }
+
+    fn entries(conn: &Connection) -> Vec<(i64, i64)> {
+        playlists::get_entries(conn, 1)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.entry_id, entry.track.id))
+            .collect()
+    }
+
+    fn name(conn: &Connection) -> String {
+        playlists::playlist_name(conn, 1).unwrap().unwrap()
+    }
+
+    #[test]
+    fn undoes_the_last_changes() {
+        let conn = library();
+        let original = entries(&conn);
+
+        record(&conn, 1, PlaylistChange::Move).unwrap();
+        playlists::move_entry(&conn, 1, original[2].0, 0).unwrap();
+        let moved = entries(&conn);
+        record(&conn, 1, PlaylistChange::Rename).unwrap();
+        queries::rename_playlist(&conn, 1, "Road Trip").unwrap();
+        record(&conn, 1, PlaylistChange::Remove).unwrap();
+        playlists::remove(&conn, 1, original[0].0).unwrap();
+
+        let journal = history(&conn, 1).unwrap();
+        let changes: Vec<&str> = journal.iter().map(|entry| entry.change.as_str()).collect();
+        assert_eq!(changes, ["remove", "rename", "move"]);
+        assert_eq!(journal[0].track_count, 3);
+
+        // The removed entry comes back with its old id
+        assert_eq!(undo(&conn, 1, 1).unwrap(), 1);
+        assert_eq!(entries(&conn), moved);
+        assert_eq!(name(&conn), "Road Trip");
+
+        assert_eq!(undo(&conn, 1, 5).unwrap(), 2);
+        assert_eq!(entries(&conn), original);
+        assert_eq!(name(&conn), "Mix");
+        assert!(history(&conn, 1).unwrap().is_empty());
+        assert!(matches!(
+            undo(&conn, 1, 1),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        save_options(
+            &conn,
+            &HistoryOptions {
+                undo_limit: 2,
+                ..HistoryOptions::default()
+            },
+        )
+        .unwrap();
+        for _ in 0..3 {
+            record(&conn, 1, PlaylistChange::Sort).unwrap();
+        }
+        assert_eq!(history(&conn, 1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn deleted_playlists_can_be_restored_until_they_expire() {
+        let conn = library();
+        trash(&conn, 1).unwrap();
+        assert!(queries::get_all_playlists(&conn).unwrap().is_empty());
+        let trashed = deleted(&conn).unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].track_count, 3);
+        assert!(trashed[0].expires_at > trashed[0].deleted_at);
+
+        restore(&conn, 1).unwrap();
+        assert_eq!(queries::get_all_playlists(&conn).unwrap().len(), 1);
+        assert_eq!(entries(&conn).len(), 3);
+        assert!(matches!(restore(&conn, 1), Err(AudionError::NotFound(_))));
+
+        trash(&conn, 1).unwrap();
+        conn.execute(
+            "UPDATE playlists SET deleted_at = datetime('now', '-31 days') WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        assert!(deleted(&conn).unwrap().is_empty());
+        assert!(matches!(restore(&conn, 1), Err(AudionError::NotFound(_))));
+        assert_eq!(playlists::playlist_name(&conn, 1).unwrap(), None);
+    }
+}
@@ -0,0 +1,577 @@
+// Playlist entries: each row of playlist_tracks has its own id, so a track can
+// appear more than once. Positions run 0..n-1 within a playlist with no gaps.
+// Entries of removed tracks keep their position but aren't listed, so indexes
+// passed in from the UI count only the listed entries.
+use super::browse::{SortDirection, TrackSort};
+use super::queries::{self, Track, TrackInsert};
+use crate::error::AudionError;
+use crate::playlist_files::PlaylistItem;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Result};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistEntry {
+    pub entry_id: i64,
+    /// Index among the listed entries
+    pub position: i64,
+    #[serde(flatten)]
+    pub track: Track,
+}
+
+pub fn get_entries(conn: &Connection, playlist_id: i64) -> Result<Vec<PlaylistEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT pt.id, ROW_NUMBER() OVER (ORDER BY pt.position, pt.id) - 1, t.id, t.path, t.title, t.artist, t.album, t.track_number, t.duration, t.album_id, t.format, t.bitrate, t.source_type, t.cover_url, t.external_id, t.local_src, t.track_cover, t.track_cover_path
+         FROM playlist_tracks pt
+         INNER JOIN tracks t ON t.id = pt.track_id
+         WHERE pt.playlist_id = ?1 AND t.removed_at IS NULL
+         ORDER BY pt.position, pt.id",
+    )?;
+
+    let entries = stmt
+        .query_map([playlist_id], |row| {
+            Ok(PlaylistEntry {
+                entry_id: row.get(0)?,
+                position: row.get(1)?,
+                track: Track {
+                    id: row.get(2)?,
+                    path: row.get(3)?,
+                    title: row.get(4)?,
+                    artist: row.get(5)?,
+                    album: row.get(6)?,
+                    track_number: row.get(7)?,
+                    duration: row.get(8)?,
+                    album_id: row.get(9)?,
+                    format: row.get(10)?,
+                    bitrate: row.get(11)?,
+                    source_type: row.get(12)?,
+                    cover_url: row.get(13)?,
+                    external_id: row.get(14)?,
+                    local_src: row.get(15)?,
+                    track_cover: row.get(16)?,
+                    track_cover_path: row.get(17)?,
+                },
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(entries)
+}
+
+pub fn playlist_name(conn: &Connection, playlist_id: i64) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT name FROM playlists WHERE id = ?1",
+        [playlist_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// The playlist's tracks with everything a playlist file can carry
+pub fn export_items(conn: &Connection, playlist_id: i64) -> Result<Vec<PlaylistItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.path, t.title, t.artist, t.album, t.track_number, t.duration, t.year, t.genre, t.source_type, t.external_id, t.isrc
+         FROM playlist_tracks pt
+         INNER JOIN tracks t ON t.id = pt.track_id
+         WHERE pt.playlist_id = ?1 AND t.removed_at IS NULL
+         ORDER BY pt.position, pt.id",
+    )?;
+
+    let items = stmt
+        .query_map([playlist_id], |row| {
+            Ok(PlaylistItem {
+                path: row.get(0)?,
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                album: row.get(3)?,
+                track_number: row.get(4)?,
+                duration: row.get(5)?,
+                year: row.get(6)?,
+                genre: row.get(7)?,
+                source_type: row.get(8)?,
+                external_id: row.get(9)?,
+                isrc: row.get(10)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(items)
+}
+
+/// Append a track, even if it's already in the playlist. Returns the new entry's id.
+pub fn add(conn: &Connection, playlist_id: i64, track_id: i64) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO playlist_tracks (playlist_id, track_id, position)
+         SELECT ?1, ?2, COALESCE(MAX(position) + 1, 0) FROM playlist_tracks WHERE playlist_id = ?1",
+        params![playlist_id, track_id],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn entry_position(conn: &Connection, playlist_id: i64, entry_id: i64) -> Result<i64, AudionError> {
+    conn.query_row(
+        "SELECT position FROM playlist_tracks WHERE id = ?1 AND playlist_id = ?2",
+        params![entry_id, playlist_id],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| {
+        AudionError::NotFound(format!(
+            "Entry {} not found in playlist {}",
+            entry_id, playlist_id
+        ))
+    })
+}
+
+/// Remove one entry and close the gap it leaves
+pub fn remove(conn: &Connection, playlist_id: i64, entry_id: i64) -> Result<(), AudionError> {
+    let position = entry_position(conn, playlist_id, entry_id)?;
+    conn.execute("DELETE FROM playlist_tracks WHERE id = ?1", [entry_id])?;
+    conn.execute(
+        "UPDATE playlist_tracks SET position = position - 1 WHERE playlist_id = ?1 AND position > ?2",
+        params![playlist_id, position],
+    )?;
+    Ok(())
+}
+
+/// Move an entry to `to_index` (0-based), shifting only the entries in between
+pub fn move_entry(
+    conn: &Connection,
+    playlist_id: i64,
+    entry_id: i64,
+    to_index: i64,
+) -> Result<(), AudionError> {
+    let mut order = listed_order(conn, playlist_id)?;
+    let from = order
+        .iter()
+        .position(|&(id, _)| id == entry_id)
+        .ok_or_else(|| {
+            AudionError::NotFound(format!(
+                "Entry {} not found in playlist {}",
+                entry_id, playlist_id
+            ))
+        })?;
+    let entry = order.remove(from);
+    let to = stored_index(&order, to_index)
+        .ok_or_else(|| AudionError::InvalidInput(format!("Invalid to_index: {}", to_index)))?;
+    order.insert(to, entry);
+    write_order(conn, playlist_id, &ids(&order))?;
+    Ok(())
+}
+
+/// Source type of tracks that stand in for entries of an imported list that
+/// aren't in the library. Only playlists show them: library listings, search,
+/// smart playlists and matching all leave them out.
+pub const PLACEHOLDER_SOURCE: &str = "placeholder";
+
+/// Library track standing in for `item`. Importing the same row again reuses it.
+pub fn placeholder_track(conn: &Connection, item: &PlaylistItem) -> Result<i64> {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    (&item.title, &item.artist, &item.album, item.duration).hash(&mut hasher);
+    let key = item
+        .isrc
+        .clone()
+        .unwrap_or_else(|| format!("{:016x}", hasher.finish()));
+
+    let (track_id, _) = queries::insert_or_update_track(
+        conn,
+        &TrackInsert {
+            path: format!("{}://{}", PLACEHOLDER_SOURCE, key),
+            title: item.title.clone(),
+            artist: item.artist.clone(),
+            // No album, so placeholders don't show up as albums
+            album: None,
+            track_number: None,
+            duration: item.duration,
+            year: None,
+            genre: None,
+            sort_title: None,
+            sort_artist: None,
+            sort_album: None,
+            rating: None,
+            isrc: item.isrc.clone(),
+            album_art: None,
+            track_cover: None,
+            format: None,
+            bitrate: None,
+            source_type: Some(PLACEHOLDER_SOURCE.to_string()),
+            cover_url: None,
+            external_id: None,
+            content_hash: None,
+            local_src: None,
+        },
+    )?;
+    Ok(track_id)
+}
+
+// Bulk edits. Callers run each one in a transaction.
+
+fn current_order(conn: &Connection, playlist_id: i64) -> Result<Vec<i64>> {
+    conn.prepare("SELECT id FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY position, id")?
+        .query_map([playlist_id], |row| row.get(0))?
+        .collect()
+}
+
+/// Entry ids in order, each with whether it's listed (its track isn't removed)
+fn listed_order(conn: &Connection, playlist_id: i64) -> Result<Vec<(i64, bool)>> {
+    conn.prepare(
+        "SELECT pt.id, t.removed_at IS NULL FROM playlist_tracks pt
+         INNER JOIN tracks t ON t.id = pt.track_id
+         WHERE pt.playlist_id = ?1
+         ORDER BY pt.position, pt.id",
+    )?
+    .query_map([playlist_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+    .collect()
+}
+
+/// Where the `index`th listed entry sits in `order`. One past the last listed
+/// entry is the end of the playlist.
+fn stored_index(order: &[(i64, bool)], index: i64) -> Option<usize> {
+    let listed: Vec<usize> = order
+        .iter()
+        .enumerate()
+        .filter(|(_, &(_, listed))| listed)
+        .map(|(i, _)| i)
+        .collect();
+    match usize::try_from(index).ok()? {
+        index if index < listed.len() => Some(listed[index]),
+        index if index == listed.len() => Some(order.len()),
+        _ => None,
+    }
+}
+
+fn ids(order: &[(i64, bool)]) -> Vec<i64> {
+    order.iter().map(|&(id, _)| id).collect()
+}
+
+/// Store `order` as the playlist's order, touching only the rows that moved
+fn write_order(conn: &Connection, playlist_id: i64, order: &[i64]) -> Result<()> {
+    let mut current =
+        conn.prepare("SELECT id, position FROM playlist_tracks WHERE playlist_id = ?1")?;
+    let positions: std::collections::HashMap<i64, i64> = current
+        .query_map([playlist_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+
+    let mut update = conn.prepare("UPDATE playlist_tracks SET position = ?2 WHERE id = ?1")?;
+    for (position, entry_id) in order.iter().enumerate() {
+        if positions.get(entry_id) != Some(&(position as i64)) {
+            update.execute(params![entry_id, position as i64])?;
+        }
+    }
+    Ok(())
+}
+
+/// Insert tracks at `index` (0-based), or append them when `index` is `None`.
+/// Returns the new entries' ids.
+pub fn add_many(
+    conn: &Connection,
+    playlist_id: i64,
+    track_ids: &[i64],
+    index: Option<i64>,
+) -> Result<Vec<i64>, AudionError> {
+    let order = listed_order(conn, playlist_id)?;
+    let index = match index {
+        Some(index) => stored_index(&order, index)
+            .ok_or_else(|| AudionError::InvalidInput(format!("Invalid index: {}", index)))?
+            as i64,
+        None => order.len() as i64,
+    };
+
+    conn.execute(
+        "UPDATE playlist_tracks SET position = position + ?3 WHERE playlist_id = ?1 AND position >= ?2",
+        params![playlist_id, index, track_ids.len() as i64],
+    )?;
+    let mut insert = conn.prepare(
+        "INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES (?1, ?2, ?3)",
+    )?;
+    let mut ids = Vec::with_capacity(track_ids.len());
+    for (offset, track_id) in track_ids.iter().enumerate() {
+        insert.execute(params![playlist_id, track_id, index + offset as i64])?;
+        ids.push(conn.last_insert_rowid());
+    }
+    Ok(ids)
+}
+
+/// Remove entries; ids from other playlists are ignored. Returns how many went.
+pub fn remove_many(conn: &Connection, playlist_id: i64, entry_ids: &[i64]) -> Result<usize> {
+    if entry_ids.is_empty() {
+        return Ok(0);
+    }
+    let placeholders = vec!["?"; entry_ids.len()].join(", ");
+    let mut params = vec![playlist_id];
+    params.extend_from_slice(entry_ids);
+    let removed = conn.execute(
+        &format!(
+            "DELETE FROM playlist_tracks WHERE playlist_id = ? AND id IN ({})",
+            placeholders
+        ),
+        params_from_iter(params),
+    )?;
+    write_order(conn, playlist_id, &current_order(conn, playlist_id)?)?;
+    Ok(removed)
+}
+
+/// Move `count` entries starting at `from_index` so the first lands at `to_index`
+/// (an index into the list without the moved entries)
+pub fn move_range(
+    conn: &Connection,
+    playlist_id: i64,
+    from_index: i64,
+    count: i64,
+    to_index: i64,
+) -> Result<(), AudionError> {
+    let mut order = listed_order(conn, playlist_id)?;
+    let listed: Vec<i64> = order
+        .iter()
+        .filter(|&&(_, listed)| listed)
+        .map(|&(id, _)| id)
+        .collect();
+    let len = listed.len() as i64;
+    if from_index < 0 || count < 1 || from_index + count > len {
+        return Err(AudionError::InvalidInput(format!(
+            "Invalid range: {} entries from {}",
+            count, from_index
+        )));
+    }
+
+    let moved = &listed[from_index as usize..(from_index + count) as usize];
+    order.retain(|(id, _)| !moved.contains(id));
+    let to = stored_index(&order, to_index)
+        .ok_or_else(|| AudionError::InvalidInput(format!("Invalid to_index: {}", to_index)))?;
+    order.splice(to..to, moved.iter().map(|&id| (id, true)));
+    write_order(conn, playlist_id, &ids(&order))?;
+    Ok(())
+}
+
+/// Drop repeated tracks, keeping each one's first entry. Returns how many went.
+pub fn dedupe(conn: &Connection, playlist_id: i64) -> Result<usize> {
+    let removed = conn.execute(
+        "DELETE FROM playlist_tracks
+         WHERE playlist_id = ?1
+           AND id NOT IN (
+               SELECT id FROM (
+                   SELECT id, ROW_NUMBER() OVER (PARTITION BY track_id ORDER BY position, id) AS n
+                   FROM playlist_tracks WHERE playlist_id = ?1
+               ) WHERE n = 1
+           )",
+        [playlist_id],
+    )?;
+    write_order(conn, playlist_id, &current_order(conn, playlist_id)?)?;
+    Ok(removed)
+}
+
+/// Reorder the entries by a track field, for good
+pub fn sort(
+    conn: &Connection,
+    playlist_id: i64,
+    sort: TrackSort,
+    direction: SortDirection,
+) -> Result<()> {
+    let order: Vec<i64> = conn
+        .prepare(&format!(
+            "SELECT pt.id FROM playlist_tracks pt
+             INNER JOIN tracks t ON t.id = pt.track_id
+             WHERE pt.playlist_id = ?1
+             ORDER BY {}, pt.position",
+            sort.order_by(direction)
+        ))?
+        .query_map([playlist_id], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    write_order(conn, playlist_id, &order)
+}
+
+/// Shuffle the stored order (as opposed to shuffled playback)
+pub fn shuffle(conn: &Connection, playlist_id: i64) -> Result<()> {
+    let order: Vec<i64> = conn
+        .prepare("SELECT id FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY RANDOM()")?
+        .query_map([playlist_id], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    write_order(conn, playlist_id, &order)
+}
+
+/// Make the playlist exactly `track_ids`, in that order
+pub fn replace(conn: &Connection, playlist_id: i64, track_ids: &[i64]) -> Result<(), AudionError> {
+    conn.execute(
+        "DELETE FROM playlist_tracks WHERE playlist_id = ?1",
+        [playlist_id],
+    )?;
+    add_many(conn, playlist_id, track_ids, None)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    fn order(conn: &Connection) -> Vec<(i64, i64)> {
+        get_entries(conn, 1)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.position, entry.track.id))
+            .collect()
+    }
+
+    fn tracks_in(conn: &Connection) -> Vec<i64> {
+        order(conn)
+            .into_iter()
+            .map(|(_, track_id)| track_id)
+            .collect()
+    }
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (id, path, title) VALUES
+                (1, '/m/1.flac', 'Echoes'), (2, '/m/2.flac', 'Airbag'), (3, '/m/3.flac', 'Creep');
+            INSERT INTO playlists (id, name) VALUES (1, 'Mix'), (2, 'Other');
+            ",
+        )
+        .unwrap();
+        crate::db::sort_names::regenerate(&conn, &Default::default()).unwrap();
+        conn
+    }
+
+    #[test]
+    fn entries_allow_duplicates_and_keep_positions_dense() {
+        let conn = library();
+
+        let first = add(&conn, 1, 1).unwrap();
+        let second = add(&conn, 1, 2).unwrap();
+        let again = add(&conn, 1, 1).unwrap();
+        let third = add(&conn, 1, 3).unwrap();
+        assert_ne!(first, again);
+        assert_eq!(order(&conn), vec![(0, 1), (1, 2), (2, 1), (3, 3)]);
+
+        move_entry(&conn, 1, third, 0).unwrap();
+        assert_eq!(order(&conn), vec![(0, 3), (1, 1), (2, 2), (3, 1)]);
+        move_entry(&conn, 1, first, 3).unwrap();
+        assert_eq!(order(&conn), vec![(0, 3), (1, 2), (2, 1), (3, 1)]);
+        assert!(matches!(
+            move_entry(&conn, 1, first, 4),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        // Removing one copy leaves the other
+        remove(&conn, 1, second).unwrap();
+        remove(&conn, 1, again).unwrap();
+        assert_eq!(order(&conn), vec![(0, 3), (1, 1)]);
+        assert!(matches!(
+            remove(&conn, 2, first),
+            Err(AudionError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn indexes_skip_removed_tracks() {
+        let conn = library();
+        conn.execute(
+            "INSERT INTO tracks (id, path, title) VALUES (4, '/m/4.flac', 'Dollars')",
+            [],
+        )
+        .unwrap();
+        let entries: Vec<i64> = [1, 2, 3, 4]
+            .iter()
+            .map(|&t| add(&conn, 1, t).unwrap())
+            .collect();
+        conn.execute(
+            "UPDATE tracks SET removed_at = CURRENT_TIMESTAMP WHERE id = 2",
+            [],
+        )
+        .unwrap();
+        assert_eq!(order(&conn), vec![(0, 1), (1, 3), (2, 4)]);
+
+        // Across the hidden entry, both ways
+        move_entry(&conn, 1, entries[0], 1).unwrap();
+        assert_eq!(tracks_in(&conn), vec![3, 1, 4]);
+        move_entry(&conn, 1, entries[3], 0).unwrap();
+        assert_eq!(tracks_in(&conn), vec![4, 3, 1]);
+        assert!(matches!(
+            move_entry(&conn, 1, entries[0], 3),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        add_many(&conn, 1, &[1], Some(3)).unwrap();
+        assert_eq!(tracks_in(&conn), vec![4, 3, 1, 1]);
+        add_many(&conn, 1, &[3], Some(1)).unwrap();
+        assert_eq!(tracks_in(&conn), vec![4, 3, 3, 1, 1]);
+        assert!(matches!(
+            add_many(&conn, 1, &[1], Some(6)),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        move_range(&conn, 1, 0, 2, 3).unwrap();
+        assert_eq!(tracks_in(&conn), vec![3, 1, 1, 4, 3]);
+
+        // The hidden entry was kept, and the stored order has no gaps
+        let stored: Vec<i64> = conn
+            .prepare("SELECT position FROM playlist_tracks WHERE playlist_id = 1 ORDER BY position")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(stored, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn bulk_edits_keep_positions_dense() {
+        let conn = library();
+
+        add_many(&conn, 1, &[1, 2, 3], None).unwrap();
+        let inserted = add_many(&conn, 1, &[3, 1], Some(1)).unwrap();
+        assert_eq!(inserted.len(), 2);
+        assert_eq!(tracks_in(&conn), vec![1, 3, 1, 2, 3]);
+        assert!(matches!(
+            add_many(&conn, 1, &[1], Some(6)),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        // [3, 1] to the end
+        move_range(&conn, 1, 1, 2, 3).unwrap();
+        assert_eq!(tracks_in(&conn), vec![1, 2, 3, 3, 1]);
+        assert!(matches!(
+            move_range(&conn, 1, 4, 2, 0),
+            Err(AudionError::InvalidInput(_))
+        ));
+
+        assert_eq!(dedupe(&conn, 1).unwrap(), 2);
+        assert_eq!(order(&conn), vec![(0, 1), (1, 2), (2, 3)]);
+
+        sort(&conn, 1, TrackSort::Title, SortDirection::Asc).unwrap();
+        assert_eq!(tracks_in(&conn), vec![2, 3, 1]);
+        sort(&conn, 1, TrackSort::Title, SortDirection::Desc).unwrap();
+        assert_eq!(tracks_in(&conn), vec![1, 3, 2]);
+
+        shuffle(&conn, 1).unwrap();
+        let mut shuffled = order(&conn);
+        assert_eq!(
+            shuffled
+                .iter()
+                .map(|(position, _)| *position)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        shuffled.sort_by_key(|(_, track_id)| *track_id);
+        assert_eq!(
+            shuffled
+                .iter()
+                .map(|(_, track_id)| *track_id)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        let entries = get_entries(&conn, 1).unwrap();
+        let removed =
+            remove_many(&conn, 1, &[entries[0].entry_id, entries[2].entry_id, 999]).unwrap();
+        assert_eq!(removed, 2);
+        assert_eq!(order(&conn), vec![(0, entries[1].track.id)]);
+
+        replace(&conn, 1, &[3, 3, 2]).unwrap();
+        assert_eq!(order(&conn), vec![(0, 3), (1, 3), (2, 2)]);
+    }
+}
@@ -1,4 +1,8 @@
 // Database query operations
+use super::browse;
+use super::removed;
+use super::search;
+use super::sort_names;
 use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -46,6 +50,21 @@ pub struct Playlist {
     pub name: String,
     pub cover_url: Option<String>,
     pub created_at: Option<String>,
+    #[serde(default)]
+    pub kind: PlaylistKind,
+    /// The folder it's filed in, if any (see `playlist_folders`)
+    #[serde(default)]
+    pub folder_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistKind {
+    /// Tracks added by hand
+    #[default]
+    Static,
+    /// Filled by stored rules (see `smart_playlists`)
+    Smart,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,6 +75,16 @@ pub struct TrackInsert {
     pub album: Option<String>,
     pub track_number: Option<i32>,
     pub duration: Option<i32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    /// Sort tags (TSOT, TSOP, TSOA...), when the file has them
+    pub sort_title: Option<String>,
+    pub sort_artist: Option<String>,
+    pub sort_album: Option<String>,
+    /// Star rating from the file's tags (POPM, FMPS_RATING, RATING)
+    pub rating: Option<f64>,
+    /// Normalized by `matching::normalize_isrc`
+    pub isrc: Option<String>,
     pub album_art: Option<Vec<u8>>,
     pub track_cover: Option<Vec<u8>>,
     pub format: Option<String>,
@@ -94,6 +123,11 @@ pub fn insert_or_update_track(conn: &Connection, track: &TrackInsert) -> Result<
         )
         .ok();
 
+    let options = sort_names::load_options(conn)?;
+    let sort_title = sort_names::sort_name(track.sort_title.as_deref(), track.title.as_deref(), &options);
+    let sort_artist = sort_names::sort_name(track.sort_artist.as_deref(), track.artist.as_deref(), &options);
+    let sort_album = sort_names::sort_name(track.sort_album.as_deref(), track.album.as_deref(), &options);
+
     // First, handle album if present
     let album_id = if let Some(album_name) = &track.album {
         let artist = track.artist.as_deref();
@@ -101,6 +135,8 @@ pub fn insert_or_update_track(conn: &Connection, track: &TrackInsert) -> Result<
             conn,
             album_name,
             artist,
+            &sort_album,
+            &sort_artist,
             track.album_art.as_deref(),
         )?)
     } else {
@@ -123,7 +159,17 @@ pub fn insert_or_update_track(conn: &Connection, track: &TrackInsert) -> Result<
                 cover_url = ?10,
                 external_id = ?11,
                 content_hash = ?12,
-                local_src = ?13
+                local_src = ?13,
+                year = ?15,
+                genre = ?16,
+                sort_title = ?17,
+                sort_artist = ?18,
+                sort_album = ?19,
+                tag_sort_title = ?20,
+                tag_sort_artist = ?21,
+                tag_sort_album = ?22,
+                rating = COALESCE(rating, ?23),
+                isrc = ?24
              WHERE id = ?14",
             params![
                 track.title,
@@ -140,15 +186,28 @@ pub fn insert_or_update_track(conn: &Connection, track: &TrackInsert) -> Result<
                 track.content_hash,
                 track.local_src,
                 track_id,  // Use existing ID
+                track.year,
+                track.genre,
+                sort_title,
+                sort_artist,
+                sort_album,
+                track.sort_title,
+                track.sort_artist,
+                track.sort_album,
+                track.rating,
+                track.isrc,
             ],
         )?;
+        if let Some(album_id) = album_id {
+            removed::reinstate_album(conn, album_id)?;
+        }
         
         Ok((track_id, false))  // Return (existing_id, was_new = false)
     } else {
         // insert new track
         conn.execute(
-            "INSERT INTO tracks (path, title, artist, album, track_number, duration, album_id, format, bitrate, source_type, cover_url, external_id, content_hash, local_src)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            "INSERT INTO tracks (path, title, artist, album, track_number, duration, album_id, format, bitrate, source_type, cover_url, external_id, content_hash, local_src, year, genre, sort_title, sort_artist, sort_album, tag_sort_title, tag_sort_artist, tag_sort_album, rating, isrc, date_added)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, CURRENT_TIMESTAMP)",
             params![
                 track.path,
                 track.title,
@@ -164,10 +223,24 @@ pub fn insert_or_update_track(conn: &Connection, track: &TrackInsert) -> Result<
                 track.external_id,
                 track.content_hash,
                 track.local_src,
+                track.year,
+                track.genre,
+                sort_title,
+                sort_artist,
+                sort_album,
+                track.sort_title,
+                track.sort_artist,
+                track.sort_album,
+                track.rating,
+                track.isrc,
             ],
         )?;
+        let track_id = conn.last_insert_rowid();
+        if let Some(album_id) = album_id {
+            removed::reinstate_album(conn, album_id)?;
+        }
 
-        Ok((conn.last_insert_rowid(), true))  // Return (new_id, was_new = true)
+        Ok((track_id, true))  // Return (new_id, was_new = true)
     }
 }
 
@@ -181,6 +254,8 @@ fn get_or_create_album(
     conn: &Connection,
     name: &str,
     artist: Option<&str>,
+    sort_name: &str,
+    sort_artist: &str,
     art_data: Option<&[u8]>,
 ) -> Result<i64> {
     // Match by album name only to avoid splitting albums when tracks have different artists
@@ -196,8 +271,8 @@ fn get_or_create_album(
         // Update artist if not set yet
         if let Some(album_artist) = artist {
             conn.execute(
-                "UPDATE albums SET artist = ?1 WHERE id = ?2 AND artist IS NULL",
-                params![album_artist, id],
+                "UPDATE albums SET artist = ?1, sort_artist = ?2 WHERE id = ?3 AND artist IS NULL",
+                params![album_artist, sort_artist, id],
             )?;
         }
         return Ok(id);
@@ -205,8 +280,8 @@ fn get_or_create_album(
 
     // Create new album (without art_data, we'll save file separately)
     conn.execute(
-        "INSERT INTO albums (name, artist) VALUES (?1, ?2)",
-        params![name, artist],
+        "INSERT INTO albums (name, artist, sort_name, sort_artist) VALUES (?1, ?2, ?3, ?4)",
+        params![name, artist, sort_name, sort_artist],
     )?;
 
     Ok(conn.last_insert_rowid())
@@ -225,49 +300,172 @@ pub fn delete_album(conn: &Connection, album_id: i64) -> Result<bool> {
 
 // FTS5 SEARCH FUNCTIONS
 
-/// Initialize FTS5 virtual table for searching
-pub fn init_fts(conn: &Connection) -> Result<()> {
+/// Repopulate the search index from `tracks` (e.g. if it got out of sync)
+pub fn rebuild_search_index(conn: &Connection) -> Result<()> {
     conn.execute_batch(
-        "CREATE VIRTUAL TABLE IF NOT EXISTS tracks_fts USING fts5(
-            title, 
-            artist, 
-            album, 
-            content='tracks', 
-            content_rowid='id'
-        );
-
-        -- Trigger to keep FTS in sync with tracks
-        CREATE TRIGGER IF NOT EXISTS tracks_ai AFTER INSERT ON tracks BEGIN
-            INSERT INTO tracks_fts(rowid, title, artist, album) VALUES (new.id, new.title, new.artist, new.album);
-        END;
-        CREATE TRIGGER IF NOT EXISTS tracks_ad AFTER DELETE ON tracks BEGIN
-            INSERT INTO tracks_fts(tracks_fts, rowid, title, artist, album) VALUES('delete', old.id, old.title, old.artist, old.album);
-        END;
-        CREATE TRIGGER IF NOT EXISTS tracks_au AFTER UPDATE ON tracks BEGIN
-            INSERT INTO tracks_fts(tracks_fts, rowid, title, artist, album) VALUES('delete', old.id, old.title, old.artist, old.album);
-            INSERT INTO tracks_fts(rowid, title, artist, album) VALUES (new.id, new.title, new.artist, new.album);
-        END;"
-    )?;
-    Ok(())
+        "INSERT INTO tracks_fts(tracks_fts) VALUES('rebuild');
+         INSERT INTO tracks_fts(tracks_fts) VALUES('optimize');
+         INSERT INTO tracks_trigram(tracks_trigram) VALUES('rebuild');
+         INSERT INTO tracks_trigram(tracks_trigram) VALUES('optimize');",
+    )
 }
 
-/// Search tracks using FTS5
+/// Run a compiled search (see `search::compile_query`). Text matches are
+/// ranked by bm25; filter-only searches use the library order.
 pub fn search_tracks(
     conn: &Connection,
-    query: &str,
+    search: &search::CompiledSearch,
     limit: i32,
     offset: i32,
+) -> Result<Vec<Track>> {
+    let (source, mut values) = search.sql_source(&[]);
+    let rank = if search.rank_match.is_some() {
+        values.extend([search::TITLE_WEIGHT, search::ARTIST_WEIGHT, search::ALBUM_WEIGHT].map(Into::into));
+        "bm25(tracks_fts, ?, ?, ?), "
+    } else {
+        ""
+    };
+    values.extend([limit, offset].map(|n| rusqlite::types::Value::Integer(n as i64)));
+
+    let sql = format!(
+        "SELECT t.id, t.path, t.title, t.artist, t.album, t.track_number, t.duration, t.album_id, t.format, t.bitrate, t.source_type, t.cover_url, t.external_id, t.local_src, t.track_cover_path
+         {}
+         ORDER BY {}t.sort_artist COLLATE SORTNAME, t.sort_album COLLATE SORTNAME, COALESCE(t.track_number, 0), t.sort_title COLLATE SORTNAME
+         LIMIT ? OFFSET ?",
+        source, rank
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+
+    let tracks = stmt
+        .query_map(rusqlite::params_from_iter(values), |row| {
+            Ok(Track {
+                id: row.get(0)?,
+                path: row.get(1)?,
+                title: row.get(2)?,
+                artist: row.get(3)?,
+                album: row.get(4)?,
+                track_number: row.get(5)?,
+                duration: row.get(6)?,
+                album_id: row.get(7)?,
+                format: row.get(8)?,
+                bitrate: row.get(9)?,
+                source_type: row.get(10)?,
+                cover_url: row.get(11)?,
+                external_id: row.get(12)?,
+                local_src: row.get(13)?,
+                track_cover: None,
+                track_cover_path: row.get(14)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(tracks)
+}
+
+/// Number of tracks a compiled search matches
+pub fn count_search_tracks(conn: &Connection, search: &search::CompiledSearch) -> Result<i64> {
+    let (source, values) = search.sql_source(&[]);
+    conn.query_row(
+        &format!("SELECT COUNT(*) {}", source),
+        rusqlite::params_from_iter(values),
+        |row| row.get(0),
+    )
+}
+
+/// An artist with the number of their tracks that matched a search
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtistMatch {
+    pub name: String,
+    pub track_count: i64,
+}
+
+/// Artists of the tracks a search matches, most matching tracks first,
+/// plus the total number of such artists
+pub fn search_artists(
+    conn: &Connection,
+    search: &search::CompiledSearch,
+    limit: i32,
+) -> Result<(Vec<ArtistMatch>, i64)> {
+    let (source, mut values) = search.sql_source(&["t.artist IS NOT NULL"]);
+    let total = conn.query_row(
+        &format!("SELECT COUNT(DISTINCT t.artist) {}", source),
+        rusqlite::params_from_iter(values.iter()),
+        |row| row.get(0),
+    )?;
+
+    values.push(limit.into());
+    let mut stmt = conn.prepare(&format!(
+        "SELECT t.artist, COUNT(*) AS n {} GROUP BY t.artist ORDER BY n DESC, MIN(t.sort_artist) COLLATE SORTNAME LIMIT ?",
+        source
+    ))?;
+    let artists = stmt
+        .query_map(rusqlite::params_from_iter(values), |row| {
+            Ok(ArtistMatch {
+                name: row.get(0)?,
+                track_count: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok((artists, total))
+}
+
+/// Albums of the tracks a search matches, most matching tracks first,
+/// plus the total number of such albums
+pub fn search_albums(
+    conn: &Connection,
+    search: &search::CompiledSearch,
+    limit: i32,
+) -> Result<(Vec<AlbumMatch>, i64)> {
+    let (source, mut values) = search.sql_source(&["t.album_id IS NOT NULL"]);
+    let total = conn.query_row(
+        &format!("SELECT COUNT(DISTINCT t.album_id) {}", source),
+        rusqlite::params_from_iter(values.iter()),
+        |row| row.get(0),
+    )?;
+
+    values.push(limit.into());
+    let mut stmt = conn.prepare(&format!(
+        "SELECT a.id, a.name, a.artist, a.art_path, COUNT(*) AS n
+         FROM albums a
+         JOIN (SELECT t.album_id {}) m ON m.album_id = a.id
+         GROUP BY a.id
+         ORDER BY n DESC, a.sort_name COLLATE SORTNAME
+         LIMIT ?",
+        source
+    ))?;
+    let albums = stmt
+        .query_map(rusqlite::params_from_iter(values), |row| {
+            Ok(AlbumMatch {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                artist: row.get(2)?,
+                art_path: row.get(3)?,
+                track_count: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok((albums, total))
+}
+
+/// Tracks sharing the most trigrams with `trigram_match` (an FTS5 OR of
+/// quoted trigrams); candidates for fuzzy matching
+pub fn fuzzy_track_candidates(
+    conn: &Connection,
+    trigram_match: &str,
+    limit: i32,
 ) -> Result<Vec<Track>> {
     let mut stmt = conn.prepare(
-        "SELECT id, path, title, artist, album, track_number, duration, album_id, format, bitrate, source_type, cover_url, external_id, local_src, track_cover_path 
-         FROM tracks 
-         WHERE id IN (SELECT rowid FROM tracks_fts WHERE tracks_fts MATCH ?1)
-         ORDER BY artist, album, track_number, title
-         LIMIT ?2 OFFSET ?3",
+        "SELECT t.id, t.path, t.title, t.artist, t.album, t.track_number, t.duration, t.album_id, t.format, t.bitrate, t.source_type, t.cover_url, t.external_id, t.local_src, t.track_cover_path
+         FROM tracks_trigram
+         JOIN tracks t ON t.id = tracks_trigram.rowid
+         WHERE tracks_trigram MATCH ?1 AND t.removed_at IS NULL AND t.source_type IS NOT 'placeholder'
+         ORDER BY rank
+         LIMIT ?2",
     )?;
 
     let tracks = stmt
-        .query_map(params![query, limit, offset], |row| {
+        .query_map(params![trigram_match, limit], |row| {
             Ok(Track {
                 id: row.get(0)?,
                 path: row.get(1)?,
@@ -292,12 +490,75 @@ pub fn search_tracks(
     Ok(tracks)
 }
 
+/// Artists with tracks matching `fts_match`, with their track counts, most tracks first
+pub fn suggest_artists(conn: &Connection, fts_match: &str, limit: i32) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.artist, COUNT(*) AS n
+         FROM tracks_fts
+         JOIN tracks t ON t.id = tracks_fts.rowid
+         WHERE tracks_fts MATCH ?1 AND t.artist IS NOT NULL AND t.removed_at IS NULL AND t.source_type IS NOT 'placeholder'
+         GROUP BY t.artist
+         ORDER BY n DESC, MIN(t.sort_artist) COLLATE SORTNAME
+         LIMIT ?2",
+    )?;
+    let artists = stmt
+        .query_map(params![fts_match, limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(artists)
+}
+
+/// An album with the number of its tracks that matched a search
+#[derive(Debug, Clone, Serialize)]
+pub struct AlbumMatch {
+    pub id: i64,
+    pub name: String,
+    pub artist: Option<String>,
+    pub art_path: Option<String>,
+    pub track_count: i64,
+}
+
+/// Albums with tracks matching `fts_match`, most matching tracks first
+pub fn suggest_albums(conn: &Connection, fts_match: &str, limit: i32) -> Result<Vec<AlbumMatch>> {
+    let mut stmt = conn.prepare(
+        "SELECT a.id, a.name, a.artist, a.art_path, COUNT(*) AS n
+         FROM tracks_fts
+         JOIN tracks t ON t.id = tracks_fts.rowid
+         JOIN albums a ON a.id = t.album_id
+         WHERE tracks_fts MATCH ?1 AND t.removed_at IS NULL AND t.source_type IS NOT 'placeholder'
+         GROUP BY a.id
+         ORDER BY n DESC, a.sort_name COLLATE SORTNAME
+         LIMIT ?2",
+    )?;
+    let albums = stmt
+        .query_map(params![fts_match, limit], |row| {
+            Ok(AlbumMatch {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                artist: row.get(2)?,
+                art_path: row.get(3)?,
+                track_count: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(albums)
+}
+
+/// Every playlist's id and name (cheap: no track data)
+pub fn get_playlist_names(conn: &Connection) -> Result<Vec<(i64, String)>> {
+    let mut stmt = conn.prepare("SELECT id, name FROM playlists WHERE deleted_at IS NULL ORDER BY name COLLATE SORTNAME")?;
+    let playlists = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(playlists)
+}
+
 /// Get paginated tracks
 pub fn get_tracks_paginated(conn: &Connection, limit: i32, offset: i32) -> Result<Vec<Track>> {
     let mut stmt = conn.prepare(
         "SELECT id, path, title, artist, album, track_number, duration, album_id, format, bitrate, source_type, cover_url, external_id, local_src, track_cover_path 
          FROM tracks 
-         ORDER BY artist, album, track_number, title
+         WHERE removed_at IS NULL AND source_type IS NOT 'placeholder'
+         ORDER BY sort_artist COLLATE SORTNAME, sort_album COLLATE SORTNAME, COALESCE(track_number, 0), sort_title COLLATE SORTNAME
          LIMIT ?1 OFFSET ?2",
     )?;
 
@@ -334,7 +595,7 @@ pub fn get_all_tracks(conn: &Connection) -> Result<Vec<Track>> {
 
     let mut stmt = conn.prepare(
         "SELECT id, path, title, artist, album, track_number, duration, album_id, format, bitrate, source_type, cover_url, external_id, local_src, track_cover, track_cover_path 
-         FROM tracks ORDER BY artist, album, track_number, title",
+         FROM tracks WHERE removed_at IS NULL AND source_type IS NOT 'placeholder' ORDER BY sort_artist COLLATE SORTNAME, sort_album COLLATE SORTNAME, COALESCE(track_number, 0), sort_title COLLATE SORTNAME",
     )?;
 
     let prepare_time = query_start.elapsed();
@@ -383,7 +644,7 @@ pub fn get_all_tracks_lightweight(conn: &Connection) -> Result<Vec<Track>> {
 
     let mut stmt = conn.prepare(
         "SELECT id, path, title, artist, album, track_number, duration, album_id, format, bitrate, source_type, cover_url, external_id, local_src 
-         FROM tracks ORDER BY artist, album, track_number, title",
+         FROM tracks WHERE removed_at IS NULL AND source_type IS NOT 'placeholder' ORDER BY sort_artist COLLATE SORTNAME, sort_album COLLATE SORTNAME, COALESCE(track_number, 0), sort_title COLLATE SORTNAME",
     )?;
 
     let prepare_time = query_start.elapsed();
@@ -436,7 +697,7 @@ pub fn get_all_tracks_with_paths(conn: &Connection) -> Result<Vec<Track>> {
 
     let mut stmt = conn.prepare(
         "SELECT id, path, title, artist, album, track_number, duration, album_id, format, bitrate, source_type, cover_url, external_id, local_src, track_cover_path 
-         FROM tracks ORDER BY artist, album, track_number, title",
+         FROM tracks WHERE removed_at IS NULL AND source_type IS NOT 'placeholder' ORDER BY sort_artist COLLATE SORTNAME, sort_album COLLATE SORTNAME, COALESCE(track_number, 0), sort_title COLLATE SORTNAME",
     )?;
 
     let tracks = stmt
@@ -541,7 +802,7 @@ pub fn get_all_albums(conn: &Connection) -> Result<Vec<Album>> {
     let query_start = Instant::now();
 
     let mut stmt = conn
-        .prepare("SELECT id, name, artist, art_data, art_path FROM albums ORDER BY artist, name")?;
+        .prepare("SELECT id, name, artist, art_data, art_path FROM albums WHERE removed_at IS NULL ORDER BY sort_artist COLLATE SORTNAME, sort_name COLLATE SORTNAME")?;
 
     let albums = stmt
         .query_map([], |row| {
@@ -569,7 +830,7 @@ pub fn get_all_albums(conn: &Connection) -> Result<Vec<Album>> {
 pub fn get_all_albums_lightweight(conn: &Connection) -> Result<Vec<Album>> {
     let query_start = Instant::now();
 
-    let mut stmt = conn.prepare("SELECT id, name, artist FROM albums ORDER BY artist, name")?;
+    let mut stmt = conn.prepare("SELECT id, name, artist FROM albums WHERE removed_at IS NULL ORDER BY sort_artist COLLATE SORTNAME, sort_name COLLATE SORTNAME")?;
 
     let albums = stmt
         .query_map([], |row| {
@@ -598,7 +859,7 @@ pub fn get_all_albums_with_paths(conn: &Connection) -> Result<Vec<Album>> {
     let query_start = Instant::now();
 
     let mut stmt =
-        conn.prepare("SELECT id, name, artist, art_path FROM albums ORDER BY artist, name")?;
+        conn.prepare("SELECT id, name, artist, art_path FROM albums WHERE removed_at IS NULL ORDER BY sort_artist COLLATE SORTNAME, sort_name COLLATE SORTNAME")?;
 
     let albums = stmt
         .query_map([], |row| {
@@ -628,7 +889,8 @@ pub fn get_albums_paginated(conn: &Connection, limit: i32, offset: i32) -> Resul
 
     let mut stmt = conn.prepare(
         "SELECT id, name, artist, art_path FROM albums 
-         ORDER BY artist, name
+         WHERE removed_at IS NULL
+         ORDER BY sort_artist COLLATE SORTNAME, sort_name COLLATE SORTNAME
          LIMIT ?1 OFFSET ?2"
     )?;
 
@@ -662,9 +924,9 @@ pub fn get_all_artists(conn: &Connection) -> Result<Vec<Artist>> {
     let mut stmt = conn.prepare(
         "SELECT artist, COUNT(*) as track_count, COUNT(DISTINCT album) as album_count 
          FROM tracks 
-         WHERE artist IS NOT NULL 
+         WHERE artist IS NOT NULL AND removed_at IS NULL AND source_type IS NOT 'placeholder'
          GROUP BY artist 
-         ORDER BY artist",
+         ORDER BY MIN(sort_artist) COLLATE SORTNAME, artist",
     )?;
 
     let artists = stmt
@@ -690,7 +952,7 @@ pub fn get_all_artists(conn: &Connection) -> Result<Vec<Artist>> {
 pub fn get_tracks_by_album(conn: &Connection, album_id: i64) -> Result<Vec<Track>> {
     let mut stmt = conn.prepare(
         "SELECT id, path, title, artist, album, track_number, duration, album_id, format, bitrate, source_type, cover_url, external_id, local_src, track_cover, track_cover_path 
-         FROM tracks WHERE album_id = ?1 ORDER BY track_number, title",
+         FROM tracks WHERE album_id = ?1 AND removed_at IS NULL AND source_type IS NOT 'placeholder' ORDER BY track_number, sort_title COLLATE SORTNAME",
     )?;
 
     let tracks = stmt
@@ -722,7 +984,7 @@ pub fn get_tracks_by_album(conn: &Connection, album_id: i64) -> Result<Vec<Track
 pub fn get_tracks_by_artist(conn: &Connection, artist: &str) -> Result<Vec<Track>> {
     let mut stmt = conn.prepare(
         "SELECT id, path, title, artist, album, track_number, duration, album_id, format, bitrate, source_type, cover_url, external_id, local_src, track_cover, track_cover_path 
-         FROM tracks WHERE artist = ?1 ORDER BY album, track_number, title",
+         FROM tracks WHERE artist = ?1 AND removed_at IS NULL AND source_type IS NOT 'placeholder' ORDER BY sort_album COLLATE SORTNAME, track_number, sort_title COLLATE SORTNAME",
     )?;
 
     let tracks = stmt
@@ -776,7 +1038,7 @@ pub fn create_playlist(conn: &Connection, name: &str) -> Result<i64> {
 
 pub fn get_all_playlists(conn: &Connection) -> Result<Vec<Playlist>> {
     let mut stmt =
-        conn.prepare("SELECT id, name, cover_url, created_at FROM playlists ORDER BY name")?;
+        conn.prepare("SELECT id, name, cover_url, created_at, smart_rules IS NOT NULL, folder_id FROM playlists WHERE deleted_at IS NULL ORDER BY name COLLATE SORTNAME")?;
 
     let playlists = stmt
         .query_map([], |row| {
@@ -785,6 +1047,12 @@ pub fn get_all_playlists(conn: &Connection) -> Result<Vec<Playlist>> {
                 name: row.get(1)?,
                 cover_url: row.get(2)?,
                 created_at: row.get(3)?,
+                kind: if row.get(4)? {
+                    PlaylistKind::Smart
+                } else {
+                    PlaylistKind::Static
+                },
+                folder_id: row.get(5)?,
             })
         })?
         .collect::<Result<Vec<_>>>()?;
@@ -797,8 +1065,8 @@ pub fn get_playlist_tracks(conn: &Connection, playlist_id: i64) -> Result<Vec<Tr
         "SELECT t.id, t.path, t.title, t.artist, t.album, t.track_number, t.duration, t.album_id, t.format, t.bitrate, t.source_type, t.cover_url, t.external_id, t.local_src, t.track_cover, t.track_cover_path 
          FROM tracks t
          INNER JOIN playlist_tracks pt ON t.id = pt.track_id
-         WHERE pt.playlist_id = ?1
-         ORDER BY pt.position",
+         WHERE pt.playlist_id = ?1 AND t.removed_at IS NULL
+         ORDER BY pt.position, pt.id",
     )?;
 
     let tracks = stmt
@@ -827,33 +1095,6 @@ pub fn get_playlist_tracks(conn: &Connection, playlist_id: i64) -> Result<Vec<Tr
     Ok(tracks)
 }
 
-pub fn add_track_to_playlist(conn: &Connection, playlist_id: i64, track_id: i64) -> Result<()> {
-    let position: i32 = conn.query_row(
-        "SELECT COALESCE(MAX(position), 0) + 1 FROM playlist_tracks WHERE playlist_id = ?1",
-        [playlist_id],
-        |row| row.get(0),
-    )?;
-
-    conn.execute(
-        "INSERT OR IGNORE INTO playlist_tracks (playlist_id, track_id, position) VALUES (?1, ?2, ?3)",
-        params![playlist_id, track_id, position],
-    )?;
-
-    Ok(())
-}
-
-pub fn remove_track_from_playlist(
-    conn: &Connection,
-    playlist_id: i64,
-    track_id: i64,
-) -> Result<()> {
-    conn.execute(
-        "DELETE FROM playlist_tracks WHERE playlist_id = ?1 AND track_id = ?2",
-        params![playlist_id, track_id],
-    )?;
-    Ok(())
-}
-
 pub fn delete_playlist(conn: &Connection, playlist_id: i64) -> Result<()> {
     conn.execute("DELETE FROM playlists WHERE id = ?1", [playlist_id])?;
     Ok(())
@@ -983,3 +1224,237 @@ pub fn update_track_cover_url(
     )?;
     Ok(())
 }
+
+// Track analysis operations
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackAnalysis {
+    pub track_id: i64,
+    pub verdict: String,
+    pub confidence: f64,
+    pub cutoff_hz: Option<f64>,
+    pub sample_rate: Option<i64>,
+    pub bits_per_sample: Option<i64>,
+    pub reasons: Vec<String>,
+    pub spectrogram_path: Option<String>,
+    pub analyzed_at: Option<String>,
+}
+
+fn row_to_track_analysis(row: &rusqlite::Row) -> Result<TrackAnalysis> {
+    let reasons: Option<String> = row.get(6)?;
+    Ok(TrackAnalysis {
+        track_id: row.get(0)?,
+        verdict: row.get(1)?,
+        confidence: row.get(2)?,
+        cutoff_hz: row.get(3)?,
+        sample_rate: row.get(4)?,
+        bits_per_sample: row.get(5)?,
+        reasons: reasons
+            .and_then(|r| serde_json::from_str(&r).ok())
+            .unwrap_or_default(),
+        spectrogram_path: row.get(7)?,
+        analyzed_at: row.get(8)?,
+    })
+}
+
+pub fn save_track_analysis(conn: &Connection, analysis: &TrackAnalysis) -> Result<()> {
+    let reasons = serde_json::to_string(&analysis.reasons).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "INSERT OR REPLACE INTO track_analysis
+            (track_id, verdict, confidence, cutoff_hz, sample_rate, bits_per_sample, reasons, spectrogram_path, analyzed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, CURRENT_TIMESTAMP)",
+        params![
+            analysis.track_id,
+            analysis.verdict,
+            analysis.confidence,
+            analysis.cutoff_hz,
+            analysis.sample_rate,
+            analysis.bits_per_sample,
+            reasons,
+            analysis.spectrogram_path,
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_track_analysis(conn: &Connection, track_id: i64) -> Result<Option<TrackAnalysis>> {
+    conn.query_row(
+        "SELECT track_id, verdict, confidence, cutoff_hz, sample_rate, bits_per_sample, reasons, spectrogram_path, analyzed_at
+         FROM track_analysis WHERE track_id = ?1",
+        [track_id],
+        row_to_track_analysis,
+    )
+    .optional()
+}
+
+/// Tracks whose analysis flagged them as transcoded or upsampled, most certain first
+pub fn get_suspect_analyses(conn: &Connection, min_confidence: f64) -> Result<Vec<TrackAnalysis>> {
+    let mut stmt = conn.prepare(
+        "SELECT track_id, verdict, confidence, cutoff_hz, sample_rate, bits_per_sample, reasons, spectrogram_path, analyzed_at
+         FROM track_analysis
+         WHERE verdict IN ('lossy_transcode', 'upsampled') AND confidence >= ?1
+         ORDER BY confidence DESC",
+    )?;
+
+    let analyses = stmt
+        .query_map([min_confidence], row_to_track_analysis)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(analyses)
+}
+
+// Verification operations
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationResult {
+    pub track_id: i64,
+    pub path: String,
+    pub status: String,
+    pub decode_error_count: i64,
+    pub errors: Vec<String>,
+    pub expected_frames: Option<i64>,
+    pub decoded_frames: Option<i64>,
+    pub checksum_ok: Option<bool>,
+    pub verified_at: Option<String>,
+}
+
+fn row_to_verification_result(row: &rusqlite::Row) -> Result<VerificationResult> {
+    let errors: Option<String> = row.get(4)?;
+    Ok(VerificationResult {
+        track_id: row.get(0)?,
+        path: row.get(1)?,
+        status: row.get(2)?,
+        decode_error_count: row.get(3)?,
+        errors: errors
+            .and_then(|e| serde_json::from_str(&e).ok())
+            .unwrap_or_default(),
+        expected_frames: row.get(5)?,
+        decoded_frames: row.get(6)?,
+        checksum_ok: row.get(7)?,
+        verified_at: row.get(8)?,
+    })
+}
+
+/// Local tracks to verify, optionally limited to the given folders
+pub fn get_local_tracks_for_verification(
+    conn: &Connection,
+    folders: &[String],
+) -> Result<Vec<(i64, String)>> {
+    let mut query = String::from(
+        "SELECT id, path FROM tracks
+         WHERE (source_type IS NULL OR source_type = 'local') AND removed_at IS NULL",
+    );
+    if !folders.is_empty() {
+        let conditions: Vec<String> = (1..=folders.len())
+            .map(|i| format!("path LIKE ?{} ESCAPE '\\'", i))
+            .collect();
+        query.push_str(&format!(" AND ({})", conditions.join(" OR ")));
+    }
+    query.push_str(" ORDER BY path");
+
+    let params: Vec<String> = folders
+        .iter()
+        .map(|f| browse::folder_pattern(f))
+        .collect();
+    let mut stmt = conn.prepare(&query)?;
+    let tracks = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(tracks)
+}
+
+/// Store the latest verification result and stamp the track's last_verified time
+pub fn save_verification_result(conn: &Connection, result: &VerificationResult) -> Result<()> {
+    let errors = serde_json::to_string(&result.errors).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "INSERT OR REPLACE INTO verification_results
+            (track_id, status, decode_error_count, errors, expected_frames, decoded_frames, checksum_ok, verified_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, CURRENT_TIMESTAMP)",
+        params![
+            result.track_id,
+            result.status,
+            result.decode_error_count,
+            errors,
+            result.expected_frames,
+            result.decoded_frames,
+            result.checksum_ok,
+        ],
+    )?;
+    conn.execute(
+        "UPDATE tracks SET last_verified = CURRENT_TIMESTAMP WHERE id = ?1",
+        [result.track_id],
+    )?;
+    Ok(())
+}
+
+pub fn get_verification_results(
+    conn: &Connection,
+    problems_only: bool,
+) -> Result<Vec<VerificationResult>> {
+    let mut stmt = conn.prepare(
+        "SELECT v.track_id, t.path, v.status, v.decode_error_count, v.errors,
+                v.expected_frames, v.decoded_frames, v.checksum_ok, v.verified_at
+         FROM verification_results v
+         JOIN tracks t ON t.id = v.track_id
+         WHERE ?1 = 0 OR v.status != 'ok'
+         ORDER BY v.status = 'ok', t.path COLLATE SORTNAME",
+    )?;
+
+    let results = stmt
+        .query_map([problems_only], row_to_verification_result)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(results)
+}
+
+// Settings
+
+pub fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row("SELECT value FROM settings WHERE key = ?1", params![key], |row| row.get(0))
+        .optional()
+}
+
+pub fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    #[test]
+    fn verification_folders_stop_at_the_separator() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (id, path) VALUES
+                (1, '/music/rock/a.flac'), (2, '/music/rockabilly/b.flac'),
+                (3, '/music/50%_off/c.flac'), (4, '/music/50x_off/d.flac');
+            ",
+        )
+        .unwrap();
+
+        let ids = |folders: &[&str]| -> Vec<i64> {
+            let folders: Vec<String> = folders.iter().map(|f| f.to_string()).collect();
+            get_local_tracks_for_verification(&conn, &folders)
+                .unwrap()
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect()
+        };
+        assert_eq!(ids(&["/music/rock"]), vec![1]);
+        assert_eq!(ids(&["/music/rock/"]), vec![1]);
+        assert_eq!(ids(&["/music/50%_off"]), vec![3]);
+        assert_eq!(ids(&["/music/rockabilly", "/music/50x_off"]), vec![4, 2]);
+    }
+}
@@ -0,0 +1,222 @@
+// Star ratings and loved flags for tracks and albums
+use super::queries;
+use crate::error::AudionError;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+
+const OPTIONS_KEY: &str = "rating_options";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RatingOptions {
+    /// Also save track rating changes into the files' tags
+    pub write_to_files: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackRating {
+    pub track_id: i64,
+    /// 0-5 stars in half steps; `None` when unrated
+    pub rating: Option<f64>,
+    pub loved: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AlbumRating {
+    pub album_id: i64,
+    pub rating: Option<f64>,
+    pub loved: bool,
+}
+
+pub fn load_options(conn: &Connection) -> Result<RatingOptions> {
+    Ok(queries::get_setting(conn, OPTIONS_KEY)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+pub fn save_options(conn: &Connection, options: &RatingOptions) -> Result<()> {
+    let json = serde_json::to_string(options)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    queries::set_setting(conn, OPTIONS_KEY, &json)
+}
+
+fn check_rating(rating: Option<f64>) -> Result<(), AudionError> {
+    match rating {
+        Some(stars) if !(0.0..=5.0).contains(&stars) || (stars * 2.0).fract() != 0.0 => {
+            Err(AudionError::InvalidInput(format!(
+                "Rating must be 0 to 5 stars in half steps, got {}",
+                stars
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn not_found(what: &str, id: i64) -> AudionError {
+    AudionError::NotFound(format!("{} {} not found", what, id))
+}
+
+pub fn set_track_rating(
+    conn: &Connection,
+    track_id: i64,
+    rating: Option<f64>,
+) -> Result<(), AudionError> {
+    check_rating(rating)?;
+    match conn.execute(
+        "UPDATE tracks SET rating = ?2 WHERE id = ?1",
+        params![track_id, rating],
+    )? {
+        0 => Err(not_found("Track", track_id)),
+        _ => Ok(()),
+    }
+}
+
+pub fn set_track_loved(conn: &Connection, track_id: i64, loved: bool) -> Result<(), AudionError> {
+    match conn.execute(
+        "UPDATE tracks SET loved = ?2 WHERE id = ?1",
+        params![track_id, loved],
+    )? {
+        0 => Err(not_found("Track", track_id)),
+        _ => Ok(()),
+    }
+}
+
+pub fn set_album_rating(
+    conn: &Connection,
+    album_id: i64,
+    rating: Option<f64>,
+) -> Result<(), AudionError> {
+    check_rating(rating)?;
+    match conn.execute(
+        "UPDATE albums SET rating = ?2 WHERE id = ?1",
+        params![album_id, rating],
+    )? {
+        0 => Err(not_found("Album", album_id)),
+        _ => Ok(()),
+    }
+}
+
+pub fn set_album_loved(conn: &Connection, album_id: i64, loved: bool) -> Result<(), AudionError> {
+    match conn.execute(
+        "UPDATE albums SET loved = ?2 WHERE id = ?1",
+        params![album_id, loved],
+    )? {
+        0 => Err(not_found("Album", album_id)),
+        _ => Ok(()),
+    }
+}
+
+pub fn get_track_ratings(conn: &Connection, track_ids: &[i64]) -> Result<Vec<TrackRating>> {
+    if track_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; track_ids.len()].join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, rating, loved FROM tracks WHERE id IN ({})",
+        placeholders
+    ))?;
+    let ratings = stmt
+        .query_map(rusqlite::params_from_iter(track_ids), |row| {
+            Ok(TrackRating {
+                track_id: row.get(0)?,
+                rating: row.get(1)?,
+                loved: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ratings)
+}
+
+pub fn get_album_ratings(conn: &Connection, album_ids: &[i64]) -> Result<Vec<AlbumRating>> {
+    if album_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; album_ids.len()].join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, rating, loved FROM albums WHERE id IN ({})",
+        placeholders
+    ))?;
+    let ratings = stmt
+        .query_map(rusqlite::params_from_iter(album_ids), |row| {
+            Ok(AlbumRating {
+                album_id: row.get(0)?,
+                rating: row.get(1)?,
+                loved: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ratings)
+}
+
+/// Path of a track whose file can take a rating tag: local files only
+pub fn local_file_path(conn: &Connection, track_id: i64) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT path FROM tracks WHERE id = ?1 AND COALESCE(source_type, 'local') = 'local'",
+        [track_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    #[test]
+    fn ratings_are_validated_and_stored() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO albums (id, name) VALUES (1, 'OK Computer');
+            INSERT INTO tracks (id, path, album_id) VALUES (1, '/m/1.flac', 1), (2, 'tidal://2', NULL);
+            UPDATE tracks SET source_type = 'tidal' WHERE id = 2;
+            ",
+        )
+        .unwrap();
+
+        set_track_rating(&conn, 1, Some(3.5)).unwrap();
+        set_track_loved(&conn, 2, true).unwrap();
+        assert!(matches!(
+            set_track_rating(&conn, 1, Some(3.2)),
+            Err(AudionError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            set_track_rating(&conn, 1, Some(5.5)),
+            Err(AudionError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            set_track_loved(&conn, 9, true),
+            Err(AudionError::NotFound(_))
+        ));
+
+        let ratings: Vec<_> = get_track_ratings(&conn, &[1, 2])
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.track_id, r.rating, r.loved))
+            .collect();
+        assert_eq!(ratings, vec![(1, Some(3.5), false), (2, None, true)]);
+
+        set_album_rating(&conn, 1, Some(5.0)).unwrap();
+        set_album_loved(&conn, 1, true).unwrap();
+        let album = get_album_ratings(&conn, &[1]).unwrap().remove(0);
+        assert_eq!((album.rating, album.loved), (Some(5.0), true));
+
+        assert_eq!(
+            local_file_path(&conn, 1).unwrap().as_deref(),
+            Some("/m/1.flac")
+        );
+        assert_eq!(local_file_path(&conn, 2).unwrap(), None);
+
+        assert!(!load_options(&conn).unwrap().write_to_files);
+        save_options(
+            &conn,
+            &RatingOptions {
+                write_to_files: true,
+            },
+        )
+        .unwrap();
+        assert!(load_options(&conn).unwrap().write_to_files);
+    }
+}
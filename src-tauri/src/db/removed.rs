@@ -0,0 +1,469 @@
+// Recently removed: tracks and albums taken out of the library are only
+// marked with `removed_at`, keeping their playlist entries, labels and stats,
+// until they're restored or the retention period runs out. Their files are
+// left alone; trashing a file is a separate command.
+//
+// An album is removed with its last track still in the library, and comes
+// back with any of its tracks. Restoring an album brings back the tracks
+// removed along with it, i.e. at the same moment.
+use super::queries::{self, Track};
+use crate::error::AudionError;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+
+const OPTIONS_KEY: &str = "removed_items_options";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemovedOptions {
+    /// How many days a removed track or album can be restored for
+    pub retention_days: u32,
+}
+
+impl Default for RemovedOptions {
+    fn default() -> Self {
+        RemovedOptions { retention_days: 30 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LibraryItem {
+    Track,
+    Album,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RemovedTrack {
+    #[serde(flatten)]
+    pub track: Track,
+    pub removed_at: String,
+    /// When it's deleted for good
+    pub expires_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RemovedAlbum {
+    pub id: i64,
+    pub name: String,
+    pub artist: Option<String>,
+    pub art_path: Option<String>,
+    /// Tracks removed along with the album
+    pub track_count: i64,
+    pub removed_at: String,
+    pub expires_at: String,
+}
+
+/// Everything that can still be restored, most recently removed first.
+/// Tracks removed along with their album are only listed under it.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemovedItems {
+    pub albums: Vec<RemovedAlbum>,
+    pub tracks: Vec<RemovedTrack>,
+}
+
+pub fn load_options(conn: &Connection) -> Result<RemovedOptions> {
+    Ok(queries::get_setting(conn, OPTIONS_KEY)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+pub fn save_options(conn: &Connection, options: &RemovedOptions) -> Result<()> {
+    let json = serde_json::to_string(options)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    queries::set_setting(conn, OPTIONS_KEY, &json)
+}
+
+// The retention period as a datetime() modifier
+fn retention(conn: &Connection) -> Result<String> {
+    Ok(format!("{} days", load_options(conn)?.retention_days))
+}
+
+// One timestamp for everything removed in one go, so what went together can
+// be told apart from what was removed before
+fn now(conn: &Connection) -> Result<String> {
+    conn.query_row("SELECT datetime('now')", [], |row| row.get(0))
+}
+
+fn not_removed(item: LibraryItem, id: i64) -> AudionError {
+    match item {
+        LibraryItem::Track => {
+            AudionError::NotFound(format!("Track {} isn't in recently removed", id))
+        }
+        LibraryItem::Album => {
+            AudionError::NotFound(format!("Album {} isn't in recently removed", id))
+        }
+    }
+}
+
+/// Permanently delete tracks and albums that have been in recently removed
+/// for longer than the retention period. Their cover files go with the next
+/// orphaned cover cleanup. Returns how many tracks went.
+pub fn purge_expired(conn: &Connection) -> Result<usize> {
+    let retention = retention(conn)?;
+    let tracks = conn.execute(
+        "DELETE FROM tracks
+         WHERE removed_at IS NOT NULL AND removed_at <= datetime('now', '-' || ?1)",
+        [&retention],
+    )?;
+    conn.execute(
+        "DELETE FROM albums
+         WHERE removed_at IS NOT NULL AND removed_at <= datetime('now', '-' || ?1)
+           AND NOT EXISTS (SELECT 1 FROM tracks WHERE album_id = albums.id)",
+        [&retention],
+    )?;
+    Ok(tracks)
+}
+
+/// Bring back a removed album that has a track in the library again, e.g.
+/// after a scan filed a new file under it
+pub fn reinstate_album(conn: &Connection, album_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE albums SET removed_at = NULL
+         WHERE id = ?1 AND removed_at IS NOT NULL
+           AND EXISTS (SELECT 1 FROM tracks WHERE album_id = ?1 AND removed_at IS NULL)",
+        [album_id],
+    )?;
+    Ok(())
+}
+
+/// Take a track out of the library, and its album too if it was the last
+/// track left in it
+pub fn remove_track(conn: &Connection, track_id: i64) -> Result<(), AudionError> {
+    let now = now(conn)?;
+    let updated = conn.execute(
+        "UPDATE tracks SET removed_at = ?1 WHERE id = ?2 AND removed_at IS NULL",
+        params![now, track_id],
+    )?;
+    if updated == 0 {
+        return Err(AudionError::NotFound(format!(
+            "Track {} not found",
+            track_id
+        )));
+    }
+    conn.execute(
+        "UPDATE albums SET removed_at = ?1
+         WHERE id = (SELECT album_id FROM tracks WHERE id = ?2) AND removed_at IS NULL
+           AND NOT EXISTS (SELECT 1 FROM tracks WHERE album_id = albums.id AND removed_at IS NULL)",
+        params![now, track_id],
+    )?;
+    purge_expired(conn)?;
+    Ok(())
+}
+
+/// Take an album and all of its tracks out of the library
+pub fn remove_album(conn: &Connection, album_id: i64) -> Result<(), AudionError> {
+    let now = now(conn)?;
+    let updated = conn.execute(
+        "UPDATE albums SET removed_at = ?1 WHERE id = ?2 AND removed_at IS NULL",
+        params![now, album_id],
+    )?;
+    if updated == 0 {
+        return Err(AudionError::NotFound(format!(
+            "Album {} not found",
+            album_id
+        )));
+    }
+    conn.execute(
+        "UPDATE tracks SET removed_at = ?1 WHERE album_id = ?2 AND removed_at IS NULL",
+        params![now, album_id],
+    )?;
+    purge_expired(conn)?;
+    Ok(())
+}
+
+pub fn removed(conn: &Connection) -> Result<RemovedItems> {
+    let retention = retention(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT a.id, a.name, a.artist, a.art_path,
+                (SELECT COUNT(*) FROM tracks t WHERE t.album_id = a.id AND t.removed_at = a.removed_at),
+                a.removed_at, datetime(a.removed_at, '+' || ?1)
+         FROM albums a
+         WHERE a.removed_at IS NOT NULL AND a.removed_at > datetime('now', '-' || ?1)
+         ORDER BY a.removed_at DESC, a.id DESC",
+    )?;
+    let albums = stmt
+        .query_map([&retention], |row| {
+            Ok(RemovedAlbum {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                artist: row.get(2)?,
+                art_path: row.get(3)?,
+                track_count: row.get(4)?,
+                removed_at: row.get(5)?,
+                expires_at: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.path, t.title, t.artist, t.album, t.track_number, t.duration, t.album_id, t.format, t.bitrate, t.source_type, t.cover_url, t.external_id, t.local_src, t.track_cover_path,
+                t.removed_at, datetime(t.removed_at, '+' || ?1)
+         FROM tracks t
+         LEFT JOIN albums a ON a.id = t.album_id
+         WHERE t.removed_at IS NOT NULL AND t.removed_at > datetime('now', '-' || ?1)
+           AND t.removed_at IS NOT a.removed_at
+         ORDER BY t.removed_at DESC, t.id DESC",
+    )?;
+    let tracks = stmt
+        .query_map([&retention], |row| {
+            Ok(RemovedTrack {
+                track: Track {
+                    id: row.get(0)?,
+                    path: row.get(1)?,
+                    title: row.get(2)?,
+                    artist: row.get(3)?,
+                    album: row.get(4)?,
+                    track_number: row.get(5)?,
+                    duration: row.get(6)?,
+                    album_id: row.get(7)?,
+                    format: row.get(8)?,
+                    bitrate: row.get(9)?,
+                    source_type: row.get(10)?,
+                    cover_url: row.get(11)?,
+                    external_id: row.get(12)?,
+                    local_src: row.get(13)?,
+                    track_cover: None,
+                    track_cover_path: row.get(14)?,
+                },
+                removed_at: row.get(15)?,
+                expires_at: row.get(16)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(RemovedItems { albums, tracks })
+}
+
+/// Put a track back in the library, along with its album if that was removed
+pub fn restore_track(conn: &Connection, track_id: i64) -> Result<(), AudionError> {
+    purge_expired(conn)?;
+    let updated = conn.execute(
+        "UPDATE tracks SET removed_at = NULL WHERE id = ?1 AND removed_at IS NOT NULL",
+        [track_id],
+    )?;
+    if updated == 0 {
+        return Err(not_removed(LibraryItem::Track, track_id));
+    }
+    conn.execute(
+        "UPDATE albums SET removed_at = NULL
+         WHERE id = (SELECT album_id FROM tracks WHERE id = ?1) AND removed_at IS NOT NULL",
+        [track_id],
+    )?;
+    Ok(())
+}
+
+/// Put an album back in the library with the tracks removed along with it
+pub fn restore_album(conn: &Connection, album_id: i64) -> Result<(), AudionError> {
+    purge_expired(conn)?;
+    let removed_at: String = conn
+        .query_row(
+            "SELECT removed_at FROM albums WHERE id = ?1 AND removed_at IS NOT NULL",
+            [album_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| not_removed(LibraryItem::Album, album_id))?;
+    conn.execute(
+        "UPDATE tracks SET removed_at = NULL WHERE album_id = ?1 AND removed_at = ?2",
+        params![album_id, removed_at],
+    )?;
+    conn.execute(
+        "UPDATE albums SET removed_at = NULL WHERE id = ?1",
+        [album_id],
+    )?;
+    Ok(())
+}
+
+/// Delete a track or album (with all its tracks) from recently removed right
+/// away, playlist entries and stats included
+pub fn delete_permanently(
+    conn: &Connection,
+    item: LibraryItem,
+    id: i64,
+) -> Result<(), AudionError> {
+    let table = match item {
+        LibraryItem::Track => "tracks",
+        LibraryItem::Album => "albums",
+    };
+    let removed = conn
+        .query_row(
+            &format!(
+                "SELECT 1 FROM {} WHERE id = ?1 AND removed_at IS NOT NULL",
+                table
+            ),
+            [id],
+            |_| Ok(()),
+        )
+        .optional()?;
+    if removed.is_none() {
+        return Err(not_removed(item, id));
+    }
+    match item {
+        LibraryItem::Track => {
+            queries::delete_track(conn, id)?;
+            // Its album may have been waiting on it
+            conn.execute(
+                "DELETE FROM albums WHERE removed_at IS NOT NULL
+                   AND NOT EXISTS (SELECT 1 FROM tracks WHERE album_id = albums.id)",
+                [],
+            )?;
+        }
+        LibraryItem::Album => {
+            queries::delete_album(conn, id)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{playlists, schema};
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO albums (id, name) VALUES (1, 'Live'), (2, 'Demos');
+            INSERT INTO tracks (id, path, title, album_id, play_count) VALUES
+                (1, '/m/1.flac', 'One', 1, 7), (2, '/m/2.flac', 'Two', 1, 0),
+                (3, '/m/3.flac', 'Three', 2, 0), (4, '/m/4.flac', 'Four', NULL, 0);
+            INSERT INTO playlists (id, name) VALUES (1, 'Mix');
+            INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES
+                (1, 4, 0), (1, 1, 1), (1, 3, 2);
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn visible(conn: &Connection) -> (Vec<i64>, Vec<i64>) {
+        let mut tracks: Vec<i64> = queries::get_all_tracks(conn)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        let mut albums: Vec<i64> = queries::get_all_albums(conn)
+            .unwrap()
+            .into_iter()
+            .map(|a| a.id)
+            .collect();
+        tracks.sort();
+        albums.sort();
+        (tracks, albums)
+    }
+
+    fn playlist(conn: &Connection) -> Vec<i64> {
+        playlists::get_entries(conn, 1)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.track.id)
+            .collect()
+    }
+
+    // Backdate everything removed, as if it happened `days` ago
+    fn age(conn: &Connection, days: u32) {
+        for table in ["tracks", "albums"] {
+            conn.execute(
+                &format!(
+                    "UPDATE {} SET removed_at = datetime(removed_at, ?1) WHERE removed_at IS NOT NULL",
+                    table
+                ),
+                [format!("-{} days", days)],
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn removed_items_keep_their_place_until_restored() {
+        let conn = library();
+        remove_track(&conn, 1).unwrap();
+        assert_eq!(visible(&conn).1, vec![1, 2]);
+        age(&conn, 1);
+        remove_album(&conn, 1).unwrap();
+        remove_album(&conn, 2).unwrap();
+        assert_eq!(visible(&conn), (vec![4], vec![]));
+        assert_eq!(playlist(&conn), vec![4]);
+        assert!(matches!(
+            remove_track(&conn, 1),
+            Err(AudionError::NotFound(_))
+        ));
+
+        // Track 1 went before its album, so it's listed on its own
+        let items = removed(&conn).unwrap();
+        let albums: Vec<(i64, i64)> = items.albums.iter().map(|a| (a.id, a.track_count)).collect();
+        assert_eq!(albums.len(), 2);
+        assert!(albums.contains(&(1, 1)) && albums.contains(&(2, 1)));
+        let tracks: Vec<i64> = items.tracks.iter().map(|t| t.track.id).collect();
+        assert_eq!(tracks, vec![1]);
+
+        restore_album(&conn, 1).unwrap();
+        assert_eq!(visible(&conn), (vec![2, 4], vec![1]));
+        // Restoring a track brings its album back, and its stats are intact
+        restore_track(&conn, 3).unwrap();
+        restore_track(&conn, 1).unwrap();
+        assert_eq!(playlist(&conn), vec![4, 1, 3]);
+        let plays: i64 = conn
+            .query_row("SELECT play_count FROM tracks WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(plays, 7);
+        assert!(matches!(
+            restore_album(&conn, 2),
+            Err(AudionError::NotFound(_))
+        ));
+
+        // The last track of an album takes the album with it
+        remove_track(&conn, 3).unwrap();
+        assert_eq!(visible(&conn).1, vec![1]);
+    }
+
+    #[test]
+    fn removed_tracks_are_not_verified() {
+        let conn = library();
+        remove_track(&conn, 2).unwrap();
+        remove_album(&conn, 2).unwrap();
+
+        let paths: Vec<String> = queries::get_local_tracks_for_verification(&conn, &[])
+            .unwrap()
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect();
+        assert_eq!(paths, vec!["/m/1.flac", "/m/4.flac"]);
+    }
+
+    #[test]
+    fn expired_items_are_purged() {
+        let conn = library();
+        save_options(&conn, &RemovedOptions { retention_days: 7 }).unwrap();
+        remove_album(&conn, 2).unwrap();
+        remove_track(&conn, 4).unwrap();
+        age(&conn, 8);
+        remove_track(&conn, 2).unwrap();
+
+        let count = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        assert_eq!((count("tracks"), count("albums")), (2, 1));
+        assert_eq!(playlist(&conn), vec![1]);
+        assert!(matches!(
+            restore_track(&conn, 4),
+            Err(AudionError::NotFound(_))
+        ));
+
+        delete_permanently(&conn, LibraryItem::Track, 2).unwrap();
+        assert!(matches!(
+            delete_permanently(&conn, LibraryItem::Track, 1),
+            Err(AudionError::NotFound(_))
+        ));
+        assert_eq!(count("tracks"), 1);
+        assert_eq!(removed(&conn).unwrap().tracks.len(), 0);
+    }
+}
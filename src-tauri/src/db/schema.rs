@@ -1,11 +1,264 @@
-// Database schema initialization
-use rusqlite::{Connection, Result};
+// Database schema initialization and versioned migrations
+use super::sort_names;
+use rusqlite::{Connection, Result, Transaction};
+use std::path::{Path, PathBuf};
 
-pub fn init_schema(conn: &Connection) -> Result<()> {
+/// A single schema change. Steps run in order, each inside its own transaction
+/// that also bumps `PRAGMA user_version`, so a failed step leaves the database
+/// exactly as it was before that step.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> Result<()>,
+}
+
+/// Every schema change, oldest first. Never edit a released step; add a new one.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "base library schema",
+        up: migrate_base_schema,
+    },
+    Migration {
+        version: 2,
+        description: "track analysis results",
+        up: migrate_track_analysis,
+    },
+    Migration {
+        version: 3,
+        description: "library verification results",
+        up: migrate_verification_results,
+    },
+    Migration {
+        version: 4,
+        description: "diacritic-insensitive search index",
+        up: migrate_search_index,
+    },
+    Migration {
+        version: 5,
+        description: "year, genre and date added",
+        up: migrate_search_fields,
+    },
+    Migration {
+        version: 6,
+        description: "trigram index for fuzzy search",
+        up: migrate_fuzzy_index,
+    },
+    Migration {
+        version: 7,
+        description: "indexes for sorted library pages",
+        up: migrate_browse_indexes,
+    },
+    Migration {
+        version: 8,
+        description: "sort names and settings",
+        up: migrate_sort_names,
+    },
+    Migration {
+        version: 9,
+        description: "play history",
+        up: migrate_play_history,
+    },
+    Migration {
+        version: 10,
+        description: "ratings and loved flags",
+        up: migrate_ratings,
+    },
+    Migration {
+        version: 11,
+        description: "smart playlists",
+        up: migrate_smart_playlists,
+    },
+    Migration {
+        version: 12,
+        description: "playlist entries",
+        up: migrate_playlist_entries,
+    },
+    Migration {
+        version: 13,
+        description: "isrc codes",
+        up: migrate_isrc,
+    },
+    Migration {
+        version: 14,
+        description: "playlist folders",
+        up: migrate_playlist_folders,
+    },
+    Migration {
+        version: 15,
+        description: "playlist history and recently deleted",
+        up: migrate_playlist_history,
+    },
+    Migration {
+        version: 16,
+        description: "saved play queue",
+        up: migrate_play_queue,
+    },
+    Migration {
+        version: 17,
+        description: "labels",
+        up: migrate_labels,
+    },
+    Migration {
+        version: 18,
+        description: "recently removed tracks and albums",
+        up: migrate_removed_items,
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Bring the database up to the latest schema version.
+///
+/// When an existing database needs migrating, a copy is first written next to
+/// `db_path` (if given). Errors are returned as user-facing messages.
+pub fn init_schema(
+    conn: &mut Connection,
+    db_path: Option<&Path>,
+) -> std::result::Result<(), String> {
     // Enable foreign keys for this connection
-    conn.execute("PRAGMA foreign_keys = ON;", [])?;
+    conn.execute("PRAGMA foreign_keys = ON;", [])
+        .map_err(|e| format!("Failed to enable foreign keys: {}", e))?;
+    sort_names::register_collation(conn)
+        .map_err(|e| format!("Failed to register collation: {}", e))?;
+
+    let current =
+        schema_version(conn).map_err(|e| format!("Failed to read schema version: {}", e))?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(format!(
+            "Your library database is at schema version {}, but this version of Audion only supports up to version {}. Please update Audion.",
+            current, latest
+        ));
+    }
+
+    if current < latest {
+        let backup_path = match db_path {
+            Some(path) if has_tables(conn).map_err(|e| e.to_string())? => {
+                Some(backup_database(conn, path, current)?)
+            }
+            _ => None,
+        };
+
+        run_migrations(conn, MIGRATIONS).map_err(|e| match &backup_path {
+            Some(backup) => format!(
+                "{} A backup of your library was saved to {}",
+                e,
+                backup.display()
+            ),
+            None => e,
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Apply every step newer than the current `user_version`
+fn run_migrations(
+    conn: &mut Connection,
+    migrations: &[Migration],
+) -> std::result::Result<(), String> {
+    let current =
+        schema_version(conn).map_err(|e| format!("Failed to read schema version: {}", e))?;
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let fail = |e: rusqlite::Error| {
+            format!(
+                "Database migration to version {} ({}) failed: {}.",
+                migration.version, migration.description, e
+            )
+        };
+
+        let tx = conn.transaction().map_err(fail)?;
+        (migration.up)(&tx).map_err(fail)?;
+        tx.pragma_update(None, "user_version", migration.version)
+            .map_err(fail)?;
+        tx.commit().map_err(fail)?;
+
+        println!(
+            "[DB] Migrated schema to version {} ({})",
+            migration.version, migration.description
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether the database already holds any tables (i.e. isn't a brand new file)
+fn has_tables(conn: &Connection) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Write a consistent copy of the database next to it before migrating
+fn backup_database(
+    conn: &Connection,
+    db_path: &Path,
+    version: u32,
+) -> std::result::Result<PathBuf, String> {
+    let file_name = db_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "rlist".to_string());
+    let backup_path = db_path.with_file_name(format!("{}.v{}.backup.db", file_name, version));
+
+    // VACUUM INTO refuses to overwrite an existing file
+    if backup_path.exists() {
+        std::fs::remove_file(&backup_path)
+            .map_err(|e| format!("Failed to replace old database backup: {}", e))?;
+    }
+
+    conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])
+        .map_err(|e| format!("Failed to back up database before migrating: {}", e))?;
+
+    println!(
+        "[DB] Backed up schema version {} to {:?}",
+        version, backup_path
+    );
+    Ok(backup_path)
+}
+
+/// Add a column unless it's already there. Only for the base schema step, which
+/// has to cope with every layout that existed before versioning was introduced.
+fn add_column_if_missing(
+    tx: &Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let exists: bool = tx.query_row(
+        &format!(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1)",
+            table
+        ),
+        [column],
+        |row| row.get(0),
+    )?;
 
-    conn.execute_batch(
+    if !exists {
+        tx.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+// Version 1: the layout as of the last unversioned release. Databases created
+// before versioning may be missing any of the later columns, so those are added
+// conditionally here; every later step can assume this exact layout.
+fn migrate_base_schema(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
         "
         -- Albums table
         CREATE TABLE IF NOT EXISTS albums (
@@ -62,91 +315,770 @@ pub fn init_schema(conn: &Connection) -> Result<()> {
             path TEXT UNIQUE NOT NULL,
             last_scanned TEXT DEFAULT CURRENT_TIMESTAMP
         );
+        ",
+    )?;
+
+    // Columns added over time to pre-versioning databases
+    add_column_if_missing(tx, "tracks", "format", "TEXT")?;
+    add_column_if_missing(tx, "tracks", "bitrate", "INTEGER")?;
+    add_column_if_missing(tx, "tracks", "source_type", "TEXT DEFAULT 'local'")?;
+    add_column_if_missing(tx, "tracks", "cover_url", "TEXT")?;
+    add_column_if_missing(tx, "tracks", "external_id", "TEXT")?;
+    add_column_if_missing(tx, "tracks", "content_hash", "TEXT")?;
+    add_column_if_missing(tx, "tracks", "local_src", "TEXT")?;
+    add_column_if_missing(tx, "tracks", "track_cover", "TEXT")?;
+    add_column_if_missing(tx, "tracks", "track_cover_path", "TEXT")?;
+    add_column_if_missing(tx, "albums", "art_path", "TEXT")?;
+    add_column_if_missing(tx, "playlists", "cover_url", "TEXT")?;
 
+    tx.execute_batch(
+        "
         -- Composite index
         -- This single index covers: ORDER BY artist, album, track_number, title
         CREATE INDEX IF NOT EXISTS idx_tracks_sort ON tracks(artist, album, track_number, title);
-        
 
-        -- Create indexes for faster queries (except content_hash which needs migration first)
         CREATE INDEX IF NOT EXISTS idx_tracks_artist ON tracks(artist);
         CREATE INDEX IF NOT EXISTS idx_tracks_album ON tracks(album);
         CREATE INDEX IF NOT EXISTS idx_tracks_album_id ON tracks(album_id);
+        CREATE INDEX IF NOT EXISTS idx_tracks_content_hash ON tracks(content_hash);
         ",
-    )?;
+    )
+}
 
-    // Migrations for existing databases
-    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN format TEXT", []);
-    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN bitrate INTEGER", []);
-    let _ = conn.execute(
-        "ALTER TABLE tracks ADD COLUMN source_type TEXT DEFAULT 'local'",
-        [],
-    );
-    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN cover_url TEXT", []);
-    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN external_id TEXT", []);
-    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN content_hash TEXT", []);
-    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN local_src TEXT", []);
-    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN track_cover TEXT", []);
-
-    // Add path columns for file-based cover storage
-    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN track_cover_path TEXT", []);
-    let _ = conn.execute("ALTER TABLE albums ADD COLUMN art_path TEXT", []);
-
-    // Create index for content_hash after migration ensures column exists
-    let _ = conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_tracks_content_hash ON tracks(content_hash)",
-        [],
-    );
+// Version 2: spectral analysis results (transcode / upsampling detection)
+fn migrate_track_analysis(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE track_analysis (
+            track_id INTEGER PRIMARY KEY,
+            verdict TEXT NOT NULL,
+            confidence REAL NOT NULL,
+            cutoff_hz REAL,
+            sample_rate INTEGER,
+            bits_per_sample INTEGER,
+            reasons TEXT,
+            spectrogram_path TEXT,
+            analyzed_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (track_id) REFERENCES tracks(id) ON DELETE CASCADE
+        );
+        ",
+    )
+}
+
+// Version 3: full-decode integrity check results (latest run per track)
+fn migrate_verification_results(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE verification_results (
+            track_id INTEGER PRIMARY KEY,
+            status TEXT NOT NULL,
+            decode_error_count INTEGER NOT NULL DEFAULT 0,
+            errors TEXT,
+            expected_frames INTEGER,
+            decoded_frames INTEGER,
+            checksum_ok INTEGER,
+            verified_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (track_id) REFERENCES tracks(id) ON DELETE CASCADE
+        );
 
-    // Add cover_url to playlists table for existing databases
-    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN cover_url TEXT", []);
+        -- Per-track timestamp of the last full-decode verification
+        ALTER TABLE tracks ADD COLUMN last_verified TEXT;
+        ",
+    )
+}
 
-    // Initialize playlist positions for existing playlists
-    initialize_playlist_positions(conn)?;
+// Version 4: (re)build the FTS5 search index with diacritics folding and prefix
+// indexes. Databases from before FTS existed, or with the old `get_library`-created
+// index, are repopulated from `tracks`.
+fn migrate_search_index(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        DROP TRIGGER IF EXISTS tracks_ai;
+        DROP TRIGGER IF EXISTS tracks_ad;
+        DROP TRIGGER IF EXISTS tracks_au;
+        DROP TABLE IF EXISTS tracks_fts;
 
-    Ok(())
+        CREATE VIRTUAL TABLE tracks_fts USING fts5(
+            title,
+            artist,
+            album,
+            content='tracks',
+            content_rowid='id',
+            tokenize='unicode61 remove_diacritics 2',
+            prefix='2 3'
+        );
+
+        -- Keep the index in sync with tracks
+        CREATE TRIGGER tracks_ai AFTER INSERT ON tracks BEGIN
+            INSERT INTO tracks_fts(rowid, title, artist, album) VALUES (new.id, new.title, new.artist, new.album);
+        END;
+        CREATE TRIGGER tracks_ad AFTER DELETE ON tracks BEGIN
+            INSERT INTO tracks_fts(tracks_fts, rowid, title, artist, album) VALUES('delete', old.id, old.title, old.artist, old.album);
+        END;
+        CREATE TRIGGER tracks_au AFTER UPDATE OF title, artist, album ON tracks BEGIN
+            INSERT INTO tracks_fts(tracks_fts, rowid, title, artist, album) VALUES('delete', old.id, old.title, old.artist, old.album);
+            INSERT INTO tracks_fts(rowid, title, artist, album) VALUES (new.id, new.title, new.artist, new.album);
+        END;
+
+        INSERT INTO tracks_fts(tracks_fts) VALUES('rebuild');
+        ",
+    )
+}
+
+// Version 5: fields the search query language filters on
+fn migrate_search_fields(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE tracks ADD COLUMN year INTEGER;
+        ALTER TABLE tracks ADD COLUMN genre TEXT;
+        ALTER TABLE tracks ADD COLUMN date_added TEXT;
+
+        -- The real date is unknown for existing tracks; start counting from now
+        UPDATE tracks SET date_added = CURRENT_TIMESTAMP;
+        ",
+    )
+}
+
+// Version 6: trigram index over the same columns as tracks_fts. It finds
+// candidates for typo-tolerant matching ("radiohaed"), which are then scored in Rust.
+fn migrate_fuzzy_index(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE VIRTUAL TABLE tracks_trigram USING fts5(
+            title,
+            artist,
+            album,
+            content='tracks',
+            content_rowid='id',
+            tokenize='trigram remove_diacritics 1'
+        );
+
+        CREATE TRIGGER tracks_trigram_ai AFTER INSERT ON tracks BEGIN
+            INSERT INTO tracks_trigram(rowid, title, artist, album) VALUES (new.id, new.title, new.artist, new.album);
+        END;
+        CREATE TRIGGER tracks_trigram_ad AFTER DELETE ON tracks BEGIN
+            INSERT INTO tracks_trigram(tracks_trigram, rowid, title, artist, album) VALUES('delete', old.id, old.title, old.artist, old.album);
+        END;
+        CREATE TRIGGER tracks_trigram_au AFTER UPDATE OF title, artist, album ON tracks BEGIN
+            INSERT INTO tracks_trigram(tracks_trigram, rowid, title, artist, album) VALUES('delete', old.id, old.title, old.artist, old.album);
+            INSERT INTO tracks_trigram(rowid, title, artist, album) VALUES (new.id, new.title, new.artist, new.album);
+        END;
+
+        INSERT INTO tracks_trigram(tracks_trigram) VALUES('rebuild');
+        ",
+    )
 }
 
-/// Initialize positions for playlists that don't have them
-/// Safe to run multiple times - only affects playlists with NULL positions
-fn initialize_playlist_positions(conn: &Connection) -> Result<()> {
-    use rusqlite::params;
+// Version 7: one index per sort order in db/browse.rs. The expressions must
+// match the ORDER BY there exactly; the rowid at the end of every index breaks ties.
+fn migrate_browse_indexes(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE INDEX idx_tracks_by_artist ON tracks(COALESCE(artist, ''), COALESCE(album, ''), COALESCE(track_number, 0), COALESCE(title, ''));
+        CREATE INDEX idx_tracks_by_album ON tracks(COALESCE(album, ''), COALESCE(album_id, 0), COALESCE(track_number, 0), COALESCE(title, ''));
+        CREATE INDEX idx_tracks_by_title ON tracks(COALESCE(title, ''));
+        CREATE INDEX idx_tracks_by_date_added ON tracks(COALESCE(date_added, ''));
+        CREATE INDEX idx_tracks_by_duration ON tracks(COALESCE(duration, 0));
+        CREATE INDEX idx_tracks_by_bitrate ON tracks(COALESCE(bitrate, 0));
+        CREATE INDEX idx_tracks_by_year ON tracks(COALESCE(year, 0));
+        CREATE INDEX idx_albums_by_artist ON albums(COALESCE(artist, ''), name);
+        CREATE INDEX idx_albums_by_name ON albums(name);
+        ",
+    )
+}
+
+// Version 8: sort names (from sort tags, or generated without a leading
+// article) compared with the SORTNAME collation, and a key/value settings table
+fn migrate_sort_names(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+
+        ALTER TABLE tracks ADD COLUMN sort_title TEXT NOT NULL DEFAULT '';
+        ALTER TABLE tracks ADD COLUMN sort_artist TEXT NOT NULL DEFAULT '';
+        ALTER TABLE tracks ADD COLUMN sort_album TEXT NOT NULL DEFAULT '';
+        ALTER TABLE tracks ADD COLUMN tag_sort_title TEXT;
+        ALTER TABLE tracks ADD COLUMN tag_sort_artist TEXT;
+        ALTER TABLE tracks ADD COLUMN tag_sort_album TEXT;
+        ALTER TABLE albums ADD COLUMN sort_name TEXT NOT NULL DEFAULT '';
+        ALTER TABLE albums ADD COLUMN sort_artist TEXT NOT NULL DEFAULT '';
+        ",
+    )?;
 
-    // Get all unique playlist IDs that have tracks without positions
-    let mut stmt = conn.prepare(
-        "SELECT DISTINCT playlist_id 
-         FROM playlist_tracks 
-         WHERE position IS NULL",
+    // Sort tags are picked up on the next rescan, so names are generated with
+    // the default articles. A copy of sort_names::generate as it was at v8.
+    let generate = |name: Option<String>| -> String {
+        let name = name.unwrap_or_default();
+        let name = name.trim();
+        if let Some((first, rest)) = name.split_once(char::is_whitespace) {
+            let rest = rest.trim_start();
+            if !rest.is_empty() && ["the", "a", "an"].contains(&first.to_lowercase().as_str()) {
+                return rest.to_string();
+            }
+        }
+        name.to_string()
+    };
+    let tracks: Vec<(i64, String, String, String)> = tx
+        .prepare("SELECT id, title, artist, album FROM tracks")?
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                generate(row.get(1)?),
+                generate(row.get(2)?),
+                generate(row.get(3)?),
+            ))
+        })?
+        .collect::<Result<_>>()?;
+    let mut update = tx.prepare(
+        "UPDATE tracks SET sort_title = ?2, sort_artist = ?3, sort_album = ?4 WHERE id = ?1",
     )?;
+    for (id, title, artist, album) in tracks {
+        update.execute(rusqlite::params![id, title, artist, album])?;
+    }
 
-    let playlist_ids: Vec<i64> = stmt
-        .query_map([], |row| row.get(0))?
-        .collect::<Result<Vec<_>>>()?;
-
-    // For each playlist, assign sequential positions
-    for playlist_id in playlist_ids {
-        // Get all track_ids in this playlist (in insertion order via rowid)
-        let mut track_stmt = conn.prepare(
-            "SELECT track_id 
-             FROM playlist_tracks 
-             WHERE playlist_id = ?1 
-             ORDER BY rowid",
-        )?;
+    let albums: Vec<(i64, String, String)> = tx
+        .prepare("SELECT id, name, artist FROM albums")?
+        .query_map([], |row| {
+            Ok((row.get(0)?, generate(row.get(1)?), generate(row.get(2)?)))
+        })?
+        .collect::<Result<_>>()?;
+    let mut update =
+        tx.prepare("UPDATE albums SET sort_name = ?2, sort_artist = ?3 WHERE id = ?1")?;
+    for (id, name, artist) in albums {
+        update.execute(rusqlite::params![id, name, artist])?;
+    }
+
+    tx.execute_batch(
+        "
+        DROP INDEX IF EXISTS idx_tracks_sort;
+        DROP INDEX idx_tracks_by_artist;
+        DROP INDEX idx_tracks_by_album;
+        DROP INDEX idx_tracks_by_title;
+        DROP INDEX idx_albums_by_artist;
+        DROP INDEX idx_albums_by_name;
+
+        CREATE INDEX idx_tracks_by_artist ON tracks(sort_artist COLLATE SORTNAME, sort_album COLLATE SORTNAME, COALESCE(track_number, 0), sort_title COLLATE SORTNAME);
+        CREATE INDEX idx_tracks_by_album ON tracks(sort_album COLLATE SORTNAME, COALESCE(album_id, 0), COALESCE(track_number, 0), sort_title COLLATE SORTNAME);
+        CREATE INDEX idx_tracks_by_title ON tracks(sort_title COLLATE SORTNAME);
+        CREATE INDEX idx_albums_by_artist ON albums(sort_artist COLLATE SORTNAME, sort_name COLLATE SORTNAME);
+        CREATE INDEX idx_albums_by_name ON albums(sort_name COLLATE SORTNAME);
+        ",
+    )
+}
+
+// Version 9: one row per playback. Title, artist and album are copied in so
+// history survives for streamed tracks that were never (or are no longer) in
+// the library. Triggers keep the per-track counters in sync, also when
+// events are deleted.
+fn migrate_play_history(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE play_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            track_id INTEGER REFERENCES tracks(id) ON DELETE SET NULL,
+            title TEXT,
+            artist TEXT,
+            album TEXT,
+            track_source TEXT,
+            external_id TEXT,
+            started_at TEXT NOT NULL,
+            listened_ms INTEGER NOT NULL DEFAULT 0,
+            completed INTEGER NOT NULL DEFAULT 0,
+            skipped INTEGER NOT NULL DEFAULT 0,
+            counted INTEGER NOT NULL DEFAULT 0,
+            source TEXT,
+            source_id TEXT
+        );
+        CREATE INDEX idx_play_events_started_at ON play_events(started_at);
+        CREATE INDEX idx_play_events_track ON play_events(track_id, started_at);
+
+        ALTER TABLE tracks ADD COLUMN play_count INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE tracks ADD COLUMN skip_count INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE tracks ADD COLUMN last_played TEXT;
+        CREATE INDEX idx_tracks_by_play_count ON tracks(play_count);
+        CREATE INDEX idx_tracks_by_last_played ON tracks(COALESCE(last_played, ''));
 
-        let track_ids: Vec<i64> = track_stmt
-            .query_map(params![playlist_id], |row| row.get(0))?
-            .collect::<Result<Vec<_>>>()?;
-
-        // Assign sequential positions
-        for (pos, track_id) in track_ids.iter().enumerate() {
-            conn.execute(
-                "UPDATE playlist_tracks 
-                 SET position = ?1 
-                 WHERE playlist_id = ?2 AND track_id = ?3",
-                params![pos as i64, playlist_id, track_id],
-            )?;
+        CREATE TRIGGER play_events_ai AFTER INSERT ON play_events WHEN new.track_id IS NOT NULL BEGIN
+            UPDATE tracks SET
+                play_count = play_count + new.counted,
+                skip_count = skip_count + new.skipped,
+                last_played = MAX(COALESCE(last_played, ''), new.started_at)
+            WHERE id = new.track_id;
+        END;
+        CREATE TRIGGER play_events_ad AFTER DELETE ON play_events WHEN old.track_id IS NOT NULL BEGIN
+            UPDATE tracks SET
+                play_count = play_count - old.counted,
+                skip_count = skip_count - old.skipped,
+                last_played = (SELECT MAX(started_at) FROM play_events WHERE track_id = old.track_id)
+            WHERE id = old.track_id;
+        END;
+        ",
+    )
+}
+
+// Version 10: star ratings (0-5 in half steps, NULL when unrated) and a loved
+// flag on tracks and albums
+fn migrate_ratings(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE tracks ADD COLUMN rating REAL;
+        ALTER TABLE tracks ADD COLUMN loved INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE albums ADD COLUMN rating REAL;
+        ALTER TABLE albums ADD COLUMN loved INTEGER NOT NULL DEFAULT 0;
+        CREATE INDEX idx_tracks_by_rating ON tracks(COALESCE(rating, -1));
+        CREATE INDEX idx_tracks_loved ON tracks(loved) WHERE loved = 1;
+        ",
+    )
+}
+
+// Version 11: smart playlists keep their rules as JSON; their tracks are the
+// result of the last evaluation
+fn migrate_smart_playlists(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE playlists ADD COLUMN smart_rules TEXT;
+        ALTER TABLE playlists ADD COLUMN smart_evaluated_at TEXT;
+        ",
+    )
+}
+
+// Version 12: playlist rows become entries with their own id, so a track can be
+// added more than once. Positions are renumbered 0..n-1 in the existing order;
+// rows from before positions existed (NULL) keep insertion order at the end.
+fn migrate_playlist_entries(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE playlist_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            playlist_id INTEGER NOT NULL,
+            track_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            FOREIGN KEY (playlist_id) REFERENCES playlists(id) ON DELETE CASCADE,
+            FOREIGN KEY (track_id) REFERENCES tracks(id) ON DELETE CASCADE
+        );
+
+        INSERT INTO playlist_entries (playlist_id, track_id, position)
+        SELECT playlist_id, track_id,
+               ROW_NUMBER() OVER (
+                   PARTITION BY playlist_id ORDER BY position IS NULL, position, rowid
+               ) - 1
+        FROM playlist_tracks
+        ORDER BY playlist_id, position IS NULL, position, rowid;
+
+        DROP TABLE playlist_tracks;
+        ALTER TABLE playlist_entries RENAME TO playlist_tracks;
+
+        CREATE INDEX idx_playlist_tracks_position ON playlist_tracks(playlist_id, position);
+        CREATE INDEX idx_playlist_tracks_track ON playlist_tracks(track_id);
+        ",
+    )
+}
+
+// Version 13: ISRC codes from tags, for matching lists from streaming services.
+// Existing tracks get theirs on the next rescan.
+fn migrate_isrc(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE tracks ADD COLUMN isrc TEXT;
+        CREATE INDEX idx_tracks_isrc ON tracks(isrc) WHERE isrc IS NOT NULL;
+        ",
+    )
+}
+
+// Version 14: playlists can be filed in nested folders. Folders and playlists
+// each have a position among their siblings; existing playlists start at the
+// top level in name order, and new ones are put at the end of their folder.
+fn migrate_playlist_folders(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE playlist_folders (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            parent_id INTEGER,
+            position INTEGER NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (parent_id) REFERENCES playlist_folders(id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_playlist_folders_parent ON playlist_folders(parent_id, position);
+
+        ALTER TABLE playlists ADD COLUMN folder_id INTEGER
+            REFERENCES playlist_folders(id) ON DELETE SET NULL;
+        ALTER TABLE playlists ADD COLUMN position INTEGER;
+        UPDATE playlists SET position = (
+            SELECT ranked.position FROM (
+                SELECT id, ROW_NUMBER() OVER (ORDER BY name COLLATE SORTNAME, id) - 1 AS position
+                FROM playlists
+            ) ranked
+            WHERE ranked.id = playlists.id
+        );
+        CREATE INDEX idx_playlists_folder ON playlists(folder_id, position);
+
+        CREATE TRIGGER playlists_position_ai AFTER INSERT ON playlists WHEN new.position IS NULL BEGIN
+            UPDATE playlists SET position = (
+                SELECT COALESCE(MAX(position) + 1, 0) FROM playlists
+                WHERE folder_id IS new.folder_id AND id != new.id
+            )
+            WHERE id = new.id;
+        END;
+        ",
+    )
+}
+
+// Version 15: deleted playlists are kept (with their tracks) until the
+// retention period runs out, and edits to a playlist are journaled as
+// snapshots of its name and entries so they can be undone
+fn migrate_playlist_history(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE playlists ADD COLUMN deleted_at TEXT;
+        CREATE INDEX idx_playlists_deleted ON playlists(deleted_at) WHERE deleted_at IS NOT NULL;
+
+        CREATE TABLE playlist_journal (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            playlist_id INTEGER NOT NULL,
+            change TEXT NOT NULL,
+            name TEXT NOT NULL,
+            entries TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_playlist_journal_playlist ON playlist_journal(playlist_id, id);
+        ",
+    )
+}
+
+// Version 16: the play queue from the last session. Queue rows keep their
+// saved position when a track is deleted, so the state row's indexes can be
+// mapped onto what's left.
+fn migrate_play_queue(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE play_queue (
+            position INTEGER PRIMARY KEY,
+            track_id INTEGER NOT NULL,
+            FOREIGN KEY (track_id) REFERENCES tracks(id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE play_queue_state (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            current_index INTEGER NOT NULL,
+            position_secs REAL NOT NULL,
+            shuffle INTEGER NOT NULL,
+            shuffle_order TEXT NOT NULL,
+            shuffle_index INTEGER NOT NULL,
+            repeat_mode TEXT NOT NULL,
+            user_queue_count INTEGER NOT NULL,
+            saved_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+        ",
+    )
+}
+
+// Version 17: user labels ("workout", "needs retag"...), each linkable to any
+// number of tracks and albums. A track also carries its album's labels, but
+// that's worked out when querying, not stored.
+fn migrate_labels(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE labels (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            color TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE track_labels (
+            track_id INTEGER NOT NULL,
+            label_id INTEGER NOT NULL,
+            PRIMARY KEY (track_id, label_id),
+            FOREIGN KEY (track_id) REFERENCES tracks(id) ON DELETE CASCADE,
+            FOREIGN KEY (label_id) REFERENCES labels(id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_track_labels_label ON track_labels(label_id);
+
+        CREATE TABLE album_labels (
+            album_id INTEGER NOT NULL,
+            label_id INTEGER NOT NULL,
+            PRIMARY KEY (album_id, label_id),
+            FOREIGN KEY (album_id) REFERENCES albums(id) ON DELETE CASCADE,
+            FOREIGN KEY (label_id) REFERENCES labels(id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_album_labels_label ON album_labels(label_id);
+        ",
+    )
+}
+
+// Version 18: removing a track or album from the library only marks it, so
+// it keeps its playlist entries, labels and stats until it's restored or the
+// retention period runs out
+fn migrate_removed_items(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE tracks ADD COLUMN removed_at TEXT;
+        CREATE INDEX idx_tracks_removed ON tracks(removed_at) WHERE removed_at IS NOT NULL;
+        ALTER TABLE albums ADD COLUMN removed_at TEXT;
+        CREATE INDEX idx_albums_removed ON albums(removed_at) WHERE removed_at IS NOT NULL;
+        ",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap()
+    }
+
+    fn table_exists(conn: &Connection, table: &str) -> bool {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [table],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    /// Earliest released layout: no format/source/cover columns and no art_path
+    fn earliest_fixture() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE albums (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                artist TEXT,
+                art_data TEXT
+            );
+            CREATE TABLE tracks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT UNIQUE NOT NULL,
+                title TEXT,
+                artist TEXT,
+                album TEXT,
+                track_number INTEGER,
+                duration INTEGER,
+                album_id INTEGER,
+                FOREIGN KEY (album_id) REFERENCES albums(id) ON DELETE CASCADE
+            );
+            CREATE TABLE playlists (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE playlist_tracks (
+                playlist_id INTEGER NOT NULL,
+                track_id INTEGER NOT NULL,
+                position INTEGER,
+                PRIMARY KEY (playlist_id, track_id)
+            );
+            CREATE TABLE music_folders (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT UNIQUE NOT NULL,
+                last_scanned TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+
+            INSERT INTO albums (id, name, artist) VALUES (1, 'Album', 'Artist');
+            INSERT INTO tracks (id, path, title, album_id) VALUES (1, '/music/a.flac', 'A', 1);
+            INSERT INTO tracks (id, path, title, album_id) VALUES (2, '/music/b.flac', 'B', 1);
+            INSERT INTO playlists (id, name) VALUES (1, 'Mix');
+            INSERT INTO playlist_tracks (playlist_id, track_id) VALUES (1, 2);
+            INSERT INTO playlist_tracks (playlist_id, track_id) VALUES (1, 1);
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn fresh_database_reaches_latest_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&mut conn, None).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        assert!(table_exists(&conn, "track_analysis"));
+        assert!(table_exists(&conn, "verification_results"));
+        assert!(columns(&conn, "tracks").contains(&"last_verified".to_string()));
+        assert!(table_exists(&conn, "tracks_fts"));
+        assert!(table_exists(&conn, "tracks_trigram"));
+        assert!(table_exists(&conn, "settings"));
+    }
+
+    #[test]
+    fn upgrades_earliest_layout_and_keeps_data() {
+        let mut conn = earliest_fixture();
+        init_schema(&mut conn, None).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        let track_columns = columns(&conn, "tracks");
+        for column in [
+            "format",
+            "source_type",
+            "track_cover_path",
+            "last_verified",
+            "year",
+            "date_added",
+            "sort_artist",
+        ] {
+            assert!(
+                track_columns.contains(&column.to_string()),
+                "missing {}",
+                column
+            );
         }
+        assert!(columns(&conn, "albums").contains(&"art_path".to_string()));
+        assert!(columns(&conn, "playlists").contains(&"cover_url".to_string()));
+
+        let (title, source): (String, String) = conn
+            .query_row(
+                "SELECT title, source_type FROM tracks WHERE id = 2",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(title, "B");
+        assert_eq!(source, "local");
+
+        // Tracks from before the search index existed are indexed
+        let hits: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM tracks_fts WHERE tracks_fts MATCH 'b'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hits, 1);
+
+        // Existing tracks and albums get sort names
+        let sort_names: (String, String) = conn
+            .query_row(
+                "SELECT t.sort_title, a.sort_artist FROM tracks t JOIN albums a ON a.id = t.album_id WHERE t.id = 2",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(sort_names, ("B".to_string(), "Artist".to_string()));
+
+        // Playlist rows become entries, numbered in insertion order
+        let positions: Vec<(i64, i64)> = conn
+            .prepare("SELECT track_id, position FROM playlist_tracks ORDER BY position")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(positions, vec![(2, 0), (1, 1)]);
+        conn.execute(
+            "INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES (1, 2, 2)",
+            [],
+        )
+        .unwrap();
+
+        // Existing playlists start at the top level
+        let placement: (Option<i64>, i64) = conn
+            .query_row(
+                "SELECT folder_id, position FROM playlists WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(placement, (None, 0));
     }
 
-    Ok(())
+    #[test]
+    fn upgrades_from_intermediate_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run_migrations(&mut conn, &MIGRATIONS[..1]).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+
+        init_schema(&mut conn, None).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        assert!(table_exists(&conn, "verification_results"));
+    }
+
+    #[test]
+    fn migrating_twice_is_a_no_op() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&mut conn, None).unwrap();
+        init_schema(&mut conn, None).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn refuses_newer_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+
+        let err = init_schema(&mut conn, None).unwrap_err();
+        assert!(err.contains("update Audion"), "{}", err);
+    }
+
+    #[test]
+    fn failed_step_is_rolled_back() {
+        fn create_then_fail(tx: &Transaction) -> Result<()> {
+            tx.execute_batch("CREATE TABLE half_done (id INTEGER); SELECT * FROM no_such_table;")
+        }
+        let steps = [
+            Migration {
+                version: 1,
+                description: "base",
+                up: migrate_base_schema,
+            },
+            Migration {
+                version: 2,
+                description: "broken",
+                up: create_then_fail,
+            },
+        ];
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        let err = run_migrations(&mut conn, &steps).unwrap_err();
+
+        assert!(err.contains("version 2 (broken)"), "{}", err);
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        assert!(!table_exists(&conn, "half_done"));
+    }
+
+    #[test]
+    fn backs_up_existing_database_before_migrating() {
+        let dir = std::env::temp_dir().join(format!("audion_schema_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("rlist.db");
+
+        let mut conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE tracks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT UNIQUE NOT NULL,
+                title TEXT,
+                artist TEXT,
+                album TEXT,
+                track_number INTEGER,
+                duration INTEGER,
+                album_id INTEGER
+            );",
+        )
+        .unwrap();
+        init_schema(&mut conn, Some(&db_path)).unwrap();
+
+        let backup = dir.join("rlist.v0.backup.db");
+        let backup_conn = Connection::open(&backup).unwrap();
+        assert_eq!(schema_version(&backup_conn).unwrap(), 0);
+        assert!(table_exists(&backup_conn, "tracks"));
+        assert!(!table_exists(&backup_conn, "playlists"));
+
+        drop(backup_conn);
+        drop(conn);
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
@@ -0,0 +1,378 @@
+// Turning parsed search queries into safe FTS5 expressions and SQL filters
+use super::labels;
+use super::search_query::{self, Comparison, DateField, Expr, NumberField, QueryError, TextField};
+use rusqlite::types::Value;
+
+/// bm25 column weights for `tracks_fts(title, artist, album)`: a hit in the
+/// title counts for more than one in the artist, which beats the album.
+pub const TITLE_WEIGHT: f64 = 10.0;
+pub const ARTIST_WEIGHT: f64 = 5.0;
+pub const ALBUM_WEIGHT: f64 = 2.0;
+
+/// A search ready to run against `tracks t`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledSearch {
+    /// FTS5 expression built from the top-level text terms; results are ranked by it
+    pub rank_match: Option<String>,
+    /// Remaining conditions, with `?` placeholders for `params`
+    pub filter: Option<String>,
+    pub params: Vec<Value>,
+    /// The query text when it's nothing but plain words, for the fuzzy fallback
+    pub plain_text: Option<String>,
+}
+
+impl CompiledSearch {
+    /// `FROM ... WHERE ...` selecting the matching tracks as `t` (joined with
+    /// `tracks_fts` when ranked), with `extra` conditions ANDed in, and the
+    /// values for its placeholders. Tracks removed from the library never match,
+    /// nor do the stand-ins for unmatched playlist entries.
+    pub fn sql_source(&self, extra: &[&str]) -> (String, Vec<Value>) {
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut values = Vec::new();
+        let from = match &self.rank_match {
+            Some(rank_match) => {
+                conditions.push("tracks_fts MATCH ?");
+                values.push(Value::Text(rank_match.clone()));
+                "FROM tracks_fts JOIN tracks t ON t.id = tracks_fts.rowid"
+            }
+            None => "FROM tracks t",
+        };
+        conditions.extend(self.filter.as_deref());
+        conditions.extend(extra);
+        conditions.push("t.removed_at IS NULL");
+        conditions.push("t.source_type IS NOT 'placeholder'");
+        values.extend(self.params.iter().cloned());
+
+        (format!("{} WHERE {}", from, conditions.join(" AND ")), values)
+    }
+}
+
+/// Parse and compile a search box query.
+/// `Ok(None)` means there's nothing to search for (empty or punctuation only).
+pub fn compile_query(input: &str) -> Result<Option<CompiledSearch>, QueryError> {
+    Ok(search_query::parse(input)?.and_then(|expr| compile(&expr)))
+}
+
+pub fn compile(expr: &Expr) -> Option<CompiledSearch> {
+    let terms: Vec<&Expr> = match expr {
+        Expr::And(terms) => terms.iter().collect(),
+        other => vec![other],
+    };
+
+    // Plain text terms that must all match go into a single ranked MATCH;
+    // everything else (negations, OR groups, field comparisons) is SQL
+    let mut rank_terms = Vec::new();
+    let mut filters = Vec::new();
+    let mut params = Vec::new();
+    let mut plain_words = Vec::new();
+    let mut only_plain = true;
+    for term in terms {
+        match term {
+            Expr::Text {
+                field,
+                value,
+                phrase,
+            } if is_fts_field(*field) => {
+                rank_terms.extend(fts_term(*field, value, *phrase));
+                only_plain &= field.is_none();
+                plain_words.push(value.as_str());
+            }
+            _ => filters.extend(sql_condition(term, &mut params)),
+        }
+    }
+
+    if rank_terms.is_empty() && filters.is_empty() {
+        return None;
+    }
+    Some(CompiledSearch {
+        rank_match: (!rank_terms.is_empty()).then(|| rank_terms.join(" ")),
+        plain_text: (only_plain && filters.is_empty()).then(|| plain_words.join(" ")),
+        filter: (!filters.is_empty()).then(|| filters.join(" AND ")),
+        params,
+    })
+}
+
+/// A ranked prefix search on one column (or all of them), e.g. for suggestions
+pub fn prefix_search(field: Option<TextField>, text: &str) -> Option<CompiledSearch> {
+    fts_term(field, text, false).map(|term| CompiledSearch {
+        rank_match: Some(term),
+        filter: None,
+        params: Vec::new(),
+        plain_text: None,
+    })
+}
+
+fn is_fts_field(field: Option<TextField>) -> bool {
+    matches!(
+        field,
+        None | Some(TextField::Title | TextField::Artist | TextField::Album)
+    )
+}
+
+/// Words are split the same way the `unicode61` tokenizer splits them (so
+/// `AC/DC` becomes `ac` and `dc`), quoted so FTS operators and punctuation are
+/// taken literally, and prefix-matched so partial words find results while
+/// typing. Phrases must match exactly. `None` if nothing searchable is left.
+fn fts_term(field: Option<TextField>, value: &str, phrase: bool) -> Option<String> {
+    let words = tokenize(value);
+    if words.is_empty() {
+        return None;
+    }
+
+    let body = if phrase {
+        format!("\"{}\"", words.join(" "))
+    } else {
+        words
+            .iter()
+            .map(|w| format!("\"{}\"*", w))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let column = match field {
+        Some(TextField::Title) => "title",
+        Some(TextField::Artist) => "artist",
+        Some(TextField::Album) => "album",
+        _ => return Some(body),
+    };
+    Some(format!("{} : ({})", column, body))
+}
+
+/// Split on anything that isn't a letter or digit
+fn tokenize(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// SQL for one term. `None` means the term doesn't restrict anything
+/// (e.g. a word made only of punctuation) and should be dropped.
+fn sql_condition(expr: &Expr, params: &mut Vec<Value>) -> Option<String> {
+    match expr {
+        Expr::And(terms) => {
+            let parts: Vec<String> = terms
+                .iter()
+                .filter_map(|t| sql_condition(t, params))
+                .collect();
+            match parts.len() {
+                0 => None,
+                1 => parts.into_iter().next(),
+                _ => Some(format!("({})", parts.join(" AND "))),
+            }
+        }
+        Expr::Or(alternatives) => {
+            let mut parts = Vec::new();
+            let mark = params.len();
+            for alternative in alternatives {
+                match sql_condition(alternative, params) {
+                    Some(part) => parts.push(part),
+                    None => {
+                        // One branch matches everything, so the whole group does
+                        params.truncate(mark);
+                        return None;
+                    }
+                }
+            }
+            Some(format!("({})", parts.join(" OR ")))
+        }
+        // Missing values (NULL year, never verified...) count as "not matching",
+        // so excluding a condition keeps those tracks
+        Expr::Not(inner) => sql_condition(inner, params).map(|c| format!("NOT COALESCE({}, 0)", c)),
+        Expr::Text {
+            field,
+            value,
+            phrase,
+        } => match field {
+            f if is_fts_field(*f) => {
+                let term = fts_term(*f, value, *phrase)?;
+                params.push(Value::Text(term));
+                Some("t.id IN (SELECT rowid FROM tracks_fts WHERE tracks_fts MATCH ?)".to_string())
+            }
+            Some(TextField::Genre) => like("t.genre", value, params),
+            Some(TextField::Path) => like("t.path", value, params),
+            Some(TextField::Label) => like("l.name", value, params).map(|c| labels::has_label(&c)),
+            Some(TextField::Format) => {
+                params.push(Value::Text(format_name(value).to_string()));
+                Some("t.format = ? COLLATE NOCASE".to_string())
+            }
+            Some(TextField::Source) => {
+                params.push(Value::Text(value.clone()));
+                Some("COALESCE(t.source_type, 'local') = ? COLLATE NOCASE".to_string())
+            }
+            _ => unreachable!("FTS fields handled above"),
+        },
+        Expr::Number { field, cmp } => {
+            let column = match field {
+                NumberField::Year => "t.year",
+                NumberField::Duration => "t.duration",
+                NumberField::Bitrate => "t.bitrate",
+                NumberField::Track => "t.track_number",
+            };
+            let (sql, values) = match cmp {
+                Comparison::Eq(v) => (format!("{} = ?", column), vec![*v]),
+                Comparison::Gt(v) => (format!("{} > ?", column), vec![*v]),
+                Comparison::Ge(v) => (format!("{} >= ?", column), vec![*v]),
+                Comparison::Lt(v) => (format!("{} < ?", column), vec![*v]),
+                Comparison::Le(v) => (format!("{} <= ?", column), vec![*v]),
+                Comparison::Between(low, high) => {
+                    (format!("{} BETWEEN ? AND ?", column), vec![*low, *high])
+                }
+            };
+            params.extend(values.into_iter().map(Value::Real));
+            Some(sql)
+        }
+        Expr::Date { field, cmp } => {
+            let column = match field {
+                DateField::Added => "t.date_added",
+                DateField::Verified => "t.last_verified",
+            };
+            // Dates are whole periods: "> 2023" means from 2024-01-01 on
+            let (sql, values) = match cmp {
+                Comparison::Eq(d) => (
+                    format!("({0} >= ? AND {0} < ?)", column),
+                    vec![&d.start, &d.end],
+                ),
+                Comparison::Gt(d) => (format!("{} >= ?", column), vec![&d.end]),
+                Comparison::Ge(d) => (format!("{} >= ?", column), vec![&d.start]),
+                Comparison::Lt(d) => (format!("{} < ?", column), vec![&d.start]),
+                Comparison::Le(d) => (format!("{} < ?", column), vec![&d.end]),
+                Comparison::Between(from, to) => (
+                    format!("({0} >= ? AND {0} < ?)", column),
+                    vec![&from.start, &to.end],
+                ),
+            };
+            params.extend(values.into_iter().map(|d| Value::Text(d.clone())));
+            Some(sql)
+        }
+    }
+}
+
+/// Case-insensitive "contains", with LIKE wildcards in the value escaped
+fn like(column: &str, value: &str, params: &mut Vec<Value>) -> Option<String> {
+    if value.is_empty() {
+        return None;
+    }
+    params.push(Value::Text(format!("%{}%", escape_like(value))));
+    Some(format!("{} LIKE ? ESCAPE '\\'", column))
+}
+
+/// Escape LIKE wildcards for use with `ESCAPE '\'`
+pub fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Map common extensions to the format names the scanner stores
+fn format_name(value: &str) -> &str {
+    match value.to_ascii_lowercase().as_str() {
+        "mp3" => "Mpeg",
+        "m4a" | "alac" => "Mp4",
+        "ogg" => "Vorbis",
+        "wv" => "WavPack",
+        "aif" => "Aiff",
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{queries, schema};
+    use rusqlite::Connection;
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (path, title, artist, album, year, duration, format, date_added) VALUES
+                ('/m/1.flac', 'Highway to Hell', 'AC/DC', 'Highway to Hell', 1979, 208, 'Flac', '2023-05-01 10:00:00'),
+                ('/m/2.flac', 'Halo', 'Beyoncé', 'I Am... Sasha Fierce', 2008, 261, 'Flac', '2024-01-15 10:00:00'),
+                ('/m/3.mp3', 'Love Song', 'Halo Tribute Band', 'Covers', NULL, 240, 'Mpeg', '2024-02-01 10:00:00'),
+                ('/m/4.flac', 'Intro', 'Someone', 'Halo Sessions (Live)', 2015, 95, 'Flac', '2024-02-29 23:59:59');
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn titles(conn: &Connection, query: &str) -> Vec<String> {
+        let Some(search) = compile_query(query).unwrap() else {
+            return Vec::new();
+        };
+        queries::search_tracks(conn, &search, 50, 0)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title.unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn builds_prefix_terms_and_phrases() {
+        let search = compile_query("AC/DC \"love song\" artist:hal").unwrap().unwrap();
+        assert_eq!(
+            search.rank_match.as_deref(),
+            Some("\"AC\"* \"DC\"* \"love song\" artist : (\"hal\"*)")
+        );
+        assert_eq!(search.filter, None);
+
+        // FTS operators are taken literally
+        let search = compile_query("NEAR NOT/OR").unwrap().unwrap();
+        assert_eq!(
+            search.rank_match.as_deref(),
+            Some("\"NEAR\"* \"NOT\"* \"OR\"*")
+        );
+        assert_eq!(compile_query(" \"\" / - ").unwrap(), None);
+    }
+
+    #[test]
+    fn punctuation_and_partial_words_match() {
+        let conn = library();
+        assert_eq!(titles(&conn, "AC/DC"), vec!["Highway to Hell"]);
+        assert_eq!(titles(&conn, "highw"), vec!["Highway to Hell"]);
+        assert_eq!(titles(&conn, "\"unbalanced"), Vec::<String>::new());
+        assert!(titles(&conn, "/").is_empty());
+    }
+
+    #[test]
+    fn diacritics_are_folded() {
+        let conn = library();
+        assert_eq!(titles(&conn, "Beyonce"), vec!["Halo"]);
+        assert_eq!(titles(&conn, "béyoncé"), vec!["Halo"]);
+    }
+
+    #[test]
+    fn title_hits_rank_above_artist_and_album() {
+        let conn = library();
+        assert_eq!(titles(&conn, "halo"), vec!["Halo", "Love Song", "Intro"]);
+    }
+
+    #[test]
+    fn fields_and_comparisons_filter() {
+        let conn = library();
+        assert_eq!(titles(&conn, "halo year:>=2000 format:flac -live"), vec!["Halo"]);
+        assert_eq!(titles(&conn, "format:mp3"), vec!["Love Song"]);
+        assert_eq!(titles(&conn, "duration:<4:00"), vec!["Highway to Hell", "Intro"]);
+        assert_eq!(titles(&conn, "artist:\"tribute band\""), vec!["Love Song"]);
+        assert_eq!(titles(&conn, "halo -artist:someone"), vec!["Halo", "Love Song"]);
+        // NULL years are kept when excluding a range
+        assert_eq!(
+            titles(&conn, "-year:1970..1999 halo"),
+            vec!["Halo", "Love Song", "Intro"]
+        );
+    }
+
+    #[test]
+    fn or_groups_and_dates() {
+        let conn = library();
+        assert_eq!(
+            titles(&conn, "(artist:ac OR artist:beyonce) year:<2000"),
+            vec!["Highway to Hell"]
+        );
+        assert_eq!(titles(&conn, "added:2024-02"), vec!["Love Song", "Intro"]);
+        assert_eq!(titles(&conn, "added:>2023 -halo"), Vec::<String>::new());
+        assert_eq!(titles(&conn, "added:<=2024-01-15 path:/m/"), vec!["Highway to Hell", "Halo"]);
+        assert_eq!(titles(&conn, "path:%"), Vec::<String>::new());
+    }
+}
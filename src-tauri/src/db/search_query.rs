@@ -0,0 +1,696 @@
+// Search query language: `artist:radiohead year:>=2000 format:flac -live`
+//
+// Grammar (whitespace between terms means AND):
+//   query   := or_expr
+//   or_expr := and_expr ("OR" and_expr)*
+//   and_expr:= unary+
+//   unary   := ("-" | "NOT") unary | primary
+//   primary := "(" or_expr ")" | field ":" value | "phrase" | word
+use serde::Serialize;
+
+/// Character range (not bytes) of the offending part of the query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl QueryError {
+    fn new(message: impl Into<String>, start: usize, end: usize) -> Self {
+        Self {
+            message: message.into(),
+            span: Span { start, end },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Format,
+    Source,
+    Path,
+    Label,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberField {
+    Year,
+    /// Seconds; `m:ss` is accepted too
+    Duration,
+    /// kbps
+    Bitrate,
+    Track,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateField {
+    Added,
+    Verified,
+}
+
+/// A comparison against a numeric or date field. `Between` is inclusive.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comparison<T> {
+    Eq(T),
+    Gt(T),
+    Ge(T),
+    Lt(T),
+    Le(T),
+    Between(T, T),
+}
+
+/// A calendar period from a `YYYY`, `YYYY-MM` or `YYYY-MM-DD` value, as the
+/// ISO date it starts on and the (exclusive) date the next period starts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateValue {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    /// Free text (`field: None`) or a text field filter
+    Text {
+        field: Option<TextField>,
+        value: String,
+        phrase: bool,
+    },
+    Number {
+        field: NumberField,
+        cmp: Comparison<f64>,
+    },
+    Date {
+        field: DateField,
+        cmp: Comparison<DateValue>,
+    },
+}
+
+/// Parse a search box query. Returns `Ok(None)` for an empty query.
+pub fn parse(input: &str) -> Result<Option<Expr>, QueryError> {
+    let tokens = lex(input)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    if parser.tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let expr = parser.or_expr()?;
+    if let Some(token) = parser.peek() {
+        // Only a stray ")" can stop the top-level expression early
+        return Err(QueryError::new("Unmatched ')'", token.start, token.end));
+    }
+    Ok(Some(expr))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    Or,
+    Not,
+    Word(String),
+    Phrase(String),
+    Field {
+        name: String,
+        name_end: usize,
+        value: String,
+        phrase: bool,
+        value_start: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+fn lex(input: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    // An unclosed quote runs to the end, so typing one mid-search isn't an error
+    let read_phrase = |open: usize| -> (String, usize) {
+        match chars[open + 1..].iter().position(|&c| c == '"') {
+            Some(len) => (chars[open + 1..open + 1 + len].iter().collect(), open + len + 2),
+            None => (chars[open + 1..].iter().collect(), chars.len()),
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let kind = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                i += 1;
+                TokenKind::LParen
+            }
+            ')' => {
+                i += 1;
+                TokenKind::RParen
+            }
+            '-' if chars
+                .get(i + 1)
+                .is_some_and(|&n| !n.is_whitespace() && n != ')') =>
+            {
+                i += 1;
+                TokenKind::Not
+            }
+            '"' => {
+                let (text, next) = read_phrase(i);
+                i = next;
+                TokenKind::Phrase(text)
+            }
+            _ => {
+                while i < chars.len() && !is_word_boundary(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                match word.split_once(':') {
+                    Some((name, value)) if is_field_name(name) => {
+                        let name_end = start + name.chars().count();
+                        let value_start = name_end + 1;
+                        if value.is_empty() {
+                            if chars.get(i) == Some(&'"') {
+                                let (text, next) = read_phrase(i);
+                                i = next;
+                                TokenKind::Field {
+                                    name: name.to_string(),
+                                    name_end,
+                                    value: text,
+                                    phrase: true,
+                                    value_start,
+                                }
+                            } else {
+                                return Err(QueryError::new(
+                                    format!("Expected a value after '{}:'", name),
+                                    start,
+                                    i,
+                                ));
+                            }
+                        } else {
+                            TokenKind::Field {
+                                name: name.to_string(),
+                                name_end,
+                                value: value.to_string(),
+                                phrase: false,
+                                value_start,
+                            }
+                        }
+                    }
+                    _ => match word.as_str() {
+                        "OR" => TokenKind::Or,
+                        "NOT" => TokenKind::Not,
+                        "AND" => continue,
+                        _ => TokenKind::Word(word),
+                    },
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: i,
+        });
+    }
+
+    Ok(tokens)
+}
+
+fn is_word_boundary(c: char) -> bool {
+    c.is_whitespace() || c == '(' || c == ')' || c == '"'
+}
+
+fn is_field_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphabetic() || c == '_')
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Position just past the last token, for "expected ... here" errors
+    fn end(&self) -> usize {
+        self.tokens.last().map(|t| t.end).unwrap_or(0)
+    }
+
+    fn or_expr(&mut self) -> Result<Expr, QueryError> {
+        let mut alternatives = vec![self.and_expr()?];
+        while let Some(Token {
+            kind: TokenKind::Or,
+            start,
+            end,
+        }) = self.peek().cloned()
+        {
+            self.pos += 1;
+            match self.peek() {
+                None | Some(Token { kind: TokenKind::RParen | TokenKind::Or, .. }) => {
+                    return Err(QueryError::new("Expected a search term after OR", start, end));
+                }
+                _ => alternatives.push(self.and_expr()?),
+            }
+        }
+        Ok(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Expr::Or(alternatives)
+        })
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, QueryError> {
+        let mut terms = Vec::new();
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::RParen | TokenKind::Or => break,
+                _ => terms.push(self.unary()?),
+            }
+        }
+        match terms.len() {
+            0 => {
+                let (start, end) = self
+                    .peek()
+                    .map(|t| (t.start, t.end))
+                    .unwrap_or((self.end(), self.end()));
+                let message = if self.peek().is_some_and(|t| t.kind == TokenKind::Or) {
+                    "Expected a search term before OR"
+                } else {
+                    "Expected a search term"
+                };
+                Err(QueryError::new(message, start, end))
+            }
+            1 => Ok(terms.remove(0)),
+            _ => Ok(Expr::And(terms)),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, QueryError> {
+        match self.peek() {
+            Some(Token {
+                kind: TokenKind::Not,
+                start,
+                end,
+            }) => {
+                let (start, end) = (*start, *end);
+                self.pos += 1;
+                match self.peek() {
+                    None | Some(Token { kind: TokenKind::RParen | TokenKind::Or, .. }) => {
+                        Err(QueryError::new("Nothing to exclude", start, end))
+                    }
+                    _ => Ok(Expr::Not(Box::new(self.unary()?))),
+                }
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, QueryError> {
+        let token = self.next().expect("primary called at end of input");
+        match token.kind {
+            TokenKind::LParen => {
+                if self.peek().is_some_and(|t| t.kind == TokenKind::RParen) {
+                    let end = self.next().map(|t| t.end).unwrap_or(token.end);
+                    return Err(QueryError::new("Empty group", token.start, end));
+                }
+                let inner = self.or_expr()?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::RParen,
+                        ..
+                    }) => Ok(inner),
+                    _ => Err(QueryError::new("Missing ')'", token.start, self.end())),
+                }
+            }
+            TokenKind::Word(value) => Ok(Expr::Text {
+                field: None,
+                value,
+                phrase: false,
+            }),
+            TokenKind::Phrase(value) => Ok(Expr::Text {
+                field: None,
+                value,
+                phrase: true,
+            }),
+            TokenKind::Field {
+                name,
+                name_end,
+                value,
+                phrase,
+                value_start,
+            } => field_expr(
+                &name,
+                (token.start, name_end),
+                &value,
+                phrase,
+                (value_start, token.end),
+            ),
+            TokenKind::RParen => Err(QueryError::new("Unmatched ')'", token.start, token.end)),
+            TokenKind::Or | TokenKind::Not => unreachable!("handled by or_expr and unary"),
+        }
+    }
+}
+
+const FIELD_NAMES: &str =
+    "title, artist, album, genre, format, source, path, label, year, duration, bitrate, track, added, verified";
+
+fn field_expr(
+    name: &str,
+    name_span: (usize, usize),
+    value: &str,
+    phrase: bool,
+    value_span: (usize, usize),
+) -> Result<Expr, QueryError> {
+    let text = |field| {
+        if !phrase && (value.starts_with('<') || value.starts_with('>')) {
+            return Err(QueryError::new(
+                format!("'{}' can't be compared with < or >", name),
+                value_span.0,
+                value_span.1,
+            ));
+        }
+        Ok(Expr::Text {
+            field: Some(field),
+            value: value.to_string(),
+            phrase,
+        })
+    };
+    let number = |field| {
+        parse_comparison(value, value_span, |v| parse_number(field, v)).map(|cmp| Expr::Number {
+            field,
+            cmp,
+        })
+    };
+    let date = |field| {
+        parse_comparison(value, value_span, parse_date).map(|cmp| Expr::Date { field, cmp })
+    };
+
+    match name.to_ascii_lowercase().as_str() {
+        "title" => text(TextField::Title),
+        "artist" => text(TextField::Artist),
+        "album" => text(TextField::Album),
+        "genre" => text(TextField::Genre),
+        "format" => text(TextField::Format),
+        "source" => text(TextField::Source),
+        "path" => text(TextField::Path),
+        "label" => text(TextField::Label),
+        "year" => number(NumberField::Year),
+        "duration" => number(NumberField::Duration),
+        "bitrate" => number(NumberField::Bitrate),
+        "track" => number(NumberField::Track),
+        "added" => date(DateField::Added),
+        "verified" => date(DateField::Verified),
+        _ => Err(QueryError::new(
+            format!("Unknown field '{}' (expected one of: {})", name, FIELD_NAMES),
+            name_span.0,
+            name_span.1,
+        )),
+    }
+}
+
+/// `>=v`, `<=v`, `>v`, `<v`, `=v`, `v` or `a..b`
+fn parse_comparison<T>(
+    value: &str,
+    span: (usize, usize),
+    parse_value: impl Fn(&str) -> Option<T>,
+) -> Result<Comparison<T>, QueryError> {
+    let (op, rest) = ["<=", ">=", "<", ">", "="]
+        .iter()
+        .find_map(|op| value.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("", value));
+    let op_len = op.chars().count();
+    let invalid = |text: &str, offset: usize| {
+        QueryError::new(
+            format!("Invalid value '{}'", text),
+            span.0 + offset,
+            span.0 + offset + text.chars().count(),
+        )
+    };
+
+    if op.is_empty() {
+        if let Some((low, high)) = value.split_once("..") {
+            let low_value = parse_value(low).ok_or_else(|| invalid(low, 0))?;
+            let high_value =
+                parse_value(high).ok_or_else(|| invalid(high, low.chars().count() + 2))?;
+            return Ok(Comparison::Between(low_value, high_value));
+        }
+    }
+
+    let parsed = parse_value(rest).ok_or_else(|| invalid(rest, op_len))?;
+    Ok(match op {
+        "<=" => Comparison::Le(parsed),
+        ">=" => Comparison::Ge(parsed),
+        "<" => Comparison::Lt(parsed),
+        ">" => Comparison::Gt(parsed),
+        _ => Comparison::Eq(parsed),
+    })
+}
+
+fn parse_number(field: NumberField, value: &str) -> Option<f64> {
+    if field == NumberField::Duration && value.contains(':') {
+        // m:ss or h:mm:ss
+        return value.split(':').try_fold(0.0, |total, part| {
+            part.parse::<u32>().ok().map(|n| total * 60.0 + n as f64)
+        });
+    }
+    value.parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0)
+}
+
+fn parse_date(value: &str) -> Option<DateValue> {
+    let parts: Vec<&str> = value.split('-').collect();
+    let numbers: Vec<u32> = parts
+        .iter()
+        .map(|p| p.parse::<u32>().ok())
+        .collect::<Option<_>>()?;
+
+    match numbers.as_slice() {
+        [year] if parts[0].len() == 4 => Some(DateValue {
+            start: format!("{:04}-01-01", year),
+            end: format!("{:04}-01-01", year + 1),
+        }),
+        [year, month] if parts[0].len() == 4 && (1..=12).contains(month) => {
+            let (next_year, next_month) = if *month == 12 { (year + 1, 1) } else { (*year, month + 1) };
+            Some(DateValue {
+                start: format!("{:04}-{:02}-01", year, month),
+                end: format!("{:04}-{:02}-01", next_year, next_month),
+            })
+        }
+        [year, month, day] if parts[0].len() == 4 && (1..=12).contains(month) => {
+            let days = days_in_month(*year, *month);
+            if !(1..=days).contains(day) {
+                return None;
+            }
+            let (next_year, next_month, next_day) = if *day < days {
+                (*year, *month, day + 1)
+            } else if *month < 12 {
+                (*year, month + 1, 1)
+            } else {
+                (year + 1, 1, 1)
+            };
+            Some(DateValue {
+                start: format!("{:04}-{:02}-{:02}", year, month, day),
+                end: format!("{:04}-{:02}-{:02}", next_year, next_month, next_day),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(field: Option<TextField>, value: &str) -> Expr {
+        Expr::Text {
+            field,
+            value: value.to_string(),
+            phrase: false,
+        }
+    }
+
+    fn error(input: &str) -> (String, usize, usize) {
+        let err = parse(input).unwrap_err();
+        (err.message, err.span.start, err.span.end)
+    }
+
+    #[test]
+    fn parses_fields_comparisons_and_negation() {
+        let expr = parse("artist:radiohead year:>=2000 format:flac duration:<240 -live")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::And(vec![
+                text(Some(TextField::Artist), "radiohead"),
+                Expr::Number {
+                    field: NumberField::Year,
+                    cmp: Comparison::Ge(2000.0),
+                },
+                text(Some(TextField::Format), "flac"),
+                Expr::Number {
+                    field: NumberField::Duration,
+                    cmp: Comparison::Lt(240.0),
+                },
+                Expr::Not(Box::new(text(None, "live"))),
+            ])
+        );
+    }
+
+    #[test]
+    fn or_binds_looser_than_and() {
+        let expr = parse("a b OR c").unwrap().unwrap();
+        assert_eq!(
+            expr,
+            Expr::Or(vec![
+                Expr::And(vec![text(None, "a"), text(None, "b")]),
+                text(None, "c"),
+            ])
+        );
+
+        let grouped = parse("(artist:muse OR artist:\"the killers\") -NOT live").unwrap().unwrap();
+        assert_eq!(
+            grouped,
+            Expr::And(vec![
+                Expr::Or(vec![
+                    text(Some(TextField::Artist), "muse"),
+                    Expr::Text {
+                        field: Some(TextField::Artist),
+                        value: "the killers".to_string(),
+                        phrase: true,
+                    },
+                ]),
+                Expr::Not(Box::new(Expr::Not(Box::new(text(None, "live"))))),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_ranges_dates_and_durations() {
+        assert_eq!(
+            parse("year:1990..1999").unwrap().unwrap(),
+            Expr::Number {
+                field: NumberField::Year,
+                cmp: Comparison::Between(1990.0, 1999.0),
+            }
+        );
+        assert_eq!(
+            parse("duration:>4:30").unwrap().unwrap(),
+            Expr::Number {
+                field: NumberField::Duration,
+                cmp: Comparison::Gt(270.0),
+            }
+        );
+        assert_eq!(
+            parse("added:2024-02").unwrap().unwrap(),
+            Expr::Date {
+                field: DateField::Added,
+                cmp: Comparison::Eq(DateValue {
+                    start: "2024-02-01".to_string(),
+                    end: "2024-03-01".to_string(),
+                }),
+            }
+        );
+        assert_eq!(
+            parse("added:<2023-12-31").unwrap().unwrap(),
+            Expr::Date {
+                field: DateField::Added,
+                cmp: Comparison::Lt(DateValue {
+                    start: "2023-12-31".to_string(),
+                    end: "2024-01-01".to_string(),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn plain_text_is_left_alone() {
+        assert_eq!(parse("").unwrap(), None);
+        assert_eq!(parse("   ").unwrap(), None);
+        assert_eq!(parse("AC/DC").unwrap().unwrap(), text(None, "AC/DC"));
+        // Not field-like, so not a field
+        assert_eq!(parse("4:00").unwrap().unwrap(), text(None, "4:00"));
+        assert_eq!(parse("jay-z").unwrap().unwrap(), text(None, "jay-z"));
+        // An unclosed quote is a phrase to the end
+        assert_eq!(
+            parse("say \"hello wor").unwrap().unwrap(),
+            Expr::And(vec![
+                text(None, "say"),
+                Expr::Text {
+                    field: None,
+                    value: "hello wor".to_string(),
+                    phrase: true,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(
+            error("genre:rock colour:red"),
+            (
+                format!("Unknown field 'colour' (expected one of: {})", FIELD_NAMES),
+                11,
+                17
+            )
+        );
+        assert_eq!(error("year:>=20x0"), ("Invalid value '20x0'".to_string(), 7, 11));
+        assert_eq!(error("year:1990..later"), ("Invalid value 'later'".to_string(), 11, 16));
+        assert_eq!(error("added:2024-13"), ("Invalid value '2024-13'".to_string(), 6, 13));
+        assert_eq!(error("(a OR b"), ("Missing ')'".to_string(), 0, 7));
+        assert_eq!(error("a)"), ("Unmatched ')'".to_string(), 1, 2));
+        assert_eq!(error("a OR"), ("Expected a search term after OR".to_string(), 2, 4));
+        assert_eq!(error("OR a"), ("Expected a search term before OR".to_string(), 0, 2));
+        assert_eq!(error("a ()"), ("Empty group".to_string(), 2, 4));
+        assert_eq!(error("artist:"), ("Expected a value after 'artist:'".to_string(), 0, 7));
+        assert_eq!(
+            error("artist:>b"),
+            ("'artist' can't be compared with < or >".to_string(), 7, 9)
+        );
+        assert_eq!(error("a NOT"), ("Nothing to exclude".to_string(), 2, 5));
+    }
+
+    #[test]
+    fn spans_count_characters_not_bytes() {
+        assert_eq!(error("Beyoncé year:x"), ("Invalid value 'x'".to_string(), 13, 14));
+    }
+}
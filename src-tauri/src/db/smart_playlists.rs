@@ -0,0 +1,683 @@
+// Smart playlists: stored rules compiled to SQL. The tracks of the last
+// evaluation are kept in playlist_tracks, so a smart playlist reads (and keeps
+// its random picks) like any other until it's refreshed.
+use super::browse::{SortDirection, TrackSort};
+use super::labels;
+use super::search::escape_like;
+use crate::error::AudionError;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleField {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Format,
+    /// "local" for files, otherwise the streaming source
+    Source,
+    Path,
+    Year,
+    /// Seconds
+    Duration,
+    /// kbps
+    Bitrate,
+    TrackNumber,
+    /// Stars, 0-5
+    Rating,
+    Loved,
+    PlayCount,
+    SkipCount,
+    LastPlayed,
+    DateAdded,
+    /// Any of the track's labels, its own or its album's
+    Label,
+}
+
+enum FieldKind {
+    Text,
+    Number,
+    Date,
+    Flag,
+}
+
+impl RuleField {
+    fn column(self) -> &'static str {
+        match self {
+            RuleField::Title => "t.title",
+            RuleField::Artist => "t.artist",
+            RuleField::Album => "t.album",
+            RuleField::Genre => "t.genre",
+            RuleField::Format => "t.format",
+            RuleField::Source => "COALESCE(t.source_type, 'local')",
+            RuleField::Path => "t.path",
+            RuleField::Year => "t.year",
+            RuleField::Duration => "t.duration",
+            RuleField::Bitrate => "t.bitrate",
+            RuleField::TrackNumber => "t.track_number",
+            RuleField::Rating => "t.rating",
+            RuleField::Loved => "t.loved",
+            RuleField::PlayCount => "t.play_count",
+            RuleField::SkipCount => "t.skip_count",
+            RuleField::LastPlayed => "t.last_played",
+            RuleField::DateAdded => "t.date_added",
+            RuleField::Label => "l.name",
+        }
+    }
+
+    fn kind(self) -> FieldKind {
+        match self {
+            RuleField::Title
+            | RuleField::Artist
+            | RuleField::Album
+            | RuleField::Genre
+            | RuleField::Format
+            | RuleField::Source
+            | RuleField::Path
+            | RuleField::Label => FieldKind::Text,
+            RuleField::Year
+            | RuleField::Duration
+            | RuleField::Bitrate
+            | RuleField::TrackNumber
+            | RuleField::Rating
+            | RuleField::PlayCount
+            | RuleField::SkipCount => FieldKind::Number,
+            RuleField::LastPlayed | RuleField::DateAdded => FieldKind::Date,
+            RuleField::Loved => FieldKind::Flag,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOperator {
+    Is,
+    IsNot,
+    Contains,
+    NotContains,
+    StartsWith,
+    EndsWith,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// Within the last `value` days
+    InLast,
+    /// Not within the last `value` days, or never
+    NotInLast,
+    IsSet,
+    IsNotSet,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub field: RuleField,
+    pub operator: RuleOperator,
+    /// Text, number, `YYYY-MM-DD` date, boolean or day count, depending on
+    /// the field and operator; unused by `is_set` / `is_not_set`
+    #[serde(default)]
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Join {
+    #[default]
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleGroup {
+    #[serde(default)]
+    pub join: Join,
+    pub rules: Vec<RuleNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RuleNode {
+    Group(RuleGroup),
+    Rule(Rule),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmartSort {
+    #[default]
+    Artist,
+    Album,
+    Title,
+    DateAdded,
+    Duration,
+    Bitrate,
+    Year,
+    PlayCount,
+    LastPlayed,
+    Rating,
+    /// A new pick on every refresh
+    Random,
+}
+
+impl SmartSort {
+    fn track_sort(self) -> Option<TrackSort> {
+        Some(match self {
+            SmartSort::Artist => TrackSort::Artist,
+            SmartSort::Album => TrackSort::Album,
+            SmartSort::Title => TrackSort::Title,
+            SmartSort::DateAdded => TrackSort::DateAdded,
+            SmartSort::Duration => TrackSort::Duration,
+            SmartSort::Bitrate => TrackSort::Bitrate,
+            SmartSort::Year => TrackSort::Year,
+            SmartSort::PlayCount => TrackSort::PlayCount,
+            SmartSort::LastPlayed => TrackSort::LastPlayed,
+            SmartSort::Rating => TrackSort::Rating,
+            SmartSort::Random => return None,
+        })
+    }
+}
+
+/// What a smart playlist holds. The top level is a group of its own; the
+/// limit applies after sorting, so "random, limit 50" picks 50 at random.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmartRules {
+    #[serde(default)]
+    pub join: Join,
+    pub rules: Vec<RuleNode>,
+    #[serde(default)]
+    pub sort: SmartSort,
+    #[serde(default)]
+    pub direction: SortDirection,
+    pub limit: Option<u32>,
+}
+
+impl SmartRules {
+    /// Whether the rules or the sort order look at any of `fields`
+    fn uses(&self, fields: &[RuleField]) -> bool {
+        fn any(nodes: &[RuleNode], fields: &[RuleField]) -> bool {
+            nodes.iter().any(|node| match node {
+                RuleNode::Rule(rule) => fields.contains(&rule.field),
+                RuleNode::Group(group) => any(&group.rules, fields),
+            })
+        }
+        let sort_field = match self.sort {
+            SmartSort::PlayCount => Some(RuleField::PlayCount),
+            SmartSort::LastPlayed => Some(RuleField::LastPlayed),
+            SmartSort::Rating => Some(RuleField::Rating),
+            _ => None,
+        };
+        any(&self.rules, fields) || sort_field.is_some_and(|field| fields.contains(&field))
+    }
+}
+
+/// `WHERE` condition for the rules, with `?` placeholders for `params`
+pub fn compile(rules: &SmartRules, params: &mut Vec<Value>) -> Result<String, AudionError> {
+    compile_group(rules.join, &rules.rules, params)
+}
+
+// An empty group matches everything, whichever way it's joined
+fn compile_group(
+    join: Join,
+    nodes: &[RuleNode],
+    params: &mut Vec<Value>,
+) -> Result<String, AudionError> {
+    if nodes.is_empty() {
+        return Ok("1".to_string());
+    }
+    let parts = nodes
+        .iter()
+        .map(|node| match node {
+            RuleNode::Group(group) => compile_group(group.join, &group.rules, params),
+            RuleNode::Rule(rule) => compile_rule(rule, params),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let separator = match join {
+        Join::And => " AND ",
+        Join::Or => " OR ",
+    };
+    Ok(format!("({})", parts.join(separator)))
+}
+
+fn compile_rule(rule: &Rule, params: &mut Vec<Value>) -> Result<String, AudionError> {
+    use RuleOperator::*;
+
+    if rule.field == RuleField::Label {
+        return compile_label_rule(rule, params);
+    }
+    let column = rule.field.column();
+    let sql = match (rule.field.kind(), rule.operator) {
+        (_, IsSet) => match rule.field.kind() {
+            FieldKind::Text => format!("COALESCE({}, '') <> ''", column),
+            _ => format!("{} IS NOT NULL", column),
+        },
+        (_, IsNotSet) => match rule.field.kind() {
+            FieldKind::Text => format!("COALESCE({}, '') = ''", column),
+            _ => format!("{} IS NULL", column),
+        },
+
+        (FieldKind::Text, Is) => {
+            params.push(Value::Text(text_value(rule)?));
+            format!("{} = ? COLLATE NOCASE", column)
+        }
+        (FieldKind::Text, IsNot) => {
+            params.push(Value::Text(text_value(rule)?));
+            format!("COALESCE({}, '') <> ? COLLATE NOCASE", column)
+        }
+        (FieldKind::Text, Contains | NotContains | StartsWith | EndsWith) => {
+            params.push(Value::Text(like_pattern(rule)?));
+            match rule.operator {
+                NotContains => format!("COALESCE({}, '') NOT LIKE ? ESCAPE '\\'", column),
+                _ => format!("{} LIKE ? ESCAPE '\\'", column),
+            }
+        }
+
+        // Missing values match "is not", but no comparison
+        (FieldKind::Number, Is | IsNot | Gt | Gte | Lt | Lte) => {
+            params.push(Value::Real(number_value(rule)?));
+            match rule.operator {
+                IsNot => format!("({0} IS NULL OR {0} <> ?)", column),
+                op => format!("{} {} ?", column, comparison(op)),
+            }
+        }
+
+        // Dates are stored as UTC `YYYY-MM-DD HH:MM:SS`; a bare date means the whole day
+        (FieldKind::Date, InLast | NotInLast) => {
+            let days = number_value(rule)?;
+            if days < 0.0 {
+                return Err(invalid(rule, "needs a number of days"));
+            }
+            params.push(Value::Text(format!("-{} days", days)));
+            match rule.operator {
+                InLast => format!("{} >= datetime('now', ?)", column),
+                _ => format!("({0} IS NULL OR {0} < datetime('now', ?))", column),
+            }
+        }
+        (FieldKind::Date, Is | IsNot | Gt | Gte | Lt | Lte) => {
+            params.push(Value::Text(text_value(rule)?));
+            match rule.operator {
+                Is => format!("date({}) = date(?)", column),
+                IsNot => format!("({0} IS NULL OR date({0}) <> date(?))", column),
+                Gt => format!("{} >= datetime(?, '+1 day')", column),
+                Gte => format!("{} >= datetime(?)", column),
+                Lt => format!("{} < datetime(?)", column),
+                _ => format!("{} < datetime(?, '+1 day')", column),
+            }
+        }
+
+        (FieldKind::Flag, Is | IsNot) => {
+            let value = rule
+                .value
+                .as_bool()
+                .ok_or_else(|| invalid(rule, "needs true or false"))?;
+            params.push(Value::Integer(value.into()));
+            match rule.operator {
+                Is => format!("{} = ?", column),
+                _ => format!("{} <> ?", column),
+            }
+        }
+
+        _ => return Err(invalid(rule, "isn't available for this field")),
+    };
+    Ok(sql)
+}
+
+// A track matches when one of its labels does; the negative operators mean
+// none of them does, so unlabelled tracks match those
+fn compile_label_rule(rule: &Rule, params: &mut Vec<Value>) -> Result<String, AudionError> {
+    use RuleOperator::*;
+
+    let condition = match rule.operator {
+        IsSet | IsNotSet => "1",
+        Is | IsNot => {
+            params.push(Value::Text(text_value(rule)?));
+            "l.name = ?"
+        }
+        Contains | NotContains | StartsWith | EndsWith => {
+            params.push(Value::Text(like_pattern(rule)?));
+            "l.name LIKE ? ESCAPE '\\'"
+        }
+        _ => return Err(invalid(rule, "isn't available for this field")),
+    };
+    let sql = labels::has_label(condition);
+    Ok(match rule.operator {
+        IsNot | NotContains | IsNotSet => format!("NOT {}", sql),
+        _ => sql,
+    })
+}
+
+fn like_pattern(rule: &Rule) -> Result<String, AudionError> {
+    let value = escape_like(&text_value(rule)?);
+    Ok(match rule.operator {
+        RuleOperator::StartsWith => format!("{}%", value),
+        RuleOperator::EndsWith => format!("%{}", value),
+        _ => format!("%{}%", value),
+    })
+}
+
+fn comparison(operator: RuleOperator) -> &'static str {
+    match operator {
+        RuleOperator::Gt => ">",
+        RuleOperator::Gte => ">=",
+        RuleOperator::Lt => "<",
+        RuleOperator::Lte => "<=",
+        _ => "=",
+    }
+}
+
+fn text_value(rule: &Rule) -> Result<String, AudionError> {
+    match &rule.value {
+        serde_json::Value::String(text) => Ok(text.clone()),
+        serde_json::Value::Number(number) => Ok(number.to_string()),
+        _ => Err(invalid(rule, "needs a text value")),
+    }
+}
+
+fn number_value(rule: &Rule) -> Result<f64, AudionError> {
+    match &rule.value {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid(rule, "needs a number"))
+}
+
+fn invalid(rule: &Rule, problem: &str) -> AudionError {
+    let name = |value: serde_json::Result<String>| value.unwrap_or_default().replace('"', "");
+    AudionError::InvalidInput(format!(
+        "Smart playlist rule \"{} {}\" {}",
+        name(serde_json::to_string(&rule.field)),
+        name(serde_json::to_string(&rule.operator)),
+        problem
+    ))
+}
+
+/// Ids of the matching tracks, in playlist order
+pub fn evaluate(conn: &Connection, rules: &SmartRules) -> Result<Vec<i64>, AudionError> {
+    let mut params = Vec::new();
+    let filter = compile(rules, &mut params)?;
+    let order = match rules.sort.track_sort() {
+        Some(sort) => sort.order_by(rules.direction),
+        None => "RANDOM()".to_string(),
+    };
+    params.push(Value::Integer(rules.limit.map_or(-1, i64::from)));
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT t.id FROM tracks t WHERE t.removed_at IS NULL AND t.source_type IS NOT 'placeholder' AND {} ORDER BY {} LIMIT ?",
+        filter, order
+    ))?;
+    let ids = stmt
+        .query_map(params_from_iter(params), |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+    Ok(ids)
+}
+
+fn to_json(rules: &SmartRules) -> Result<String, AudionError> {
+    serde_json::to_string(rules).map_err(AudionError::from)
+}
+
+pub fn get_rules(conn: &Connection, playlist_id: i64) -> Result<Option<SmartRules>, AudionError> {
+    let json: Option<Option<String>> = conn
+        .query_row(
+            "SELECT smart_rules FROM playlists WHERE id = ?1",
+            [playlist_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match json {
+        None => Err(AudionError::NotFound(format!(
+            "Playlist {} not found",
+            playlist_id
+        ))),
+        Some(json) => Ok(json.map(|json| serde_json::from_str(&json)).transpose()?),
+    }
+}
+
+/// Editing tracks by hand only makes sense for regular playlists
+pub fn ensure_static(conn: &Connection, playlist_id: i64) -> Result<(), AudionError> {
+    match get_rules(conn, playlist_id)? {
+        Some(_) => Err(AudionError::InvalidInput(
+            "Smart playlists are filled by their rules and can't be edited by hand".to_string(),
+        )),
+        None => Ok(()),
+    }
+}
+
+pub fn create(conn: &Connection, name: &str, rules: &SmartRules) -> Result<i64, AudionError> {
+    // Compile before storing anything, so bad rules are rejected up front
+    compile(rules, &mut Vec::new())?;
+    conn.execute(
+        "INSERT INTO playlists (name, smart_rules) VALUES (?1, ?2)",
+        params![name, to_json(rules)?],
+    )?;
+    let playlist_id = conn.last_insert_rowid();
+    refresh(conn, playlist_id)?;
+    Ok(playlist_id)
+}
+
+/// Replace the rules (turning a regular playlist into a smart one) and refresh
+pub fn update(
+    conn: &Connection,
+    playlist_id: i64,
+    rules: &SmartRules,
+) -> Result<usize, AudionError> {
+    compile(rules, &mut Vec::new())?;
+    let updated = conn.execute(
+        "UPDATE playlists SET smart_rules = ?2 WHERE id = ?1",
+        params![playlist_id, to_json(rules)?],
+    )?;
+    if updated == 0 {
+        return Err(AudionError::NotFound(format!(
+            "Playlist {} not found",
+            playlist_id
+        )));
+    }
+    refresh(conn, playlist_id)
+}
+
+/// Re-evaluate one smart playlist. Returns its track count.
+pub fn refresh(conn: &Connection, playlist_id: i64) -> Result<usize, AudionError> {
+    let rules = get_rules(conn, playlist_id)?.ok_or_else(|| {
+        AudionError::InvalidInput(format!("Playlist {} is not a smart playlist", playlist_id))
+    })?;
+    let track_ids = evaluate(conn, &rules)?;
+
+    conn.execute(
+        "DELETE FROM playlist_tracks WHERE playlist_id = ?1",
+        [playlist_id],
+    )?;
+    let mut insert = conn.prepare(
+        "INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES (?1, ?2, ?3)",
+    )?;
+    for (position, track_id) in track_ids.iter().enumerate() {
+        insert.execute(params![playlist_id, track_id, position as i64])?;
+    }
+    conn.execute(
+        "UPDATE playlists SET smart_evaluated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        [playlist_id],
+    )?;
+    Ok(track_ids.len())
+}
+
+/// Re-evaluate every smart playlist, e.g. after a scan
+pub fn refresh_all(conn: &Connection) -> Result<(), AudionError> {
+    let ids: Vec<i64> = conn
+        .prepare("SELECT id FROM playlists WHERE smart_rules IS NOT NULL AND deleted_at IS NULL")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    for playlist_id in ids {
+        refresh(conn, playlist_id)?;
+    }
+    Ok(())
+}
+
+/// Re-evaluate the smart playlists that depend on `fields`, after those
+/// changed outside a scan (a rating, a label, a play...)
+pub fn refresh_using(conn: &Connection, fields: &[RuleField]) -> Result<(), AudionError> {
+    let playlists: Vec<(i64, String)> = conn
+        .prepare("SELECT id, smart_rules FROM playlists WHERE smart_rules IS NOT NULL AND deleted_at IS NULL")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (playlist_id, json) in playlists {
+        let rules: SmartRules = serde_json::from_str(&json)?;
+        if rules.uses(fields) {
+            refresh(conn, playlist_id)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{schema, sort_names};
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (id, path, title, artist, genre, year, rating, loved, last_played) VALUES
+                (1, '/m/1.flac', 'So What', 'Miles Davis', 'Jazz', 1959, 5, 1, datetime('now', '-90 days')),
+                (2, '/m/2.flac', 'Blue in Green', 'Miles Davis', 'Jazz', 1959, 4.5, 0, datetime('now', '-2 days')),
+                (3, '/m/3.flac', 'Naima', 'John Coltrane', 'jazz', 1960, 4, 0, NULL),
+                (4, '/m/4.flac', 'Airbag', 'Radiohead', 'Rock', 1997, 5, 1, NULL),
+                (5, '/m/5.flac', 'Take Five', 'Dave Brubeck', 'Jazz', 1959, 3, 0, NULL);
+            ",
+        )
+        .unwrap();
+        sort_names::regenerate(&conn, &Default::default()).unwrap();
+        conn
+    }
+
+    fn rules(json: serde_json::Value) -> SmartRules {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn rules_compile_to_filters() {
+        let conn = library();
+        // genre is Jazz AND rating >= 4 AND not played in 30 days
+        let jazz = rules(serde_json::json!({
+            "rules": [
+                { "field": "genre", "operator": "is", "value": "Jazz" },
+                { "field": "rating", "operator": "gte", "value": 4 },
+                { "field": "last_played", "operator": "not_in_last", "value": 30 }
+            ],
+            "sort": "title"
+        }));
+        assert_eq!(evaluate(&conn, &jazz).unwrap(), vec![3, 1]);
+
+        // Loved OR (Brubeck AND before 1960)
+        let mixed = rules(serde_json::json!({
+            "join": "or",
+            "rules": [
+                { "field": "loved", "operator": "is", "value": true },
+                { "join": "and", "rules": [
+                    { "field": "artist", "operator": "contains", "value": "brubeck" },
+                    { "field": "year", "operator": "lt", "value": 1960 }
+                ]}
+            ],
+            "sort": "rating",
+            "direction": "desc",
+            "limit": 2
+        }));
+        assert_eq!(evaluate(&conn, &mixed).unwrap(), vec![4, 1]);
+
+        let random = rules(serde_json::json!({ "rules": [], "sort": "random", "limit": 3 }));
+        assert_eq!(evaluate(&conn, &random).unwrap().len(), 3);
+
+        let bad = rules(serde_json::json!({
+            "rules": [{ "field": "rating", "operator": "contains", "value": 4 }]
+        }));
+        assert!(matches!(
+            evaluate(&conn, &bad),
+            Err(AudionError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn smart_playlists_store_their_last_evaluation() {
+        let conn = library();
+        let loved = rules(serde_json::json!({
+            "rules": [{ "field": "loved", "operator": "is", "value": true }],
+            "sort": "title"
+        }));
+        let id = create(&conn, "Loved", &loved).unwrap();
+        let tracks: Vec<i64> = crate::db::queries::get_playlist_tracks(&conn, id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(tracks, vec![4, 1]);
+        assert!(ensure_static(&conn, id).is_err());
+
+        conn.execute("UPDATE tracks SET loved = 1 WHERE id = 3", [])
+            .unwrap();
+        refresh_all(&conn).unwrap();
+        assert_eq!(
+            crate::db::queries::get_playlist_tracks(&conn, id)
+                .unwrap()
+                .len(),
+            3
+        );
+
+        let playlists = crate::db::queries::get_all_playlists(&conn).unwrap();
+        assert_eq!(playlists[0].kind, crate::db::queries::PlaylistKind::Smart);
+        assert_eq!(get_rules(&conn, id).unwrap(), Some(loved));
+    }
+
+    #[test]
+    fn playlists_follow_ratings_and_plays() {
+        let conn = library();
+        let tracks = |id| -> Vec<i64> {
+            crate::db::queries::get_playlist_tracks(&conn, id)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.id)
+                .collect()
+        };
+        // rating >= 4 AND not played in 30 days
+        let forgotten = create(
+            &conn,
+            "Forgotten favourites",
+            &rules(serde_json::json!({
+                "rules": [
+                    { "field": "rating", "operator": "gte", "value": 4 },
+                    { "field": "last_played", "operator": "not_in_last", "value": 30 }
+                ],
+                "sort": "title"
+            })),
+        )
+        .unwrap();
+        let jazz = create(
+            &conn,
+            "Jazz",
+            &rules(serde_json::json!({
+                "rules": [{ "field": "genre", "operator": "is", "value": "jazz" }]
+            })),
+        )
+        .unwrap();
+        assert_eq!(tracks(forgotten), vec![4, 3, 1]);
+        // Emptied by hand, so a refresh would show
+        conn.execute("DELETE FROM playlist_tracks WHERE playlist_id = ?1", [jazz])
+            .unwrap();
+
+        crate::db::ratings::set_track_rating(&conn, 5, Some(4.0)).unwrap();
+        refresh_using(&conn, &[RuleField::Rating]).unwrap();
+        assert_eq!(tracks(forgotten), vec![4, 3, 1, 5]);
+
+        conn.execute(
+            "UPDATE tracks SET last_played = datetime('now') WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        refresh_using(&conn, &[RuleField::LastPlayed]).unwrap();
+        assert_eq!(tracks(forgotten), vec![4, 3, 5]);
+
+        // Playlists that don't look at what changed are left alone
+        assert_eq!(tracks(jazz), Vec::<i64>::new());
+    }
+}
@@ -0,0 +1,222 @@
+// Sort names, so "The Beatles" files under B and "émilie" sits next to "Emma"
+use super::queries;
+use crate::utils::fold_text;
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Collation for names: case and accents only break ties, and runs of digits
+/// compare by value ("Track 2" before "Track 10"). It must be registered on
+/// every connection before it touches the database, since indexes use it.
+pub const COLLATION: &str = "SORTNAME";
+
+const OPTIONS_KEY: &str = "sort_name_options";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SortNameOptions {
+    /// Leading words skipped when generating a sort name, matched ignoring case
+    pub articles: Vec<String>,
+}
+
+impl Default for SortNameOptions {
+    fn default() -> Self {
+        Self {
+            articles: ["the", "a", "an"].map(String::from).to_vec(),
+        }
+    }
+}
+
+pub fn register_collation(conn: &Connection) -> Result<()> {
+    conn.create_collation(COLLATION, compare)
+}
+
+pub fn compare(a: &str, b: &str) -> Ordering {
+    natural_cmp(&fold_text(a), &fold_text(b)).then_with(|| a.cmp(b))
+}
+
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                // Longer numbers (ignoring leading zeros) are bigger
+                let order = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        if !(digits.is_empty() && c == '0') {
+            digits.push(c);
+        }
+    }
+    digits
+}
+
+/// `name` without a leading article ("The Beatles" -> "Beatles"). A name that
+/// is nothing but an article ("The The" keeps its second word) stays as it is.
+pub fn generate(name: &str, options: &SortNameOptions) -> String {
+    let name = name.trim();
+    if let Some((first, rest)) = name.split_once(char::is_whitespace) {
+        let first = fold_text(first);
+        let rest = rest.trim_start();
+        if !rest.is_empty() && options.articles.iter().any(|a| fold_text(a) == first) {
+            return rest.to_string();
+        }
+    }
+    name.to_string()
+}
+
+/// The sort tag when the file has one, otherwise a generated sort name
+pub fn sort_name(tag: Option<&str>, name: Option<&str>, options: &SortNameOptions) -> String {
+    match tag.map(str::trim) {
+        Some(tag) if !tag.is_empty() => tag.to_string(),
+        _ => generate(name.unwrap_or_default(), options),
+    }
+}
+
+pub fn load_options(conn: &Connection) -> Result<SortNameOptions> {
+    Ok(queries::get_setting(conn, OPTIONS_KEY)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+/// Save new options and regenerate every sort name that didn't come from a tag
+pub fn save_options(conn: &Connection, options: &SortNameOptions) -> Result<()> {
+    let json = serde_json::to_string(options)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    queries::set_setting(conn, OPTIONS_KEY, &json)?;
+    regenerate(conn, options)
+}
+
+/// Recompute the sort names of all tracks and albums
+pub fn regenerate(conn: &Connection, options: &SortNameOptions) -> Result<()> {
+    let text = |row: &rusqlite::Row, i| row.get::<_, Option<String>>(i);
+
+    let tracks: Vec<(i64, String, String, String)> = conn
+        .prepare(
+            "SELECT id, title, artist, album, tag_sort_title, tag_sort_artist, tag_sort_album FROM tracks",
+        )?
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                sort_name(text(row, 4)?.as_deref(), text(row, 1)?.as_deref(), options),
+                sort_name(text(row, 5)?.as_deref(), text(row, 2)?.as_deref(), options),
+                sort_name(text(row, 6)?.as_deref(), text(row, 3)?.as_deref(), options),
+            ))
+        })?
+        .collect::<Result<_>>()?;
+    let mut update = conn.prepare(
+        "UPDATE tracks SET sort_title = ?2, sort_artist = ?3, sort_album = ?4 WHERE id = ?1",
+    )?;
+    for (id, title, artist, album) in tracks {
+        update.execute(params![id, title, artist, album])?;
+    }
+
+    // Albums take their sort tags from any of their tracks
+    let albums: Vec<(i64, String, String)> = conn
+        .prepare(
+            "SELECT a.id, a.name, a.artist,
+                (SELECT tag_sort_album FROM tracks WHERE album_id = a.id AND tag_sort_album IS NOT NULL LIMIT 1),
+                (SELECT tag_sort_artist FROM tracks WHERE album_id = a.id AND tag_sort_artist IS NOT NULL AND artist = a.artist LIMIT 1)
+             FROM albums a",
+        )?
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                sort_name(text(row, 3)?.as_deref(), text(row, 1)?.as_deref(), options),
+                sort_name(text(row, 4)?.as_deref(), text(row, 2)?.as_deref(), options),
+            ))
+        })?
+        .collect::<Result<_>>()?;
+    let mut update = conn.prepare("UPDATE albums SET sort_name = ?2, sort_artist = ?3 WHERE id = ?1")?;
+    for (id, name, artist) in albums {
+        update.execute(params![id, name, artist])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    #[test]
+    fn compares_ignoring_case_and_accents_with_numbers_by_value() {
+        let mut names = vec!["Zebra", "émilie", "Emma", "abba", "Track 10", "Track 2", "Track 02b"];
+        names.sort_by(|a, b| compare(a, b));
+        assert_eq!(
+            names,
+            vec!["abba", "émilie", "Emma", "Track 2", "Track 02b", "Track 10", "Zebra"]
+        );
+        assert_eq!(compare("Abba", "abba"), Ordering::Less);
+    }
+
+    #[test]
+    fn strips_configured_articles() {
+        let options = SortNameOptions::default();
+        assert_eq!(generate("The Beatles", &options), "Beatles");
+        assert_eq!(generate("the  Who", &options), "Who");
+        assert_eq!(generate("The", &options), "The");
+        assert_eq!(generate("Theatre of Tragedy", &options), "Theatre of Tragedy");
+        assert_eq!(sort_name(Some("Beatles, The"), Some("The Beatles"), &options), "Beatles, The");
+
+        let french = SortNameOptions {
+            articles: vec!["Les".into()],
+        };
+        assert_eq!(generate("Les Négresses Vertes", &french), "Négresses Vertes");
+        assert_eq!(generate("The Beatles", &french), "The Beatles");
+    }
+
+    #[test]
+    fn changing_options_regenerates_all_but_tagged_names() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO albums (id, name, artist) VALUES (1, 'The Wall', 'Pink Floyd');
+            INSERT INTO tracks (path, title, artist, album, album_id, tag_sort_artist) VALUES
+                ('/m/1.flac', 'Hey You', 'Pink Floyd', 'The Wall', 1, NULL),
+                ('/m/2.flac', 'Help!', 'The Beatles', 'Help!', NULL, 'Beatles, The');
+            ",
+        )
+        .unwrap();
+
+        regenerate(&conn, &SortNameOptions::default()).unwrap();
+        let album: String = conn
+            .query_row("SELECT sort_name FROM albums WHERE id = 1", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(album, "Wall");
+
+        save_options(&conn, &SortNameOptions { articles: Vec::new() }).unwrap();
+        assert_eq!(load_options(&conn).unwrap().articles, Vec::<String>::new());
+        let artists: Vec<String> = conn
+            .prepare("SELECT sort_album || '|' || sort_artist FROM tracks ORDER BY id")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(artists, vec!["The Wall|Pink Floyd", "Help!|Beatles, The"]);
+    }
+}
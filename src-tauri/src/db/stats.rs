@@ -0,0 +1,474 @@
+// Listening statistics built from play_events
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, Result};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_TOP_LIMIT: u32 = 10;
+
+/// Date range in the listener's local time. Events are stored in UTC, so the
+/// offset (minutes east of UTC, i.e. `-new Date().getTimezoneOffset()`) is
+/// applied before grouping by day, hour or weekday.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StatsQuery {
+    /// Inclusive, `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS`
+    pub since: Option<String>,
+    /// Exclusive
+    pub until: Option<String>,
+    pub utc_offset_minutes: i32,
+    /// Length of each top list
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopTrack {
+    pub track_id: Option<i64>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub plays: i64,
+    pub listened_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopAlbum {
+    pub album_id: Option<i64>,
+    pub name: String,
+    pub artist: Option<String>,
+    pub plays: i64,
+    pub listened_ms: i64,
+}
+
+/// An artist or genre
+#[derive(Debug, Clone, Serialize)]
+pub struct TopEntry {
+    pub name: String,
+    pub plays: i64,
+    pub listened_ms: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Streak {
+    pub days: i64,
+    /// Local dates, inclusive
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewArtist {
+    pub name: String,
+    /// UTC time of the first ever play
+    pub first_played: String,
+    pub plays: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ListeningStats {
+    pub total_listened_ms: i64,
+    /// Playbacks that counted as plays
+    pub plays: i64,
+    pub skips: i64,
+    pub top_tracks: Vec<TopTrack>,
+    pub top_artists: Vec<TopEntry>,
+    pub top_albums: Vec<TopAlbum>,
+    /// Library tracks only; streamed tracks have no genre
+    pub top_genres: Vec<TopEntry>,
+    /// Milliseconds listened per local hour, 0-23
+    pub by_hour: Vec<i64>,
+    /// Milliseconds listened per weekday, 0 = Sunday
+    pub by_weekday: Vec<i64>,
+    /// Most consecutive days with something played
+    pub longest_streak: Option<Streak>,
+    /// The streak still running at the end of the range (or today), if any
+    pub current_streak: Option<Streak>,
+    /// Artists first ever played within the range
+    pub new_artists: Vec<NewArtist>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct YearSummary {
+    pub year: i32,
+    pub stats: ListeningStats,
+}
+
+/// `WHERE` clause selecting the events in range, and its parameters
+struct Range {
+    filter: String,
+    params: Vec<Value>,
+    offset: String,
+}
+
+impl Range {
+    fn new(query: &StatsQuery) -> Self {
+        let offset = format!("{:+} minutes", query.utc_offset_minutes);
+        let to_utc = Value::Text(format!("{:+} minutes", -query.utc_offset_minutes));
+        let mut conditions = vec!["1".to_string()];
+        let mut params = Vec::new();
+        // Bounds are converted to UTC once, so the index on started_at is used
+        if let Some(since) = &query.since {
+            conditions.push("e.started_at >= datetime(?, ?)".to_string());
+            params.extend([Value::Text(since.clone()), to_utc.clone()]);
+        }
+        if let Some(until) = &query.until {
+            conditions.push("e.started_at < datetime(?, ?)".to_string());
+            params.extend([Value::Text(until.clone()), to_utc]);
+        }
+        Self {
+            filter: conditions.join(" AND "),
+            params,
+            offset,
+        }
+    }
+
+    /// Parameters for a query that uses the offset first, then the range
+    fn with_offset(&self) -> Vec<Value> {
+        let mut params = vec![Value::Text(self.offset.clone())];
+        params.extend(self.params.iter().cloned());
+        params
+    }
+}
+
+pub fn listening_stats(conn: &Connection, query: &StatsQuery) -> Result<ListeningStats> {
+    let range = Range::new(query);
+    let limit = Value::Integer(query.limit.unwrap_or(DEFAULT_TOP_LIMIT).into());
+    let with_limit = |mut params: Vec<Value>| {
+        params.push(limit.clone());
+        params
+    };
+
+    let (total_listened_ms, plays, skips) = conn.query_row(
+        &format!(
+            "SELECT COALESCE(SUM(e.listened_ms), 0), COALESCE(SUM(e.counted), 0), COALESCE(SUM(e.skipped), 0)
+             FROM play_events e WHERE {}",
+            range.filter
+        ),
+        params_from_iter(range.params.iter()),
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    // Library tracks are grouped by id, streamed ones by what they're called
+    let top_tracks = conn
+        .prepare(&format!(
+            "SELECT MAX(e.track_id), MAX(e.title), MAX(e.artist), SUM(e.counted) AS plays, SUM(e.listened_ms) AS ms
+             FROM play_events e WHERE {}
+             GROUP BY COALESCE('id:' || e.track_id, 'ext:' || e.external_id, 'name:' || COALESCE(e.title, '') || '|' || COALESCE(e.artist, ''))
+             HAVING plays > 0
+             ORDER BY plays DESC, ms DESC
+             LIMIT ?",
+            range.filter
+        ))?
+        .query_map(params_from_iter(with_limit(range.params.clone())), |row| {
+            Ok(TopTrack {
+                track_id: row.get(0)?,
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                plays: row.get(3)?,
+                listened_ms: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let top_artists = top_entries(
+        conn,
+        &range,
+        "e.artist",
+        "play_events e",
+        with_limit(range.params.clone()),
+    )?;
+    let top_genres = top_entries(
+        conn,
+        &range,
+        "t.genre",
+        "play_events e JOIN tracks t ON t.id = e.track_id",
+        with_limit(range.params.clone()),
+    )?;
+
+    let top_albums = conn
+        .prepare(&format!(
+            "SELECT MAX(t.album_id), e.album, MAX(e.artist), SUM(e.counted) AS plays, SUM(e.listened_ms) AS ms
+             FROM play_events e LEFT JOIN tracks t ON t.id = e.track_id
+             WHERE {} AND e.album IS NOT NULL
+             GROUP BY e.album
+             HAVING plays > 0
+             ORDER BY plays DESC, ms DESC
+             LIMIT ?",
+            range.filter
+        ))?
+        .query_map(params_from_iter(with_limit(range.params.clone())), |row| {
+            Ok(TopAlbum {
+                album_id: row.get(0)?,
+                name: row.get(1)?,
+                artist: row.get(2)?,
+                plays: row.get(3)?,
+                listened_ms: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let by_hour = distribution(conn, &range, "%H", 24)?;
+    let by_weekday = distribution(conn, &range, "%w", 7)?;
+    let (longest_streak, current_streak) = streaks(conn, &range, query)?;
+
+    // First play ever, not just within the range
+    let mut new_artist_params = range.params.clone();
+    new_artist_params.push(limit);
+    let new_artists = conn
+        .prepare(&format!(
+            "SELECT e.artist, e.started_at, (SELECT SUM(counted) FROM play_events WHERE artist = e.artist)
+             FROM play_events e
+             WHERE {} AND e.artist IS NOT NULL
+               AND NOT EXISTS (SELECT 1 FROM play_events p WHERE p.artist = e.artist AND p.started_at < e.started_at)
+             GROUP BY e.artist
+             ORDER BY e.started_at
+             LIMIT ?",
+            range.filter
+        ))?
+        .query_map(params_from_iter(new_artist_params), |row| {
+            Ok(NewArtist {
+                name: row.get(0)?,
+                first_played: row.get(1)?,
+                plays: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ListeningStats {
+        total_listened_ms,
+        plays,
+        skips,
+        top_tracks,
+        top_artists,
+        top_albums,
+        top_genres,
+        by_hour,
+        by_weekday,
+        longest_streak,
+        current_streak,
+        new_artists,
+    })
+}
+
+/// Statistics for one calendar year in local time
+pub fn year_summary(conn: &Connection, year: i32, utc_offset_minutes: i32) -> Result<YearSummary> {
+    let query = StatsQuery {
+        since: Some(format!("{:04}-01-01", year)),
+        until: Some(format!("{:04}-01-01", year + 1)),
+        utc_offset_minutes,
+        limit: Some(5),
+    };
+    Ok(YearSummary {
+        year,
+        stats: listening_stats(conn, &query)?,
+    })
+}
+
+fn top_entries(
+    conn: &Connection,
+    range: &Range,
+    column: &str,
+    from: &str,
+    params: Vec<Value>,
+) -> Result<Vec<TopEntry>> {
+    conn.prepare(&format!(
+        "SELECT {0}, SUM(e.counted) AS plays, SUM(e.listened_ms) AS ms
+         FROM {1}
+         WHERE {2} AND {0} IS NOT NULL
+         GROUP BY {0}
+         HAVING plays > 0
+         ORDER BY plays DESC, ms DESC
+         LIMIT ?",
+        column, from, range.filter
+    ))?
+    .query_map(params_from_iter(params), |row| {
+        Ok(TopEntry {
+            name: row.get(0)?,
+            plays: row.get(1)?,
+            listened_ms: row.get(2)?,
+        })
+    })?
+    .collect()
+}
+
+/// Milliseconds listened per value of a `strftime` field in local time
+fn distribution(conn: &Connection, range: &Range, field: &str, buckets: usize) -> Result<Vec<i64>> {
+    let mut totals = vec![0; buckets];
+    let mut params = vec![Value::Text(field.to_string())];
+    params.extend(range.with_offset());
+    let mut stmt = conn.prepare(&format!(
+        "SELECT CAST(strftime(?, e.started_at, ?) AS INTEGER) AS bucket, SUM(e.listened_ms)
+         FROM play_events e WHERE {}
+         GROUP BY bucket",
+        range.filter
+    ))?;
+    let rows = stmt.query_map(params_from_iter(params), |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+    })?;
+    for row in rows {
+        let (bucket, ms) = row?;
+        if let Some(total) = totals.get_mut(bucket as usize) {
+            *total = ms;
+        }
+    }
+    Ok(totals)
+}
+
+/// Longest and current runs of consecutive local days with any playback
+fn streaks(
+    conn: &Connection,
+    range: &Range,
+    query: &StatsQuery,
+) -> Result<(Option<Streak>, Option<Streak>)> {
+    let mut params = range.with_offset();
+    params.insert(0, Value::Text(range.offset.clone()));
+    let days: Vec<(String, i64)> = conn
+        .prepare(&format!(
+            "SELECT date(e.started_at, ?) AS day, CAST(julianday(date(e.started_at, ?)) AS INTEGER)
+             FROM play_events e WHERE {}
+             GROUP BY day ORDER BY day",
+            range.filter
+        ))?
+        .query_map(params_from_iter(params), |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<_>>()?;
+
+    let mut runs: Vec<Streak> = Vec::new();
+    let mut previous = None;
+    for (day, number) in days {
+        match (runs.last_mut(), previous) {
+            (Some(run), Some(p)) if number == p + 1 => {
+                run.days += 1;
+                run.end = day;
+            }
+            _ => runs.push(Streak {
+                days: 1,
+                start: day.clone(),
+                end: day,
+            }),
+        }
+        previous = Some(number);
+    }
+
+    // Still running if the last day played is the range's last day (or today) or the one before
+    let reference: String = conn.query_row(
+        "SELECT date(COALESCE(datetime(?1, '-1 second'), datetime('now', ?2)), '-1 day')",
+        rusqlite::params![query.until, range.offset],
+        |row| row.get(0),
+    )?;
+    let current = runs.last().filter(|run| run.end >= reference).cloned();
+    let longest = runs
+        .into_iter()
+        .reduce(|best, run| if run.days > best.days { run } else { best });
+    Ok((longest, current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    fn history() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO tracks (id, path, title, artist, album, genre) VALUES
+                (1, '/m/1.flac', 'Airbag', 'Radiohead', 'OK Computer', 'Rock'),
+                (2, '/m/2.flac', 'Karma Police', 'Radiohead', 'OK Computer', 'Rock'),
+                (3, '/m/3.flac', 'Halo', 'Beyoncé', 'I Am', 'Pop');
+            INSERT INTO play_events (track_id, title, artist, album, started_at, listened_ms, counted, skipped) VALUES
+                (1, 'Airbag', 'Radiohead', 'OK Computer', '2023-12-31 23:30:00', 60000, 1, 0),
+                (1, 'Airbag', 'Radiohead', 'OK Computer', '2024-01-01 09:00:00', 60000, 1, 0),
+                (2, 'Karma Police', 'Radiohead', 'OK Computer', '2024-01-02 09:00:00', 60000, 1, 0),
+                (1, 'Airbag', 'Radiohead', 'OK Computer', '2024-01-03 22:30:00', 60000, 1, 0),
+                (3, 'Halo', 'Beyoncé', 'I Am', '2024-01-10 12:00:00', 5000, 0, 1),
+                (3, 'Halo', 'Beyoncé', 'I Am', '2024-01-10 12:05:00', 120000, 1, 0),
+                (NULL, 'Formation', 'Beyoncé', NULL, '2024-01-11 12:00:00', 200000, 1, 0);
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn january(utc_offset_minutes: i32) -> StatsQuery {
+        StatsQuery {
+            since: Some("2024-01-01".into()),
+            until: Some("2024-02-01".into()),
+            utc_offset_minutes,
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn totals_and_top_lists() {
+        let conn = history();
+        let stats = listening_stats(&conn, &january(0)).unwrap();
+
+        assert_eq!(stats.plays, 5);
+        assert_eq!(stats.skips, 1);
+        assert_eq!(stats.total_listened_ms, 505_000);
+        assert_eq!(stats.top_tracks[0].title.as_deref(), Some("Airbag"));
+        assert_eq!(stats.top_tracks[0].plays, 2);
+        assert!(stats
+            .top_tracks
+            .iter()
+            .any(|t| t.track_id.is_none() && t.title.as_deref() == Some("Formation")));
+        let artists: Vec<_> = stats
+            .top_artists
+            .iter()
+            .map(|a| (a.name.as_str(), a.plays))
+            .collect();
+        assert_eq!(artists, vec![("Radiohead", 3), ("Beyoncé", 2)]);
+        assert_eq!(stats.top_albums[0].name, "OK Computer");
+        assert_eq!(stats.top_albums[0].album_id, None);
+        let genres: Vec<_> = stats.top_genres.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(genres, vec!["Rock", "Pop"]);
+        assert_eq!(stats.by_hour[9], 120_000);
+        // 2024-01-01 was a Monday
+        assert_eq!(stats.by_weekday[1], 60_000);
+
+        let new: Vec<_> = stats.new_artists.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(new, vec!["Beyoncé"]);
+    }
+
+    #[test]
+    fn local_time_decides_days_and_hours() {
+        let conn = history();
+        // Two hours east of UTC the New Year's Eve play lands on January 1st
+        let stats = listening_stats(&conn, &january(120)).unwrap();
+        assert_eq!(stats.plays, 6);
+        assert_eq!(stats.by_hour[1], 60_000);
+        // ...and the late play on the 3rd moves to the 4th, breaking the run
+        assert_eq!(
+            stats.longest_streak,
+            Some(Streak {
+                days: 2,
+                start: "2024-01-01".into(),
+                end: "2024-01-02".into(),
+            })
+        );
+        assert_eq!(stats.current_streak, None);
+    }
+
+    #[test]
+    fn streaks_count_consecutive_days() {
+        let conn = history();
+        let stats = listening_stats(
+            &conn,
+            &StatsQuery {
+                until: Some("2024-01-12".into()),
+                ..january(0)
+            },
+        )
+        .unwrap();
+        assert_eq!(stats.longest_streak.map(|s| s.days), Some(3));
+        assert_eq!(
+            stats.current_streak.map(|s| (s.start, s.days)),
+            Some(("2024-01-10".into(), 2))
+        );
+
+        let summary = year_summary(&conn, 2023, 0).unwrap();
+        assert_eq!(summary.stats.plays, 1);
+        assert_eq!(summary.stats.new_artists[0].name, "Radiohead");
+    }
+}
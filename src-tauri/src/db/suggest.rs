@@ -0,0 +1,265 @@
+// Search-as-you-type suggestions across artists, albums, playlists and tracks
+use super::fuzzy;
+use super::queries;
+use super::search;
+use super::search_query::TextField;
+use crate::utils::fold_text;
+use rusqlite::{Connection, Result};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    Artist,
+    Album,
+    Playlist,
+    Track,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    /// Album, playlist or track id; artists are identified by `label`
+    pub id: Option<i64>,
+    pub label: String,
+    /// The artist, for albums and tracks
+    pub detail: Option<String>,
+    /// Tracks in the library, for artists and albums
+    pub track_count: Option<i64>,
+    /// Came from the typo-tolerant fallback rather than a prefix match
+    pub fuzzy: bool,
+    /// Higher is better: 3 = name starts with the prefix, 2 = a later word
+    /// does, 1 = matched elsewhere, below 1 = fuzzy similarity
+    pub score: f64,
+}
+
+/// Ranked suggestions for a partially typed `prefix`, at most `limit`.
+/// Prefix matches come first; near matches fill up the rest for typos.
+pub fn search_suggestions(conn: &Connection, prefix: &str, limit: usize) -> Result<Vec<Suggestion>> {
+    let folded = fold_text(prefix.trim());
+    if folded.is_empty() || limit == 0 {
+        return Ok(Vec::new());
+    }
+    let per_kind = limit as i32;
+    let mut suggestions = Vec::new();
+
+    if let Some(artists) = search::prefix_search(Some(TextField::Artist), prefix) {
+        let fts_match = artists.rank_match.unwrap_or_default();
+        for (name, count) in queries::suggest_artists(conn, &fts_match, per_kind)? {
+            suggestions.push(Suggestion {
+                kind: SuggestionKind::Artist,
+                id: None,
+                score: match_quality(&folded, &name),
+                label: name,
+                detail: None,
+                track_count: Some(count),
+                fuzzy: false,
+            });
+        }
+    }
+
+    if let Some(albums) = search::prefix_search(Some(TextField::Album), prefix) {
+        let fts_match = albums.rank_match.unwrap_or_default();
+        for album in queries::suggest_albums(conn, &fts_match, per_kind)? {
+            suggestions.push(Suggestion {
+                kind: SuggestionKind::Album,
+                id: Some(album.id),
+                score: match_quality(&folded, &album.name),
+                label: album.name,
+                detail: album.artist,
+                track_count: Some(album.track_count),
+                fuzzy: false,
+            });
+        }
+    }
+
+    for (id, name) in queries::get_playlist_names(conn)? {
+        if fold_text(&name).contains(&folded) {
+            suggestions.push(Suggestion {
+                kind: SuggestionKind::Playlist,
+                id: Some(id),
+                score: match_quality(&folded, &name),
+                label: name,
+                detail: None,
+                track_count: None,
+                fuzzy: false,
+            });
+        }
+    }
+
+    if let Some(titles) = search::prefix_search(Some(TextField::Title), prefix) {
+        for track in queries::search_tracks(conn, &titles, per_kind, 0)? {
+            let title = track.title.unwrap_or_default();
+            suggestions.push(Suggestion {
+                kind: SuggestionKind::Track,
+                id: Some(track.id),
+                score: match_quality(&folded, &title),
+                label: title,
+                detail: track.artist,
+                track_count: None,
+                fuzzy: false,
+            });
+        }
+    }
+
+    suggestions.sort_by(compare);
+    suggestions.truncate(limit);
+
+    if suggestions.len() < limit && folded.chars().count() >= 3 {
+        add_fuzzy_suggestions(conn, prefix, limit, &mut suggestions)?;
+    }
+    Ok(suggestions)
+}
+
+/// Best score first, then artists before albums before playlists before tracks,
+/// then the bigger artist or album
+fn compare(a: &Suggestion, b: &Suggestion) -> Ordering {
+    b.score
+        .partial_cmp(&a.score)
+        .unwrap_or(Ordering::Equal)
+        .then(a.kind.cmp(&b.kind))
+        .then(b.track_count.cmp(&a.track_count))
+}
+
+fn match_quality(folded_prefix: &str, name: &str) -> f64 {
+    let name = fold_text(name);
+    if name.starts_with(folded_prefix) {
+        return 3.0;
+    }
+    let starts_a_word = name.char_indices().any(|(i, c)| {
+        !c.is_alphanumeric() && name[i + c.len_utf8()..].starts_with(folded_prefix)
+    });
+    if starts_a_word {
+        2.0
+    } else {
+        1.0
+    }
+}
+
+/// Artists, albums and titles of near-matching tracks, skipping anything
+/// already suggested
+fn add_fuzzy_suggestions(
+    conn: &Connection,
+    prefix: &str,
+    limit: usize,
+    suggestions: &mut Vec<Suggestion>,
+) -> Result<()> {
+    let query_words = fuzzy::words(prefix);
+    let mut seen: HashSet<(SuggestionKind, String)> = suggestions
+        .iter()
+        .map(|s| (s.kind, fold_text(&s.label)))
+        .collect();
+    let mut extra = Vec::new();
+
+    for (track, _) in fuzzy::fuzzy_search_tracks(conn, prefix, limit)? {
+        let candidates = [
+            (SuggestionKind::Artist, None, track.artist.clone(), None),
+            (SuggestionKind::Album, track.album_id, track.album.clone(), track.artist.clone()),
+            (SuggestionKind::Track, Some(track.id), track.title.clone(), track.artist.clone()),
+        ];
+        for (kind, id, label, detail) in candidates {
+            let Some(label) = label else { continue };
+            let score = fuzzy::text_similarity(&query_words, &label);
+            if score < fuzzy::MIN_SIMILARITY || !seen.insert((kind, fold_text(&label))) {
+                continue;
+            }
+            extra.push(Suggestion {
+                kind,
+                id,
+                label,
+                detail,
+                track_count: None,
+                fuzzy: true,
+                score,
+            });
+        }
+    }
+
+    extra.sort_by(compare);
+    let room = limit - suggestions.len();
+    suggestions.extend(extra.into_iter().take(room));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+
+    fn library() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO albums (id, name, artist) VALUES (1, 'OK Computer', 'Radiohead'), (2, 'Kid A', 'Radiohead');
+            INSERT INTO tracks (path, title, artist, album, album_id) VALUES
+                ('/m/1.flac', 'Airbag', 'Radiohead', 'OK Computer', 1),
+                ('/m/2.flac', 'Karma Police', 'Radiohead', 'OK Computer', 1),
+                ('/m/3.flac', 'Idioteque', 'Radiohead', 'Kid A', 2),
+                ('/m/4.flac', 'Radio Ga Ga', 'Queen', 'The Works', NULL),
+                ('/m/5.flac', 'Video Killed the Radio Star', 'The Buggles', 'The Age of Plastic', NULL);
+            INSERT INTO playlists (name) VALUES ('Radio Edits'), ('Gym');
+            ",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn labels(suggestions: &[Suggestion]) -> Vec<(SuggestionKind, &str)> {
+        suggestions.iter().map(|s| (s.kind, s.label.as_str())).collect()
+    }
+
+    #[test]
+    fn ranks_name_prefixes_first() {
+        let conn = library();
+        let suggestions = search_suggestions(&conn, "radio", 10).unwrap();
+
+        assert_eq!(
+            labels(&suggestions),
+            vec![
+                (SuggestionKind::Artist, "Radiohead"),
+                (SuggestionKind::Playlist, "Radio Edits"),
+                (SuggestionKind::Track, "Radio Ga Ga"),
+                (SuggestionKind::Track, "Video Killed the Radio Star"),
+            ]
+        );
+        assert_eq!(suggestions[0].track_count, Some(3));
+        assert!(suggestions.iter().all(|s| !s.fuzzy));
+    }
+
+    #[test]
+    fn limit_applies_across_kinds() {
+        let conn = library();
+        let suggestions = search_suggestions(&conn, "radio", 2).unwrap();
+        assert_eq!(
+            labels(&suggestions),
+            vec![
+                (SuggestionKind::Artist, "Radiohead"),
+                (SuggestionKind::Playlist, "Radio Edits"),
+            ]
+        );
+    }
+
+    #[test]
+    fn typos_fall_back_to_near_matches() {
+        let conn = library();
+        let suggestions = search_suggestions(&conn, "radiohaed", 3).unwrap();
+
+        assert_eq!(suggestions[0].kind, SuggestionKind::Artist);
+        assert_eq!(suggestions[0].label, "Radiohead");
+        assert!(suggestions[0].fuzzy);
+
+        let albums = search_suggestions(&conn, "ok compter", 5).unwrap();
+        assert!(albums
+            .iter()
+            .any(|s| s.kind == SuggestionKind::Album && s.id == Some(1)));
+    }
+
+    #[test]
+    fn empty_prefix_suggests_nothing() {
+        let conn = library();
+        assert!(search_suggestions(&conn, "  ", 10).unwrap().is_empty());
+    }
+}
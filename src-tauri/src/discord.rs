@@ -4,6 +4,8 @@ use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 use tauri::State;
+
+use crate::error::AudionError;
 const DISCORD_APP_ID: &str = "1464631480251715676";
 
 pub struct DiscordState(pub Mutex<Option<DiscordIpcClient>>);
@@ -25,9 +27,9 @@ pub struct PresenceData {
 }
 
 #[tauri::command]
-pub fn discord_connect(state: State<DiscordState>) -> Result<String, String> {
+pub fn discord_connect(state: State<DiscordState>) -> Result<String, AudionError> {
     let mut client_guard = state.0.lock()
-        .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        .map_err(|e| AudionError::Internal(format!("Failed to acquire lock: {}", e)))?;
 
     // Don't reconnect if already connected
     if client_guard.is_some() {
@@ -40,7 +42,7 @@ pub fn discord_connect(state: State<DiscordState>) -> Result<String, String> {
     // Connect
     client
         .connect()
-        .map_err(|e| format!("Failed to connect: {}", e))?;
+        .map_err(|e| AudionError::Network(format!("Failed to connect: {}", e)))?;
 
     *client_guard = Some(client);
 
@@ -51,9 +53,9 @@ pub fn discord_connect(state: State<DiscordState>) -> Result<String, String> {
 pub fn discord_update_presence(
     state: State<DiscordState>,
     data: PresenceData,
-) -> Result<String, String> {
+) -> Result<String, AudionError> {
     let mut client_guard = state.0.lock()
-        .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        .map_err(|e| AudionError::Internal(format!("Failed to acquire lock: {}", e)))?;
 
     if let Some(client) = client_guard.as_mut() {
         // Format:
@@ -140,30 +142,30 @@ pub fn discord_update_presence(
 
         client
             .set_activity(activity)
-            .map_err(|e| format!("Failed to set activity: {}", e))?;
+            .map_err(|e| AudionError::Network(format!("Failed to set activity: {}", e)))?;
 
         Ok("Presence updated".to_string())
     } else {
-        Err("Not connected to Discord".to_string())
+        Err(AudionError::Unsupported("Not connected to Discord".to_string()))
     }
 }
 
 #[tauri::command]
-pub fn discord_clear_presence(state: State<DiscordState>) -> Result<String, String> {
+pub fn discord_clear_presence(state: State<DiscordState>) -> Result<String, AudionError> {
     let mut client_guard = state.0.lock().unwrap();
 
     if let Some(client) = client_guard.as_mut() {
         client
             .clear_activity()
-            .map_err(|e| format!("Failed to clear activity: {}", e))?;
+            .map_err(|e| AudionError::Network(format!("Failed to clear activity: {}", e)))?;
         Ok("Presence cleared".to_string())
     } else {
-        Err("Not connected to Discord".to_string())
+        Err(AudionError::Unsupported("Not connected to Discord".to_string()))
     }
 }
 
 #[tauri::command]
-pub fn discord_disconnect(state: State<DiscordState>) -> Result<String, String> {
+pub fn discord_disconnect(state: State<DiscordState>) -> Result<String, AudionError> {
     let mut client_guard = state.0.lock().unwrap();
 
     if let Some(mut client) = client_guard.take() {
@@ -175,7 +177,7 @@ pub fn discord_disconnect(state: State<DiscordState>) -> Result<String, String>
 }
 
 #[tauri::command]
-pub fn discord_reconnect(state: State<DiscordState>) -> Result<String, String> {
+pub fn discord_reconnect(state: State<DiscordState>) -> Result<String, AudionError> {
     discord_disconnect(state.clone())?;
     std::thread::sleep(std::time::Duration::from_millis(500));
     discord_connect(state)
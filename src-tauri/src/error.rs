@@ -0,0 +1,247 @@
+// Crate-wide error type returned by every Tauri command
+use crate::db::search_query::QueryError;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Errors surfaced to the frontend.
+///
+/// Serialized as `{ "code": "...", "message": "..." }`. The codes are stable and
+/// meant for the UI to branch on or localise; messages are English detail text.
+/// Search syntax errors also carry `details: { start, end }`, the character
+/// range of the problem in the query.
+#[derive(Debug, thiserror::Error)]
+pub enum AudionError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    /// The search query couldn't be parsed
+    #[error("{}", .0.message)]
+    InvalidQuery(QueryError),
+    #[error("{0}")]
+    PermissionDenied(String),
+    /// The database (or another exclusive resource) is busy; retrying may succeed
+    #[error("{0}")]
+    Locked(String),
+    /// A conflicting operation is already in progress
+    #[error("{0}")]
+    AlreadyRunning(String),
+    #[error("{0}")]
+    Unsupported(String),
+    #[error("{0}")]
+    Io(String),
+    #[error("{0}")]
+    Database(String),
+    #[error("{0}")]
+    Network(String),
+    /// Audio or tag data couldn't be decoded
+    #[error("{0}")]
+    Decode(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AudionError {
+    /// Stable machine-readable identifier for this kind of error
+    pub fn code(&self) -> &'static str {
+        match self {
+            AudionError::NotFound(_) => "not_found",
+            AudionError::InvalidInput(_) => "invalid_input",
+            AudionError::InvalidQuery(_) => "invalid_query",
+            AudionError::PermissionDenied(_) => "permission_denied",
+            AudionError::Locked(_) => "locked",
+            AudionError::AlreadyRunning(_) => "already_running",
+            AudionError::Unsupported(_) => "unsupported",
+            AudionError::Io(_) => "io",
+            AudionError::Database(_) => "database",
+            AudionError::Network(_) => "network",
+            AudionError::Decode(_) => "decode",
+            AudionError::Internal(_) => "internal",
+        }
+    }
+
+    fn message_mut(&mut self) -> &mut String {
+        match self {
+            AudionError::InvalidQuery(e) => &mut e.message,
+            AudionError::NotFound(m)
+            | AudionError::InvalidInput(m)
+            | AudionError::PermissionDenied(m)
+            | AudionError::Locked(m)
+            | AudionError::AlreadyRunning(m)
+            | AudionError::Unsupported(m)
+            | AudionError::Io(m)
+            | AudionError::Database(m)
+            | AudionError::Network(m)
+            | AudionError::Decode(m)
+            | AudionError::Internal(m) => m,
+        }
+    }
+
+    /// Prefix the message with what was being attempted, keeping the error kind
+    pub fn context(mut self, context: &str) -> Self {
+        let message = self.message_mut();
+        *message = format!("{}: {}", context, message);
+        self
+    }
+}
+
+impl Serialize for AudionError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let details = match self {
+            AudionError::InvalidQuery(e) => Some(&e.span),
+            _ => None,
+        };
+        let mut state =
+            serializer.serialize_struct("AudionError", 2 + details.is_some() as usize)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let Some(details) = details {
+            state.serialize_field("details", details)?;
+        }
+        state.end()
+    }
+}
+
+/// Attach context to any error that converts into `AudionError`
+pub trait ResultExt<T> {
+    fn context(self, context: &str) -> Result<T, AudionError>;
+}
+
+impl<T, E: Into<AudionError>> ResultExt<T> for Result<T, E> {
+    fn context(self, context: &str) -> Result<T, AudionError> {
+        self.map_err(|e| e.into().context(context))
+    }
+}
+
+impl From<rusqlite::Error> for AudionError {
+    fn from(e: rusqlite::Error) -> Self {
+        use rusqlite::ErrorCode;
+        match &e {
+            rusqlite::Error::QueryReturnedNoRows => AudionError::NotFound(e.to_string()),
+            rusqlite::Error::SqliteFailure(err, _)
+                if matches!(err.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) =>
+            {
+                AudionError::Locked(e.to_string())
+            }
+            rusqlite::Error::SqliteFailure(err, _)
+                if err.code == ErrorCode::ReadOnly || err.code == ErrorCode::PermissionDenied =>
+            {
+                AudionError::PermissionDenied(e.to_string())
+            }
+            _ => AudionError::Database(e.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for AudionError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => AudionError::NotFound(e.to_string()),
+            std::io::ErrorKind::PermissionDenied => AudionError::PermissionDenied(e.to_string()),
+            _ => AudionError::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AudionError {
+    fn from(e: reqwest::Error) -> Self {
+        AudionError::Network(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AudionError {
+    fn from(e: serde_json::Error) -> Self {
+        AudionError::InvalidInput(e.to_string())
+    }
+}
+
+impl From<lofty::error::LoftyError> for AudionError {
+    fn from(e: lofty::error::LoftyError) -> Self {
+        AudionError::Decode(e.to_string())
+    }
+}
+
+impl From<image::ImageError> for AudionError {
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(e) => e.into(),
+            image::ImageError::Decoding(_) => AudionError::Decode(e.to_string()),
+            image::ImageError::Unsupported(_) => AudionError::Unsupported(e.to_string()),
+            _ => AudionError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<QueryError> for AudionError {
+    fn from(e: QueryError) -> Self {
+        AudionError::InvalidQuery(e)
+    }
+}
+
+impl From<tauri::Error> for AudionError {
+    fn from(e: tauri::Error) -> Self {
+        AudionError::Internal(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_code_and_message() {
+        let err = AudionError::NotFound("Track 7 not found".to_string());
+        let json = serde_json::to_value(&err).unwrap();
+
+        assert_eq!(json["code"], "not_found");
+        assert_eq!(json["message"], "Track 7 not found");
+        assert!(json.get("details").is_none());
+    }
+
+    #[test]
+    fn query_errors_include_the_span() {
+        let err: AudionError = crate::db::search_query::parse("year:x").unwrap_err().into();
+        let json = serde_json::to_value(&err).unwrap();
+
+        assert_eq!(json["code"], "invalid_query");
+        assert_eq!(json["message"], "Invalid value 'x'");
+        assert_eq!(json["details"]["start"], 5);
+        assert_eq!(json["details"]["end"], 6);
+    }
+
+    #[test]
+    fn context_keeps_the_kind() {
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        let err: Result<(), _> = Err(io);
+        let err = err.context("Failed to save LRC file").unwrap_err();
+
+        assert_eq!(err.code(), "permission_denied");
+        assert_eq!(err.to_string(), "Failed to save LRC file: denied");
+    }
+
+    #[test]
+    fn helper_errors_keep_their_kind() {
+        let missing = crate::security::is_safe_path(std::path::Path::new("/no/such/track.flac"));
+        assert_eq!(missing.unwrap_err().code(), "not_found");
+
+        let dir = std::env::temp_dir();
+        let escaped = crate::utils::resolve_path(&dir, "../../escaped.flac");
+        assert_eq!(escaped.unwrap_err().code(), "permission_denied");
+
+        let cover = crate::scanner::cover_storage::save_track_cover_from_base64(1, "not base64!");
+        assert_eq!(cover.unwrap_err().code(), "invalid_input");
+    }
+
+    #[test]
+    fn sqlite_errors_are_classified() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let missing: AudionError = conn
+            .query_row("SELECT 1 WHERE 0", [], |row| row.get::<_, i64>(0))
+            .unwrap_err()
+            .into();
+        assert_eq!(missing.code(), "not_found");
+
+        let syntax: AudionError = conn.execute("NOT SQL", []).unwrap_err().into();
+        assert_eq!(syntax.code(), "database");
+    }
+}
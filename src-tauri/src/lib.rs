@@ -1,17 +1,23 @@
 // Audion - Local Spotify-style Music Player
 // Main library entry point
 
+mod analysis;
 mod commands;
 mod db;
 #[cfg(desktop)]
 mod discord;
+mod error;
 mod scanner;
 mod security;
+mod playlist_collage;
+mod playlist_files;
 mod utils;
+mod year_card;
 
 use db::Database;
 use std::path::PathBuf;
 use tauri::Manager;
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -45,11 +51,40 @@ pub fn run() {
             // Initialize cover storage app data directory (cross-platform)
             scanner::cover_storage::init_app_data_dir(app_dir.clone());
 
-            // Initialize database
-            let database = Database::new(&app_dir).expect("Failed to initialize database");
+            // Initialize database; on failure, unload the UI and exit once the error is dismissed
+            let database = match Database::new(&app_dir) {
+                Ok(database) => database,
+                Err(e) => {
+                    eprintln!("[DB] {}", e);
+                    if let Some(window) = app.get_webview_window("main") {
+                        window.hide().ok();
+                        window.navigate("about:blank".parse().unwrap()).ok();
+                    }
+                    let handle = app.handle().clone();
+                    app.dialog()
+                        .message(e.to_string())
+                        .title("Audion could not open your library")
+                        .kind(MessageDialogKind::Error)
+                        .show(move |_| handle.exit(1));
+                    return Ok(());
+                }
+            };
+
+            // Retention periods are otherwise only enforced when something else changes
+            if let Ok(conn) = database.write() {
+                if let Err(e) = db::removed::purge_expired(&conn) {
+                    eprintln!("[DB] Failed to purge removed tracks: {}", e);
+                }
+                if let Err(e) = db::playlist_history::purge_expired(&conn) {
+                    eprintln!("[DB] Failed to purge deleted playlists: {}", e);
+                }
+            }
 
             app.manage(database);
 
+            // Cancellation flag for library verification runs
+            app.manage(commands::VerificationState::default());
+
             // Initialize Discord RPC state (desktop only)
             #[cfg(desktop)]
             app.manage(discord::DiscordState(std::sync::Mutex::new(None)));
@@ -88,7 +123,74 @@ pub fn run() {
                     commands::get_library,
                     commands::get_tracks_paginated,
                     commands::get_albums_paginated,
+                    commands::browse_tracks,
+                    commands::browse_albums,
+                    commands::browse_artists,
+                    commands::get_sort_name_options,
+                    commands::set_sort_name_options,
                     commands::search_library,
+                    commands::rebuild_search_index,
+                    commands::search_suggestions,
+                    commands::search_grouped,
+                    commands::record_play_event,
+                    commands::get_play_history,
+                    commands::get_play_stats,
+                    commands::clear_play_history,
+                    commands::get_listening_stats,
+                    commands::get_on_this_day,
+                    commands::get_year_summary,
+                    commands::export_year_summary,
+                    commands::set_track_rating,
+                    commands::set_track_loved,
+                    commands::set_album_rating,
+                    commands::set_album_loved,
+                    commands::get_track_ratings,
+                    commands::get_album_ratings,
+                    commands::get_rating_options,
+                    commands::set_rating_options,
+            commands::create_smart_playlist,
+            commands::update_smart_playlist,
+            commands::get_smart_playlist_rules,
+            commands::refresh_smart_playlist,
+            commands::export_playlist,
+            commands::import_playlist,
+            commands::match_track_list,
+            commands::create_playlist_from_track_list,
+            commands::get_playlist_tree,
+            commands::create_playlist_folder,
+            commands::rename_playlist_folder,
+            commands::move_playlist_folder,
+            commands::move_playlist_to_folder,
+            commands::delete_playlist_folder,
+            commands::get_playlist_history,
+            commands::undo_playlist_changes,
+            commands::get_deleted_playlists,
+            commands::restore_playlist,
+            commands::delete_playlist_permanently,
+            commands::get_playlist_history_options,
+            commands::set_playlist_history_options,
+            commands::get_playlist_collage,
+            commands::save_play_queue,
+            commands::save_play_queue_position,
+            commands::load_play_queue,
+            commands::clear_play_queue,
+            commands::get_labels,
+            commands::get_item_labels,
+            commands::create_label,
+            commands::rename_label,
+            commands::set_label_color,
+            commands::merge_labels,
+            commands::delete_label,
+            commands::assign_labels,
+            commands::unassign_labels,
+            commands::trash_track_file,
+            commands::trash_album_files,
+            commands::get_removed_items,
+            commands::restore_track,
+            commands::restore_album,
+            commands::delete_removed_permanently,
+            commands::get_removed_options,
+            commands::set_removed_options,
                     commands::get_tracks_by_album,
                     commands::get_tracks_by_artist,
                     commands::get_album,
@@ -108,16 +210,31 @@ pub fn run() {
                     commands::covers::cleanup_orphaned_cover_files,
                     commands::covers::clear_base64_covers,
                     commands::covers::merge_duplicate_covers,
+                    // Analysis commands
+                    commands::analyze_track,
+                    commands::get_track_analysis,
+                    commands::get_transcode_suspects,
+                    commands::verify_library,
+                    commands::cancel_verification,
+                    commands::get_verification_results,
                     // Playlist commands
                     commands::create_playlist,
                     commands::get_playlists,
                     commands::get_playlist_tracks,
+                    commands::get_playlist_entries,
                     commands::add_track_to_playlist,
-                    commands::remove_track_from_playlist,
+                    commands::remove_playlist_entry,
                     commands::delete_playlist,
                     commands::rename_playlist,
                     commands::update_playlist_cover,
-                    commands::reorder_playlist_tracks,
+                    commands::move_playlist_entry,
+                    commands::add_tracks_to_playlist,
+                    commands::remove_playlist_entries,
+                    commands::move_playlist_entries,
+                    commands::dedupe_playlist,
+                    commands::sort_playlist,
+                    commands::shuffle_playlist,
+                    commands::replace_playlist_tracks,
                     // Lyrics commands
                     commands::save_lrc_file,
                     commands::load_lrc_file,
@@ -172,7 +289,74 @@ pub fn run() {
                     commands::get_library,
                     commands::get_tracks_paginated,
                     commands::get_albums_paginated,
+                    commands::browse_tracks,
+                    commands::browse_albums,
+                    commands::browse_artists,
+                    commands::get_sort_name_options,
+                    commands::set_sort_name_options,
                     commands::search_library,
+                    commands::rebuild_search_index,
+                    commands::search_suggestions,
+                    commands::search_grouped,
+                    commands::record_play_event,
+                    commands::get_play_history,
+                    commands::get_play_stats,
+                    commands::clear_play_history,
+                    commands::get_listening_stats,
+                    commands::get_on_this_day,
+                    commands::get_year_summary,
+                    commands::export_year_summary,
+                    commands::set_track_rating,
+                    commands::set_track_loved,
+                    commands::set_album_rating,
+                    commands::set_album_loved,
+                    commands::get_track_ratings,
+                    commands::get_album_ratings,
+                    commands::get_rating_options,
+                    commands::set_rating_options,
+            commands::create_smart_playlist,
+            commands::update_smart_playlist,
+            commands::get_smart_playlist_rules,
+            commands::refresh_smart_playlist,
+            commands::export_playlist,
+            commands::import_playlist,
+            commands::match_track_list,
+            commands::create_playlist_from_track_list,
+            commands::get_playlist_tree,
+            commands::create_playlist_folder,
+            commands::rename_playlist_folder,
+            commands::move_playlist_folder,
+            commands::move_playlist_to_folder,
+            commands::delete_playlist_folder,
+            commands::get_playlist_history,
+            commands::undo_playlist_changes,
+            commands::get_deleted_playlists,
+            commands::restore_playlist,
+            commands::delete_playlist_permanently,
+            commands::get_playlist_history_options,
+            commands::set_playlist_history_options,
+            commands::get_playlist_collage,
+            commands::save_play_queue,
+            commands::save_play_queue_position,
+            commands::load_play_queue,
+            commands::clear_play_queue,
+            commands::get_labels,
+            commands::get_item_labels,
+            commands::create_label,
+            commands::rename_label,
+            commands::set_label_color,
+            commands::merge_labels,
+            commands::delete_label,
+            commands::assign_labels,
+            commands::unassign_labels,
+            commands::trash_track_file,
+            commands::trash_album_files,
+            commands::get_removed_items,
+            commands::restore_track,
+            commands::restore_album,
+            commands::delete_removed_permanently,
+            commands::get_removed_options,
+            commands::set_removed_options,
                     commands::get_tracks_by_album,
                     commands::get_tracks_by_artist,
                     commands::get_album,
@@ -192,16 +376,31 @@ pub fn run() {
                     commands::covers::cleanup_orphaned_cover_files,
                     commands::covers::clear_base64_covers,
                     commands::covers::merge_duplicate_covers,
+                    // Analysis commands
+                    commands::analyze_track,
+                    commands::get_track_analysis,
+                    commands::get_transcode_suspects,
+                    commands::verify_library,
+                    commands::cancel_verification,
+                    commands::get_verification_results,
                     // Playlist commands
                     commands::create_playlist,
                     commands::get_playlists,
                     commands::get_playlist_tracks,
+                    commands::get_playlist_entries,
                     commands::add_track_to_playlist,
-                    commands::remove_track_from_playlist,
+                    commands::remove_playlist_entry,
                     commands::delete_playlist,
                     commands::rename_playlist,
                     commands::update_playlist_cover,
-                    commands::reorder_playlist_tracks,
+                    commands::move_playlist_entry,
+                    commands::add_tracks_to_playlist,
+                    commands::remove_playlist_entries,
+                    commands::move_playlist_entries,
+                    commands::dedupe_playlist,
+                    commands::sort_playlist,
+                    commands::shuffle_playlist,
+                    commands::replace_playlist_tracks,
                     // Lyrics commands
                     commands::save_lrc_file,
                     commands::load_lrc_file,
@@ -0,0 +1,226 @@
+// Generated playlist covers: a mosaic of the art of a playlist's first albums,
+// rendered as a PNG and cached under covers/playlists/. The file name carries
+// a hash of the art used, so a collage is redrawn only when that changes.
+use crate::error::{AudionError, ResultExt};
+use crate::scanner::cover_storage;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageReader, RgbImage};
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+const SIZE: u32 = 600;
+
+/// Art for up to this many albums is used (a 3x3 grid)
+const MAX_TILES: usize = 9;
+
+/// Art files of the playlist's first albums, in playlist order; tracks without
+/// album art fall back to their own cover. `None` when the playlist has a
+/// custom cover, which a collage never replaces.
+pub fn sources(conn: &Connection, playlist_id: i64) -> Result<Option<Vec<String>>, AudionError> {
+    let custom: Option<String> = conn
+        .query_row(
+            "SELECT cover_url FROM playlists WHERE id = ?1",
+            [playlist_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| AudionError::NotFound(format!("Playlist {} not found", playlist_id)))?;
+    if custom.is_some_and(|url| !url.is_empty()) {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT t.album_id, COALESCE(a.art_path, t.track_cover_path)
+         FROM playlist_tracks pt
+         JOIN tracks t ON t.id = pt.track_id
+         LEFT JOIN albums a ON a.id = t.album_id
+         WHERE pt.playlist_id = ?1 AND t.removed_at IS NULL AND COALESCE(a.art_path, t.track_cover_path) IS NOT NULL
+         ORDER BY pt.position, pt.id",
+    )?;
+    let mut rows = stmt.query([playlist_id])?;
+    let mut albums = HashSet::new();
+    let mut art = Vec::new();
+    while let Some(row) = rows.next()? {
+        let album_id: Option<i64> = row.get(0)?;
+        let path: String = row.get(1)?;
+        // Tracks without an album count as albums of their own
+        let album = album_id.map_or_else(|| path.clone(), |id| id.to_string());
+        if albums.contains(&album) || !Path::new(&path).exists() {
+            continue;
+        }
+        albums.insert(album);
+        art.push(path);
+        if art.len() == MAX_TILES {
+            break;
+        }
+    }
+    Ok(Some(art))
+}
+
+/// The collage of `art` for a playlist, rendered now unless it's cached.
+/// Collages of the playlist's earlier art are removed. `None` when none of the
+/// art can be read.
+pub fn cached(playlist_id: i64, art: &[String]) -> Result<Option<PathBuf>, AudionError> {
+    cached_in(
+        &cover_storage::get_playlists_covers_directory()?,
+        playlist_id,
+        art,
+    )
+}
+
+fn cached_in(dir: &Path, playlist_id: i64, art: &[String]) -> Result<Option<PathBuf>, AudionError> {
+    let prefix = format!("{}-", playlist_id);
+    let mut hasher = DefaultHasher::new();
+    art.hash(&mut hasher);
+    let path = dir.join(format!("{}{:016x}.png", prefix, hasher.finish()));
+    if !art.is_empty() && path.exists() {
+        return Ok(Some(path));
+    }
+
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                fs::remove_file(entry.path()).ok();
+            }
+        }
+    }
+    match render(art) {
+        Some(img) => {
+            img.save_with_format(&path, image::ImageFormat::Png)
+                .context("Failed to save playlist cover")?;
+            Ok(Some(path))
+        }
+        None => Ok(None),
+    }
+}
+
+/// A 3x3 grid from nine or more pictures, 2x2 from four or more, otherwise
+/// just the first. Files that can't be decoded are skipped.
+pub fn render(art: &[String]) -> Option<RgbImage> {
+    let tiles: Vec<DynamicImage> = art
+        .iter()
+        .filter_map(|path| load(path))
+        .take(MAX_TILES)
+        .collect();
+    let columns: u32 = match tiles.len() {
+        0 => return None,
+        1..=3 => 1,
+        4..=8 => 2,
+        _ => 3,
+    };
+
+    let tile = SIZE / columns;
+    let mut img = RgbImage::new(SIZE, SIZE);
+    for (i, picture) in (0..).zip(tiles.iter().take((columns * columns) as usize)) {
+        let scaled = picture
+            .resize_to_fill(tile, tile, FilterType::Triangle)
+            .to_rgb8();
+        let (x, y) = (i % columns * tile, i / columns * tile);
+        imageops::replace(&mut img, &scaled, x as i64, y as i64);
+    }
+    Some(img)
+}
+
+// Cover files keep the extension of the format they were saved in, but the
+// content is what counts
+fn load(path: &str) -> Option<DynamicImage> {
+    ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema;
+    use image::Rgb;
+
+    const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0]];
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("audion_collage_{}_{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_art(dir: &Path) -> Vec<String> {
+        COLORS
+            .iter()
+            .enumerate()
+            .map(|(i, color)| {
+                let path = dir.join(format!("art{}.png", i));
+                RgbImage::from_pixel(40, 30, Rgb(*color))
+                    .save_with_format(&path, image::ImageFormat::Png)
+                    .unwrap();
+                path.to_string_lossy().into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn picks_the_first_distinct_albums() {
+        let dir = temp_dir("sources");
+        let art = write_art(&dir);
+        let mut conn = Connection::open_in_memory().unwrap();
+        schema::init_schema(&mut conn, None).unwrap();
+        conn.execute_batch(&format!(
+            "
+            INSERT INTO albums (id, name, art_path) VALUES (1, 'One', '{}'), (2, 'Two', '{}'), (3, 'Gone', '/missing.png');
+            INSERT INTO tracks (id, path, album_id, track_cover_path) VALUES
+                (1, '/a.flac', 1, NULL), (2, '/b.flac', 2, NULL), (3, '/c.flac', 1, NULL),
+                (4, '/d.flac', 3, NULL), (5, '/e.flac', NULL, '{}');
+            INSERT INTO playlists (id, name) VALUES (1, 'Mix');
+            INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES
+                (1, 2, 0), (1, 3, 1), (1, 1, 2), (1, 4, 3), (1, 5, 4);
+            ",
+            art[0], art[1], art[2]
+        ))
+        .unwrap();
+
+        assert_eq!(
+            sources(&conn, 1).unwrap(),
+            Some(vec![art[1].clone(), art[0].clone(), art[2].clone()])
+        );
+
+        conn.execute("UPDATE playlists SET cover_url = 'custom.png'", [])
+            .unwrap();
+        assert_eq!(sources(&conn, 1).unwrap(), None);
+        assert!(matches!(sources(&conn, 2), Err(AudionError::NotFound(_))));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn caches_a_grid_until_the_art_changes() {
+        let dir = temp_dir("cache");
+        let art = write_art(&dir);
+
+        let grid = cached_in(&dir, 7, &art).unwrap().unwrap();
+        let img = image::open(&grid).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (SIZE, SIZE));
+        let quarter = SIZE / 4;
+        for (i, color) in (0..).zip(COLORS) {
+            let (x, y) = (i % 2 * SIZE / 2 + quarter, i / 2 * SIZE / 2 + quarter);
+            assert_eq!(*img.get_pixel(x, y), Rgb(color));
+        }
+        assert_eq!(cached_in(&dir, 7, &art).unwrap(), Some(grid.clone()));
+
+        // Fewer than four albums: the first one fills the cover
+        let single = cached_in(&dir, 7, &art[1..3]).unwrap().unwrap();
+        assert_ne!(single, grid);
+        assert!(!grid.exists());
+        let img = image::open(&single).unwrap().to_rgb8();
+        assert_eq!(*img.get_pixel(SIZE - 1, SIZE - 1), Rgb(COLORS[1]));
+
+        assert_eq!(cached_in(&dir, 7, &[]).unwrap(), None);
+        assert!(!single.exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,891 @@
+// Playlist files: writing and reading M3U8, PLS, XSPF and Audion's own JSON
+use crate::error::AudionError;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    M3u8,
+    Pls,
+    Xspf,
+    Json,
+}
+
+impl PlaylistFormat {
+    /// Guess the format from a file name; plain `.m3u` reads as M3U8
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "m3u" | "m3u8" => Some(PlaylistFormat::M3u8),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            "json" => Some(PlaylistFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// Write local paths relative to the playlist file, so the music and the
+    /// playlist can move together
+    pub relative_paths: bool,
+    /// M3U8 only: add `#EXTINF` lines with duration, artist and title
+    pub extended_info: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            relative_paths: false,
+            extended_info: true,
+        }
+    }
+}
+
+/// One track as a playlist file describes it. Every field is optional since
+/// most formats only carry some of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaylistItem {
+    pub path: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<i32>,
+    /// Seconds
+    pub duration: Option<i32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub source_type: Option<String>,
+    pub external_id: Option<String>,
+    pub isrc: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistFile {
+    pub name: Option<String>,
+    pub items: Vec<PlaylistItem>,
+}
+
+const JSON_FORMAT: &str = "audion-playlist";
+const JSON_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct PortablePlaylist {
+    format: String,
+    version: u32,
+    name: Option<String>,
+    tracks: Vec<PlaylistItem>,
+}
+
+/// Render a playlist to be saved at `target`, whose folder relative paths start from
+pub fn write(
+    format: PlaylistFormat,
+    playlist: &PlaylistFile,
+    target: &Path,
+    options: &ExportOptions,
+) -> Result<String, AudionError> {
+    let base = target.parent().unwrap_or(Path::new(""));
+    let location = |item: &PlaylistItem| {
+        let path = item.path.as_deref().unwrap_or_default();
+        if !options.relative_paths || is_url(path) {
+            return path.to_string();
+        }
+        relative_to(Path::new(path), base)
+            .map(|relative| relative.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string())
+    };
+
+    let mut out = String::new();
+    match format {
+        PlaylistFormat::M3u8 => {
+            if options.extended_info {
+                out.push_str("#EXTM3U\n");
+                if let Some(name) = &playlist.name {
+                    out.push_str(&format!("#PLAYLIST:{}\n", one_line(name)));
+                }
+            }
+            for item in &playlist.items {
+                if options.extended_info {
+                    out.push_str(&format!(
+                        "#EXTINF:{},{}\n",
+                        item.duration.unwrap_or(-1),
+                        one_line(&display_title(item))
+                    ));
+                }
+                out.push_str(&location(item));
+                out.push('\n');
+            }
+        }
+        PlaylistFormat::Pls => {
+            out.push_str("[playlist]\n");
+            for (i, item) in playlist.items.iter().enumerate() {
+                let n = i + 1;
+                out.push_str(&format!("File{}={}\n", n, location(item)));
+                out.push_str(&format!("Title{}={}\n", n, one_line(&display_title(item))));
+                out.push_str(&format!("Length{}={}\n", n, item.duration.unwrap_or(-1)));
+            }
+            out.push_str(&format!(
+                "NumberOfEntries={}\nVersion=2\n",
+                playlist.items.len()
+            ));
+        }
+        PlaylistFormat::Xspf => {
+            out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            out.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+            if let Some(name) = &playlist.name {
+                out.push_str(&format!("  <title>{}</title>\n", xml_escape(name)));
+            }
+            out.push_str("  <trackList>\n");
+            for item in &playlist.items {
+                out.push_str("    <track>\n");
+                let location = location(item);
+                let location = if is_url(&location) || !Path::new(&location).is_absolute() {
+                    percent_encode(&location)
+                } else {
+                    file_url(&location)
+                };
+                let fields = [
+                    ("location", Some(location)),
+                    ("title", item.title.clone()),
+                    ("creator", item.artist.clone()),
+                    ("album", item.album.clone()),
+                    ("trackNum", item.track_number.map(|n| n.to_string())),
+                    (
+                        "duration",
+                        item.duration.map(|s| (s as i64 * 1000).to_string()),
+                    ),
+                ];
+                for (tag, value) in fields {
+                    if let Some(value) = value {
+                        out.push_str(&format!("      <{0}>{1}</{0}>\n", tag, xml_escape(&value)));
+                    }
+                }
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+        }
+        PlaylistFormat::Json => {
+            let tracks = playlist
+                .items
+                .iter()
+                .map(|item| PlaylistItem {
+                    path: item.path.as_ref().map(|_| location(item)),
+                    ..item.clone()
+                })
+                .collect();
+            let portable = PortablePlaylist {
+                format: JSON_FORMAT.to_string(),
+                version: JSON_VERSION,
+                name: playlist.name.clone(),
+                tracks,
+            };
+            out = serde_json::to_string_pretty(&portable)
+                .map_err(|e| AudionError::Internal(format!("Failed to write playlist: {}", e)))?;
+        }
+    }
+    Ok(out)
+}
+
+/// Read a playlist file's contents. Relative locations are resolved against
+/// `base`, the folder the file was in.
+pub fn parse(format: PlaylistFormat, contents: &str, base: &Path) -> Result<PlaylistFile, String> {
+    let contents = contents.trim_start_matches('\u{feff}');
+    let mut playlist = match format {
+        PlaylistFormat::M3u8 => parse_m3u(contents),
+        PlaylistFormat::Pls => parse_pls(contents),
+        PlaylistFormat::Xspf => parse_xspf(contents),
+        PlaylistFormat::Json => {
+            let portable: PortablePlaylist = serde_json::from_str(contents)
+                .map_err(|e| format!("Not an Audion playlist: {}", e))?;
+            if portable.format != JSON_FORMAT {
+                return Err(format!("Unknown playlist format: {}", portable.format));
+            }
+            PlaylistFile {
+                name: portable.name,
+                items: portable.tracks,
+            }
+        }
+    };
+    for item in &mut playlist.items {
+        item.path = item
+            .path
+            .take()
+            .filter(|path| !path.trim().is_empty())
+            .map(|path| resolve_location(&path, base));
+    }
+    Ok(playlist)
+}
+
+/// Track lists exported from streaming services, one row per track
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackListFormat {
+    Csv,
+    Json,
+}
+
+impl TrackListFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" | "tsv" | "txt" => Some(TrackListFormat::Csv),
+            "json" => Some(TrackListFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ListField {
+    Title,
+    Artist,
+    Album,
+    Duration,
+    DurationMs,
+    Isrc,
+}
+
+/// Column or key names used by the common exporters, compared without case or punctuation
+fn list_field(name: &str) -> Option<ListField> {
+    let name: String = name
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    Some(match name.as_str() {
+        "title" | "name" | "track" | "trackname" | "tracktitle" | "song" | "songname" => {
+            ListField::Title
+        }
+        "artist" | "artists" | "artistname" | "artistnames" | "trackartist" | "creator" => {
+            ListField::Artist
+        }
+        "album" | "albumname" | "albumtitle" | "release" => ListField::Album,
+        "duration" | "length" | "time" | "durationseconds" => ListField::Duration,
+        "durationms" | "lengthms" | "durationmillis" => ListField::DurationMs,
+        "isrc" => ListField::Isrc,
+        _ => return None,
+    })
+}
+
+fn set_list_field(item: &mut PlaylistItem, field: ListField, value: &str) {
+    let value = value.trim();
+    if value.is_empty() {
+        return;
+    }
+    match field {
+        ListField::Title => item.title = Some(value.to_string()),
+        ListField::Artist => item.artist = Some(value.to_string()),
+        ListField::Album => item.album = Some(value.to_string()),
+        ListField::Duration => item.duration = parse_duration(value, false),
+        ListField::DurationMs => item.duration = parse_duration(value, true),
+        ListField::Isrc => item.isrc = Some(value.to_string()),
+    }
+}
+
+/// Seconds from "4:44", "1:02:03", "284" or "284000" (milliseconds)
+fn parse_duration(value: &str, millis: bool) -> Option<i32> {
+    let seconds = if value.contains(':') {
+        value.split(':').try_fold(0.0, |total, part| {
+            part.trim().parse::<f64>().ok().map(|n| total * 60.0 + n)
+        })?
+    } else {
+        let n: f64 = value.parse().ok()?;
+        // Some exports put milliseconds in a plain "duration" column; no
+        // track in a list like this is ten hours long
+        if millis || n > 36_000.0 {
+            n / 1000.0
+        } else {
+            n
+        }
+    };
+    (seconds >= 0.0).then(|| seconds.round() as i32)
+}
+
+/// Read a track list. Rows without a title are skipped.
+pub fn parse_track_list(
+    format: TrackListFormat,
+    contents: &str,
+) -> Result<Vec<PlaylistItem>, String> {
+    let contents = contents.trim_start_matches('\u{feff}');
+    let items = match format {
+        TrackListFormat::Csv => {
+            let mut records = csv_records(contents).into_iter();
+            let header: Vec<Option<ListField>> = records
+                .next()
+                .ok_or("The track list is empty")?
+                .iter()
+                .map(|name| list_field(name))
+                .collect();
+            if !header
+                .iter()
+                .any(|field| matches!(field, Some(ListField::Title)))
+            {
+                return Err("The track list has no title column".to_string());
+            }
+            records
+                .map(|record| {
+                    let mut item = PlaylistItem::default();
+                    for (field, value) in header.iter().zip(&record) {
+                        if let Some(field) = field {
+                            set_list_field(&mut item, *field, value);
+                        }
+                    }
+                    item
+                })
+                .collect::<Vec<_>>()
+        }
+        TrackListFormat::Json => {
+            let value: serde_json::Value =
+                serde_json::from_str(contents).map_err(|e| format!("Invalid JSON: {}", e))?;
+            let rows = match &value {
+                serde_json::Value::Array(rows) => rows,
+                serde_json::Value::Object(map) => map
+                    .get("tracks")
+                    .or_else(|| map.get("items"))
+                    .and_then(|rows| rows.as_array())
+                    .ok_or("Expected a list of tracks")?,
+                _ => return Err("Expected a list of tracks".to_string()),
+            };
+            rows.iter()
+                .map(|row| {
+                    // Spotify's API wraps each playlist item's track
+                    let row = row.get("track").filter(|t| t.is_object()).unwrap_or(row);
+                    let mut item = PlaylistItem::default();
+                    for (key, value) in row.as_object().into_iter().flatten() {
+                        if key == "external_ids" {
+                            if let Some(isrc) = value.get("isrc").and_then(|v| v.as_str()) {
+                                item.isrc = Some(isrc.to_string());
+                            }
+                        } else if let Some(field) = list_field(key) {
+                            set_list_field(&mut item, field, &json_text(value));
+                        }
+                    }
+                    item
+                })
+                .collect()
+        }
+    };
+    Ok(items
+        .into_iter()
+        .filter(|item| item.title.is_some())
+        .collect())
+}
+
+/// Text of a JSON value; lists of artists (names or objects) are joined with ", "
+fn json_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Number(number) => number.to_string(),
+        serde_json::Value::Array(values) => values
+            .iter()
+            .map(json_text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(", "),
+        serde_json::Value::Object(map) => map.get("name").map(json_text).unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// Split CSV text into records. Quoted fields may hold delimiters, doubled
+/// quotes and line breaks. The delimiter (comma, semicolon or tab) is
+/// whichever the header line has most of.
+fn csv_records(contents: &str) -> Vec<Vec<String>> {
+    let header = contents.lines().next().unwrap_or_default();
+    let delimiter = [',', ';', '\t']
+        .into_iter()
+        .max_by_key(|d| header.matches(*d).count())
+        .unwrap_or(',');
+
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if c == delimiter && !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|f| !f.is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    record.push(field);
+    if record.iter().any(|f| !f.is_empty()) {
+        records.push(record);
+    }
+    records
+}
+
+/// Decode a playlist file's bytes: UTF-8, falling back to Latin-1 for old `.m3u`s
+pub fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+fn parse_m3u(contents: &str) -> PlaylistFile {
+    let mut playlist = PlaylistFile::default();
+    let mut pending = PlaylistItem::default();
+    for line in contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (duration, title) = info.split_once(',').unwrap_or((info, ""));
+            pending.duration = duration
+                .split_whitespace()
+                .next()
+                .and_then(|d| d.parse::<f64>().ok())
+                .filter(|d| *d >= 0.0)
+                .map(|d| d.round() as i32);
+            split_display_title(title, &mut pending);
+        } else if let Some(name) = line.strip_prefix("#PLAYLIST:") {
+            playlist.name = Some(name.trim().to_string());
+        } else if !line.starts_with('#') {
+            pending.path = Some(line.to_string());
+            playlist.items.push(std::mem::take(&mut pending));
+        }
+    }
+    playlist
+}
+
+fn parse_pls(contents: &str) -> PlaylistFile {
+    let mut items: std::collections::BTreeMap<u32, PlaylistItem> = Default::default();
+    for line in contents.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let split = key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len());
+        let (field, number) = key.split_at(split);
+        let Ok(number) = number.parse() else {
+            continue;
+        };
+        let item = items.entry(number).or_default();
+        let value = value.trim();
+        match field {
+            "file" => item.path = Some(value.to_string()),
+            "title" => split_display_title(value, item),
+            "length" => item.duration = value.parse().ok().filter(|d| *d >= 0),
+            _ => {}
+        }
+    }
+    PlaylistFile {
+        name: None,
+        items: items
+            .into_values()
+            .filter(|item| item.path.is_some())
+            .collect(),
+    }
+}
+
+// There's no XML crate in the tree. XSPF is simple enough to pick apart by
+// tag, which also copes with the odd namespace prefix and extension block.
+fn parse_xspf(contents: &str) -> PlaylistFile {
+    let track_list_start = contents.find("<trackList").unwrap_or(contents.len());
+    let mut playlist = PlaylistFile {
+        name: element(&contents[..track_list_start], "title"),
+        items: Vec::new(),
+    };
+    let mut rest = &contents[track_list_start..];
+    while let Some(start) = rest.find("<track>").or_else(|| rest.find("<track ")) {
+        let end = rest[start..]
+            .find("</track>")
+            .map_or(rest.len(), |end| start + end);
+        let track = &rest[start..end];
+        playlist.items.push(PlaylistItem {
+            // Relative locations are URI references too
+            path: element(track, "location").map(|location| match is_url(&location) {
+                true => location,
+                false => percent_decode(&location),
+            }),
+            title: element(track, "title"),
+            artist: element(track, "creator"),
+            album: element(track, "album"),
+            track_number: element(track, "trackNum").and_then(|n| n.parse().ok()),
+            duration: element(track, "duration")
+                .and_then(|ms| ms.parse::<i64>().ok())
+                .map(|ms| ((ms + 500) / 1000) as i32),
+            ..Default::default()
+        });
+        rest = &rest[end..];
+    }
+    playlist
+}
+
+/// Text of the first `<tag>` in `xml`, unescaped
+fn element(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    let text = xml[start..end].trim();
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+        .map(str::to_string)
+        .unwrap_or_else(|| xml_unescape(text));
+    Some(text).filter(|t| !t.is_empty())
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else { break };
+        let decoded = match &rest[1..semi] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// "Artist - Title", the usual M3U/PLS way of putting both in one field
+fn display_title(item: &PlaylistItem) -> String {
+    match (&item.artist, &item.title) {
+        (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+        (None, Some(title)) => title.clone(),
+        (Some(artist), None) => artist.clone(),
+        (None, None) => item
+            .path
+            .as_deref()
+            .and_then(|path| Path::new(path).file_stem())
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    }
+}
+
+fn split_display_title(text: &str, item: &mut PlaylistItem) {
+    let text = text.trim();
+    match text.split_once(" - ") {
+        Some((artist, title)) => {
+            item.artist = Some(artist.trim().to_string());
+            item.title = Some(title.trim().to_string());
+        }
+        None if !text.is_empty() => item.title = Some(text.to_string()),
+        None => {}
+    }
+}
+
+fn one_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+fn is_url(location: &str) -> bool {
+    location.split_once("://").is_some_and(|(scheme, _)| {
+        scheme.len() > 1 && scheme.chars().all(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+/// `path` relative to the folder `base`, or `None` if they don't share a root
+fn relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() || !base.is_absolute() {
+        return None;
+    }
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    if path.first() != base.first() {
+        return None;
+    }
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    Some(relative)
+}
+
+/// Library path for a location read from a playlist file
+fn resolve_location(location: &str, base: &Path) -> String {
+    let location = location.trim();
+    let decoded;
+    let location = match location.strip_prefix("file://") {
+        Some(rest) => {
+            decoded = percent_decode(rest.strip_prefix("localhost").unwrap_or(rest));
+            // file:///C:/Music -> C:/Music
+            match decoded.as_bytes() {
+                [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => &decoded[1..],
+                _ => decoded.as_str(),
+            }
+        }
+        None if is_url(location) => return location.to_string(),
+        None => location,
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in base.join(location).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    resolved.to_string_lossy().into_owned()
+}
+
+fn file_url(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let path = if path.starts_with('/') {
+        path
+    } else {
+        format!("/{}", path)
+    };
+    format!("file://{}", percent_encode(&path))
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PlaylistFile {
+        PlaylistFile {
+            name: Some("Road Trip".into()),
+            items: vec![
+                PlaylistItem {
+                    path: Some("/music/Sigur Rós/01 Glósóli.flac".into()),
+                    title: Some("Glósóli".into()),
+                    artist: Some("Sigur Rós".into()),
+                    album: Some("Takk... & more".into()),
+                    track_number: Some(1),
+                    duration: Some(375),
+                    ..Default::default()
+                },
+                PlaylistItem {
+                    path: Some("tidal://track/123".into()),
+                    title: Some("Airbag".into()),
+                    artist: Some("Radiohead".into()),
+                    duration: Some(284),
+                    source_type: Some("tidal".into()),
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let target = Path::new("/music/playlists/trip.txt");
+        let base = target.parent().unwrap();
+        for relative_paths in [false, true] {
+            let options = ExportOptions {
+                relative_paths,
+                extended_info: true,
+            };
+            for format in [
+                PlaylistFormat::M3u8,
+                PlaylistFormat::Pls,
+                PlaylistFormat::Xspf,
+                PlaylistFormat::Json,
+            ] {
+                let text = write(format, &sample(), target, &options).unwrap();
+                let read = parse(format, &text, base).unwrap();
+                let expected = sample();
+
+                assert_eq!(read.items.len(), 2, "{:?}", format);
+                for (read, expected) in read.items.iter().zip(&expected.items) {
+                    assert_eq!(read.path, expected.path, "{:?} {}", format, text);
+                    assert_eq!(read.title, expected.title, "{:?}", format);
+                    assert_eq!(read.artist, expected.artist, "{:?}", format);
+                    assert_eq!(read.duration, expected.duration, "{:?}", format);
+                }
+                if format == PlaylistFormat::Json {
+                    assert_eq!(read, expected);
+                }
+                if format != PlaylistFormat::Pls {
+                    assert_eq!(read.name.as_deref(), Some("Road Trip"), "{:?}", format);
+                }
+            }
+        }
+
+        let relative = write(
+            PlaylistFormat::M3u8,
+            &sample(),
+            target,
+            &ExportOptions {
+                relative_paths: true,
+                extended_info: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            relative,
+            "../Sigur Rós/01 Glósóli.flac\ntidal://track/123\n"
+        );
+    }
+
+    #[test]
+    fn reads_playlists_from_other_players() {
+        let base = Path::new("/home/me/Music");
+        let m3u = "\u{feff}#EXTM3U\n#EXTINF:123.4 tvg-id=\"x\",Björk - Jóga\r\n\r\nBjork/Joga.mp3\n# comment\n./../Other/song.ogg\n";
+        let playlist = parse(PlaylistFormat::M3u8, m3u, base).unwrap();
+        assert_eq!(playlist.items.len(), 2);
+        assert_eq!(
+            playlist.items[0].path.as_deref(),
+            Some("/home/me/Music/Bjork/Joga.mp3")
+        );
+        assert_eq!(playlist.items[0].artist.as_deref(), Some("Björk"));
+        assert_eq!(playlist.items[0].duration, Some(123));
+        assert_eq!(
+            playlist.items[1].path.as_deref(),
+            Some("/home/me/Other/song.ogg")
+        );
+        assert_eq!(playlist.items[1].title, None);
+
+        let pls =
+            "[Playlist]\nfile2=/b.mp3\nFile1=/a.mp3\nTitle1=Intro\nLength1=-1\nNumberOfEntries=2\n";
+        let playlist = parse(PlaylistFormat::Pls, pls, base).unwrap();
+        let paths: Vec<_> = playlist
+            .items
+            .iter()
+            .map(|i| i.path.as_deref().unwrap())
+            .collect();
+        assert_eq!(paths, vec!["/a.mp3", "/b.mp3"]);
+        assert_eq!(playlist.items[0].title.as_deref(), Some("Intro"));
+        assert_eq!(playlist.items[0].duration, None);
+
+        let xspf = r#"<playlist><trackList>
+            <track><location>file:///home/me/Music/AC%2FDC%20&amp;%20Co.flac</location><title><![CDATA[T.N.T.]]></title></track>
+            <track><location>file:///C:/Music/a.mp3</location><creator>Sigur R&#243;s</creator></track>
+        </trackList></playlist>"#;
+        let playlist = parse(PlaylistFormat::Xspf, xspf, base).unwrap();
+        assert_eq!(playlist.name, None);
+        assert_eq!(
+            playlist.items[0].path.as_deref(),
+            Some("/home/me/Music/AC/DC & Co.flac")
+        );
+        assert_eq!(playlist.items[0].title.as_deref(), Some("T.N.T."));
+        assert_eq!(playlist.items[1].artist.as_deref(), Some("Sigur Rós"));
+
+        assert!(parse(
+            PlaylistFormat::Json,
+            "{\"format\":\"other\",\"version\":1,\"name\":null,\"tracks\":[]}",
+            base
+        )
+        .is_err());
+        assert_eq!(decode(b"caf\xe9"), "café");
+        assert_eq!(
+            PlaylistFormat::from_path(Path::new("Mix.M3U")),
+            Some(PlaylistFormat::M3u8)
+        );
+    }
+
+    #[test]
+    fn reads_streaming_service_track_lists() {
+        let csv = "\u{feff}\"Track URI\",\"Track Name\",\"Artist Name(s)\",\"Album Name\",\"Duration (ms)\",\"ISRC\"\r\n\
+            \"spotify:track:1\",\"Paranoid Android\",\"Radiohead\",\"OK Computer\",\"386733\",\"GBAYE9700123\"\r\n\
+            \"spotify:track:2\",\"Say \"\"Hi\"\", then\nleave\",\"A, B\",,\"\",\r\n\
+            \"spotify:track:3\",,\"No title\",,,\r\n";
+        let items = parse_track_list(TrackListFormat::Csv, csv).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title.as_deref(), Some("Paranoid Android"));
+        assert_eq!(items[0].duration, Some(387));
+        assert_eq!(items[0].isrc.as_deref(), Some("GBAYE9700123"));
+        assert_eq!(items[1].title.as_deref(), Some("Say \"Hi\", then\nleave"));
+        assert_eq!(items[1].artist.as_deref(), Some("A, B"));
+        assert_eq!(items[1].album, None);
+
+        let semicolons = "Title;Artist;Length\nJóga;Björk;5:05\n";
+        let items = parse_track_list(TrackListFormat::Csv, semicolons).unwrap();
+        assert_eq!(items[0].artist.as_deref(), Some("Björk"));
+        assert_eq!(items[0].duration, Some(305));
+        assert!(parse_track_list(TrackListFormat::Csv, "a,b\n1,2\n").is_err());
+
+        let json = r#"{"items": [
+            {"track": {"name": "Airbag", "artists": [{"name": "Radiohead"}], "album": {"name": "OK Computer"},
+                       "duration_ms": 284000, "external_ids": {"isrc": "GBAYE9700100"}}},
+            {"title": "Creep", "artist": "Radiohead", "duration": 238}
+        ]}"#;
+        let items = parse_track_list(TrackListFormat::Json, json).unwrap();
+        assert_eq!(items[0].artist.as_deref(), Some("Radiohead"));
+        assert_eq!(items[0].album.as_deref(), Some("OK Computer"));
+        assert_eq!(items[0].duration, Some(284));
+        assert_eq!(items[0].isrc.as_deref(), Some("GBAYE9700100"));
+        assert_eq!(items[1].duration, Some(238));
+    }
+}
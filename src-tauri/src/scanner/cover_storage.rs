@@ -5,6 +5,7 @@ use std::fs;
 use std::path::PathBuf;
 use std::collections::HashSet;
 use std::sync::OnceLock;
+use crate::error::{AudionError, ResultExt};
 use crate::security;
 
 /// App data directory set from Tauri's app.path().app_data_dir()
@@ -65,7 +66,7 @@ impl ImageFormat {
 /// Get the covers directory path
 /// Uses the app data dir set by Tauri (cross-platform),
 /// with fallback to APPDATA on Windows for backwards compatibility.
-pub fn get_covers_directory() -> std::result::Result<PathBuf, String> {
+pub fn get_covers_directory() -> std::result::Result<PathBuf, AudionError> {
     let base_dir = if let Some(dir) = APP_DATA_DIR.get() {
         // Use Tauri-provided app data dir (works on all platforms)
         dir.clone()
@@ -74,13 +75,13 @@ pub fn get_covers_directory() -> std::result::Result<PathBuf, String> {
         #[cfg(target_os = "windows")]
         {
             let app_data = std::env::var("APPDATA")
-                .map_err(|_| "Failed to get APPDATA environment variable".to_string())?;
+                .map_err(|_| AudionError::NotFound("Failed to get APPDATA environment variable".to_string()))?;
             PathBuf::from(app_data).join("com.audion.app")
         }
         #[cfg(not(target_os = "windows"))]
         {
             dirs::data_dir()
-                .ok_or_else(|| "Failed to get data directory".to_string())?
+                .ok_or_else(|| AudionError::NotFound("Failed to get data directory".to_string()))?
                 .join("com.audion.app")
         }
     };
@@ -89,87 +90,109 @@ pub fn get_covers_directory() -> std::result::Result<PathBuf, String> {
 
     // Create directories if they don't exist
     fs::create_dir_all(&covers_dir)
-        .map_err(|e| format!("Failed to create covers directory: {}", e))?;
+        .context("Failed to create covers directory")?;
 
     Ok(covers_dir)
 }
 
 /// Get the tracks covers subdirectory
-pub fn get_tracks_covers_directory() -> Result<PathBuf, String> {
+pub fn get_tracks_covers_directory() -> Result<PathBuf, AudionError> {
     let covers_dir = get_covers_directory()?;
     let tracks_dir = covers_dir.join("tracks");
     
     fs::create_dir_all(&tracks_dir)
-        .map_err(|e| format!("Failed to create tracks covers directory: {}", e))?;
+        .context("Failed to create tracks covers directory")?;
     
     Ok(tracks_dir)
 }
 
 /// Get the albums covers subdirectory
-pub fn get_albums_covers_directory() -> Result<PathBuf, String> {
+pub fn get_albums_covers_directory() -> Result<PathBuf, AudionError> {
     let covers_dir = get_covers_directory()?;
     let albums_dir = covers_dir.join("albums");
     
     fs::create_dir_all(&albums_dir)
-        .map_err(|e| format!("Failed to create albums covers directory: {}", e))?;
+        .context("Failed to create albums covers directory")?;
     
     Ok(albums_dir)
 }
 
+/// Get the playlists covers subdirectory (generated collages)
+pub fn get_playlists_covers_directory() -> Result<PathBuf, AudionError> {
+    let covers_dir = get_covers_directory()?;
+    let playlists_dir = covers_dir.join("playlists");
+
+    fs::create_dir_all(&playlists_dir)
+        .context("Failed to create playlists covers directory")?;
+
+    Ok(playlists_dir)
+}
+
+/// Get the cache subdirectory (generated images such as spectrograms)
+pub fn get_cache_directory() -> Result<PathBuf, AudionError> {
+    let covers_dir = get_covers_directory()?;
+    let cache_dir = covers_dir.join("cache");
+
+    fs::create_dir_all(&cache_dir)
+        .context("Failed to create cache directory")?;
+
+    Ok(cache_dir)
+}
+
 /// Save track cover image to file
 /// Returns the file path as a string
-pub fn save_track_cover(track_id: i64, image_data: &[u8]) -> Result<String, String> {
+pub fn save_track_cover(track_id: i64, image_data: &[u8]) -> Result<String, AudionError> {
     let tracks_dir = get_tracks_covers_directory()?;
     
     // Detect image format
     let format = ImageFormat::from_bytes(image_data)
-        .ok_or_else(|| "Unsupported or invalid image format".to_string())?;
+        .ok_or_else(|| AudionError::Unsupported("Unsupported or invalid image format".to_string()))?;
     
     let filename = format!("{}.{}", track_id, format.extension());
     let file_path = tracks_dir.join(&filename);
     
     // Write image data to file
     fs::write(&file_path, image_data)
-        .map_err(|e| format!("Failed to write cover file: {}", e))?;
+        .context("Failed to write cover file")?;
     
     Ok(file_path.to_string_lossy().to_string())
 }
 
 /// Save track cover from base64 string (for migration)
-pub fn save_track_cover_from_base64(track_id: i64, base64_data: &str) -> Result<String, String> {
+pub fn save_track_cover_from_base64(track_id: i64, base64_data: &str) -> Result<String, AudionError> {
     // Decode base64
     let image_bytes = STANDARD
         .decode(base64_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+        .map_err(|e| AudionError::InvalidInput(format!("Failed to decode base64: {}", e)))?;
     
     save_track_cover(track_id, &image_bytes)
 }
 
 /// Save album art image to file
 /// Returns the file path as a string
-pub fn save_album_art(album_id: i64, image_data: &[u8]) -> Result<String, String> {
+pub fn save_album_art(album_id: i64, image_data: &[u8]) -> Result<String, AudionError> {
     let albums_dir = get_albums_covers_directory()?;
     
     // Detect image format
     let format = ImageFormat::from_bytes(image_data)
-        .ok_or_else(|| "Unsupported or invalid image format".to_string())?;
+        .ok_or_else(|| AudionError::Unsupported("Unsupported or invalid image format".to_string()))?;
     
     let filename = format!("{}.{}", album_id, format.extension());
     let file_path = albums_dir.join(&filename);
     
     // Write image data to file
     fs::write(&file_path, image_data)
-        .map_err(|e| format!("Failed to write album art file: {}", e))?;
+        .context("Failed to write album art file")?;
     
     Ok(file_path.to_string_lossy().to_string())
 }
 
 /// Save album art from base64 string (for migration)
-pub fn save_album_art_from_base64(album_id: i64, base64_data: &str) -> Result<String, String> {
+pub fn save_album_art_from_base64(album_id: i64, base64_data: &str) -> Result<String, AudionError> {
     // Decode base64
     let image_bytes = STANDARD
         .decode(base64_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+        .map_err(|e| AudionError::InvalidInput(format!("Failed to decode base64: {}", e)))?;
     
     save_album_art(album_id, &image_bytes)
 }
@@ -217,7 +240,7 @@ pub fn get_album_art_file_path(conn: &Connection, album_id: i64) -> Result<Optio
 }
 
 /// Delete cover file for a track (uses secure deletion with logging)
-pub fn delete_track_cover_file(track_cover_path: Option<&str>) -> Result<(), String> {
+pub fn delete_track_cover_file(track_cover_path: Option<&str>) -> Result<(), AudionError> {
     if let Some(path) = track_cover_path {
         let path_obj = std::path::Path::new(path);
         if path_obj.exists() {
@@ -225,28 +248,28 @@ pub fn delete_track_cover_file(track_cover_path: Option<&str>) -> Result<(), Str
             // (secure deletion is for music files in user directories)
             log::debug!("[AUDIT] Deleting track cover file: {:?}", path_obj);
             fs::remove_file(path_obj)
-                .map_err(|e| format!("Failed to delete cover file: {}", e))?;
+                .context("Failed to delete cover file")?;
         }
     }
     Ok(())
 }
 
 /// Delete album art file (uses logging for audit trail)
-pub fn delete_album_art_file(art_path: Option<&str>) -> Result<(), String> {
+pub fn delete_album_art_file(art_path: Option<&str>) -> Result<(), AudionError> {
     if let Some(path) = art_path {
         let path_obj = std::path::Path::new(path);
         if path_obj.exists() {
             // Album art files are in app data directory, use direct deletion
             log::debug!("[AUDIT] Deleting album art file: {:?}", path_obj);
             fs::remove_file(path_obj)
-                .map_err(|e| format!("Failed to delete album art file: {}", e))?;
+                .context("Failed to delete album art file")?;
         }
     }
     Ok(())
 }
 
 /// Clean up orphaned cover files (covers without corresponding tracks/albums)
-pub fn cleanup_orphaned_covers(conn: &Connection) -> Result<usize, String> {
+pub fn cleanup_orphaned_covers(conn: &Connection) -> Result<usize, AudionError> {
     let mut deleted_count = 0;
     
     // 1: Load all valid IDs from database
@@ -255,13 +278,13 @@ pub fn cleanup_orphaned_covers(conn: &Connection) -> Result<usize, String> {
     let track_ids: HashSet<i64> = {
         let mut stmt = conn
             .prepare("SELECT id FROM tracks")
-            .map_err(|e| format!("Failed to prepare track IDs query: {}", e))?;
+            .context("Failed to prepare track IDs query")?;
         
         let ids = stmt
             .query_map([], |row| row.get(0))
-            .map_err(|e| format!("Failed to query track IDs: {}", e))?
+            .context("Failed to query track IDs")?
             .collect::<std::result::Result<HashSet<i64>, _>>()
-            .map_err(|e| format!("Failed to collect track IDs: {}", e))?;
+            .context("Failed to collect track IDs")?;
         
         ids
     };
@@ -270,13 +293,13 @@ pub fn cleanup_orphaned_covers(conn: &Connection) -> Result<usize, String> {
     let album_ids: HashSet<i64> = {
         let mut stmt = conn
             .prepare("SELECT id FROM albums")
-            .map_err(|e| format!("Failed to prepare album IDs query: {}", e))?;
+            .context("Failed to prepare album IDs query")?;
         
         let ids = stmt
             .query_map([], |row| row.get(0))
-            .map_err(|e| format!("Failed to query album IDs: {}", e))?
+            .context("Failed to query album IDs")?
             .collect::<std::result::Result<HashSet<i64>, _>>()
-            .map_err(|e| format!("Failed to collect album IDs: {}", e))?;
+            .context("Failed to collect album IDs")?;
         
         ids
     };
@@ -286,9 +309,9 @@ pub fn cleanup_orphaned_covers(conn: &Connection) -> Result<usize, String> {
     let tracks_dir = get_tracks_covers_directory()?;
     if tracks_dir.exists() {
         for entry in fs::read_dir(&tracks_dir)
-            .map_err(|e| format!("Failed to read tracks covers directory: {}", e))? 
+            .context("Failed to read tracks covers directory")? 
         {
-            let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+            let entry = entry.context("Failed to read directory entry")?;
             let path = entry.path();
             
             if path.is_file() {
@@ -315,9 +338,9 @@ pub fn cleanup_orphaned_covers(conn: &Connection) -> Result<usize, String> {
     let albums_dir = get_albums_covers_directory()?;
     if albums_dir.exists() {
         for entry in fs::read_dir(&albums_dir)
-            .map_err(|e| format!("Failed to read albums covers directory: {}", e))? 
+            .context("Failed to read albums covers directory")? 
         {
-            let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+            let entry = entry.context("Failed to read directory entry")?;
             let path = entry.path();
             
             if path.is_file() {
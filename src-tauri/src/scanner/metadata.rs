@@ -1,12 +1,23 @@
 // Audio metadata extraction using lofty
+use lofty::config::ParseOptions;
+use lofty::file::FileType;
+use lofty::id3::v2::{Frame, FrameFlags, Id3v2Tag, PopularimeterFrame};
 use lofty::prelude::*;
 use lofty::probe::Probe;
+use lofty::tag::{ItemValue, Tag, TagItem, TagType};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::path::Path;
 
+use crate::db::matching::normalize_isrc;
 use crate::db::queries::TrackInsert;
 
+/// Email written into POPM frames; the one most players read ratings from
+const POPM_EMAIL: &str = "Windows Media Player 9 Series";
+
+/// POPM bytes for 0.5 to 5 stars, as Windows Media Player and MusicBee write them
+const POPM_HALF_STARS: [u8; 10] = [13, 1, 54, 64, 118, 128, 186, 196, 242, 255];
+
 /// Generate a content hash based on metadata for duplicate detection
 fn generate_content_hash(
     title: Option<&str>,
@@ -66,6 +77,12 @@ pub fn extract_metadata(path: &str) -> Option<TrackInsert> {
         .primary_tag()
         .or_else(|| tagged_file.first_tag());
 
+    // POPM frames don't survive lofty's conversion to a generic tag, so ID3v2
+    // ratings need a second (tags only) read
+    let rating = read_id3v2(path, tagged_file.file_type())
+        .and_then(|id3v2| popm_rating(&id3v2))
+        .or_else(|| tag.and_then(read_rating));
+
     match tag {
         Some(tag) => {
             let title = tag
@@ -74,6 +91,23 @@ pub fn extract_metadata(path: &str) -> Option<TrackInsert> {
                 .or_else(|| get_filename_without_ext(path));
             let artist = tag.artist().map(|s| s.to_string());
             let album = tag.album().map(|s| s.to_string());
+            let year = tag.year().map(|y| y as i32);
+            let genre = tag.genre().map(|s| s.to_string());
+            let isrc = tag.get_string(&ItemKey::Isrc).and_then(normalize_isrc);
+
+            // Sort tags. ALBUMARTISTSORT only stands in for the artist's own
+            // sort tag when the album artist is the track artist.
+            let sort_title = tag.get_string(&ItemKey::TrackTitleSortOrder).map(|s| s.to_string());
+            let sort_album = tag.get_string(&ItemKey::AlbumTitleSortOrder).map(|s| s.to_string());
+            let sort_artist = tag
+                .get_string(&ItemKey::TrackArtistSortOrder)
+                .or_else(|| {
+                    let album_artist = tag.get_string(&ItemKey::AlbumArtist);
+                    (album_artist.is_some() && album_artist == artist.as_deref())
+                        .then(|| tag.get_string(&ItemKey::AlbumArtistSortOrder))
+                        .flatten()
+                })
+                .map(|s| s.to_string());
 
             // Extract track number, handling both simple numbers and "X/Y" format
             let track_number = tag.track().map(|n| n as i32)
@@ -115,6 +149,13 @@ pub fn extract_metadata(path: &str) -> Option<TrackInsert> {
                 album,
                 track_number,
                 duration: Some(duration),
+                year,
+                genre,
+                sort_title,
+                sort_artist,
+                sort_album,
+                rating,
+                isrc,
                 album_art,
                 track_cover,
                 format,
@@ -132,6 +173,7 @@ pub fn extract_metadata(path: &str) -> Option<TrackInsert> {
             track.duration = Some(duration);
             track.format = format;
             track.bitrate = bitrate;
+            track.rating = rating;
             // Generate content hash for fallback
             track.content_hash = Some(generate_content_hash(
                 track.title.as_deref(),
@@ -152,6 +194,13 @@ fn create_fallback_metadata(path: &Path) -> TrackInsert {
         album: None,
         track_number: None,
         duration: None,
+        year: None,
+        genre: None,
+        sort_title: None,
+        sort_artist: None,
+        sort_album: None,
+        rating: None,
+        isrc: None,
         album_art: None,
         track_cover: None,
         format: None,
@@ -164,6 +213,115 @@ fn create_fallback_metadata(path: &Path) -> TrackInsert {
     }
 }
 
+fn read_id3v2(path: &Path, file_type: FileType) -> Option<Id3v2Tag> {
+    let options = ParseOptions::new()
+        .read_properties(false)
+        .read_cover_art(false);
+    let mut file = std::fs::File::open(path).ok()?;
+    match file_type {
+        FileType::Mpeg => lofty::mpeg::MpegFile::read_from(&mut file, options)
+            .ok()?
+            .remove_id3v2(),
+        FileType::Wav => lofty::iff::wav::WavFile::read_from(&mut file, options)
+            .ok()?
+            .remove_id3v2(),
+        FileType::Aiff => lofty::iff::aiff::AiffFile::read_from(&mut file, options)
+            .ok()?
+            .remove_id3v2(),
+        _ => None,
+    }
+}
+
+fn popm_rating(tag: &Id3v2Tag) -> Option<f64> {
+    tag.into_iter().find_map(|frame| match frame {
+        Frame::Popularimeter(popm) => popm_to_stars(popm.rating),
+        _ => None,
+    })
+}
+
+/// Star rating from FMPS_RATING (0.0-1.0), or from RATING / MP4 "rate", which
+/// hold a percentage or, from some taggers, the stars themselves
+fn read_rating(tag: &Tag) -> Option<f64> {
+    tag.items().find_map(|item| match (item.key(), item.value()) {
+        (ItemKey::Unknown(key), ItemValue::Text(text)) if key.eq_ignore_ascii_case("FMPS_RATING") => {
+            text.trim().parse().ok().and_then(fraction_to_stars)
+        }
+        (ItemKey::Popularimeter, ItemValue::Text(text)) => {
+            let value: f64 = text.trim().parse().ok()?;
+            if value <= 5.0 {
+                fraction_to_stars(value / 5.0)
+            } else {
+                fraction_to_stars(value / 100.0)
+            }
+        }
+        (ItemKey::Popularimeter, ItemValue::Binary(bytes)) => {
+            PopularimeterFrame::parse(&mut &bytes[..], FrameFlags::default())
+                .ok()
+                .and_then(|popm| popm_to_stars(popm.rating))
+        }
+        _ => None,
+    })
+}
+
+/// Replace the rating tags; `None` or 0 stars removes them
+pub fn set_rating(tag: &mut Tag, stars: Option<f64>) {
+    tag.remove_key(&ItemKey::Popularimeter);
+    tag.retain(|item| !matches!(item.key(), ItemKey::Unknown(key) if key.eq_ignore_ascii_case("FMPS_RATING")));
+    let Some(stars) = stars.filter(|stars| *stars > 0.0) else {
+        return;
+    };
+
+    let fmps_key = match tag.tag_type() {
+        TagType::Id3v2 => {
+            let popm = PopularimeterFrame::new(POPM_EMAIL.to_string(), stars_to_popm(stars), 0);
+            if let Ok(bytes) = popm.as_bytes() {
+                tag.insert(TagItem::new(ItemKey::Popularimeter, ItemValue::Binary(bytes)));
+            }
+            Some("FMPS_Rating")
+        }
+        tag_type => {
+            tag.insert_text(ItemKey::Popularimeter, format!("{}", (stars * 20.0).round()));
+            (tag_type == TagType::VorbisComments).then_some("FMPS_RATING")
+        }
+    };
+    if let Some(key) = fmps_key {
+        tag.insert_unchecked(TagItem::new(
+            ItemKey::Unknown(key.to_string()),
+            ItemValue::Text(format!("{}", stars / 5.0)),
+        ));
+    }
+}
+
+/// 0 means "no rating"; bytes other players write are mapped by range
+fn popm_to_stars(byte: u8) -> Option<f64> {
+    let half_stars = match POPM_HALF_STARS.iter().position(|b| *b == byte) {
+        Some(i) => i + 1,
+        None => match byte {
+            0 => return None,
+            1..=31 => 2,
+            32..=95 => 4,
+            96..=159 => 6,
+            160..=223 => 8,
+            _ => 10,
+        },
+    };
+    Some(half_stars as f64 / 2.0)
+}
+
+fn stars_to_popm(stars: f64) -> u8 {
+    match (stars * 2.0).round() as usize {
+        0 => 0,
+        half_stars => POPM_HALF_STARS[half_stars.min(10) - 1],
+    }
+}
+
+/// Rounded to the nearest half star
+fn fraction_to_stars(fraction: f64) -> Option<f64> {
+    (0.0..=1.0)
+        .contains(&fraction)
+        .then(|| (fraction * 10.0).round() / 2.0)
+}
+
 fn get_filename_without_ext(path: &Path) -> Option<String> {
     path.file_stem()
         .and_then(|s| s.to_str())
@@ -185,4 +343,42 @@ mod tests {
             Some("artist - track".to_string())
         );
     }
+
+    #[test]
+    fn maps_popm_bytes_to_stars() {
+        assert_eq!(popm_to_stars(0), None);
+        assert_eq!(popm_to_stars(1), Some(1.0));
+        assert_eq!(popm_to_stars(13), Some(0.5));
+        assert_eq!(popm_to_stars(186), Some(3.5));
+        assert_eq!(popm_to_stars(255), Some(5.0));
+        // Written by other players
+        assert_eq!(popm_to_stars(100), Some(3.0));
+        for half_stars in 1..=10 {
+            let stars = half_stars as f64 / 2.0;
+            assert_eq!(popm_to_stars(stars_to_popm(stars)), Some(stars));
+        }
+    }
+
+    #[test]
+    fn reads_and_writes_rating_tags() {
+        for tag_type in [TagType::Id3v2, TagType::VorbisComments, TagType::Mp4Ilst] {
+            let mut tag = Tag::new(tag_type);
+            set_rating(&mut tag, Some(3.5));
+            assert_eq!(read_rating(&tag), Some(3.5), "{:?}", tag_type);
+            set_rating(&mut tag, None);
+            assert_eq!(read_rating(&tag), None, "{:?}", tag_type);
+        }
+
+        let mut vorbis = Tag::new(TagType::VorbisComments);
+        vorbis.insert_text(ItemKey::Popularimeter, "80".into());
+        assert_eq!(read_rating(&vorbis), Some(4.0));
+        vorbis.insert_text(ItemKey::Popularimeter, "4.5".into());
+        assert_eq!(read_rating(&vorbis), Some(4.5));
+        vorbis.remove_key(&ItemKey::Popularimeter);
+        vorbis.insert_unchecked(TagItem::new(
+            ItemKey::Unknown("FMPS_RATING".into()),
+            ItemValue::Text("0.6".into()),
+        ));
+        assert_eq!(read_rating(&vorbis), Some(3.0));
+    }
 }
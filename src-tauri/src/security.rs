@@ -1,6 +1,7 @@
 // Security utilities for file operations
 // Provides path validation, safe deletion (trash), and audit logging
 
+use crate::error::{AudionError, ResultExt};
 use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
@@ -21,11 +22,11 @@ pub fn add_allowed_dir(dir: PathBuf) {
 
 /// Validate that a path is within allowed directories
 /// Returns true if the path is safe to operate on
-pub fn is_safe_path(path: &Path) -> Result<bool, String> {
+pub fn is_safe_path(path: &Path) -> Result<bool, AudionError> {
     // Canonicalize the path to resolve symlinks and ../ traversals
-    let canonical = path.canonicalize().map_err(|e| {
-        format!("Failed to canonicalize path {:?}: {}", path, e)
-    })?;
+    let canonical = path
+        .canonicalize()
+        .context(&format!("Failed to canonicalize path {:?}", path))?;
 
     // Get allowed directories from the static, or return true if not initialized
     // (fallback for backward compatibility during transition)
@@ -55,7 +56,7 @@ pub fn is_safe_path(path: &Path) -> Result<bool, String> {
 
 /// Safely delete a file by moving it to trash instead of permanent deletion
 /// Returns Ok(true) if successfully trashed, Ok(false) if file didn't exist
-pub fn safe_delete_file(path: &Path) -> Result<bool, String> {
+pub fn safe_delete_file(path: &Path) -> Result<bool, AudionError> {
     if !path.exists() {
         log::debug!("[SECURITY] File does not exist, skipping deletion: {:?}", path);
         return Ok(false);
@@ -63,10 +64,10 @@ pub fn safe_delete_file(path: &Path) -> Result<bool, String> {
 
     // Validate path is within allowed directories
     if !is_safe_path(path)? {
-        return Err(format!(
+        return Err(AudionError::PermissionDenied(format!(
             "Security: Cannot delete file outside allowed directories: {:?}",
             path
-        ));
+        )));
     }
 
     // Log the deletion attempt
@@ -87,9 +88,7 @@ pub fn safe_delete_file(path: &Path) -> Result<bool, String> {
                 log::error!("[AUDIT] Failed to move file to trash: {:?} - {}", path, e);
                 // Fallback: try permanent deletion if trash fails (e.g., network drives)
                 log::warn!("[AUDIT] Attempting permanent deletion as fallback: {:?}", path);
-                std::fs::remove_file(path).map_err(|e| {
-                    format!("Failed to delete file {:?}: {}", path, e)
-                })?;
+                std::fs::remove_file(path).context(&format!("Failed to delete file {:?}", path))?;
                 log::info!("[AUDIT] File permanently deleted (trash unavailable): {:?}", path);
                 Ok(true)
             }
@@ -99,22 +98,20 @@ pub fn safe_delete_file(path: &Path) -> Result<bool, String> {
     // On mobile (Android/iOS), just delete directly - no trash API
     #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
     {
-        std::fs::remove_file(path).map_err(|e| {
-            format!("Failed to delete file {:?}: {}", path, e)
-        })?;
+        std::fs::remove_file(path).context(&format!("Failed to delete file {:?}", path))?;
         log::info!("[AUDIT] File permanently deleted: {:?}", path);
         Ok(true)
     }
 }
 
 /// Batch validate multiple paths
-pub fn validate_paths(paths: &[&Path]) -> Result<(), String> {
+pub fn validate_paths(paths: &[&Path]) -> Result<(), AudionError> {
     for path in paths {
         if !is_safe_path(path)? {
-            return Err(format!(
+            return Err(AudionError::PermissionDenied(format!(
                 "Security: Path {:?} is outside allowed directories",
                 path
-            ));
+            )));
         }
     }
     Ok(())
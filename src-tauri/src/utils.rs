@@ -1,7 +1,8 @@
+use crate::error::{AudionError, ResultExt};
 use std::path::{Path, PathBuf};
 
 /// Helper function to validate paths and prevent directory traversal
-pub fn resolve_path(base_dir: &Path, unsafe_path: &str) -> Result<PathBuf, String> {
+pub fn resolve_path(base_dir: &Path, unsafe_path: &str) -> Result<PathBuf, AudionError> {
     // Join the base directory with the unsafe path
     let full_path = base_dir.join(unsafe_path);
 
@@ -11,18 +12,16 @@ pub fn resolve_path(base_dir: &Path, unsafe_path: &str) -> Result<PathBuf, Strin
 
     // For existing paths, we can use canonicalize
     if full_path.exists() {
-        let canonical = full_path
-            .canonicalize()
-            .map_err(|e| format!("Invalid path: {}", e))?;
+        let canonical = full_path.canonicalize().context("Invalid path")?;
 
         // Ensure the canonical path starts with the base_dir
         // We also need to canonicalize base_dir to be sure
-        let canonical_base = base_dir
-            .canonicalize()
-            .map_err(|e| format!("Invalid base directory: {}", e))?;
+        let canonical_base = base_dir.canonicalize().context("Invalid base directory")?;
 
         if !canonical.starts_with(&canonical_base) {
-            return Err("Path traversal detected".to_string());
+            return Err(AudionError::PermissionDenied(
+                "Path traversal detected".to_string(),
+            ));
         }
         Ok(canonical)
     } else {
@@ -39,7 +38,9 @@ pub fn resolve_path(base_dir: &Path, unsafe_path: &str) -> Result<PathBuf, Strin
                 }
                 std::path::Component::ParentDir => {
                     if !normalized.pop() {
-                        return Err("Path traversal detected (out of bounds)".to_string());
+                        return Err(AudionError::PermissionDenied(
+                            "Path traversal detected (out of bounds)".to_string(),
+                        ));
                     }
                 }
                 std::path::Component::Normal(c) => normalized.push(c),
@@ -51,9 +52,22 @@ pub fn resolve_path(base_dir: &Path, unsafe_path: &str) -> Result<PathBuf, Strin
         // This is tricky without canonicalization, but we can check if it stays within
         // the string representation if we know base_dir is already safe.
         if !normalized.starts_with(base_dir) {
-            return Err("Path traversal detected".to_string());
+            return Err(AudionError::PermissionDenied(
+                "Path traversal detected".to_string(),
+            ));
         }
 
         Ok(normalized)
     }
 }
+
+/// Lowercase and strip accents ("Beyoncé" -> "beyonce") for comparing names
+pub fn fold_text(text: &str) -> String {
+    use unicode_normalization::char::is_combining_mark;
+    use unicode_normalization::UnicodeNormalization;
+
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
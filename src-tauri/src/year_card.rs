@@ -0,0 +1,242 @@
+// Shareable "year in music" card, rendered as a PNG
+use crate::db::stats::YearSummary;
+use crate::error::{AudionError, ResultExt};
+use crate::utils::fold_text;
+use image::{Rgb, RgbImage};
+use std::path::Path;
+
+const SIZE: u32 = 1080;
+const MARGIN: u32 = 80;
+
+const BACKGROUND_TOP: [u8; 3] = [24, 24, 38];
+const BACKGROUND_BOTTOM: [u8; 3] = [58, 22, 64];
+const TEXT: Rgb<u8> = Rgb([240, 240, 245]);
+const ACCENT: Rgb<u8> = Rgb([29, 185, 84]);
+const MUTED: Rgb<u8> = Rgb([160, 160, 175]);
+
+/// 5x7 glyphs for ' ' through 'Z', one byte per column with the top row in
+/// the lowest bit. There's no font crate in the tree and the card only needs
+/// capitals, digits and punctuation; anything else is drawn as '?'.
+const FONT: [[u8; 5]; 59] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x00, 0x08, 0x14, 0x22, 0x41], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x41, 0x22, 0x14, 0x08, 0x00], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3E, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x04, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x7F, 0x20, 0x18, 0x20, 0x7F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+];
+
+pub fn render(summary: &YearSummary) -> RgbImage {
+    let stats = &summary.stats;
+    let mut img = RgbImage::from_fn(SIZE, SIZE, |_, y| {
+        let t = y as f32 / SIZE as f32;
+        let mix = |i: usize| {
+            (BACKGROUND_TOP[i] as f32
+                + (BACKGROUND_BOTTOM[i] as f32 - BACKGROUND_TOP[i] as f32) * t) as u8
+        };
+        Rgb([mix(0), mix(1), mix(2)])
+    });
+
+    draw_text(&mut img, "AUDION", MARGIN, MARGIN, 4, ACCENT);
+    draw_text(
+        &mut img,
+        &format!("{} IN MUSIC", summary.year),
+        MARGIN,
+        130,
+        10,
+        TEXT,
+    );
+
+    let hours = stats.total_listened_ms / 3_600_000;
+    draw_text(
+        &mut img,
+        &format!("{} HOURS LISTENED", hours),
+        MARGIN,
+        250,
+        6,
+        TEXT,
+    );
+    draw_text(
+        &mut img,
+        &format!("{} PLAYS", stats.plays),
+        MARGIN,
+        310,
+        6,
+        TEXT,
+    );
+
+    let mut y = 400;
+    draw_text(&mut img, "TOP ARTISTS", MARGIN, y, 4, ACCENT);
+    for (i, artist) in stats.top_artists.iter().take(5).enumerate() {
+        y += 45;
+        draw_text(
+            &mut img,
+            &format!("{}. {}", i + 1, artist.name),
+            MARGIN,
+            y,
+            5,
+            TEXT,
+        );
+    }
+
+    y = y.max(400 + 5 * 45) + 70;
+    draw_text(&mut img, "TOP TRACKS", MARGIN, y, 4, ACCENT);
+    for (i, track) in stats.top_tracks.iter().take(5).enumerate() {
+        y += 45;
+        let line = match (&track.title, &track.artist) {
+            (Some(title), Some(artist)) => format!("{}. {} - {}", i + 1, title, artist),
+            (title, _) => format!("{}. {}", i + 1, title.as_deref().unwrap_or("Unknown")),
+        };
+        draw_text(&mut img, &line, MARGIN, y, 5, TEXT);
+    }
+
+    let mut footer = Vec::new();
+    if let Some(genre) = stats.top_genres.first() {
+        footer.push(format!("TOP GENRE: {}", genre.name));
+    }
+    if let Some(streak) = &stats.longest_streak {
+        footer.push(format!("LONGEST STREAK: {} DAYS", streak.days));
+    }
+    draw_text(
+        &mut img,
+        &footer.join("   "),
+        MARGIN,
+        SIZE - MARGIN - 28,
+        4,
+        MUTED,
+    );
+
+    img
+}
+
+pub fn save_png(summary: &YearSummary, path: &Path) -> Result<(), AudionError> {
+    render(summary)
+        .save_with_format(path, image::ImageFormat::Png)
+        .context("Failed to save image")
+}
+
+/// Draw one line of text, cut off with "..." where it would cross the right margin
+fn draw_text(img: &mut RgbImage, text: &str, x: u32, y: u32, scale: u32, color: Rgb<u8>) {
+    let advance = 6 * scale;
+    let fits = ((SIZE - MARGIN - x) / advance) as usize;
+    let mut chars: Vec<char> = fold_text(text).to_uppercase().chars().collect();
+    if chars.len() > fits {
+        chars.truncate(fits.saturating_sub(3));
+        chars.extend("...".chars());
+    }
+
+    for (i, c) in chars.into_iter().enumerate() {
+        let glyph = (c as u32)
+            .checked_sub(' ' as u32)
+            .and_then(|index| FONT.get(index as usize))
+            .unwrap_or(&FONT['?' as usize - ' ' as usize]);
+        let left = x + i as u32 * advance;
+        for (column, bits) in glyph.iter().enumerate() {
+            for row in (0..7).filter(|row| bits & (1 << row) != 0) {
+                for dx in 0..scale {
+                    for dy in 0..scale {
+                        img.put_pixel(
+                            left + column as u32 * scale + dx,
+                            y + row * scale + dy,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::stats::{ListeningStats, TopEntry};
+
+    #[test]
+    fn renders_a_readable_png() {
+        let summary = YearSummary {
+            year: 2024,
+            stats: ListeningStats {
+                total_listened_ms: 90 * 3_600_000,
+                plays: 1234,
+                skips: 0,
+                top_tracks: Vec::new(),
+                top_artists: vec![TopEntry {
+                    name: "Sigur Rós, with a name far too long to fit on one line".into(),
+                    plays: 10,
+                    listened_ms: 1,
+                }],
+                top_albums: Vec::new(),
+                top_genres: Vec::new(),
+                by_hour: vec![0; 24],
+                by_weekday: vec![0; 7],
+                longest_streak: None,
+                current_streak: None,
+                new_artists: Vec::new(),
+            },
+        };
+
+        let path =
+            std::env::temp_dir().join(format!("audion_year_card_{}.png", std::process::id()));
+        save_png(&summary, &path).unwrap();
+        let img = image::open(&path).unwrap().to_rgb8();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(img.dimensions(), (SIZE, SIZE));
+        assert!(img.pixels().any(|p| *p == TEXT));
+        assert!(img.pixels().any(|p| *p == ACCENT));
+    }
+}
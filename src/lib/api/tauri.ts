@@ -35,9 +35,81 @@ async function ensureTauriLoaded() {
     }
 }
 
+// Stable error codes returned by backend commands (see src-tauri/src/error.rs)
+export type AudionErrorCode =
+    | 'not_found'
+    | 'invalid_input'
+    | 'invalid_query'
+    | 'permission_denied'
+    | 'locked'
+    | 'already_running'
+    | 'unsupported'
+    | 'io'
+    | 'database'
+    | 'network'
+    | 'decode'
+    | 'internal';
+
+// Error thrown when a backend command fails. `message` is English detail text;
+// branch on `code` (or use it to pick a localised message) instead of parsing it.
+export class AudionError extends Error {
+    readonly code: AudionErrorCode;
+    // For 'invalid_query': the character range of the problem in the query
+    readonly details?: { start: number; end: number };
+
+    constructor(code: AudionErrorCode, message: string, details?: { start: number; end: number }) {
+        super(message);
+        this.name = 'AudionError';
+        this.code = code;
+        this.details = details;
+    }
+
+    toString(): string {
+        return this.message;
+    }
+}
+
+// Convert a rejected command's `{ code, message }` payload into an AudionError.
+// Anything else (including errors raised by Tauri itself) is returned unchanged.
+export function toAudionError(err: unknown): unknown {
+    if (
+        err !== null &&
+        typeof err === 'object' &&
+        !(err instanceof Error) &&
+        typeof (err as { code?: unknown }).code === 'string' &&
+        typeof (err as { message?: unknown }).message === 'string'
+    ) {
+        const { code, message, details } = err as {
+            code: AudionErrorCode;
+            message: string;
+            details?: { start: number; end: number };
+        };
+        return new AudionError(code, message, details);
+    }
+    return err;
+}
+
+export function isAudionError(err: unknown, code?: AudionErrorCode): err is AudionError {
+    return err instanceof AudionError && (code === undefined || err.code === code);
+}
+
 async function invoke<T>(cmd: string, args?: Record<string, unknown>): Promise<T> {
     await ensureTauriLoaded();
-    return invokeFunc!(cmd, args);
+    try {
+        return await invokeFunc!(cmd, args);
+    } catch (err) {
+        throw toAudionError(err);
+    }
+}
+
+// For the plugin API: failures reject with the message text, as commands did
+// before they returned `{ code, message }`, so existing plugins keep working.
+export async function invokeForPlugin<T>(cmd: string, args?: Record<string, unknown>): Promise<T> {
+    try {
+        return await invoke<T>(cmd, args);
+    } catch (err) {
+        throw err instanceof AudionError ? err.message : err;
+    }
 }
 
 export function convertFileSrc(filePath: string): string {
@@ -93,6 +165,30 @@ export interface Playlist {
     id: number;
     name: string;
     created_at: string | null;
+    kind: 'static' | 'smart';
+    folder_id: number | null;
+}
+
+export interface PlaylistFolder {
+    id: number;
+    name: string;
+    parent_id: number | null;
+    created_at: string | null;
+    folders: PlaylistFolder[];
+    playlists: Playlist[];
+}
+
+/** Every folder and playlist; at each level folders come first */
+export interface PlaylistTree {
+    folders: PlaylistFolder[];
+    playlists: Playlist[];
+}
+
+/** A track's place in a playlist; the same track can have several */
+export interface PlaylistEntry extends Track {
+    entry_id: number;
+    // Index among the listed entries (those of removed tracks aren't listed)
+    position: number;
 }
 
 export interface Library {
@@ -139,6 +235,53 @@ export interface MergeCoverResult {
     errors: string[];
 }
 
+export type AnalysisVerdict = 'lossless' | 'lossy_transcode' | 'upsampled' | 'inconclusive';
+
+export interface TrackAnalysis {
+    track_id: number;
+    verdict: AnalysisVerdict;
+    confidence: number;
+    cutoff_hz: number | null;
+    sample_rate: number | null;
+    bits_per_sample: number | null;
+    reasons: string[];
+    spectrogram_path: string | null;
+    analyzed_at: string | null;
+}
+
+export type VerifyStatus = 'ok' | 'checksum_mismatch' | 'truncated' | 'decode_errors' | 'unreadable';
+
+export interface VerificationResult {
+    track_id: number;
+    path: string;
+    status: VerifyStatus;
+    decode_error_count: number;
+    errors: string[];
+    expected_frames: number | null;
+    decoded_frames: number | null;
+    checksum_ok: boolean | null;
+    verified_at: string | null;
+}
+
+// Payload of the verify-progress event
+export interface VerifyProgress {
+    current: number;
+    total: number;
+    problems: number;
+    current_path: string | null;
+    estimated_time_remaining_ms: number;
+}
+
+// Returned by verifyLibrary and emitted as verify-complete
+export interface VerifySummary {
+    total: number;
+    verified: number;
+    ok: number;
+    problems: number;
+    cancelled: boolean;
+    errors: string[];
+}
+
 // Library commands
 export async function scanMusic(paths: string[]): Promise<ScanResult> {
     return await invoke('scan_music', { paths });
@@ -168,10 +311,419 @@ export async function getAlbumsPaginated(limit: number, offset: number): Promise
     return await invoke<Album[]>("get_albums_paginated", { limit, offset });
 }
 
+export type SortDirection = 'asc' | 'desc';
+export type TrackSort = 'artist' | 'album' | 'title' | 'date_added' | 'duration' | 'bitrate' | 'year' | 'play_count' | 'last_played' | 'rating';
+export type AlbumSort = 'artist' | 'name' | 'added';
+export type ArtistSort = 'name' | 'track_count';
+
+export interface TrackFilter {
+    format?: string;
+    source_type?: string;  // 'local' also matches tracks without a source type
+    album_id?: number;
+    folder?: string;
+    min_bitrate?: number;
+    min_rating?: number;
+    loved?: boolean;
+    label_id?: number;  // labelled directly or through the album
+}
+
+export interface AlbumFilter {
+    artist?: string;
+    min_rating?: number;
+    loved?: boolean;
+}
+
+export interface PageRequest<S> {
+    sort?: S;
+    direction?: SortDirection;
+    cursor?: string | null;  // `next_cursor` of the previous page
+    limit?: number;
+}
+
+export interface Page<T> {
+    items: T[];
+    next_cursor: string | null;  // null on the last page
+}
+
+export async function browseTracks(request: PageRequest<TrackSort> & { filter?: TrackFilter }): Promise<Page<Track>> {
+    return await invoke('browse_tracks', { request });
+}
+
+export async function browseAlbums(request: PageRequest<AlbumSort> & { filter?: AlbumFilter }): Promise<Page<Album>> {
+    return await invoke('browse_albums', { request });
+}
+
+export async function browseArtists(request: PageRequest<ArtistSort>): Promise<Page<Artist>> {
+    return await invoke('browse_artists', { request });
+}
+
+export interface SortNameOptions {
+    articles: string[];  // leading words ignored when sorting, e.g. "the"
+}
+
+export async function getSortNameOptions(): Promise<SortNameOptions> {
+    return await invoke('get_sort_name_options');
+}
+
+export async function setSortNameOptions(options: SortNameOptions): Promise<void> {
+    return await invoke('set_sort_name_options', { options });
+}
+
+export interface TrackRating {
+    track_id: number;
+    rating: number | null;  // 0-5 in half steps, null when unrated
+    loved: boolean;
+}
+
+export interface AlbumRating {
+    album_id: number;
+    rating: number | null;
+    loved: boolean;
+}
+
+export interface RatingOptions {
+    write_to_files: boolean;  // also save track ratings in the files' tags
+}
+
+export async function setTrackRating(trackId: number, rating: number | null): Promise<void> {
+    return await invoke('set_track_rating', { trackId, rating });
+}
+
+export async function setTrackLoved(trackId: number, loved: boolean): Promise<void> {
+    return await invoke('set_track_loved', { trackId, loved });
+}
+
+export async function setAlbumRating(albumId: number, rating: number | null): Promise<void> {
+    return await invoke('set_album_rating', { albumId, rating });
+}
+
+export async function setAlbumLoved(albumId: number, loved: boolean): Promise<void> {
+    return await invoke('set_album_loved', { albumId, loved });
+}
+
+export async function getTrackRatings(trackIds: number[]): Promise<TrackRating[]> {
+    return await invoke('get_track_ratings', { trackIds });
+}
+
+export async function getAlbumRatings(albumIds: number[]): Promise<AlbumRating[]> {
+    return await invoke('get_album_ratings', { albumIds });
+}
+
+export async function getRatingOptions(): Promise<RatingOptions> {
+    return await invoke('get_rating_options');
+}
+
+export async function setRatingOptions(options: RatingOptions): Promise<void> {
+    return await invoke('set_rating_options', { options });
+}
+
+export type PlaySource = 'library' | 'album' | 'artist' | 'playlist' | 'search' | 'queue' | 'other';
+
+export interface NewPlayEvent {
+    track_id?: number | null;
+    external_id?: string | null;  // for streamed tracks not in the library
+    track_source?: string | null;
+    title?: string | null;
+    artist?: string | null;
+    album?: string | null;
+    duration_ms?: number | null;
+    started_at?: string | null;  // UTC 'YYYY-MM-DD HH:MM:SS', defaults to now
+    listened_ms: number;
+    completed: boolean;
+    skipped: boolean;
+    source?: PlaySource | null;
+    source_id?: string | null;  // playlist/album id, search query...
+}
+
+export interface PlayEvent {
+    id: number;
+    track_id: number | null;  // null when the track isn't in the library
+    title: string | null;
+    artist: string | null;
+    album: string | null;
+    track_source: string | null;
+    external_id: string | null;
+    started_at: string;
+    listened_ms: number;
+    completed: boolean;
+    skipped: boolean;
+    counted: boolean;  // counted towards the play count
+    source: PlaySource | null;
+    source_id: string | null;
+}
+
+export interface HistoryQuery {
+    track_id?: number;
+    since?: string;
+    until?: string;
+    before_id?: number;  // page back from this event
+    limit?: number;
+}
+
+export interface PlayStats {
+    track_id: number;
+    play_count: number;
+    skip_count: number;
+    last_played: string | null;
+}
+
+export async function recordPlayEvent(event: NewPlayEvent): Promise<number> {
+    return await invoke('record_play_event', { event });
+}
+
+export async function getPlayHistory(query?: HistoryQuery): Promise<PlayEvent[]> {
+    return await invoke('get_play_history', { query });
+}
+
+export async function getPlayStats(trackIds: number[]): Promise<PlayStats[]> {
+    return await invoke('get_play_stats', { trackIds });
+}
+
+export async function clearPlayHistory(trackId?: number): Promise<number> {
+    return await invoke('clear_play_history', { trackId });
+}
+
+export interface PlayQueueState {
+    track_ids: number[];
+    current_index: number;
+    position: number;  // seconds into the current track
+    shuffle: boolean;
+    shuffle_order?: number[];  // queue indexes in shuffled play order
+    shuffle_index?: number;  // where in shuffle_order playback is
+    repeat?: 'none' | 'one' | 'all';
+    user_queue_count?: number;
+}
+
+export interface SavedPlayQueue extends Required<PlayQueueState> {
+    tracks: Track[];  // matching track_ids; tracks deleted since the save are left out
+    saved_at: string | null;
+}
+
+/** Replace the saved queue (atomically) */
+export async function savePlayQueue(state: PlayQueueState): Promise<void> {
+    return await invoke('save_play_queue', { state });
+}
+
+/** Cheap update of where playback is, without saving the queue again */
+export async function savePlayQueuePosition(currentIndex: number, position: number, shuffleIndex?: number): Promise<void> {
+    return await invoke('save_play_queue_position', { currentIndex, position, shuffleIndex });
+}
+
+/** The last saved session, or null if there's nothing to restore */
+export async function loadPlayQueue(): Promise<SavedPlayQueue | null> {
+    return await invoke('load_play_queue');
+}
+
+export async function clearPlayQueue(): Promise<void> {
+    return await invoke('clear_play_queue');
+}
+
+// Labels
+
+export interface Label {
+    id: number;
+    name: string;
+    color: string | null;  // #rgb or #rrggbb
+    created_at: string | null;
+    track_count: number;
+    album_count: number;
+}
+
+export type LabelTarget = 'track' | 'album';
+
+export async function getLabels(): Promise<Label[]> {
+    return await invoke('get_labels');
+}
+
+/** Labels linked directly to a track or album (not a track's album labels) */
+export async function getItemLabels(target: LabelTarget, id: number): Promise<Label[]> {
+    return await invoke('get_item_labels', { target, id });
+}
+
+export async function createLabel(name: string, color?: string): Promise<number> {
+    return await invoke('create_label', { name, color });
+}
+
+export async function renameLabel(labelId: number, name: string): Promise<void> {
+    return await invoke('rename_label', { labelId, name });
+}
+
+/** Pass null to remove the colour */
+export async function setLabelColor(labelId: number, color: string | null): Promise<void> {
+    return await invoke('set_label_color', { labelId, color });
+}
+
+/** Move everything labelled sourceId to targetId and delete sourceId */
+export async function mergeLabels(sourceId: number, targetId: number): Promise<void> {
+    return await invoke('merge_labels', { sourceId, targetId });
+}
+
+export async function deleteLabel(labelId: number): Promise<void> {
+    return await invoke('delete_label', { labelId });
+}
+
+/** Returns how many links were added */
+export async function assignLabels(labelIds: number[], target: LabelTarget, ids: number[]): Promise<number> {
+    return await invoke('assign_labels', { labelIds, target, ids });
+}
+
+/** Returns how many links were removed */
+export async function unassignLabels(labelIds: number[], target: LabelTarget, ids: number[]): Promise<number> {
+    return await invoke('unassign_labels', { labelIds, target, ids });
+}
+
+export interface StatsQuery {
+    since?: string;
+    until?: string;
+    utc_offset_minutes?: number;
+    limit?: number;
+}
+
+export interface TopTrack {
+    track_id: number | null;
+    title: string | null;
+    artist: string | null;
+    plays: number;
+    listened_ms: number;
+}
+
+export interface TopAlbum {
+    album_id: number | null;
+    name: string;
+    artist: string | null;
+    plays: number;
+    listened_ms: number;
+}
+
+export interface TopEntry {
+    name: string;
+    plays: number;
+    listened_ms: number;
+}
+
+export interface Streak {
+    days: number;
+    start: string;
+    end: string;
+}
+
+export interface NewArtist {
+    name: string;
+    first_played: string;
+    plays: number;
+}
+
+export interface ListeningStats {
+    total_listened_ms: number;
+    plays: number;
+    skips: number;
+    top_tracks: TopTrack[];
+    top_artists: TopEntry[];
+    top_albums: TopAlbum[];
+    top_genres: TopEntry[];
+    by_hour: number[];
+    by_weekday: number[];
+    longest_streak: Streak | null;
+    current_streak: Streak | null;
+    new_artists: NewArtist[];
+}
+
+export interface OnThisDay {
+    year: number;
+    events: PlayEvent[];
+}
+
+export interface YearSummary {
+    year: number;
+    stats: ListeningStats;
+}
+
+/** Minutes east of UTC, as the stats commands expect */
+export function localUtcOffset(): number {
+    return -new Date().getTimezoneOffset();
+}
+
+export async function getListeningStats(query?: StatsQuery): Promise<ListeningStats> {
+    return await invoke('get_listening_stats', { query });
+}
+
+export async function getOnThisDay(date: string, utcOffsetMinutes: number, limit?: number): Promise<OnThisDay[]> {
+    return await invoke('get_on_this_day', { date, utcOffsetMinutes, limit });
+}
+
+export async function getYearSummary(year: number, utcOffsetMinutes: number): Promise<YearSummary> {
+    return await invoke('get_year_summary', { year, utcOffsetMinutes });
+}
+
+export async function exportYearSummary(
+    year: number,
+    utcOffsetMinutes: number,
+    path: string,
+    format: 'json' | 'png'
+): Promise<void> {
+    return await invoke('export_year_summary', { year, utcOffsetMinutes, path, format });
+}
+
 export async function searchLibrary(query: string, limit: number, offset: number): Promise<Track[]> {
     return await invoke('search_library', { query, limit, offset });
 }
 
+export async function rebuildSearchIndex(): Promise<void> {
+    return await invoke('rebuild_search_index');
+}
+
+export type SuggestionKind = 'artist' | 'album' | 'playlist' | 'track';
+
+export interface Suggestion {
+    kind: SuggestionKind;
+    id: number | null;  // album, playlist or track id; artists use `label`
+    label: string;
+    detail: string | null;  // artist for albums and tracks
+    track_count: number | null;
+    fuzzy: boolean;  // near match for a typo rather than a prefix match
+    score: number;
+}
+
+export async function searchSuggestions(prefix: string, limit?: number): Promise<Suggestion[]> {
+    return await invoke('search_suggestions', { prefix, limit });
+}
+
+export interface ArtistMatch {
+    name: string;
+    track_count: number;
+}
+
+export interface AlbumMatch {
+    id: number;
+    name: string;
+    artist: string | null;
+    art_path: string | null;
+    track_count: number;
+}
+
+export interface SearchGroup<T> {
+    items: T[];
+    total: number;  // everything that matched, not just `items`
+}
+
+export interface GroupLimits {
+    artists?: number;
+    albums?: number;
+    playlists?: number;
+    tracks?: number;
+}
+
+export interface GroupedResults {
+    artists: SearchGroup<ArtistMatch>;
+    albums: SearchGroup<AlbumMatch>;
+    playlists: SearchGroup<Playlist>;
+    tracks: SearchGroup<Track>;
+}
+
+export async function searchGrouped(query: string, limits?: GroupLimits): Promise<GroupedResults> {
+    return await invoke('search_grouped', { query, limits });
+}
+
 export async function getTracksByAlbum(albumId: number): Promise<Track[]> {
     return await invoke('get_tracks_by_album', { albumId });
 }
@@ -204,14 +756,80 @@ export async function addExternalTrack(track: ExternalTrackInput): Promise<numbe
     return await invoke('add_external_track', { track });
 }
 
+/** Remove a track from the library; it can be restored from recently removed. The file is kept. */
 export async function deleteTrack(trackId: number): Promise<boolean> {
     return await invoke('delete_track', { trackId });
 }
 
+/** Remove an album and its tracks from the library, restorably. The files are kept. */
 export async function deleteAlbum(albumId: number): Promise<boolean> {
     return await invoke('delete_album', { albumId });
 }
 
+/** Move a local track's file to the trash and delete the track for good */
+export async function trashTrackFile(trackId: number): Promise<boolean> {
+    return await invoke('trash_track_file', { trackId });
+}
+
+/** Move an album's local files to the trash and delete the album for good */
+export async function trashAlbumFiles(albumId: number): Promise<boolean> {
+    return await invoke('trash_album_files', { albumId });
+}
+
+// Recently removed
+
+export type LibraryItem = 'track' | 'album';
+
+export interface RemovedTrack extends Track {
+    removed_at: string;
+    expires_at: string;  // when it's deleted for good
+}
+
+export interface RemovedAlbum {
+    id: number;
+    name: string;
+    artist: string | null;
+    art_path: string | null;
+    track_count: number;  // tracks removed along with the album
+    removed_at: string;
+    expires_at: string;
+}
+
+export interface RemovedItems {
+    albums: RemovedAlbum[];
+    tracks: RemovedTrack[];  // tracks removed along with their album are only counted there
+}
+
+export interface RemovedOptions {
+    retention_days: number;
+}
+
+export async function getRemovedItems(): Promise<RemovedItems> {
+    return await invoke('get_removed_items');
+}
+
+/** Also brings back the track's album if that was removed */
+export async function restoreTrack(trackId: number): Promise<void> {
+    return await invoke('restore_track', { trackId });
+}
+
+/** Brings back the tracks removed along with the album */
+export async function restoreAlbum(albumId: number): Promise<void> {
+    return await invoke('restore_album', { albumId });
+}
+
+export async function deleteRemovedPermanently(item: LibraryItem, id: number): Promise<void> {
+    return await invoke('delete_removed_permanently', { item, id });
+}
+
+export async function getRemovedOptions(): Promise<RemovedOptions> {
+    return await invoke('get_removed_options');
+}
+
+export async function setRemovedOptions(options: RemovedOptions): Promise<void> {
+    return await invoke('set_removed_options', { options });
+}
+
 export async function resetDatabase(): Promise<void> {
     return await invoke('reset_database');
 }
@@ -335,6 +953,32 @@ export async function mergeDuplicateCovers(): Promise<MergeCoverResult> {
     return await invoke('merge_duplicate_covers');
 }
 
+// Analysis commands
+
+export async function analyzeTrack(trackId: number, renderSpectrogram: boolean = false): Promise<TrackAnalysis> {
+    return await invoke('analyze_track', { trackId, renderSpectrogram });
+}
+
+export async function getTrackAnalysis(trackId: number): Promise<TrackAnalysis | null> {
+    return await invoke('get_track_analysis', { trackId });
+}
+
+export async function getTranscodeSuspects(minConfidence?: number): Promise<TrackAnalysis[]> {
+    return await invoke('get_transcode_suspects', { minConfidence });
+}
+
+export async function verifyLibrary(folders?: string[]): Promise<VerifySummary> {
+    return await invoke('verify_library', { folders });
+}
+
+export async function cancelVerification(): Promise<boolean> {
+    return await invoke('cancel_verification');
+}
+
+export async function getVerificationResults(problemsOnly: boolean = false): Promise<VerificationResult[]> {
+    return await invoke('get_verification_results', { problemsOnly });
+}
+
 
 // Playlist commands
 
@@ -350,14 +994,19 @@ export async function getPlaylistTracks(playlistId: number): Promise<Track[]> {
     return await invoke('get_playlist_tracks', { playlistId });
 }
 
-export async function addTrackToPlaylist(playlistId: number, trackId: number): Promise<void> {
+export async function getPlaylistEntries(playlistId: number): Promise<PlaylistEntry[]> {
+    return await invoke('get_playlist_entries', { playlistId });
+}
+
+export async function addTrackToPlaylist(playlistId: number, trackId: number): Promise<number> {
     return await invoke('add_track_to_playlist', { playlistId, trackId });
 }
 
-export async function removeTrackFromPlaylist(playlistId: number, trackId: number): Promise<void> {
-    return await invoke('remove_track_from_playlist', { playlistId, trackId });
+export async function removePlaylistEntry(playlistId: number, entryId: number): Promise<void> {
+    return await invoke('remove_playlist_entry', { playlistId, entryId });
 }
 
+/** Moves it to recently deleted, where it can be restored until it expires */
 export async function deletePlaylist(playlistId: number): Promise<void> {
     return await invoke('delete_playlist', { playlistId });
 }
@@ -366,8 +1015,269 @@ export async function renamePlaylist(playlistId: number, newName: string): Promi
     return await invoke('rename_playlist', { playlistId, newName });
 }
 
-export async function reorderPlaylistTracks(playlistId: number, fromIndex: number, toIndex: number): Promise<void> {
-    return await invoke('reorder_playlist_tracks', { playlistId, fromIndex, toIndex });
+/**
+ * File path of a cover made from the art of the playlist's first albums, redrawn when they change.
+ * Null when the playlist has a custom cover or no art. Load it with convertFileSrc.
+ */
+export async function getPlaylistCollage(playlistId: number): Promise<string | null> {
+    return await invoke('get_playlist_collage', { playlistId });
+}
+
+// Playlist folders
+
+export async function getPlaylistTree(): Promise<PlaylistTree> {
+    return await invoke('get_playlist_tree');
+}
+
+export async function createPlaylistFolder(name: string, parentId?: number): Promise<number> {
+    return await invoke('create_playlist_folder', { name, parentId });
+}
+
+export async function renamePlaylistFolder(folderId: number, name: string): Promise<void> {
+    return await invoke('rename_playlist_folder', { folderId, name });
+}
+
+/** Omit parentId for the top level and position for the end */
+export async function movePlaylistFolder(folderId: number, parentId?: number, position?: number): Promise<void> {
+    return await invoke('move_playlist_folder', { folderId, parentId, position });
+}
+
+/** Omit folderId for the top level and position for the end */
+export async function movePlaylistToFolder(playlistId: number, folderId?: number, position?: number): Promise<void> {
+    return await invoke('move_playlist_to_folder', { playlistId, folderId, position });
+}
+
+/** Without deletePlaylists the folder's contents move up a level; with it they go to recently deleted. Returns how many playlists were deleted. */
+export async function deletePlaylistFolder(folderId: number, deletePlaylists: boolean): Promise<number> {
+    return await invoke('delete_playlist_folder', { folderId, deletePlaylists });
+}
+
+// Playlist history and recently deleted
+
+export type PlaylistChange = 'add' | 'remove' | 'move' | 'dedupe' | 'sort' | 'shuffle' | 'replace' | 'rename';
+
+export interface PlaylistJournalEntry {
+    id: number;
+    change: PlaylistChange;
+    /** Name and track count from before the change */
+    name: string;
+    track_count: number;
+    created_at: string | null;
+}
+
+export interface DeletedPlaylist {
+    id: number;
+    name: string;
+    track_count: number;
+    deleted_at: string;
+    /** When it's deleted for good */
+    expires_at: string;
+}
+
+export interface PlaylistHistoryOptions {
+    /** How many days a deleted playlist can be restored for */
+    retention_days: number;
+    /** Changes kept per playlist for undo; 0 turns the journal off */
+    undo_limit: number;
+}
+
+/** Changes that can be undone, newest first */
+export async function getPlaylistHistory(playlistId: number): Promise<PlaylistJournalEntry[]> {
+    return await invoke('get_playlist_history', { playlistId });
+}
+
+/** Undo the last `steps` changes (1 by default); returns the restored entries */
+export async function undoPlaylistChanges(playlistId: number, steps?: number): Promise<PlaylistEntry[]> {
+    return await invoke('undo_playlist_changes', { playlistId, steps });
+}
+
+export async function getDeletedPlaylists(): Promise<DeletedPlaylist[]> {
+    return await invoke('get_deleted_playlists');
+}
+
+export async function restorePlaylist(playlistId: number): Promise<void> {
+    return await invoke('restore_playlist', { playlistId });
+}
+
+export async function deletePlaylistPermanently(playlistId: number): Promise<void> {
+    return await invoke('delete_playlist_permanently', { playlistId });
+}
+
+export async function getPlaylistHistoryOptions(): Promise<PlaylistHistoryOptions> {
+    return await invoke('get_playlist_history_options');
+}
+
+export async function setPlaylistHistoryOptions(options: PlaylistHistoryOptions): Promise<void> {
+    return await invoke('set_playlist_history_options', { options });
+}
+
+export async function movePlaylistEntry(playlistId: number, entryId: number, toIndex: number): Promise<void> {
+    return await invoke('move_playlist_entry', { playlistId, entryId, toIndex });
+}
+
+// Bulk playlist edits: each runs in one transaction and returns the new order
+
+export async function addTracksToPlaylist(playlistId: number, trackIds: number[], position?: number): Promise<PlaylistEntry[]> {
+    return await invoke('add_tracks_to_playlist', { playlistId, trackIds, position });
+}
+
+export async function removePlaylistEntries(playlistId: number, entryIds: number[]): Promise<PlaylistEntry[]> {
+    return await invoke('remove_playlist_entries', { playlistId, entryIds });
+}
+
+export async function movePlaylistEntries(playlistId: number, fromIndex: number, count: number, toIndex: number): Promise<PlaylistEntry[]> {
+    return await invoke('move_playlist_entries', { playlistId, fromIndex, count, toIndex });
+}
+
+export async function dedupePlaylist(playlistId: number): Promise<PlaylistEntry[]> {
+    return await invoke('dedupe_playlist', { playlistId });
+}
+
+export async function sortPlaylist(playlistId: number, sort: TrackSort, direction?: SortDirection): Promise<PlaylistEntry[]> {
+    return await invoke('sort_playlist', { playlistId, sort, direction });
+}
+
+export async function shufflePlaylist(playlistId: number): Promise<PlaylistEntry[]> {
+    return await invoke('shuffle_playlist', { playlistId });
+}
+
+export async function replacePlaylistTracks(playlistId: number, trackIds: number[]): Promise<PlaylistEntry[]> {
+    return await invoke('replace_playlist_tracks', { playlistId, trackIds });
+}
+
+// Playlist files
+
+export type PlaylistFormat = 'm3u8' | 'pls' | 'xspf' | 'json';
+
+export interface PlaylistExportOptions {
+    /** Paths relative to the playlist file instead of absolute */
+    relative_paths?: boolean;
+    /** M3U8 only: #EXTINF lines (on by default) */
+    extended_info?: boolean;
+}
+
+export interface PlaylistItem {
+    path: string | null;
+    title: string | null;
+    artist: string | null;
+    album: string | null;
+    track_number: number | null;
+    duration: number | null;
+    year: number | null;
+    genre: string | null;
+    source_type: string | null;
+    external_id: string | null;
+    isrc: string | null;
+}
+
+export interface PlaylistImportReport {
+    playlist_id: number;
+    name: string;
+    matched_by_path: number;
+    matched_by_metadata: number;
+    unresolved: (PlaylistItem & { index: number })[];
+}
+
+export async function exportPlaylist(playlistId: number, format: PlaylistFormat, path: string, options?: PlaylistExportOptions): Promise<number> {
+    return await invoke('export_playlist', { playlistId, format, path, options });
+}
+
+/** The format is taken from the file extension unless given */
+export async function importPlaylist(path: string, format?: PlaylistFormat, name?: string): Promise<PlaylistImportReport> {
+    return await invoke('import_playlist', { path, format, name });
+}
+
+export type TrackListFormat = 'csv' | 'json';
+
+export interface TrackMatch {
+    track_id: number;
+    kind: 'path' | 'metadata' | 'isrc' | 'fuzzy';
+    /** 0-1 */
+    confidence: number;
+    title: string | null;
+    artist: string | null;
+    album: string | null;
+    duration: number | null;
+}
+
+export interface TrackListRow {
+    index: number;
+    item: PlaylistItem;
+    status: 'matched' | 'ambiguous' | 'unmatched';
+    /** Set when status is 'matched' */
+    track_id: number | null;
+    /** Best first */
+    candidates: TrackMatch[];
+}
+
+export interface TrackListImport {
+    playlist_id: number;
+    matched: number;
+    placeholders: number;
+    skipped: number;
+}
+
+/** Match a CSV or JSON export from a streaming service against the library, without saving anything */
+export async function matchTrackList(path: string, format?: TrackListFormat): Promise<TrackListRow[]> {
+    return await invoke('match_track_list', { path, format });
+}
+
+/** Create a playlist from confirmed rows; unmatched rows become placeholders when keepUnmatched is set */
+export async function createPlaylistFromTrackList(
+    name: string,
+    rows: { item: PlaylistItem; track_id: number | null }[],
+    keepUnmatched: boolean
+): Promise<TrackListImport> {
+    return await invoke('create_playlist_from_track_list', { name, rows, keepUnmatched });
+}
+
+// Smart playlists
+
+export type RuleField =
+    | 'title' | 'artist' | 'album' | 'genre' | 'format' | 'source' | 'path'
+    | 'year' | 'duration' | 'bitrate' | 'track_number' | 'rating' | 'loved'
+    | 'play_count' | 'skip_count' | 'last_played' | 'date_added'
+    | 'label';  // any of the track's labels, its own or its album's
+
+export type RuleOperator =
+    | 'is' | 'is_not' | 'contains' | 'not_contains' | 'starts_with' | 'ends_with'
+    | 'gt' | 'gte' | 'lt' | 'lte' | 'in_last' | 'not_in_last' | 'is_set' | 'is_not_set';
+
+export interface Rule {
+    field: RuleField;
+    operator: RuleOperator;
+    value?: string | number | boolean | null;
+}
+
+export interface RuleGroup {
+    join?: 'and' | 'or';
+    rules: RuleNode[];
+}
+
+export type RuleNode = Rule | RuleGroup;
+
+export interface SmartRules {
+    join?: 'and' | 'or';
+    rules: RuleNode[];
+    sort?: TrackSort | 'random';
+    direction?: SortDirection;
+    limit?: number | null;
+}
+
+export async function createSmartPlaylist(name: string, rules: SmartRules): Promise<number> {
+    return await invoke('create_smart_playlist', { name, rules });
+}
+
+export async function updateSmartPlaylist(playlistId: number, rules: SmartRules): Promise<number> {
+    return await invoke('update_smart_playlist', { playlistId, rules });
+}
+
+export async function getSmartPlaylistRules(playlistId: number): Promise<SmartRules | null> {
+    return await invoke('get_smart_playlist_rules', { playlistId });
+}
+
+export async function refreshSmartPlaylist(playlistId: number): Promise<number> {
+    return await invoke('refresh_smart_playlist', { playlistId });
 }
 
 
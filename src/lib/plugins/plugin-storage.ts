@@ -1,7 +1,7 @@
 // Isolated storage system for plugins
 // Prevents cross-plugin access and enforces quotas
 
-import { invoke } from '@tauri-apps/api/core';
+import { invokeForPlugin as invoke } from '$lib/api/tauri';
 
 const STORAGE_PREFIX = 'audion_plugin_';
 const DEFAULT_QUOTA_BYTES = 5 * 1024 * 1024; // 5MB
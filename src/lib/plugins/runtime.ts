@@ -26,7 +26,7 @@ import type { EventListener } from './event-emitter';
 import { uiSlotManager, type UISlotName } from './ui-slots';
 import { appSettings } from '$lib/stores/settings';
 import { theme } from '$lib/stores/theme';
-import { invoke } from '@tauri-apps/api/core';
+import { invokeForPlugin as invoke } from '$lib/api/tauri';
 
 // Cross-Plugin Permission Manager
 class PluginPermissionManager {
@@ -7,7 +7,7 @@ import { appSettings } from '$lib/stores/settings';
 import { pluginStore } from '$lib/stores/plugin-store';
 import { addToast } from '$lib/stores/toast';
 import { loadLibrary } from '$lib/stores/library';
-import type { Track } from '$lib/api/tauri';
+import { toAudionError, type Track } from '$lib/api/tauri';
 
 export interface DownloadProgress {
     current: number;
@@ -258,7 +258,8 @@ export async function downloadTracks(
             // This prevents duplicate listeners and memory leaks
             const savedPath = await downloadTrack(track, { setupListener: false });
             result.success.push(savedPath);
-        } catch (error) {
+        } catch (err) {
+            const error = toAudionError(err);
             result.failed.push({
                 track,
                 error: error instanceof Error ? error.message : String(error)
@@ -1,5 +1,5 @@
 // Helper for batch adding tracks to a playlist
-import { addTrackToPlaylist } from '$lib/api/tauri';
+import { addTracksToPlaylist as addTracks } from '$lib/api/tauri';
 
 export interface BatchAddResult {
     success: number;
@@ -8,7 +8,7 @@ export interface BatchAddResult {
 }
 
 /**
- * Add multiple tracks to a playlist sequentially
+ * Add multiple tracks to a playlist in one call; it's all or nothing
  */
 export async function addTracksToPlaylist(
     playlistId: number,
@@ -20,15 +20,13 @@ export async function addTracksToPlaylist(
         errors: [],
     };
 
-    for (const trackId of trackIds) {
-        try {
-            await addTrackToPlaylist(playlistId, trackId);
-            result.success++;
-        } catch (error) {
-            result.failed++;
-            result.errors.push(`Failed to add track ${trackId}: ${error}`);
-            console.error(`Failed to add track ${trackId}:`, error);
-        }
+    try {
+        await addTracks(playlistId, trackIds);
+        result.success = trackIds.length;
+    } catch (error) {
+        result.failed = trackIds.length;
+        result.errors.push(`Failed to add ${trackIds.length} tracks: ${error}`);
+        console.error('Failed to add tracks:', error);
     }
 
     return result;
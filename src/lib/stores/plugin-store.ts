@@ -3,6 +3,7 @@ import { writable, derived, get } from 'svelte/store';
 import { invoke } from '@tauri-apps/api/core';
 import { convertFileSrc } from '@tauri-apps/api/core';
 import type { AudionPluginManifest } from '../plugins/schema';
+import { toAudionError } from '../api/tauri';
 import type { MarketplacePlugin } from '../plugins/marketplace';
 import { fetchMarketplacePlugins, searchPlugins, filterByCategory } from '../plugins/marketplace';
 import { PluginRuntime, setGlobalPermissionManager } from '../plugins/runtime';
@@ -80,7 +81,8 @@ function createPluginStore() {
     let runtime: PluginRuntime | null = null;
 
     // Helper: Record a plugin loading failure
-    const recordPluginFailure = (name: string, error: Error | unknown) => {
+    const recordPluginFailure = (name: string, err: Error | unknown) => {
+        const error = toAudionError(err);
         const errorMessage = error instanceof Error ? error.message : String(error);
         console.error(`[PluginStore] Failed to load ${name}:`, error);
 
@@ -106,7 +108,8 @@ function createPluginStore() {
     };
 
     // Helper: Set critical error (stops user, displayed prominently)
-    const setCriticalError = (message: string, error?: Error | unknown) => {
+    const setCriticalError = (message: string, err?: Error | unknown) => {
+        const error = toAudionError(err);
         const fullMessage = error instanceof Error
             ? `${message}: ${error.message}`
             : message;